@@ -0,0 +1,122 @@
+//! Property animation imported from KHR_animation_pointer channels, e.g. pulsing emission or scrolling uvs
+//!
+//! Tracks are keyed by a [`PropertyPath`] rather than a node, so they stay valid for any animatable property of the file.
+//! Only material tracks are imported for now, tracks of other targets are skipped until node animation import exists
+
+use speedy::{Readable, Writable};
+
+/// Kind of object a [`PropertyPath`] points into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Readable, Writable)]
+pub enum PropertyTarget {
+    Node,
+    Material,
+    Mesh,
+    Camera,
+    /// KHR_lights_punctual light
+    Light,
+}
+
+/// Animated property as (target type, index, property), parsed from a KHR_animation_pointer json pointer
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Readable, Writable)]
+pub struct PropertyPath {
+    pub target: PropertyTarget,
+    /// Index of the target, model material index for [`PropertyTarget::Material`]
+    pub index: u32,
+    /// Json pointer of the property relative to the target, e.g. `emissiveFactor` or `normalTexture/extensions/KHR_texture_transform/offset`
+    pub property: String,
+}
+
+impl PropertyPath {
+    /// Parse a pointer like `/materials/0/emissiveFactor`, `None` if it doesn't point into a known target type
+    pub fn parse(pointer: &str) -> Option<Self> {
+        let pointer = pointer.strip_prefix('/')?;
+        let (target, pointer) =
+            if let Some(pointer) = pointer.strip_prefix("extensions/KHR_lights_punctual/lights/") {
+                (PropertyTarget::Light, pointer)
+            } else {
+                let (collection, pointer) = pointer.split_once('/')?;
+                let target = match collection {
+                    "nodes" => PropertyTarget::Node,
+                    "materials" => PropertyTarget::Material,
+                    "meshes" => PropertyTarget::Mesh,
+                    "cameras" => PropertyTarget::Camera,
+                    _ => return None,
+                };
+                (target, pointer)
+            };
+
+        let (index, property) = pointer.split_once('/')?;
+        if property.is_empty() {
+            return None;
+        }
+        Some(Self {
+            target,
+            index: index.parse().ok()?,
+            property: property.to_owned(),
+        })
+    }
+
+    /// Json pointer of the property, the inverse of [`PropertyPath::parse`]
+    pub fn pointer(&self) -> String {
+        let collection = match self.target {
+            PropertyTarget::Node => "nodes",
+            PropertyTarget::Material => "materials",
+            PropertyTarget::Mesh => "meshes",
+            PropertyTarget::Camera => "cameras",
+            PropertyTarget::Light => "extensions/KHR_lights_punctual/lights",
+        };
+        format!("/{}/{}/{}", collection, self.index, self.property)
+    }
+}
+
+/// Interpolation between keyframes, matching the glTF sampler interpolation modes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Readable, Writable)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    Step,
+    /// Every keyframe stores an in-tangent, value and out-tangent
+    CubicSpline,
+}
+
+#[derive(Debug, Clone, PartialEq, Readable, Writable)]
+pub struct PropertyTrack {
+    pub path: PropertyPath,
+    pub interpolation: Interpolation,
+    /// Keyframe times in seconds
+    pub times: Vec<f32>,
+    /// `components` floats per keyframe, three times as many for [`Interpolation::CubicSpline`]
+    pub values: Vec<f32>,
+    pub components: u32,
+}
+
+impl PropertyTrack {
+    /// Value of the keyframe at `keyframe_idx`, skipping the tangents of cubic spline tracks
+    pub fn value(&self, keyframe_idx: usize) -> &[f32] {
+        let components = self.components as usize;
+        let offset = match self.interpolation {
+            Interpolation::CubicSpline => (keyframe_idx * 3 + 1) * components,
+            _ => keyframe_idx * components,
+        };
+        &self.values[offset..offset + components]
+    }
+}
+
+/// Point material tracks at remapped material indices, dropping the tracks of materials that map to `None`
+pub(crate) fn remap_material_tracks(
+    tracks: &mut Vec<PropertyTrack>,
+    remap: impl Fn(u32) -> Option<u32>,
+) {
+    tracks.retain_mut(|track| {
+        if track.path.target != PropertyTarget::Material {
+            return true;
+        }
+        match remap(track.path.index) {
+            Some(material_idx) => {
+                track.path.index = material_idx;
+                true
+            }
+            None => false,
+        }
+    });
+}
@@ -0,0 +1,400 @@
+//! Packing of small textures into atlases, baking the atlas placement into mesh uvs
+
+use std::collections::HashMap;
+
+use glam::Vec2;
+use image::{DynamicImage, RgbaImage};
+
+use crate::{
+    material::Material,
+    mesh::{Indices, Mesh, PackedVertex},
+    resample,
+    texture::{
+        AddressMode, MipFilter, Texture, TextureCreateDesc, TextureDimension, TextureFormat,
+        TextureUsage, UncompressedTextureFormat,
+    },
+    Model,
+};
+
+/// Usage of each material slot, in the order of [`Material::textures`]
+/// Allowed distance of uvs outside of the 0 to 1 range, before a material is considered to be tiling
+const UV_EPSILON: f32 = 1e-4;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TextureAtlasDesc {
+    /// Width and height of each atlas
+    pub atlas_size: u32,
+    /// Only materials with all textures at most this large in both dimensions are atlased
+    pub max_texture_size: u32,
+    /// Texels the edges of each packed texture are extended by, limiting bleeding between neighbours in filtering and mips
+    pub padding: u32,
+}
+
+impl Default for TextureAtlasDesc {
+    fn default() -> Self {
+        Self {
+            atlas_size: 2048,
+            max_texture_size: 256,
+            padding: 4,
+        }
+    }
+}
+
+/// Texture slots of one or more materials, packed into the same rect of each slot atlas
+struct AtlasEntry {
+    textures: [Option<u32>; 11],
+    width: u32,
+    height: u32,
+    page: usize,
+    x: u32,
+    y: u32,
+}
+
+impl AtlasEntry {
+    /// Maps a uv of the source textures onto the atlas
+    fn transform_uv(&self, uv: Vec2, desc: &TextureAtlasDesc) -> Vec2 {
+        let atlas_size = desc.atlas_size as f32;
+        let offset = Vec2::new(self.x as f32, self.y as f32) / atlas_size;
+        let scale = Vec2::new(self.width as f32, self.height as f32) / atlas_size;
+        offset + uv * scale
+    }
+}
+
+/// Place `entries` on shelves of atlas pages, tallest first
+fn pack_shelves(entries: &mut [AtlasEntry], desc: &TextureAtlasDesc) {
+    let mut order: Vec<usize> = (0..entries.len()).collect();
+    order.sort_by_key(|entry_idx| std::cmp::Reverse(entries[*entry_idx].height));
+
+    let mut page = 0;
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+    for entry_idx in order {
+        let entry = &mut entries[entry_idx];
+        let width = entry.width + desc.padding * 2;
+        let height = entry.height + desc.padding * 2;
+
+        if x + width > desc.atlas_size {
+            x = 0;
+            y += shelf_height;
+            shelf_height = 0;
+        }
+        if y + height > desc.atlas_size {
+            page += 1;
+            (x, y, shelf_height) = (0, 0, 0);
+        }
+
+        entry.page = page;
+        entry.x = x + desc.padding;
+        entry.y = y + desc.padding;
+        x += width;
+        shelf_height = shelf_height.max(height);
+    }
+}
+
+/// Copy `image` into `atlas` at `x`, `y`, extending its edges by `padding` texels
+fn blit_padded(atlas: &mut RgbaImage, image: &RgbaImage, x: u32, y: u32, padding: u32) {
+    let width = image.width() + padding * 2;
+    let height = image.height() + padding * 2;
+    for py in 0..height {
+        for px in 0..width {
+            let sx = px.saturating_sub(padding).min(image.width() - 1);
+            let sy = py.saturating_sub(padding).min(image.height() - 1);
+            atlas.put_pixel(x - padding + px, y - padding + py, *image.get_pixel(sx, sy));
+        }
+    }
+}
+
+impl Model {
+    /// Returns the uv transform shared by all textures of `material` if it can be atlased
+    fn atlas_uv_transform(
+        &self,
+        material: &Material,
+        desc: &TextureAtlasDesc,
+    ) -> Option<(Vec2, Vec2)> {
+        let mut uv_transform = None;
+        for (slot, texture_idx) in material.textures().into_iter().enumerate() {
+            let Some(texture_idx) = texture_idx else {
+                continue;
+            };
+            let texture = &self.textures[texture_idx as usize];
+
+            let is_atlasable = texture.format()
+                == TextureFormat::Uncompressed(UncompressedTextureFormat::Rgba8Unorm)
+                && texture.dimension() == TextureDimension::D2
+                && !texture.reconstruct_normal_z()
+                && texture.color_space() == TextureUsage::ALL[slot].color_space()
+                && texture.width() <= desc.max_texture_size
+                && texture.height() <= desc.max_texture_size
+                && material.tex_coords()[slot] == 0;
+            if !is_atlasable {
+                return None;
+            }
+
+            let texture_uv_transform = (
+                Vec2::from_array(texture.uv_offset()),
+                Vec2::from_array(texture.uv_scale()),
+            );
+            if *uv_transform.get_or_insert(texture_uv_transform) != texture_uv_transform {
+                return None;
+            }
+        }
+        uv_transform
+    }
+
+    /// Returns true if all uvs of triangles using `material_idx` stay within their texture after `uv_transform`
+    fn is_uv_range_clamped(&self, material_idx: u32, (offset, scale): (Vec2, Vec2)) -> bool {
+        self.meshes.iter().all(|mesh| {
+            let Some(local_material_idx) = mesh
+                .material_indices
+                .iter()
+                .position(|idx| *idx == material_idx)
+            else {
+                return true;
+            };
+
+            mesh.indices
+                .triangles()
+                .zip(&mesh.triangle_material_indices)
+                .filter(|(_, idx)| **idx == local_material_idx as u32)
+                .flat_map(|(triangle, _)| triangle)
+                .all(|vertex_idx| {
+                    let uv = offset
+                        + Vec2::from_array(mesh.packed_vertices[vertex_idx as usize].tex_coord)
+                            * scale;
+                    uv.cmpge(Vec2::splat(-UV_EPSILON)).all()
+                        && uv.cmple(Vec2::splat(1.0 + UV_EPSILON)).all()
+                })
+        })
+    }
+
+    /// Pack the textures of materials using only small, uncompressed rgba8 textures into atlases, one atlas per material slot and page.
+    /// Uvs of the affected triangles are rewritten to sample the atlas, materials with tiling uvs are left as is.
+    /// Textures left unused are removed, returns the indices of the atlases
+    pub fn build_texture_atlas(&mut self, desc: &TextureAtlasDesc) -> Vec<u32> {
+        assert!(
+            desc.max_texture_size + desc.padding * 2 <= desc.atlas_size,
+            "Padded textures of max_texture_size must fit the atlas."
+        );
+
+        let mut entries: Vec<AtlasEntry> = vec![];
+        let mut material_entries: Vec<Option<(usize, (Vec2, Vec2))>> =
+            vec![None; self.materials.len()];
+
+        for (material_idx, material) in self.materials.iter().enumerate() {
+            let textures = material.textures();
+            if textures.iter().all(Option::is_none) {
+                continue;
+            }
+            let Some(uv_transform) = self.atlas_uv_transform(material, desc) else {
+                continue;
+            };
+            if !self.is_uv_range_clamped(material_idx as u32, uv_transform) {
+                continue;
+            }
+
+            let entry_idx = if let Some(entry_idx) =
+                entries.iter().position(|entry| entry.textures == textures)
+            {
+                entry_idx
+            } else {
+                let (width, height) = textures.iter().flatten().fold((1, 1), |(w, h), idx| {
+                    let texture = &self.textures[*idx as usize];
+                    (w.max(texture.width()), h.max(texture.height()))
+                });
+                entries.push(AtlasEntry {
+                    textures,
+                    width,
+                    height,
+                    page: 0,
+                    x: 0,
+                    y: 0,
+                });
+                entries.len() - 1
+            };
+            material_entries[material_idx] = Some((entry_idx, uv_transform));
+        }
+
+        if entries.is_empty() {
+            return vec![];
+        }
+        pack_shelves(&mut entries, desc);
+        let num_pages = entries
+            .iter()
+            .map(|entry| entry.page + 1)
+            .max()
+            .unwrap_or(0);
+
+        // Slots always referencing the same textures as an earlier slot share its atlas, such as packed orm textures
+        let shared_slot: Vec<usize> = (0..TextureUsage::ALL.len())
+            .map(|slot| {
+                (0..slot)
+                    .find(|other| {
+                        entries
+                            .iter()
+                            .all(|entry| entry.textures[slot] == entry.textures[*other])
+                    })
+                    .unwrap_or(slot)
+            })
+            .collect();
+
+        let mut atlas_indices = vec![vec![None; TextureUsage::ALL.len()]; num_pages];
+        for (page, page_atlases) in atlas_indices.iter_mut().enumerate() {
+            for slot in 0..TextureUsage::ALL.len() {
+                if shared_slot[slot] != slot {
+                    page_atlases[slot] = page_atlases[shared_slot[slot]];
+                    continue;
+                }
+
+                let page_entries = entries
+                    .iter()
+                    .filter(|entry| entry.page == page && entry.textures[slot].is_some());
+                let Some(first) = page_entries.clone().next() else {
+                    continue;
+                };
+                let first_texture = &self.textures[first.textures[slot].unwrap() as usize];
+
+                let mut atlas = RgbaImage::new(desc.atlas_size, desc.atlas_size);
+                let mut mips = false;
+                for entry in page_entries {
+                    let texture = &self.textures[entry.textures[slot].unwrap() as usize];
+                    mips |= texture.mip_count() > 1;
+
+                    let image = texture.to_image(0).unwrap();
+                    let image = if image.width() == entry.width && image.height() == entry.height {
+                        image.to_rgba8()
+                    } else {
+                        resample::resize(
+                            &image,
+                            entry.width,
+                            entry.height,
+                            MipFilter::default(),
+                            texture.color_space(),
+                        )
+                        .to_rgba8()
+                    };
+                    blit_padded(&mut atlas, &image, entry.x, entry.y, desc.padding);
+                }
+
+                let mut sampler = first_texture.sampler();
+                sampler.address_mode_u = AddressMode::ClampToEdge;
+                sampler.address_mode_v = AddressMode::ClampToEdge;
+
+                self.textures.push(Texture::new(TextureCreateDesc {
+                    name: Some(&format!("Atlas{}_{:?}", page, TextureUsage::ALL[slot])),
+                    image: DynamicImage::ImageRgba8(atlas),
+                    mips,
+                    max_mip_levels: None,
+                    min_mip_size: None,
+                    precomputed_mips: vec![],
+                    mip_filter: MipFilter::default(),
+                    is_normal_map: TextureUsage::ALL[slot].is_normal_map(),
+                    color_space: first_texture.color_space(),
+                    alpha_coverage_cutoff: None,
+                    premultiply_alpha: false,
+                    uv_offset: [0.0; 2],
+                    uv_scale: [1.0; 2],
+                    sampler,
+                    source_image: None,
+                }));
+                page_atlases[slot] = Some(self.textures.len() as u32 - 1);
+            }
+        }
+
+        for (material, entry) in self.materials.iter_mut().zip(&material_entries) {
+            if let Some((entry_idx, _)) = entry {
+                let page = entries[*entry_idx].page;
+                for (slot, texture_idx) in material.textures_mut().into_iter().enumerate() {
+                    if texture_idx.is_some() {
+                        *texture_idx = atlas_indices[page][slot];
+                    }
+                }
+            }
+        }
+
+        for mesh in &mut self.meshes {
+            remap_mesh_uvs(mesh, &material_entries, &entries, desc);
+        }
+
+        let remap = self.remove_unused_textures();
+        let mut atlases: Vec<u32> = atlas_indices
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|texture_idx| remap[texture_idx as usize])
+            .collect();
+        atlases.sort_unstable();
+        atlases.dedup();
+        atlases
+    }
+}
+
+/// Rewrite uvs of triangles using atlased materials, duplicating vertices shared with triangles mapped differently
+fn remap_mesh_uvs(
+    mesh: &mut Mesh,
+    material_entries: &[Option<(usize, (Vec2, Vec2))>],
+    entries: &[AtlasEntry],
+    desc: &TextureAtlasDesc,
+) {
+    let triangle_entries: Vec<Option<(usize, (Vec2, Vec2))>> = mesh
+        .triangle_material_indices
+        .iter()
+        .map(|idx| material_entries[mesh.material_indices[*idx as usize] as usize])
+        .collect();
+    if triangle_entries.iter().all(Option::is_none) {
+        return;
+    }
+
+    let source_vertices: Vec<PackedVertex> = mesh.packed_vertices.clone();
+    // Entry each vertex is mapped to, `Some(None)` for vertices left as is
+    let mut vertex_entries: Vec<Option<Option<usize>>> = vec![None; source_vertices.len()];
+    let mut duplicates: HashMap<(u32, Option<usize>), u32> = HashMap::new();
+
+    let mut indices = mesh.indices.to_u32().into_owned();
+    for (triangle, entry) in indices.chunks_exact_mut(3).zip(&triangle_entries) {
+        let entry_idx = entry.map(|(entry_idx, _)| entry_idx);
+
+        for vertex_idx in triangle {
+            let vertex_entry = &mut vertex_entries[*vertex_idx as usize];
+            if *vertex_entry == Some(entry_idx) {
+                continue;
+            }
+
+            let target_idx = if vertex_entry.is_none() {
+                *vertex_entry = Some(entry_idx);
+                *vertex_idx
+            } else {
+                *duplicates
+                    .entry((*vertex_idx, entry_idx))
+                    .or_insert_with(|| {
+                        mesh.packed_vertices
+                            .push(source_vertices[*vertex_idx as usize]);
+                        if !mesh.unpacked_vertices.is_empty() {
+                            let vertex = mesh.unpacked_vertices[*vertex_idx as usize];
+                            mesh.unpacked_vertices.push(vertex);
+                        }
+                        if !mesh.secondary_tex_coords.is_empty() {
+                            let tex_coord = mesh.secondary_tex_coords[*vertex_idx as usize];
+                            mesh.secondary_tex_coords.push(tex_coord);
+                        }
+                        mesh.packed_vertices.len() as u32 - 1
+                    })
+            };
+
+            if let Some((entry_idx, (offset, scale))) = entry {
+                let vertex = &mut mesh.packed_vertices[target_idx as usize];
+                let uv = offset
+                    + Vec2::from_array(source_vertices[*vertex_idx as usize].tex_coord) * scale;
+                vertex.tex_coord = entries[*entry_idx].transform_uv(uv, desc).to_array();
+                if !mesh.unpacked_vertices.is_empty() {
+                    mesh.unpacked_vertices[target_idx as usize].tex_coord = vertex.tex_coord;
+                }
+            }
+            *vertex_idx = target_idx;
+        }
+    }
+    // Duplicated vertices may no longer be addressable with 16 bit indices
+    mesh.indices = Indices::new(indices, mesh.packed_vertices.len());
+
+    if let Some(meshlets) = &mesh.meshlets {
+        mesh.meshlets = Some(mesh.build_meshlets(meshlets.max_vertices, meshlets.max_triangles));
+    }
+}
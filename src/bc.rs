@@ -0,0 +1,1073 @@
+//! Block compression decoders, used to convert compressed textures back to images
+
+use half::f16;
+
+use Bc6hField::{Partition as D, W, X, Y, Z};
+
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Subset of each pixel for 2 subset partitions, one bit per pixel
+const PARTITIONS_2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80, 0xc800, 0xffec, 0xfe80, 0xe800,
+    0xffe8, 0xff00, 0xfff0, 0xf000, 0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c, 0xaaaa, 0xf0f0, 0x5a5a, 0x33cc,
+    0x3c3c, 0x55aa, 0x9696, 0xa55a, 0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c, 0x9336, 0x9cc6, 0x817e, 0xe718,
+    0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+/// Subset of each pixel for 3 subset partitions
+const PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// Anchor pixel of the second subset in 2 subset partitions
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor pixels of the second and third subset in 3 subset partitions
+const ANCHORS_3: [[u8; 2]; 64] = [
+    [3, 15],
+    [3, 8],
+    [15, 8],
+    [15, 3],
+    [8, 15],
+    [3, 15],
+    [15, 3],
+    [15, 8],
+    [8, 15],
+    [8, 15],
+    [6, 15],
+    [6, 15],
+    [6, 15],
+    [5, 15],
+    [3, 15],
+    [3, 8],
+    [3, 15],
+    [3, 8],
+    [8, 15],
+    [15, 3],
+    [3, 15],
+    [3, 8],
+    [6, 15],
+    [10, 8],
+    [5, 3],
+    [8, 15],
+    [8, 6],
+    [6, 10],
+    [8, 15],
+    [5, 15],
+    [15, 10],
+    [15, 8],
+    [8, 15],
+    [15, 3],
+    [3, 15],
+    [5, 10],
+    [6, 10],
+    [10, 8],
+    [8, 9],
+    [15, 10],
+    [15, 6],
+    [3, 15],
+    [15, 8],
+    [5, 15],
+    [15, 3],
+    [15, 6],
+    [15, 6],
+    [15, 8],
+    [3, 15],
+    [15, 3],
+    [5, 15],
+    [5, 15],
+    [5, 15],
+    [8, 15],
+    [5, 15],
+    [10, 15],
+    [5, 15],
+    [10, 15],
+    [8, 15],
+    [13, 15],
+    [15, 3],
+    [12, 15],
+    [3, 15],
+    [3, 8],
+];
+
+/// Reads bits of a 128 bit block, least significant bit first
+struct BitReader {
+    bits: u128,
+}
+
+impl BitReader {
+    fn new(block: &[u8]) -> Self {
+        Self {
+            bits: u128::from_le_bytes(block.try_into().unwrap()),
+        }
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        let value = (self.bits & ((1u128 << count) - 1)) as u32;
+        self.bits >>= count;
+        value
+    }
+}
+
+fn interpolate(a: u32, b: u32, weight: u32) -> u32 {
+    ((64 - weight) * a + weight * b + 32) >> 6
+}
+
+/// Decode all blocks of a `width` x `height` image, `decode_block` writes 16 pixels of `N` components
+pub(crate) fn decode_blocks<T: Copy + Default, const N: usize>(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    block_size: usize,
+    decode_block: impl Fn(&[u8]) -> [[T; N]; 16],
+) -> Vec<T> {
+    let width = width as usize;
+    let height = height as usize;
+    let mut pixels = vec![T::default(); width * height * N];
+
+    for (block_idx, block) in data.chunks_exact(block_size).enumerate() {
+        let block_x = (block_idx % width.div_ceil(4)) * 4;
+        let block_y = (block_idx / width.div_ceil(4)) * 4;
+        if block_y >= height {
+            break;
+        }
+
+        let block_pixels = decode_block(block);
+        for (i, pixel) in block_pixels.iter().enumerate() {
+            let x = block_x + i % 4;
+            let y = block_y + i / 4;
+            if x < width && y < height {
+                let offset = (y * width + x) * N;
+                pixels[offset..offset + N].copy_from_slice(pixel);
+            }
+        }
+    }
+
+    pixels
+}
+
+pub(crate) fn decode_bc1(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    decode_blocks(data, width, height, 8, decode_bc1_block)
+}
+
+pub(crate) fn decode_bc4(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    decode_blocks(data, width, height, 8, |block| {
+        decode_bc4_block(block).map(|value| [value])
+    })
+}
+
+pub(crate) fn decode_bc5(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    decode_blocks(data, width, height, 16, |block| {
+        let r = decode_bc4_block(&block[..8]);
+        let g = decode_bc4_block(&block[8..]);
+        std::array::from_fn(|i| [r[i], g[i]])
+    })
+}
+
+pub(crate) fn decode_bc6h(data: &[u8], width: u32, height: u32) -> Vec<f32> {
+    decode_blocks(data, width, height, 16, decode_bc6h_block)
+}
+
+pub(crate) fn decode_bc7(data: &[u8], width: u32, height: u32) -> Vec<u8> {
+    decode_blocks(data, width, height, 16, decode_bc7_block)
+}
+
+fn decode_bc1_block(block: &[u8]) -> [[u8; 4]; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+    let expand = |c: u16| -> [u32; 3] {
+        let r = (c >> 11) as u32 & 0x1f;
+        let g = (c >> 5) as u32 & 0x3f;
+        let b = c as u32 & 0x1f;
+        [
+            (r << 3) | (r >> 2),
+            (g << 2) | (g >> 4),
+            (b << 3) | (b >> 2),
+        ]
+    };
+    let e0 = expand(c0);
+    let e1 = expand(c1);
+
+    let mut palette = [[0u8; 4]; 4];
+    palette[0] = [e0[0] as u8, e0[1] as u8, e0[2] as u8, 255];
+    palette[1] = [e1[0] as u8, e1[1] as u8, e1[2] as u8, 255];
+    if c0 > c1 {
+        for c in 0..3 {
+            palette[2][c] = ((2 * e0[c] + e1[c]) / 3) as u8;
+            palette[3][c] = ((e0[c] + 2 * e1[c]) / 3) as u8;
+        }
+        palette[2][3] = 255;
+        palette[3][3] = 255;
+    } else {
+        for c in 0..3 {
+            palette[2][c] = ((e0[c] + e1[c]) / 2) as u8;
+        }
+        palette[2][3] = 255;
+    }
+
+    std::array::from_fn(|i| palette[((indices >> (i * 2)) & 0x3) as usize])
+}
+
+fn decode_bc4_block(block: &[u8]) -> [u8; 16] {
+    let r0 = block[0] as u32;
+    let r1 = block[1] as u32;
+    let indices = u64::from_le_bytes(block.try_into().unwrap()) >> 16;
+
+    let mut palette = [0u8; 8];
+    palette[0] = r0 as u8;
+    palette[1] = r1 as u8;
+    if r0 > r1 {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u32) * r0 + i as u32 * r1) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u32) * r0 + i as u32 * r1) / 5) as u8;
+        }
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    std::array::from_fn(|i| palette[((indices >> (i * 3)) & 0x7) as usize])
+}
+
+struct Bc7Mode {
+    num_subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode {
+        num_subsets: 3,
+        partition_bits: 4,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 4,
+        alpha_bits: 0,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 3,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        num_subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 6,
+        alpha_bits: 0,
+        endpoint_pbits: false,
+        shared_pbits: true,
+        index_bits: 3,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        num_subsets: 3,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 0,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        num_subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 0,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        num_subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 1,
+        color_bits: 5,
+        alpha_bits: 6,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 3,
+    },
+    Bc7Mode {
+        num_subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 8,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 2,
+    },
+    Bc7Mode {
+        num_subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 7,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 4,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        num_subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 5,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+];
+
+fn weights(index_bits: u32) -> &'static [u32] {
+    match index_bits {
+        2 => &WEIGHTS_2,
+        3 => &WEIGHTS_3,
+        _ => &WEIGHTS_4,
+    }
+}
+
+fn decode_bc7_block(block: &[u8]) -> [[u8; 4]; 16] {
+    let mut reader = BitReader::new(block);
+
+    let mode_idx = block[0].trailing_zeros() as usize;
+    if mode_idx >= BC7_MODES.len() {
+        // Reserved mode, decodes to transparent black
+        return [[0; 4]; 16];
+    }
+    let mode = &BC7_MODES[mode_idx];
+    reader.read(mode_idx as u32 + 1);
+
+    let partition = reader.read(mode.partition_bits) as usize;
+    let rotation = reader.read(mode.rotation_bits);
+    let index_selection = reader.read(mode.index_selection_bits);
+
+    let num_endpoints = mode.num_subsets * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+    for c in 0..3 {
+        for endpoint in endpoints.iter_mut().take(num_endpoints) {
+            endpoint[c] = reader.read(mode.color_bits);
+        }
+    }
+    for endpoint in endpoints.iter_mut().take(num_endpoints) {
+        endpoint[3] = reader.read(mode.alpha_bits);
+    }
+
+    let mut color_bits = mode.color_bits;
+    let mut alpha_bits = mode.alpha_bits;
+    if mode.endpoint_pbits || mode.shared_pbits {
+        let mut pbits = [0u32; 6];
+        if mode.endpoint_pbits {
+            for pbit in pbits.iter_mut().take(num_endpoints) {
+                *pbit = reader.read(1);
+            }
+        } else {
+            for subset in 0..mode.num_subsets {
+                let pbit = reader.read(1);
+                pbits[subset * 2] = pbit;
+                pbits[subset * 2 + 1] = pbit;
+            }
+        }
+
+        for (endpoint, pbit) in endpoints.iter_mut().zip(pbits).take(num_endpoints) {
+            for (c, value) in endpoint.iter_mut().enumerate() {
+                if c < 3 || mode.alpha_bits > 0 {
+                    *value = (*value << 1) | pbit;
+                }
+            }
+        }
+        color_bits += 1;
+        if alpha_bits > 0 {
+            alpha_bits += 1;
+        }
+    }
+
+    for endpoint in endpoints.iter_mut().take(num_endpoints) {
+        for (c, value) in endpoint.iter_mut().enumerate() {
+            let bits = if c < 3 { color_bits } else { alpha_bits };
+            *value = if bits == 0 {
+                255
+            } else {
+                let shifted = *value << (8 - bits);
+                shifted | (shifted >> bits)
+            };
+        }
+    }
+
+    let subset_of = |pixel: usize| -> usize {
+        match mode.num_subsets {
+            1 => 0,
+            2 => ((PARTITIONS_2[partition] >> pixel) & 1) as usize,
+            _ => PARTITIONS_3[partition][pixel] as usize,
+        }
+    };
+    let anchors: Vec<usize> = match mode.num_subsets {
+        1 => vec![0],
+        2 => vec![0, ANCHORS_2[partition] as usize],
+        _ => vec![
+            0,
+            ANCHORS_3[partition][0] as usize,
+            ANCHORS_3[partition][1] as usize,
+        ],
+    };
+
+    let mut indices = [0u32; 16];
+    for (pixel, index) in indices.iter_mut().enumerate() {
+        let bits = mode.index_bits - anchors.contains(&pixel) as u32;
+        *index = reader.read(bits);
+    }
+    let mut secondary_indices = [0u32; 16];
+    if mode.secondary_index_bits > 0 {
+        for (pixel, index) in secondary_indices.iter_mut().enumerate() {
+            let bits = mode.secondary_index_bits - (pixel == 0) as u32;
+            *index = reader.read(bits);
+        }
+    }
+
+    std::array::from_fn(|pixel| {
+        let subset = subset_of(pixel);
+        let e0 = endpoints[subset * 2];
+        let e1 = endpoints[subset * 2 + 1];
+
+        let (color_weight, alpha_weight) = if mode.secondary_index_bits == 0 {
+            let weight = weights(mode.index_bits)[indices[pixel] as usize];
+            (weight, weight)
+        } else if index_selection == 0 {
+            (
+                weights(mode.index_bits)[indices[pixel] as usize],
+                weights(mode.secondary_index_bits)[secondary_indices[pixel] as usize],
+            )
+        } else {
+            (
+                weights(mode.secondary_index_bits)[secondary_indices[pixel] as usize],
+                weights(mode.index_bits)[indices[pixel] as usize],
+            )
+        };
+
+        let mut color = [0u8; 4];
+        for c in 0..3 {
+            color[c] = interpolate(e0[c], e1[c], color_weight) as u8;
+        }
+        color[3] = interpolate(e0[3], e1[3], alpha_weight) as u8;
+
+        match rotation {
+            1 => color.swap(0, 3),
+            2 => color.swap(1, 3),
+            3 => color.swap(2, 3),
+            _ => {}
+        }
+        color
+    })
+}
+
+#[derive(Clone, Copy)]
+enum Bc6hField {
+    /// Base endpoint
+    W,
+    X,
+    Y,
+    Z,
+    Partition,
+}
+
+/// Run of bits in a bc6h header, read from `first` to `last` which may be in descending order
+#[derive(Clone, Copy)]
+struct Bc6hBits {
+    field: Bc6hField,
+    channel: usize,
+    first: u8,
+    last: u8,
+}
+
+const fn bits(field: Bc6hField, channel: usize, first: u8, last: u8) -> Bc6hBits {
+    Bc6hBits {
+        field,
+        channel,
+        first,
+        last,
+    }
+}
+
+const fn bit(field: Bc6hField, channel: usize, bit: u8) -> Bc6hBits {
+    bits(field, channel, bit, bit)
+}
+
+struct Bc6hMode {
+    transformed: bool,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    layout: &'static [Bc6hBits],
+}
+
+const BC6H_MODE_1: Bc6hMode = Bc6hMode {
+    transformed: true,
+    endpoint_bits: 10,
+    delta_bits: [5, 5, 5],
+    layout: &[
+        bit(Y, 1, 4),
+        bit(Y, 2, 4),
+        bit(Z, 2, 4),
+        bits(W, 0, 0, 9),
+        bits(W, 1, 0, 9),
+        bits(W, 2, 0, 9),
+        bits(X, 0, 0, 4),
+        bit(Z, 1, 4),
+        bits(Y, 1, 0, 3),
+        bits(X, 1, 0, 4),
+        bit(Z, 2, 0),
+        bits(Z, 1, 0, 3),
+        bits(X, 2, 0, 4),
+        bit(Z, 2, 1),
+        bits(Y, 2, 0, 3),
+        bits(Y, 0, 0, 4),
+        bit(Z, 2, 2),
+        bits(Z, 0, 0, 4),
+        bit(Z, 2, 3),
+        bits(D, 0, 0, 4),
+    ],
+};
+
+const BC6H_MODE_2: Bc6hMode = Bc6hMode {
+    transformed: true,
+    endpoint_bits: 7,
+    delta_bits: [6, 6, 6],
+    layout: &[
+        bit(Y, 1, 5),
+        bit(Z, 1, 4),
+        bit(Z, 1, 5),
+        bits(W, 0, 0, 6),
+        bit(Z, 2, 0),
+        bit(Z, 2, 1),
+        bit(Y, 2, 4),
+        bits(W, 1, 0, 6),
+        bit(Y, 2, 5),
+        bit(Z, 2, 2),
+        bit(Y, 1, 4),
+        bits(W, 2, 0, 6),
+        bit(Z, 2, 3),
+        bit(Z, 2, 5),
+        bit(Z, 2, 4),
+        bits(X, 0, 0, 5),
+        bits(Y, 1, 0, 3),
+        bits(X, 1, 0, 5),
+        bits(Z, 1, 0, 3),
+        bits(X, 2, 0, 5),
+        bits(Y, 2, 0, 3),
+        bits(Y, 0, 0, 5),
+        bits(Z, 0, 0, 5),
+        bits(D, 0, 0, 4),
+    ],
+};
+
+const BC6H_MODE_3: Bc6hMode = Bc6hMode {
+    transformed: true,
+    endpoint_bits: 11,
+    delta_bits: [5, 4, 4],
+    layout: &[
+        bits(W, 0, 0, 9),
+        bits(W, 1, 0, 9),
+        bits(W, 2, 0, 9),
+        bits(X, 0, 0, 4),
+        bit(W, 0, 10),
+        bits(Y, 1, 0, 3),
+        bits(X, 1, 0, 3),
+        bit(W, 1, 10),
+        bit(Z, 2, 0),
+        bits(Z, 1, 0, 3),
+        bits(X, 2, 0, 3),
+        bit(W, 2, 10),
+        bit(Z, 2, 1),
+        bits(Y, 2, 0, 3),
+        bits(Y, 0, 0, 4),
+        bit(Z, 2, 2),
+        bits(Z, 0, 0, 4),
+        bit(Z, 2, 3),
+        bits(D, 0, 0, 4),
+    ],
+};
+
+const BC6H_MODE_4: Bc6hMode = Bc6hMode {
+    transformed: true,
+    endpoint_bits: 11,
+    delta_bits: [4, 5, 4],
+    layout: &[
+        bits(W, 0, 0, 9),
+        bits(W, 1, 0, 9),
+        bits(W, 2, 0, 9),
+        bits(X, 0, 0, 3),
+        bit(W, 0, 10),
+        bit(Z, 1, 4),
+        bits(Y, 1, 0, 3),
+        bits(X, 1, 0, 4),
+        bit(W, 1, 10),
+        bits(Z, 1, 0, 3),
+        bits(X, 2, 0, 3),
+        bit(W, 2, 10),
+        bit(Z, 2, 1),
+        bits(Y, 2, 0, 3),
+        bits(Y, 0, 0, 3),
+        bit(Z, 2, 0),
+        bit(Z, 2, 2),
+        bits(Z, 0, 0, 3),
+        bit(Y, 1, 4),
+        bit(Z, 2, 3),
+        bits(D, 0, 0, 4),
+    ],
+};
+
+const BC6H_MODE_5: Bc6hMode = Bc6hMode {
+    transformed: true,
+    endpoint_bits: 11,
+    delta_bits: [4, 4, 5],
+    layout: &[
+        bits(W, 0, 0, 9),
+        bits(W, 1, 0, 9),
+        bits(W, 2, 0, 9),
+        bits(X, 0, 0, 3),
+        bit(W, 0, 10),
+        bit(Y, 2, 4),
+        bits(Y, 1, 0, 3),
+        bits(X, 1, 0, 3),
+        bit(W, 1, 10),
+        bit(Z, 2, 0),
+        bits(Z, 1, 0, 3),
+        bits(X, 2, 0, 4),
+        bit(W, 2, 10),
+        bits(Y, 2, 0, 3),
+        bits(Y, 0, 0, 3),
+        bit(Z, 2, 1),
+        bit(Z, 2, 2),
+        bits(Z, 0, 0, 3),
+        bit(Z, 2, 4),
+        bit(Z, 2, 3),
+        bits(D, 0, 0, 4),
+    ],
+};
+
+const BC6H_MODE_6: Bc6hMode = Bc6hMode {
+    transformed: true,
+    endpoint_bits: 9,
+    delta_bits: [5, 5, 5],
+    layout: &[
+        bits(W, 0, 0, 8),
+        bit(Y, 2, 4),
+        bits(W, 1, 0, 8),
+        bit(Y, 1, 4),
+        bits(W, 2, 0, 8),
+        bit(Z, 2, 4),
+        bits(X, 0, 0, 4),
+        bit(Z, 1, 4),
+        bits(Y, 1, 0, 3),
+        bits(X, 1, 0, 4),
+        bit(Z, 2, 0),
+        bits(Z, 1, 0, 3),
+        bits(X, 2, 0, 4),
+        bit(Z, 2, 1),
+        bits(Y, 2, 0, 3),
+        bits(Y, 0, 0, 4),
+        bit(Z, 2, 2),
+        bits(Z, 0, 0, 4),
+        bit(Z, 2, 3),
+        bits(D, 0, 0, 4),
+    ],
+};
+
+const BC6H_MODE_7: Bc6hMode = Bc6hMode {
+    transformed: true,
+    endpoint_bits: 8,
+    delta_bits: [6, 5, 5],
+    layout: &[
+        bits(W, 0, 0, 7),
+        bit(Z, 1, 4),
+        bit(Y, 2, 4),
+        bits(W, 1, 0, 7),
+        bit(Z, 2, 2),
+        bit(Y, 1, 4),
+        bits(W, 2, 0, 7),
+        bit(Z, 2, 3),
+        bit(Z, 2, 4),
+        bits(X, 0, 0, 5),
+        bits(Y, 1, 0, 3),
+        bits(X, 1, 0, 4),
+        bit(Z, 2, 0),
+        bits(Z, 1, 0, 3),
+        bits(X, 2, 0, 4),
+        bit(Z, 2, 1),
+        bits(Y, 2, 0, 3),
+        bits(Y, 0, 0, 5),
+        bits(Z, 0, 0, 5),
+        bits(D, 0, 0, 4),
+    ],
+};
+
+const BC6H_MODE_8: Bc6hMode = Bc6hMode {
+    transformed: true,
+    endpoint_bits: 8,
+    delta_bits: [5, 6, 5],
+    layout: &[
+        bits(W, 0, 0, 7),
+        bit(Z, 2, 0),
+        bit(Y, 2, 4),
+        bits(W, 1, 0, 7),
+        bit(Y, 1, 5),
+        bit(Y, 1, 4),
+        bits(W, 2, 0, 7),
+        bit(Z, 1, 5),
+        bit(Z, 2, 4),
+        bits(X, 0, 0, 4),
+        bit(Z, 1, 4),
+        bits(Y, 1, 0, 3),
+        bits(X, 1, 0, 5),
+        bits(Z, 1, 0, 3),
+        bits(X, 2, 0, 4),
+        bit(Z, 2, 1),
+        bits(Y, 2, 0, 3),
+        bits(Y, 0, 0, 4),
+        bit(Z, 2, 2),
+        bits(Z, 0, 0, 4),
+        bit(Z, 2, 3),
+        bits(D, 0, 0, 4),
+    ],
+};
+
+const BC6H_MODE_9: Bc6hMode = Bc6hMode {
+    transformed: true,
+    endpoint_bits: 8,
+    delta_bits: [5, 5, 6],
+    layout: &[
+        bits(W, 0, 0, 7),
+        bit(Z, 2, 1),
+        bit(Y, 2, 4),
+        bits(W, 1, 0, 7),
+        bit(Y, 2, 5),
+        bit(Y, 1, 4),
+        bits(W, 2, 0, 7),
+        bit(Z, 2, 5),
+        bit(Z, 2, 4),
+        bits(X, 0, 0, 4),
+        bit(Z, 1, 4),
+        bits(Y, 1, 0, 3),
+        bits(X, 1, 0, 4),
+        bit(Z, 2, 0),
+        bits(Z, 1, 0, 3),
+        bits(X, 2, 0, 5),
+        bits(Y, 2, 0, 3),
+        bits(Y, 0, 0, 4),
+        bit(Z, 2, 2),
+        bits(Z, 0, 0, 4),
+        bit(Z, 2, 3),
+        bits(D, 0, 0, 4),
+    ],
+};
+
+const BC6H_MODE_10: Bc6hMode = Bc6hMode {
+    transformed: false,
+    endpoint_bits: 6,
+    delta_bits: [6, 6, 6],
+    layout: &[
+        bits(W, 0, 0, 5),
+        bit(Z, 1, 4),
+        bit(Z, 2, 0),
+        bit(Z, 2, 1),
+        bit(Y, 2, 4),
+        bits(W, 1, 0, 5),
+        bit(Y, 1, 5),
+        bit(Y, 2, 5),
+        bit(Z, 2, 2),
+        bit(Y, 1, 4),
+        bits(W, 2, 0, 5),
+        bit(Z, 1, 5),
+        bit(Z, 2, 3),
+        bit(Z, 2, 5),
+        bit(Z, 2, 4),
+        bits(X, 0, 0, 5),
+        bits(Y, 1, 0, 3),
+        bits(X, 1, 0, 5),
+        bits(Z, 1, 0, 3),
+        bits(X, 2, 0, 5),
+        bits(Y, 2, 0, 3),
+        bits(Y, 0, 0, 5),
+        bits(Z, 0, 0, 5),
+        bits(D, 0, 0, 4),
+    ],
+};
+
+const BC6H_MODE_11: Bc6hMode = Bc6hMode {
+    transformed: false,
+    endpoint_bits: 10,
+    delta_bits: [10, 10, 10],
+    layout: &[
+        bits(W, 0, 0, 9),
+        bits(W, 1, 0, 9),
+        bits(W, 2, 0, 9),
+        bits(X, 0, 0, 9),
+        bits(X, 1, 0, 9),
+        bits(X, 2, 0, 9),
+    ],
+};
+
+const BC6H_MODE_12: Bc6hMode = Bc6hMode {
+    transformed: true,
+    endpoint_bits: 11,
+    delta_bits: [9, 9, 9],
+    layout: &[
+        bits(W, 0, 0, 9),
+        bits(W, 1, 0, 9),
+        bits(W, 2, 0, 9),
+        bits(X, 0, 0, 8),
+        bit(W, 0, 10),
+        bits(X, 1, 0, 8),
+        bit(W, 1, 10),
+        bits(X, 2, 0, 8),
+        bit(W, 2, 10),
+    ],
+};
+
+const BC6H_MODE_13: Bc6hMode = Bc6hMode {
+    transformed: true,
+    endpoint_bits: 12,
+    delta_bits: [8, 8, 8],
+    layout: &[
+        bits(W, 0, 0, 9),
+        bits(W, 1, 0, 9),
+        bits(W, 2, 0, 9),
+        bits(X, 0, 0, 7),
+        bits(W, 0, 11, 10),
+        bits(X, 1, 0, 7),
+        bits(W, 1, 11, 10),
+        bits(X, 2, 0, 7),
+        bits(W, 2, 11, 10),
+    ],
+};
+
+const BC6H_MODE_14: Bc6hMode = Bc6hMode {
+    transformed: true,
+    endpoint_bits: 16,
+    delta_bits: [4, 4, 4],
+    layout: &[
+        bits(W, 0, 0, 9),
+        bits(W, 1, 0, 9),
+        bits(W, 2, 0, 9),
+        bits(X, 0, 0, 3),
+        bits(W, 0, 15, 10),
+        bits(X, 1, 0, 3),
+        bits(W, 1, 15, 10),
+        bits(X, 2, 0, 3),
+        bits(W, 2, 15, 10),
+    ],
+};
+
+fn bc6h_mode(mode_bits: u32) -> Option<&'static Bc6hMode> {
+    match mode_bits {
+        0b00 => Some(&BC6H_MODE_1),
+        0b01 => Some(&BC6H_MODE_2),
+        0b00010 => Some(&BC6H_MODE_3),
+        0b00110 => Some(&BC6H_MODE_4),
+        0b01010 => Some(&BC6H_MODE_5),
+        0b01110 => Some(&BC6H_MODE_6),
+        0b10010 => Some(&BC6H_MODE_7),
+        0b10110 => Some(&BC6H_MODE_8),
+        0b11010 => Some(&BC6H_MODE_9),
+        0b11110 => Some(&BC6H_MODE_10),
+        0b00011 => Some(&BC6H_MODE_11),
+        0b00111 => Some(&BC6H_MODE_12),
+        0b01011 => Some(&BC6H_MODE_13),
+        0b01111 => Some(&BC6H_MODE_14),
+        _ => None,
+    }
+}
+
+fn sign_extend(value: u32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    ((value << shift) as i32) >> shift
+}
+
+fn unquantize_bc6h(value: i32, bits: u32) -> u32 {
+    let max = (1 << bits) - 1;
+    if bits >= 15 || value == 0 {
+        value as u32
+    } else if value == max {
+        0xffff
+    } else {
+        (((value as u32) << 16) + 0x8000) >> bits
+    }
+}
+
+fn decode_bc6h_block(block: &[u8]) -> [[f32; 4]; 16] {
+    let mut reader = BitReader::new(block);
+
+    let mut mode_bits = reader.read(2);
+    if mode_bits > 1 {
+        mode_bits |= reader.read(3) << 2;
+    }
+    let Some(mode) = bc6h_mode(mode_bits) else {
+        // Reserved mode, decodes to black
+        return [[0.0, 0.0, 0.0, 1.0]; 16];
+    };
+
+    // Endpoint fields w, x, y, z per channel, followed by the partition
+    let mut fields = [[0u32; 3]; 4];
+    let mut partition = 0;
+    for run in mode.layout {
+        let ascending = run.first <= run.last;
+        let count = run.first.abs_diff(run.last) + 1;
+        for i in 0..count {
+            let bit_idx = if ascending {
+                run.first + i
+            } else {
+                run.first - i
+            };
+            let value = reader.read(1) << bit_idx;
+            match run.field {
+                D => partition |= value,
+                W => fields[0][run.channel] |= value,
+                X => fields[1][run.channel] |= value,
+                Y => fields[2][run.channel] |= value,
+                Z => fields[3][run.channel] |= value,
+            }
+        }
+    }
+
+    // Modes ending in 0b11 use a single region
+    let two_regions = (mode_bits & 0b11) != 0b11;
+    let num_endpoints = if two_regions { 4 } else { 2 };
+
+    let mut endpoints = [[0i32; 3]; 4];
+    for c in 0..3 {
+        endpoints[0][c] = fields[0][c] as i32;
+        for e in 1..num_endpoints {
+            endpoints[e][c] = if mode.transformed {
+                let delta = sign_extend(fields[e][c], mode.delta_bits[c]);
+                (fields[0][c] as i32 + delta) & ((1 << mode.endpoint_bits) - 1)
+            } else {
+                fields[e][c] as i32
+            };
+        }
+    }
+
+    let endpoints =
+        endpoints.map(|endpoint| endpoint.map(|value| unquantize_bc6h(value, mode.endpoint_bits)));
+
+    let (index_bits, anchors) = if two_regions {
+        (3, vec![0, ANCHORS_2[partition as usize] as usize])
+    } else {
+        (4, vec![0])
+    };
+
+    std::array::from_fn(|pixel| {
+        let index = reader.read(index_bits - anchors.contains(&pixel) as u32);
+        let subset = if two_regions {
+            ((PARTITIONS_2[partition as usize] >> pixel) & 1) as usize
+        } else {
+            0
+        };
+
+        let weight = weights(index_bits)[index as usize];
+        let e0 = endpoints[subset * 2];
+        let e1 = endpoints[subset * 2 + 1];
+
+        let mut color = [1.0; 4];
+        for c in 0..3 {
+            let value = interpolate(e0[c], e1[c], weight);
+            color[c] = f16::from_bits(((value * 31) >> 6) as u16).to_f32();
+        }
+        color
+    })
+}
@@ -0,0 +1,453 @@
+//! Bevy asset loader turning serialized ugm models into bevy meshes, materials, images and a scene
+
+use std::{collections::HashMap, fmt};
+
+use bevy::{
+    app::{App, Plugin},
+    asset::{io::Reader, Asset, AssetApp, AssetLoader, Handle, LoadContext},
+    color::{Color, LinearRgba},
+    ecs::{entity::Entity, hierarchy::ChildOf, name::Name, world::World},
+    image::{Image, ImageAddressMode, ImageFilterMode, ImageSampler, ImageSamplerDescriptor},
+    math::{Affine2, Mat4, Vec2},
+    pbr::{MeshMaterial3d, StandardMaterial, UvChannel},
+    reflect::TypePath,
+    render::{
+        alpha::AlphaMode,
+        mesh::{Indices, Mesh, Mesh3d, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+        render_resource::{
+            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+            TextureViewDescriptor, TextureViewDimension,
+        },
+        view::Visibility,
+    },
+    scene::Scene,
+    transform::components::Transform,
+};
+use speedy::Readable;
+
+use crate::{
+    material::Material,
+    serialization::{ENDIANNESS, MAGIC},
+    texture::{
+        AddressMode, ColorSpace, CompressedTextureFormat, FilterMode,
+        TextureDimension as UgmTextureDimension, TextureFormat as UgmTextureFormat,
+        UncompressedTextureFormat,
+    },
+    Model,
+};
+
+/// Registers [`UgmLoader`] for `.ugm` files
+pub struct UgmPlugin;
+
+impl Plugin for UgmPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<UgmAsset>()
+            .register_asset_loader(UgmLoader);
+    }
+}
+
+/// Loaded ugm model, all handles are labeled sub assets of the model file
+#[derive(Asset, TypePath, Debug)]
+pub struct UgmAsset {
+    /// Node hierarchy of the model, labeled `Scene`
+    pub scene: Handle<Scene>,
+    /// Bevy meshes of each ugm mesh, one per submesh together with its material index. Labeled `Mesh{}/Submesh{}`
+    pub meshes: Vec<Vec<(Handle<Mesh>, u32)>>,
+    /// Labeled `Material{}`
+    pub materials: Vec<Handle<StandardMaterial>>,
+    /// Labeled `Texture{}`
+    pub textures: Vec<Handle<Image>>,
+}
+
+#[derive(Debug)]
+pub enum UgmLoaderError {
+    Io(std::io::Error),
+    Deserialize(speedy::Error),
+    Container(anyhow::Error),
+}
+
+impl fmt::Display for UgmLoaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "Failed to read ugm model: {}", error),
+            Self::Deserialize(error) => write!(f, "Failed to deserialize ugm model: {}", error),
+            Self::Container(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for UgmLoaderError {}
+
+impl From<std::io::Error> for UgmLoaderError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<speedy::Error> for UgmLoaderError {
+    fn from(error: speedy::Error) -> Self {
+        Self::Deserialize(error)
+    }
+}
+
+#[derive(Default)]
+pub struct UgmLoader;
+
+impl AssetLoader for UgmLoader {
+    type Asset = UgmAsset;
+    type Settings = ();
+    type Error = UgmLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<UgmAsset, UgmLoaderError> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).await?;
+        // Raw models without container header are still supported
+        let mut model = if bytes.starts_with(&MAGIC) {
+            Model::read_ugm(&mut bytes.as_slice()).map_err(UgmLoaderError::Container)?
+        } else {
+            Model::read_from_buffer_with_ctx(ENDIANNESS, &bytes)?
+        };
+        model.dequantize_positions();
+
+        let textures: Vec<Handle<Image>> = model
+            .textures
+            .iter()
+            .enumerate()
+            .map(|(texture_idx, texture)| {
+                load_context
+                    .add_labeled_asset(format!("Texture{}", texture_idx), to_bevy_image(texture))
+            })
+            .collect();
+
+        let materials: Vec<Handle<StandardMaterial>> = model
+            .materials
+            .iter()
+            .enumerate()
+            .map(|(material_idx, material)| {
+                let material = to_bevy_material(material, &model, &textures);
+                load_context.add_labeled_asset(format!("Material{}", material_idx), material)
+            })
+            .collect();
+
+        let meshes: Vec<Vec<(Handle<Mesh>, u32)>> = model
+            .meshes
+            .iter()
+            .enumerate()
+            .map(|(mesh_idx, mesh)| {
+                mesh.material_indices
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(local_material_idx, material_idx)| {
+                        let submesh = to_bevy_submesh(mesh, local_material_idx as u32)?;
+                        let handle = load_context.add_labeled_asset(
+                            format!("Mesh{}/Submesh{}", mesh_idx, local_material_idx),
+                            submesh,
+                        );
+                        Some((handle, *material_idx))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut world = World::new();
+        for root_node_idx in &model.root_node_indices {
+            spawn_node(
+                &mut world,
+                &model,
+                *root_node_idx,
+                None,
+                &meshes,
+                &materials,
+            );
+        }
+        let scene = load_context.add_labeled_asset("Scene".to_owned(), Scene::new(world));
+
+        Ok(UgmAsset {
+            scene,
+            meshes,
+            materials,
+            textures,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ugm"]
+    }
+}
+
+fn spawn_node(
+    world: &mut World,
+    model: &Model,
+    node_idx: u32,
+    parent: Option<Entity>,
+    meshes: &[Vec<(Handle<Mesh>, u32)>],
+    materials: &[Handle<StandardMaterial>],
+) {
+    let node = &model.nodes[node_idx as usize];
+
+    let mut entity = world.spawn((
+        Name::new(node.name.clone()),
+        Transform::from_matrix(Mat4::from_cols_array(&node.transform)),
+        Visibility::default(),
+    ));
+    if let Some(parent) = parent {
+        entity.insert(ChildOf(parent));
+    }
+    let entity = entity.id();
+
+    if let Some(mesh_idx) = node.mesh_idx {
+        for (mesh, material_idx) in &meshes[mesh_idx as usize] {
+            world.spawn((
+                Mesh3d(mesh.clone()),
+                MeshMaterial3d(materials[*material_idx as usize].clone()),
+                ChildOf(entity),
+            ));
+        }
+    }
+
+    for child_node_idx in &node.child_node_indices {
+        spawn_node(
+            world,
+            model,
+            *child_node_idx,
+            Some(entity),
+            meshes,
+            materials,
+        );
+    }
+}
+
+fn to_bevy_format(format: UgmTextureFormat) -> TextureFormat {
+    match format {
+        UgmTextureFormat::Uncompressed(format) => match format {
+            UncompressedTextureFormat::R8Unorm => TextureFormat::R8Unorm,
+            UncompressedTextureFormat::Rg8Unorm => TextureFormat::Rg8Unorm,
+            UncompressedTextureFormat::Rgba8Unorm => TextureFormat::Rgba8Unorm,
+            UncompressedTextureFormat::R16Unorm => TextureFormat::R16Unorm,
+            UncompressedTextureFormat::Rg16Unorm => TextureFormat::Rg16Unorm,
+            UncompressedTextureFormat::Rgba16Unorm => TextureFormat::Rgba16Unorm,
+            UncompressedTextureFormat::Rgba32Float => TextureFormat::Rgba32Float,
+        },
+        UgmTextureFormat::Compressed(format) => match format {
+            CompressedTextureFormat::Bc1RgbaUnorm => TextureFormat::Bc1RgbaUnorm,
+            CompressedTextureFormat::Bc4RUnorm => TextureFormat::Bc4RUnorm,
+            CompressedTextureFormat::Bc5RgUnorm => TextureFormat::Bc5RgUnorm,
+            CompressedTextureFormat::Bc6hRgbUfloat => TextureFormat::Bc6hRgbUfloat,
+            CompressedTextureFormat::Bc7RgbaUnorm => TextureFormat::Bc7RgbaUnorm,
+            // Etc1 blocks are valid Etc2 rgb blocks
+            CompressedTextureFormat::Etc1 | CompressedTextureFormat::Etc2Rgb8Unorm => {
+                TextureFormat::Etc2Rgb8Unorm
+            }
+            CompressedTextureFormat::Etc2Rgba8Unorm => TextureFormat::Etc2Rgba8Unorm,
+            CompressedTextureFormat::EacR11Unorm => TextureFormat::EacR11Unorm,
+            CompressedTextureFormat::EacRg11Unorm => TextureFormat::EacRg11Unorm,
+        },
+    }
+}
+
+fn to_bevy_address_mode(address_mode: AddressMode) -> ImageAddressMode {
+    match address_mode {
+        AddressMode::ClampToEdge => ImageAddressMode::ClampToEdge,
+        AddressMode::Repeat => ImageAddressMode::Repeat,
+        AddressMode::MirrorRepeat => ImageAddressMode::MirrorRepeat,
+    }
+}
+
+fn to_bevy_filter_mode(filter_mode: FilterMode) -> ImageFilterMode {
+    match filter_mode {
+        FilterMode::Nearest => ImageFilterMode::Nearest,
+        FilterMode::Linear => ImageFilterMode::Linear,
+    }
+}
+
+/// Image with all mips and layers of `texture`
+pub fn to_bevy_image(texture: &crate::texture::Texture) -> Image {
+    let mut format = to_bevy_format(texture.format());
+    if texture.color_space() == ColorSpace::Srgb {
+        format = format.add_srgb_suffix();
+    }
+
+    let sampler = texture.sampler();
+    // Bevy uploads all mips of a layer before the next layer, while ugm stores all layers of a mip before the next mip
+    let mut data = Vec::with_capacity(texture.data().len());
+    for layer in 0..texture.layer_count() {
+        for mip in 0..texture.mip_count() {
+            data.extend_from_slice(texture.layer_data(mip, layer));
+        }
+    }
+
+    let mut image = Image {
+        data: Some(data),
+        texture_descriptor: TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: texture.width(),
+                height: texture.height(),
+                depth_or_array_layers: texture.layer_count(),
+            },
+            mip_level_count: texture.mip_count(),
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        },
+        sampler: ImageSampler::Descriptor(ImageSamplerDescriptor {
+            address_mode_u: to_bevy_address_mode(sampler.address_mode_u),
+            address_mode_v: to_bevy_address_mode(sampler.address_mode_v),
+            mag_filter: to_bevy_filter_mode(sampler.mag_filter),
+            min_filter: to_bevy_filter_mode(sampler.min_filter),
+            mipmap_filter: to_bevy_filter_mode(sampler.mipmap_filter),
+            ..Default::default()
+        }),
+        asset_usage: RenderAssetUsages::RENDER_WORLD,
+        ..Default::default()
+    };
+    if texture.dimension() == UgmTextureDimension::Cube {
+        image.texture_view_descriptor = Some(TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::Cube),
+            ..Default::default()
+        });
+    }
+
+    image
+}
+
+fn to_bevy_material(
+    material: &Material,
+    model: &Model,
+    textures: &[Handle<Image>],
+) -> StandardMaterial {
+    let texture = |texture_idx: Option<u32>| {
+        texture_idx.and_then(|texture_idx| textures.get(texture_idx as usize).cloned())
+    };
+    let uv_channel = |tex_coord: u32| {
+        if tex_coord == 0 {
+            UvChannel::Uv0
+        } else {
+            UvChannel::Uv1
+        }
+    };
+
+    // Bevy applies a single uv transform to all textures, use the one of the base color texture
+    let uv_transform = material
+        .color_texture
+        .and_then(|texture_idx| model.textures.get(texture_idx as usize))
+        .map_or(Affine2::IDENTITY, |texture| {
+            Affine2::from_scale_angle_translation(
+                Vec2::from_array(texture.uv_scale()),
+                0.0,
+                Vec2::from_array(texture.uv_offset()),
+            )
+        });
+
+    let premultiplied_alpha = material
+        .color_texture
+        .and_then(|texture_idx| model.textures.get(texture_idx as usize))
+        .is_some_and(|texture| texture.premultiplied_alpha());
+
+    let alpha_mode = if material.is_opaque {
+        AlphaMode::Opaque
+    } else if !material.is_blended() {
+        AlphaMode::Mask(material.alpha_cutoff)
+    } else if premultiplied_alpha {
+        AlphaMode::Premultiplied
+    } else {
+        AlphaMode::Blend
+    };
+
+    StandardMaterial {
+        base_color: Color::linear_rgb(material.color[0], material.color[1], material.color[2]),
+        base_color_texture: texture(material.color_texture),
+        base_color_channel: uv_channel(material.color_tex_coord),
+        metallic: material.metallic,
+        perceptual_roughness: material.roughness,
+        metallic_roughness_texture: texture(material.metallic_roughness_texture),
+        metallic_roughness_channel: uv_channel(material.metallic_roughness_tex_coord),
+        normal_map_texture: texture(material.normal_texture),
+        normal_map_channel: uv_channel(material.normal_tex_coord),
+        emissive: LinearRgba::rgb(
+            material.emission[0],
+            material.emission[1],
+            material.emission[2],
+        ),
+        emissive_texture: texture(material.emission_texture),
+        emissive_channel: uv_channel(material.emission_tex_coord),
+        occlusion_texture: texture(material.occlusion_texture),
+        occlusion_channel: uv_channel(material.occlusion_tex_coord),
+        specular_transmission: material.transmission,
+        ior: 1.0 / material.eta,
+        clearcoat: material.clearcoat,
+        clearcoat_perceptual_roughness: material.clearcoat_roughness,
+        alpha_mode,
+        uv_transform,
+        ..Default::default()
+    }
+}
+
+/// Triangles of `mesh` using its `local_material_idx`th material, returns `None` if there are none
+pub fn to_bevy_submesh(mesh: &crate::mesh::Mesh, local_material_idx: u32) -> Option<Mesh> {
+    let mut remap: HashMap<u32, u32> = HashMap::new();
+    let mut vertices = vec![];
+    let indices: Vec<u32> = mesh
+        .indices
+        .triangles()
+        .zip(&mesh.triangle_material_indices)
+        .filter(|(_, triangle_material_idx)| **triangle_material_idx == local_material_idx)
+        .flat_map(|(triangle, _)| triangle)
+        .map(|vertex_idx| {
+            *remap.entry(vertex_idx).or_insert_with(|| {
+                vertices.push(vertex_idx as usize);
+                vertices.len() as u32 - 1
+            })
+        })
+        .collect();
+    if indices.is_empty() {
+        return None;
+    }
+
+    let packed = |vertex_idx: &usize| &mesh.packed_vertices[*vertex_idx];
+    let positions: Vec<[f32; 3]> = vertices.iter().map(|v| packed(v).position).collect();
+    let normals: Vec<[f32; 3]> = vertices
+        .iter()
+        .map(|v| packed(v).normal.unpack().to_array())
+        .collect();
+    let tangents: Vec<[f32; 4]> = vertices
+        .iter()
+        .map(|v| {
+            let vertex = packed(v);
+            vertex
+                .tangent
+                .unpack()
+                .extend(vertex.tangent_handiness)
+                .to_array()
+        })
+        .collect();
+    let tex_coords: Vec<[f32; 2]> = vertices.iter().map(|v| packed(v).tex_coord).collect();
+
+    let mut submesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, normals)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_TANGENT, tangents)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, tex_coords)
+    .with_inserted_indices(Indices::U32(indices));
+
+    if !mesh.secondary_tex_coords.is_empty() {
+        let secondary_tex_coords: Vec<[f32; 2]> = vertices
+            .iter()
+            .map(|v| mesh.secondary_tex_coords[*v])
+            .collect();
+        submesh.insert_attribute(Mesh::ATTRIBUTE_UV_1, secondary_tex_coords);
+    }
+
+    Some(submesh)
+}
@@ -0,0 +1,239 @@
+//! Command line tool baking glb files into ugm containers, built with the `cli` feature
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use ugm::{
+    parser::{MaxTextureResolution, ParseOptions},
+    texture::TextureCompression,
+    Model,
+};
+
+const USAGE: &str = "Usage:
+  ugm convert <input> [-o <output>] [--compress bc|etc1|etc2] [--mips] [--max-res 256|512|1024|2048|4096] [--deterministic]
+  ugm inspect <input>
+
+Commands:
+  convert    Parse a glb or ugm file and write it as ugm container, or as glb if the output ends in .glb
+  inspect    Print the node tree, meshes, materials, textures and serialized size of each part of a glb or ugm file";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(error) = run(&args) {
+        eprintln!("error: {:#}\n\n{}", error, USAGE);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<()> {
+    match args.first().map(String::as_str) {
+        Some("convert") => convert(&args[1..]),
+        Some("inspect") => inspect(&args[1..]),
+        Some("-h" | "--help") | None => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some(command) => anyhow::bail!("Failed to run unknown command {:?}.", command),
+    }
+}
+
+fn convert(args: &[String]) -> Result<()> {
+    let mut input = None;
+    let mut output = None;
+    let mut opt = ParseOptions::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .with_context(|| format!("Failed to parse {}, it requires a value.", arg))
+        };
+
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+            "--compress" => {
+                opt.texture_compression = Some(match value()?.as_str() {
+                    "bc" => TextureCompression::Bc,
+                    "etc1" => TextureCompression::Etc1,
+                    "etc2" => TextureCompression::Etc2,
+                    compression => anyhow::bail!(
+                        "Failed to parse texture compression {:?}, expected bc, etc1 or etc2.",
+                        compression
+                    ),
+                })
+            }
+            "--mips" => opt.generate_mips = true,
+            "--deterministic" => opt.deterministic_uuids = true,
+            "--max-res" => {
+                opt.max_texture_resolution = Some(match value()?.as_str() {
+                    "256" => MaxTextureResolution::Res256,
+                    "512" => MaxTextureResolution::Res512,
+                    "1024" => MaxTextureResolution::Res1024,
+                    "2048" => MaxTextureResolution::Res2048,
+                    "4096" => MaxTextureResolution::Res4096,
+                    resolution => anyhow::bail!(
+                        "Failed to parse max texture resolution {:?}, expected 256, 512, 1024, 2048 or 4096.",
+                        resolution
+                    ),
+                })
+            }
+            flag if flag.starts_with('-') => {
+                anyhow::bail!("Failed to parse unknown flag {:?}.", flag)
+            }
+            path if input.is_none() => input = Some(PathBuf::from(path)),
+            path => anyhow::bail!("Failed to parse {:?}, an input was already given.", path),
+        }
+    }
+
+    let input = input.context("Failed to convert, no input file given.")?;
+    let output = output.unwrap_or_else(|| input.with_extension("ugm"));
+
+    let model = read_model(&input, opt)?;
+    let mut writer = BufWriter::new(
+        File::create(&output).with_context(|| format!("Failed to create {:?}.", output))?,
+    );
+    if output
+        .extension()
+        .is_some_and(|extension| extension == "glb")
+    {
+        model.write_glb(&mut writer)?;
+    } else {
+        model.write_ugm(&mut writer)?;
+    }
+    writer.flush()?;
+
+    println!("{} -> {}", input.display(), output.display());
+    Ok(())
+}
+
+fn inspect(args: &[String]) -> Result<()> {
+    let [input] = args else {
+        anyhow::bail!("Failed to inspect, expected exactly one input file.");
+    };
+    let model = read_model(Path::new(input), ParseOptions::default())?;
+    let stats = model.stats();
+
+    println!("{}", input);
+    println!("bounds {:?} to {:?}", model.bounds_min, model.bounds_max);
+
+    println!(
+        "\nnodes ({}, depth {})",
+        model.nodes.len(),
+        stats.max_node_depth
+    );
+    let mut stack: Vec<(u32, usize)> = model
+        .root_node_indices
+        .iter()
+        .rev()
+        .map(|node_idx| (*node_idx, 0))
+        .collect();
+    while let Some((node_idx, depth)) = stack.pop() {
+        let node = &model.nodes[node_idx as usize];
+        match node.mesh_idx {
+            Some(mesh_idx) => println!(
+                "{}{} [mesh {}]",
+                "  ".repeat(depth + 1),
+                node.name,
+                mesh_idx
+            ),
+            None => println!("{}{}", "  ".repeat(depth + 1), node.name),
+        }
+        stack.extend(
+            node.child_node_indices
+                .iter()
+                .rev()
+                .map(|child_idx| (*child_idx, depth + 1)),
+        );
+    }
+
+    println!("\nmeshes ({})", model.meshes.len());
+    for (mesh_idx, (mesh, mesh_stats)) in model.meshes.iter().zip(&stats.meshes).enumerate() {
+        println!(
+            "  {} {}: {} vertices, {} triangles, materials {:?}{}",
+            mesh_idx,
+            mesh_stats.name,
+            mesh_stats.num_vertices,
+            mesh_stats.num_triangles,
+            mesh.material_indices,
+            if mesh.quantized_vertices.is_some() {
+                ", quantized"
+            } else {
+                ""
+            }
+        );
+    }
+    println!(
+        "  total: {} vertices, {} triangles",
+        stats.num_vertices(),
+        stats.num_triangles()
+    );
+
+    println!("\nmaterials ({})", model.materials.len());
+    for (material_idx, material) in model.materials.iter().enumerate() {
+        println!(
+            "  {} {}: color {:?}, metallic {}, roughness {}, {}, textures {:?}",
+            material_idx,
+            material.name,
+            material.color,
+            material.metallic,
+            material.roughness,
+            if material.is_opaque {
+                "opaque"
+            } else {
+                "transparent"
+            },
+            material
+                .textures()
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    println!("\ntextures ({})", model.textures.len());
+    for (texture_idx, texture) in model.textures.iter().enumerate() {
+        println!(
+            "  {} {}: {:?} {:?} {}x{}, {} mips, {} bytes",
+            texture_idx,
+            texture.name(),
+            texture.format(),
+            texture.color_space(),
+            texture.width(),
+            texture.height(),
+            texture.mip_count(),
+            texture.data().len()
+        );
+    }
+    for (format, bytes) in &stats.texture_bytes_per_format {
+        println!("  {:?}: {} bytes", format, bytes);
+    }
+    println!("  total: {} bytes", stats.texture_bytes);
+
+    let mut sections = model.serialized_layout();
+    let total: usize = sections.iter().map(|section| section.bytes).sum();
+    sections.sort_by_key(|section| std::cmp::Reverse(section.bytes));
+    println!("\nserialized sections, largest first");
+    for section in &sections {
+        println!(
+            "  {:>12} bytes {:>5.1}% {:?} {}",
+            section.bytes,
+            section.bytes as f64 / total.max(1) as f64 * 100.0,
+            section.kind,
+            section.name
+        );
+    }
+    println!("  {:>12} bytes total", total);
+
+    Ok(())
+}
+
+/// Parse a glb or read a ugm container, see [`Model::from_reader`]
+fn read_model(path: &Path, opt: ParseOptions) -> Result<Model> {
+    let file = File::open(path).with_context(|| format!("Failed to open {:?}.", path))?;
+    Model::from_reader(&mut BufReader::new(file), opt)
+        .with_context(|| format!("Failed to read {:?}.", path))
+}
@@ -0,0 +1,238 @@
+//! Triangle bounding volume hierarchies for cpu ray tracing and physics queries
+
+use glam::Vec3;
+use speedy::{Readable, Writable};
+
+use crate::{mesh::Mesh, Model};
+
+/// Number of bins along the split axis evaluated by the surface area heuristic
+const NUM_BINS: usize = 16;
+/// Nodes with this many triangles or fewer are never split
+const MIN_SPLIT_TRIANGLES: u32 = 2;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Readable, Writable)]
+pub struct BvhNode {
+    pub bounds_min: [f32; 3],
+    pub bounds_max: [f32; 3],
+    /// Index of the left child for interior nodes, the right child directly follows it.
+    /// Offset into [`Bvh::triangle_indices`] for leaves
+    pub left_first: u32,
+    /// Number of triangles in a leaf, 0 for interior nodes
+    pub triangle_count: u32,
+}
+
+impl BvhNode {
+    pub fn is_leaf(&self) -> bool {
+        self.triangle_count > 0
+    }
+}
+
+/// Binned sah bvh over the triangles of a mesh, the root is the first node
+#[derive(Debug, Clone, Default, PartialEq, Readable, Writable)]
+pub struct Bvh {
+    pub nodes: Vec<BvhNode>,
+    /// Mesh triangle indices, ordered such that every leaf references a contiguous range
+    pub triangle_indices: Vec<u32>,
+}
+
+#[derive(Clone, Copy)]
+struct Bounds {
+    min: Vec3,
+    max: Vec3,
+}
+
+impl Bounds {
+    const EMPTY: Self = Self {
+        min: Vec3::INFINITY,
+        max: Vec3::NEG_INFINITY,
+    };
+
+    fn grow(&mut self, point: Vec3) {
+        self.min = self.min.min(point);
+        self.max = self.max.max(point);
+    }
+
+    fn union(&mut self, other: &Self) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    fn surface_area(&self) -> f32 {
+        if self.min.cmpgt(self.max).any() {
+            return 0.0;
+        }
+
+        let extent = self.max - self.min;
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+}
+
+/// Best split found by [`find_split`], triangles in bins up to and including `bin` go left
+struct Split {
+    axis: usize,
+    bin: usize,
+}
+
+fn bin_of(centroid: f32, min: f32, extent: f32) -> usize {
+    (((centroid - min) / extent * NUM_BINS as f32) as usize).min(NUM_BINS - 1)
+}
+
+/// Lowest cost binned split, `None` if no split is cheaper than a single leaf
+fn find_split(
+    triangles: &[[Vec3; 3]],
+    centroids: &[Vec3],
+    triangle_indices: &[u32],
+    bounds: &Bounds,
+    centroid_bounds: &Bounds,
+) -> Option<Split> {
+    let leaf_cost = triangle_indices.len() as f32 * bounds.surface_area();
+
+    let mut best: Option<(f32, Split)> = None;
+    let axes = centroid_bounds
+        .min
+        .to_array()
+        .into_iter()
+        .zip(centroid_bounds.max.to_array());
+    for (axis, (min, max)) in axes.enumerate() {
+        let extent = max - min;
+        if extent <= 0.0 {
+            continue;
+        }
+
+        let mut bin_bounds = [Bounds::EMPTY; NUM_BINS];
+        let mut bin_counts = [0u32; NUM_BINS];
+        for triangle_idx in triangle_indices {
+            let bin = bin_of(centroids[*triangle_idx as usize][axis], min, extent);
+            bin_counts[bin] += 1;
+            for vertex in triangles[*triangle_idx as usize] {
+                bin_bounds[bin].grow(vertex);
+            }
+        }
+
+        // Sweep from the right to get the cost of everything right of each plane
+        let mut right_costs = [0.0; NUM_BINS];
+        let mut right_bounds = Bounds::EMPTY;
+        let mut right_count = 0;
+        for bin in (1..NUM_BINS).rev() {
+            right_bounds.union(&bin_bounds[bin]);
+            right_count += bin_counts[bin];
+            right_costs[bin - 1] = right_count as f32 * right_bounds.surface_area();
+        }
+
+        let mut left_bounds = Bounds::EMPTY;
+        let mut left_count = 0;
+        for bin in 0..NUM_BINS - 1 {
+            left_bounds.union(&bin_bounds[bin]);
+            left_count += bin_counts[bin];
+            let cost = left_count as f32 * left_bounds.surface_area() + right_costs[bin];
+            if cost < leaf_cost && best.as_ref().is_none_or(|(best_cost, _)| cost < *best_cost) {
+                best = Some((cost, Split { axis, bin }));
+            }
+        }
+    }
+
+    best.map(|(_, split)| split)
+}
+
+impl Mesh {
+    /// Build a bvh over all triangles using binned surface area heuristic splits
+    pub fn build_bvh(&self) -> Bvh {
+        let indices = self.indices.to_u32();
+        let triangles: Vec<[Vec3; 3]> = indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                [0, 1, 2]
+                    .map(|i| Vec3::from_array(self.packed_vertices[triangle[i] as usize].position))
+            })
+            .collect();
+        let centroids: Vec<Vec3> = triangles
+            .iter()
+            .map(|[a, b, c]| (*a + *b + *c) / 3.0)
+            .collect();
+
+        if triangles.is_empty() {
+            return Bvh::default();
+        }
+
+        let mut bvh = Bvh {
+            nodes: vec![BvhNode {
+                triangle_count: triangles.len() as u32,
+                ..Default::default()
+            }],
+            triangle_indices: (0..triangles.len() as u32).collect(),
+        };
+
+        let mut stack = vec![0];
+        while let Some(node_idx) = stack.pop() {
+            let node = bvh.nodes[node_idx];
+            let first = node.left_first as usize;
+            let count = node.triangle_count as usize;
+            let node_triangles = &mut bvh.triangle_indices[first..first + count];
+
+            let mut bounds = Bounds::EMPTY;
+            let mut centroid_bounds = Bounds::EMPTY;
+            for triangle_idx in node_triangles.iter() {
+                for vertex in triangles[*triangle_idx as usize] {
+                    bounds.grow(vertex);
+                }
+                centroid_bounds.grow(centroids[*triangle_idx as usize]);
+            }
+            bvh.nodes[node_idx].bounds_min = bounds.min.to_array();
+            bvh.nodes[node_idx].bounds_max = bounds.max.to_array();
+
+            if node.triangle_count <= MIN_SPLIT_TRIANGLES {
+                continue;
+            }
+            let Some(split) = find_split(
+                &triangles,
+                &centroids,
+                node_triangles,
+                &bounds,
+                &centroid_bounds,
+            ) else {
+                continue;
+            };
+
+            // Partition in place, left triangles move to the front
+            let min = centroid_bounds.min[split.axis];
+            let extent = centroid_bounds.max[split.axis] - min;
+            let mut left_count = 0;
+            for i in 0..count {
+                let centroid = centroids[node_triangles[i] as usize][split.axis];
+                if bin_of(centroid, min, extent) <= split.bin {
+                    node_triangles.swap(i, left_count);
+                    left_count += 1;
+                }
+            }
+            if left_count == 0 || left_count == count {
+                continue;
+            }
+
+            let left_idx = bvh.nodes.len();
+            bvh.nodes.push(BvhNode {
+                left_first: first as u32,
+                triangle_count: left_count as u32,
+                ..Default::default()
+            });
+            bvh.nodes.push(BvhNode {
+                left_first: (first + left_count) as u32,
+                triangle_count: (count - left_count) as u32,
+                ..Default::default()
+            });
+            bvh.nodes[node_idx].left_first = left_idx as u32;
+            bvh.nodes[node_idx].triangle_count = 0;
+            stack.extend([left_idx, left_idx + 1]);
+        }
+
+        bvh
+    }
+}
+
+impl Model {
+    /// Build a bvh for every non empty mesh, stored with the model so it doesn't need to be rebuilt at load time, see [`Mesh::build_bvh`]
+    pub fn build_bvhs(&mut self) {
+        for mesh in &mut self.meshes {
+            mesh.bvh = (!mesh.is_empty()).then(|| mesh.build_bvh());
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Chunked ugm containers, storing the scene, every mesh and every texture as an independently addressable chunk
+//!
+//! A chunked container starts with a [`UgmHeader`] flagged [`FLAG_CHUNKED`], followed by the little endian u64 size of the serialized [`ChunkTable`],
+//! the table itself and finally all chunks. Chunks are compressed individually with the compression recorded in the header,
+//! so a game can load the geometry first and stream textures later, or load a single mesh without touching the rest of the file
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use anyhow::Result;
+use speedy::{Endianness, Readable, Writable};
+
+use crate::{
+    animation::PropertyTrack,
+    material::Material,
+    mesh::Mesh,
+    serialization::{Compression, UgmHeader, ENDIANNESS, FLAG_CHUNKED},
+    stats::serialized_size,
+    texture::{Texture, TextureInfo},
+    Model, ModelNode,
+};
+
+/// Location of a chunk, `offset` is relative to the start of the container
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Readable, Writable)]
+pub struct ChunkRange {
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// Table of contents of a chunked container
+#[derive(Debug, Clone, Default, PartialEq, Eq, Readable, Writable)]
+pub struct ChunkTable {
+    pub scene: ChunkRange,
+    pub meshes: Vec<ChunkRange>,
+    pub textures: Vec<ChunkRange>,
+}
+
+/// Everything of a model except its meshes and textures, always loaded by [`ChunkedReader::new`]
+#[derive(Debug, Clone, Readable, Writable)]
+pub struct SceneChunk {
+    pub root_node_indices: Vec<u32>,
+    pub nodes: Vec<ModelNode>,
+    pub bounds_min: [f32; 3],
+    pub bounds_max: [f32; 3],
+    pub materials: Vec<Material>,
+    /// Metadata of every texture, available without reading the texture chunks
+    pub textures: Vec<TextureInfo>,
+    pub property_tracks: Vec<PropertyTrack>,
+}
+
+/// Reads individual chunks of a container written by [`Model::write_chunked`]
+pub struct ChunkedReader<R> {
+    reader: R,
+    /// Stream position of the start of the container
+    base: u64,
+    /// Stream length, every chunk must end before it
+    end: u64,
+    header: UgmHeader,
+    table: ChunkTable,
+    scene: SceneChunk,
+}
+
+impl<R: Read + Seek> ChunkedReader<R> {
+    /// Read the header, chunk table and scene chunk, the container starts at the current position of `reader`
+    pub fn new(mut reader: R) -> Result<Self> {
+        let base = reader.stream_position()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(base))?;
+        let mut header = [0; UgmHeader::SIZE];
+        reader.read_exact(&mut header)?;
+        let header = UgmHeader::from_bytes(&header)?;
+        anyhow::ensure!(
+            header.flags & FLAG_CHUNKED != 0,
+            "Failed to read chunked ugm container, the container isn't chunked."
+        );
+
+        let mut size = [0; 8];
+        reader.read_exact(&mut size)?;
+        let size = u64::from_le_bytes(size);
+        anyhow::ensure!(
+            size <= end.saturating_sub(reader.stream_position()?),
+            "Failed to read chunked ugm container, its chunk table of {} bytes exceeds the stream.",
+            size
+        );
+        let mut table = vec![0; size as usize];
+        reader.read_exact(&mut table)?;
+        let table = ChunkTable::read_from_buffer_with_ctx(header.endianness, &table)?;
+
+        let scene = read_chunk(&mut reader, base, end, &header, table.scene)?;
+        Ok(Self {
+            reader,
+            base,
+            end,
+            header,
+            table,
+            scene,
+        })
+    }
+
+    pub fn header(&self) -> &UgmHeader {
+        &self.header
+    }
+
+    pub fn table(&self) -> &ChunkTable {
+        &self.table
+    }
+
+    pub fn scene(&self) -> &SceneChunk {
+        &self.scene
+    }
+
+    pub fn mesh_count(&self) -> usize {
+        self.table.meshes.len()
+    }
+
+    pub fn texture_count(&self) -> usize {
+        self.table.textures.len()
+    }
+
+    pub fn read_mesh(&mut self, mesh_idx: usize) -> Result<Mesh> {
+        let range = *self.table.meshes.get(mesh_idx).ok_or_else(|| {
+            anyhow::anyhow!("Failed to read mesh {}, index out of range.", mesh_idx)
+        })?;
+        read_chunk(&mut self.reader, self.base, self.end, &self.header, range)
+    }
+
+    pub fn read_texture(&mut self, texture_idx: usize) -> Result<Texture> {
+        let range = *self.table.textures.get(texture_idx).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Failed to read texture {}, index out of range.",
+                texture_idx
+            )
+        })?;
+        read_chunk(&mut self.reader, self.base, self.end, &self.header, range)
+    }
+
+    /// Read all chunks into a complete model
+    pub fn read_model(mut self) -> Result<Model> {
+        let mut model = self.read_model_without_textures()?;
+        model.textures = (0..self.texture_count())
+            .map(|texture_idx| self.read_texture(texture_idx))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(model)
+    }
+
+    /// Read the scene and all meshes into a model without textures, material texture indices refer to [`SceneChunk::textures`]
+    pub fn read_model_without_textures(&mut self) -> Result<Model> {
+        let meshes = (0..self.mesh_count())
+            .map(|mesh_idx| self.read_mesh(mesh_idx))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Model {
+            root_node_indices: self.scene.root_node_indices.clone(),
+            nodes: self.scene.nodes.clone(),
+            bounds_min: self.scene.bounds_min,
+            bounds_max: self.scene.bounds_max,
+            meshes,
+            materials: self.scene.materials.clone(),
+            textures: vec![],
+            property_tracks: self.scene.property_tracks.clone(),
+        })
+    }
+}
+
+/// Read the chunk at `range`, failing before allocating if it doesn't end before `end`
+fn read_chunk<R: Read + Seek, T: for<'a> Readable<'a, Endianness>>(
+    reader: &mut R,
+    base: u64,
+    end: u64,
+    header: &UgmHeader,
+    range: ChunkRange,
+) -> Result<T> {
+    let chunk_end = base
+        .checked_add(range.offset)
+        .and_then(|start| start.checked_add(range.size));
+    anyhow::ensure!(
+        chunk_end.is_some_and(|chunk_end| chunk_end <= end),
+        "Failed to read chunk of {} bytes at offset {}, it exceeds the stream.",
+        range.size,
+        range.offset
+    );
+
+    let mut data = vec![0; range.size as usize];
+    reader.seek(SeekFrom::Start(base + range.offset))?;
+    reader.read_exact(&mut data)?;
+
+    let data = header.compression.decompress(&data)?;
+    Ok(T::read_from_buffer_with_ctx(header.endianness, &data)?)
+}
+
+impl Model {
+    /// Serialize as chunked ugm container with every chunk compressed by `compression`, see [`ChunkedReader`]
+    pub fn write_chunked(&self, writer: &mut impl Write, compression: Compression) -> Result<()> {
+        let scene = SceneChunk {
+            root_node_indices: self.root_node_indices.clone(),
+            nodes: self.nodes.clone(),
+            bounds_min: self.bounds_min,
+            bounds_max: self.bounds_max,
+            materials: self.materials.clone(),
+            textures: self.textures.iter().map(Texture::info).collect(),
+            property_tracks: self.property_tracks.clone(),
+        };
+
+        let compress = |data: Vec<u8>| compression.compress(data);
+        let scene = compress(scene.write_to_vec_with_ctx(ENDIANNESS)?)?;
+        let meshes = self
+            .meshes
+            .iter()
+            .map(|mesh| compress(mesh.write_to_vec_with_ctx(ENDIANNESS)?))
+            .collect::<Result<Vec<_>>>()?;
+        let textures = self
+            .textures
+            .iter()
+            .map(|texture| compress(texture.write_to_vec_with_ctx(ENDIANNESS)?))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut table = ChunkTable {
+            scene: ChunkRange::default(),
+            meshes: vec![ChunkRange::default(); meshes.len()],
+            textures: vec![ChunkRange::default(); textures.len()],
+        };
+
+        // Offsets don't change the serialized size, so they're assigned after measuring it
+        let mut offset = (UgmHeader::SIZE + 8 + serialized_size(&table)) as u64;
+        let ranges = std::iter::once(&mut table.scene)
+            .chain(&mut table.meshes)
+            .chain(&mut table.textures);
+        let chunks = std::iter::once(&scene).chain(&meshes).chain(&textures);
+        for (range, chunk) in ranges.zip(chunks.clone()) {
+            *range = ChunkRange {
+                offset,
+                size: chunk.len() as u64,
+            };
+            offset += chunk.len() as u64;
+        }
+
+        let header = UgmHeader {
+            flags: FLAG_CHUNKED,
+            compression,
+            ..UgmHeader::new(ENDIANNESS)
+        };
+        let table = table.write_to_vec_with_ctx(ENDIANNESS)?;
+        writer.write_all(&header.to_bytes())?;
+        writer.write_all(&(table.len() as u64).to_le_bytes())?;
+        writer.write_all(&table)?;
+        for chunk in chunks {
+            writer.write_all(chunk)?;
+        }
+
+        Ok(())
+    }
+}
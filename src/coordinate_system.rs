@@ -0,0 +1,179 @@
+//! Conversion between the glTF coordinate system and the conventions of other engines
+
+use glam::{Mat3, Mat4, Vec3, Vec4, Vec4Swizzles};
+
+use crate::{
+    mesh::{Indices, Mesh},
+    packing::PackedNormalizedXyz10,
+    Model,
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Handedness {
+    #[default]
+    Right,
+    Left,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UpAxis {
+    #[default]
+    Y,
+    Z,
+}
+
+/// Axis conventions of a model, the default matches glTF.
+/// Z up systems rotate glTF forward (+z) to -y, left handed systems mirror the forward axis
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CoordinateSystem {
+    pub handedness: Handedness,
+    pub up_axis: UpAxis,
+}
+
+impl CoordinateSystem {
+    pub const GLTF: Self = Self {
+        handedness: Handedness::Right,
+        up_axis: UpAxis::Y,
+    };
+
+    /// Maps glTF coordinates to this coordinate system
+    fn basis_from_gltf(&self) -> Mat3 {
+        match (self.handedness, self.up_axis) {
+            (Handedness::Right, UpAxis::Y) => Mat3::IDENTITY,
+            (Handedness::Left, UpAxis::Y) => Mat3::from_diagonal(Vec3::new(1.0, 1.0, -1.0)),
+            (Handedness::Right, UpAxis::Z) => Mat3::from_cols(Vec3::X, Vec3::Z, Vec3::NEG_Y),
+            (Handedness::Left, UpAxis::Z) => Mat3::from_cols(Vec3::X, Vec3::Z, Vec3::Y),
+        }
+    }
+
+    /// Orthonormal change of basis from `self` to `target`
+    pub fn conversion_to(&self, target: &CoordinateSystem) -> Mat3 {
+        target.basis_from_gltf() * self.basis_from_gltf().transpose()
+    }
+}
+
+fn transform_point(basis: Mat3, point: &mut [f32; 3]) {
+    *point = (basis * Vec3::from_array(*point)).to_array();
+}
+
+fn transform_packed(basis: Mat3, dir: &mut PackedNormalizedXyz10) {
+    *dir = PackedNormalizedXyz10::new(basis * dir.unpack());
+}
+
+/// Swap the last two corners of every triangle
+fn flip_winding<T>(indices: &mut [T]) {
+    for triangle in indices.chunks_exact_mut(3) {
+        triangle.swap(1, 2);
+    }
+}
+
+impl Mesh {
+    /// Apply an orthonormal change of basis to all geometry, flipping triangle winding and tangent handiness when `basis` mirrors
+    pub fn change_basis(&mut self, basis: Mat3) {
+        let is_quantized = self.quantized_vertices.is_some();
+        self.dequantize_positions();
+
+        let mirrors = basis.determinant() < 0.0;
+        let handiness_sign = if mirrors { -1.0 } else { 1.0 };
+
+        for vertex in &mut self.packed_vertices {
+            transform_point(basis, &mut vertex.position);
+            transform_packed(basis, &mut vertex.normal);
+            transform_packed(basis, &mut vertex.tangent);
+            vertex.tangent_handiness *= handiness_sign;
+        }
+        for vertex in &mut self.unpacked_vertices {
+            transform_point(basis, &mut vertex.position);
+            transform_point(basis, &mut vertex.normal);
+            let tangent = Vec4::from_array(vertex.tangent);
+            let tangent = (basis * tangent.xyz()).extend(tangent.w * handiness_sign);
+            vertex.tangent = tangent.to_array();
+        }
+
+        let corners = [
+            basis * Vec3::from_array(self.bounds_min),
+            basis * Vec3::from_array(self.bounds_max),
+        ];
+        self.bounds_min = corners[0].min(corners[1]).to_array();
+        self.bounds_max = corners[0].max(corners[1]).to_array();
+
+        if let Some(shadow_proxy) = &mut self.shadow_proxy {
+            for position in &mut shadow_proxy.positions {
+                transform_point(basis, position);
+            }
+            if mirrors {
+                flip_winding(&mut shadow_proxy.indices);
+            }
+        }
+        if let Some(meshlets) = &mut self.meshlets {
+            for meshlet in &mut meshlets.meshlets {
+                transform_point(basis, &mut meshlet.center);
+                transform_point(basis, &mut meshlet.cone_apex);
+                transform_point(basis, &mut meshlet.cone_axis);
+            }
+        }
+        if let Some(bvh) = &mut self.bvh {
+            for node in &mut bvh.nodes {
+                let corners = [
+                    basis * Vec3::from_array(node.bounds_min),
+                    basis * Vec3::from_array(node.bounds_max),
+                ];
+                node.bounds_min = corners[0].min(corners[1]).to_array();
+                node.bounds_max = corners[0].max(corners[1]).to_array();
+            }
+        }
+
+        if mirrors {
+            let mut indices = self.indices.to_u32().into_owned();
+            flip_winding(&mut indices);
+            self.indices = Indices::new(indices, self.packed_vertices.len());
+            if let Some(meshlets) = &mut self.meshlets {
+                flip_winding(&mut meshlets.triangles);
+            }
+        }
+
+        if is_quantized {
+            self.quantize_positions();
+        } else {
+            self.refresh_position_stream();
+        }
+    }
+}
+
+impl Model {
+    /// Convert node transforms and all mesh geometry from the coordinate system `from` to `to`
+    pub fn convert_coordinate_system(&mut self, from: CoordinateSystem, to: CoordinateSystem) {
+        if from == to {
+            return;
+        }
+
+        let basis = from.conversion_to(&to);
+        for mesh in &mut self.meshes {
+            mesh.change_basis(basis);
+        }
+
+        let basis = Mat4::from_mat3(basis);
+        for node in &mut self.nodes {
+            let transform = basis * Mat4::from_cols_array(&node.transform) * basis.transpose();
+            node.transform = transform.to_cols_array();
+        }
+
+        (self.bounds_min, self.bounds_max) = self.compute_world_bounds(Mat4::IDENTITY);
+    }
+
+    /// Scale the model by a positive `scale` through its root transforms, leaving mesh data untouched
+    pub fn scale_uniformly(&mut self, scale: f32) {
+        if scale == 1.0 {
+            return;
+        }
+
+        let scale_transform = Mat4::from_scale(Vec3::splat(scale));
+        for root_idx in &self.root_node_indices {
+            let node = &mut self.nodes[*root_idx as usize];
+            node.transform =
+                (scale_transform * Mat4::from_cols_array(&node.transform)).to_cols_array();
+        }
+        self.bounds_min = (Vec3::from_array(self.bounds_min) * scale).to_array();
+        self.bounds_max = (Vec3::from_array(self.bounds_max) * scale).to_array();
+    }
+}
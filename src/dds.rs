@@ -0,0 +1,143 @@
+//! DDS writer using the DX10 header extension
+
+use std::io::{self, Write};
+
+use crate::texture::{
+    ColorSpace, CompressedTextureFormat, Texture, TextureDimension, TextureFormat,
+    UncompressedTextureFormat,
+};
+
+const MAGIC: &[u8; 4] = b"DDS ";
+
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_LINEARSIZE: u32 = 0x80000;
+
+const DDPF_FOURCC: u32 = 0x4;
+
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x400000;
+
+const DDSCAPS2_CUBEMAP_ALL_FACES: u32 = 0xfe00;
+
+const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+const D3D10_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+fn dxgi_format(format: TextureFormat) -> Option<u32> {
+    match format {
+        TextureFormat::Uncompressed(format) => Some(match format {
+            UncompressedTextureFormat::R8Unorm => 61,
+            UncompressedTextureFormat::Rg8Unorm => 49,
+            UncompressedTextureFormat::Rgba8Unorm => 28,
+            UncompressedTextureFormat::R16Unorm => 56,
+            UncompressedTextureFormat::Rg16Unorm => 35,
+            UncompressedTextureFormat::Rgba16Unorm => 11,
+            UncompressedTextureFormat::Rgba32Float => 2,
+        }),
+        TextureFormat::Compressed(format) => match format {
+            CompressedTextureFormat::Bc1RgbaUnorm => Some(71),
+            CompressedTextureFormat::Bc4RUnorm => Some(80),
+            CompressedTextureFormat::Bc5RgUnorm => Some(83),
+            CompressedTextureFormat::Bc6hRgbUfloat => Some(95),
+            CompressedTextureFormat::Bc7RgbaUnorm => Some(98),
+            CompressedTextureFormat::Etc1
+            | CompressedTextureFormat::Etc2Rgb8Unorm
+            | CompressedTextureFormat::Etc2Rgba8Unorm
+            | CompressedTextureFormat::EacR11Unorm
+            | CompressedTextureFormat::EacRg11Unorm => None,
+        },
+    }
+}
+
+/// Srgb variant of a unorm dxgi format, if there is one
+fn srgb_dxgi_format(dxgi_format: u32) -> Option<u32> {
+    match dxgi_format {
+        28 => Some(29),
+        71 => Some(72),
+        98 => Some(99),
+        _ => None,
+    }
+}
+
+pub(crate) fn write_dds(texture: &Texture, writer: &mut impl Write) -> io::Result<()> {
+    let format = texture.format();
+    let Some(dxgi_format) = dxgi_format(format) else {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{:?} can't be stored in a dds file.", format),
+        ));
+    };
+    let dxgi_format = srgb_dxgi_format(dxgi_format)
+        .filter(|_| texture.color_space() == ColorSpace::Srgb)
+        .unwrap_or(dxgi_format);
+
+    let mip_count = texture.mip_count();
+    let is_cube = texture.dimension() == TextureDimension::Cube;
+
+    let (pitch_flag, pitch_or_linear_size) = match format {
+        TextureFormat::Uncompressed(_) => (DDSD_PITCH, format.bytes_per_row(texture.width())),
+        TextureFormat::Compressed(_) => (DDSD_LINEARSIZE, texture.layer_data(0, 0).len()),
+    };
+
+    let mut caps = DDSCAPS_TEXTURE;
+    if mip_count > 1 {
+        caps |= DDSCAPS_COMPLEX | DDSCAPS_MIPMAP;
+    }
+    let mut caps2 = 0;
+    let mut misc_flag = 0;
+    if is_cube {
+        caps |= DDSCAPS_COMPLEX;
+        caps2 |= DDSCAPS2_CUBEMAP_ALL_FACES;
+        misc_flag |= D3D10_RESOURCE_MISC_TEXTURECUBE;
+    }
+
+    let mut header = Vec::with_capacity(4 + 124 + 20);
+    header.extend_from_slice(MAGIC);
+    for value in [
+        124,
+        DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT | DDSD_MIPMAPCOUNT | pitch_flag,
+        texture.height(),
+        texture.width(),
+        pitch_or_linear_size as u32,
+        0,
+        mip_count,
+    ] {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+    header.extend_from_slice(&[0; 11 * 4]);
+
+    // Pixel format, deferring to the dx10 header
+    header.extend_from_slice(&32u32.to_le_bytes());
+    header.extend_from_slice(&DDPF_FOURCC.to_le_bytes());
+    header.extend_from_slice(b"DX10");
+    header.extend_from_slice(&[0; 5 * 4]);
+
+    for value in [caps, caps2, 0, 0, 0] {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+
+    for value in [
+        dxgi_format,
+        D3D10_RESOURCE_DIMENSION_TEXTURE2D,
+        misc_flag,
+        1,
+        0,
+    ] {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // Layers are stored one after another, each with all of its mips
+    writer.write_all(&header)?;
+    for layer in 0..texture.layer_count() {
+        for mip in 0..mip_count {
+            writer.write_all(texture.layer_data(mip, layer))?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+//! Minimal EAC block encoder, used for ETC2 alpha and the R11/RG11 formats, and the R11/RG11 decoder
+
+use crate::bc::decode_blocks;
+
+const MODIFIER_TABLES: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+#[derive(Clone, Copy)]
+pub(crate) enum EacMode {
+    /// 8 bit values, used as ETC2 alpha channel
+    #[cfg_attr(not(feature = "intel_tex_2"), allow(dead_code))]
+    Alpha8,
+    /// 11 bit values, used by EAC R11 and RG11
+    Unorm11,
+}
+
+impl EacMode {
+    /// Returns (codeword scale, codeword offset, max value)
+    fn params(&self) -> (i32, i32, i32) {
+        match self {
+            Self::Alpha8 => (1, 0, 255),
+            Self::Unorm11 => (8, 4, 2047),
+        }
+    }
+
+    fn decode(&self, base: i32, multiplier: i32, modifier: i32) -> i32 {
+        let (scale, offset, max) = self.params();
+        // A zero multiplier of 11 bit blocks steps by single values instead
+        let step = match self {
+            Self::Unorm11 if multiplier == 0 => 1,
+            _ => multiplier * scale,
+        };
+        (base * scale + offset + modifier * step).clamp(0, max)
+    }
+}
+
+/// Decode 8 byte EAC R11 blocks into 16 bit unorm values, ordered row major
+pub(crate) fn decode_r11(data: &[u8], width: u32, height: u32) -> Vec<u16> {
+    decode_blocks(data, width, height, 8, |block| {
+        decode_block(block, EacMode::Unorm11).map(|value| [unorm11_to_unorm16(value)])
+    })
+}
+
+/// Decode 16 byte EAC RG11 blocks into interleaved 16 bit unorm values, ordered row major
+pub(crate) fn decode_rg11(data: &[u8], width: u32, height: u32) -> Vec<u16> {
+    decode_blocks(data, width, height, 16, |block| {
+        let r = decode_block(&block[..8], EacMode::Unorm11);
+        let g = decode_block(&block[8..], EacMode::Unorm11);
+        std::array::from_fn(|i| [unorm11_to_unorm16(r[i]), unorm11_to_unorm16(g[i])])
+    })
+}
+
+/// Values of a single block, ordered row major
+fn decode_block(block: &[u8], mode: EacMode) -> [i32; 16] {
+    let bits = u64::from_be_bytes(block[..8].try_into().unwrap());
+    let base = (bits >> 56) as i32;
+    let multiplier = ((bits >> 52) & 0xf) as i32;
+    let table = ((bits >> 48) & 0xf) as usize;
+
+    std::array::from_fn(|i| {
+        // EAC orders pixels column major, the first pixel in the highest bits
+        let pixel = (i % 4) * 4 + i / 4;
+        let index = ((bits >> (45 - 3 * pixel)) & 0x7) as usize;
+        mode.decode(base, multiplier, MODIFIER_TABLES[table][index])
+    })
+}
+
+fn unorm11_to_unorm16(value: i32) -> u16 {
+    ((value * 65535 + 1023) / 2047) as u16
+}
+
+/// Compress a single channel of 8 bit data into 8 byte EAC blocks, ordered row major
+#[cfg(feature = "intel_tex_2")]
+pub(crate) fn compress_channel(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    num_channels: usize,
+    channel: usize,
+    mode: EacMode,
+) -> Vec<[u8; 8]> {
+    let (_, _, max) = mode.params();
+
+    let mut blocks = Vec::with_capacity((width.div_ceil(4) * height.div_ceil(4)) as usize);
+    for block_y in 0..height.div_ceil(4) {
+        for block_x in 0..width.div_ceil(4) {
+            let mut values = [0i32; 16];
+            for x in 0..4 {
+                for y in 0..4 {
+                    let px = (block_x * 4 + x).min(width - 1) as usize;
+                    let py = (block_y * 4 + y).min(height - 1) as usize;
+                    let value = data[(py * width as usize + px) * num_channels + channel] as i32;
+
+                    // EAC orders pixels column major
+                    values[(x * 4 + y) as usize] = (value * max + 127) / 255;
+                }
+            }
+
+            blocks.push(compress_block(&values, mode));
+        }
+    }
+
+    blocks
+}
+
+#[cfg(feature = "intel_tex_2")]
+fn compress_block(values: &[i32; 16], mode: EacMode) -> [u8; 8] {
+    let (scale, offset, _) = mode.params();
+    let min = *values.iter().min().unwrap();
+    let max = *values.iter().max().unwrap();
+
+    let evaluate = |base: i32, multiplier: i32, table: usize| -> (i64, u64) {
+        let mut error = 0i64;
+        let mut indices = 0u64;
+        for value in values {
+            let (index, value_error) = MODIFIER_TABLES[table]
+                .iter()
+                .map(|modifier| (mode.decode(base, multiplier, *modifier) - value).abs() as i64)
+                .enumerate()
+                .min_by_key(|(_, error)| *error)
+                .unwrap();
+
+            error += value_error * value_error;
+            indices = (indices << 3) | index as u64;
+        }
+
+        (error, indices)
+    };
+
+    // Estimate base and multiplier per table, then refine the best candidate
+    let mut best = (i64::MAX, 0, 1, 0, 0u64);
+    for (table, modifiers) in MODIFIER_TABLES.iter().enumerate() {
+        let span = modifiers[7] - modifiers[3];
+        let multiplier = ((max - min) as f32 / (span * scale) as f32).round() as i32;
+        let multiplier = multiplier.clamp(1, 15);
+
+        let center = (min + max) / 2 - (modifiers[7] + modifiers[3]) * multiplier * scale / 2;
+        let base = ((center - offset) as f32 / scale as f32).round() as i32;
+        let base = base.clamp(0, 255);
+
+        let (error, indices) = evaluate(base, multiplier, table);
+        if error < best.0 {
+            best = (error, base, multiplier, table, indices);
+        }
+    }
+
+    let (_, best_base, best_multiplier, table, _) = best;
+    for base in (best_base - 1).max(0)..=(best_base + 1).min(255) {
+        for multiplier in (best_multiplier - 1).max(1)..=(best_multiplier + 1).min(15) {
+            let (error, indices) = evaluate(base, multiplier, table);
+            if error < best.0 {
+                best = (error, base, multiplier, table, indices);
+            }
+        }
+    }
+
+    let (_, base, multiplier, table, indices) = best;
+    let bits =
+        ((base as u64) << 56) | ((multiplier as u64) << 52) | ((table as u64) << 48) | indices;
+    bits.to_be_bytes()
+}
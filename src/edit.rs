@@ -0,0 +1,329 @@
+//! Editing of models with derived data maintained incrementally, for tools applying many small edits
+
+use std::ops::Deref;
+
+use glam::{Mat4, Vec3};
+
+use crate::{
+    material::Material,
+    mesh::Mesh,
+    stats::{MeshStats, ModelStats},
+    texture::{TextureUsage, TextureUsages},
+    transform_bounds, Model, ModelNode,
+};
+
+/// Model with its bounds, mesh flags, sort keys and stats kept up to date across edits.
+/// Edits only touch what they change, derived data they make out of date is brought up to date by [`EditableModel::update_derived`]
+#[derive(Debug, Clone)]
+pub struct EditableModel {
+    model: Model,
+    /// Model bounds shrunk by a removed or replaced mesh, or changed by a node transform
+    dirty_bounds: bool,
+    /// Meshes whose opaque and emissive flags and sort keys are out of date with their materials
+    dirty_meshes: Vec<u32>,
+    /// Kept up to date with every edit
+    stats: ModelStats,
+}
+
+/// Returns true if the bounds `min` to `max` touch the boundary of the bounds `outer_min` to `outer_max`
+fn touches_boundary(
+    min: [f32; 3],
+    max: [f32; 3],
+    outer_min: [f32; 3],
+    outer_max: [f32; 3],
+) -> bool {
+    Vec3::from_array(min)
+        .cmple(Vec3::from_array(outer_min))
+        .any()
+        || Vec3::from_array(max)
+            .cmpge(Vec3::from_array(outer_max))
+            .any()
+}
+
+impl EditableModel {
+    pub fn new(model: Model) -> Self {
+        Self {
+            stats: ModelStats::new(&model),
+            model,
+            dirty_bounds: false,
+            dirty_meshes: vec![],
+        }
+    }
+
+    pub fn model(&self) -> &Model {
+        &self.model
+    }
+
+    /// Bring derived data up to date and return the edited model
+    pub fn into_model(mut self) -> Model {
+        self.update_derived();
+        self.model
+    }
+
+    /// Returns true if edits left derived data out of date
+    pub fn is_dirty(&self) -> bool {
+        self.dirty_bounds || !self.dirty_meshes.is_empty()
+    }
+
+    pub fn stats(&self) -> &ModelStats {
+        &self.stats
+    }
+
+    /// Recompute derived data invalidated by edits, only touching meshes that changed
+    pub fn update_derived(&mut self) {
+        let model = &mut self.model;
+        for mesh_idx in std::mem::take(&mut self.dirty_meshes) {
+            let mesh = &mut model.meshes[mesh_idx as usize];
+            mesh.opaque = mesh
+                .material_indices
+                .iter()
+                .all(|material_idx| model.materials[*material_idx as usize].is_opaque);
+            mesh.is_emissive = mesh
+                .material_indices
+                .iter()
+                .any(|material_idx| model.materials[*material_idx as usize].is_emissive());
+            mesh.compute_sort_keys(&model.materials);
+        }
+
+        if self.dirty_bounds {
+            (model.bounds_min, model.bounds_max) = model.compute_world_bounds(Mat4::IDENTITY);
+            self.dirty_bounds = false;
+        }
+    }
+
+    /// Add a mesh, not instanced by any node yet so the model bounds stay the same, returns its index
+    pub fn add_mesh(&mut self, mesh: Mesh) -> u32 {
+        let mesh_idx = self.model.meshes.len() as u32;
+
+        self.stats.meshes.push(MeshStats::new(&mesh));
+        self.stats.update_max_quantization_error();
+        self.model.meshes.push(mesh);
+        self.mark_mesh(mesh_idx);
+
+        mesh_idx
+    }
+
+    /// Replace the mesh at `mesh_idx`, returns the previous mesh
+    pub fn replace_mesh(&mut self, mesh_idx: u32, mesh: Mesh) -> Mesh {
+        self.grow_bounds(mesh_idx, &mesh);
+        self.stats.meshes[mesh_idx as usize] = MeshStats::new(&mesh);
+        self.stats.update_max_quantization_error();
+        let previous = std::mem::replace(&mut self.model.meshes[mesh_idx as usize], mesh);
+        self.shrink_bounds(mesh_idx, &previous);
+        self.mark_mesh(mesh_idx);
+
+        previous
+    }
+
+    /// Remove the mesh at `mesh_idx`, nodes instancing it are left without mesh and indices of later meshes shift down
+    pub fn remove_mesh(&mut self, mesh_idx: u32) -> Mesh {
+        let mesh = self.model.meshes.remove(mesh_idx as usize);
+        self.shrink_bounds(mesh_idx, &mesh);
+        self.stats.meshes.remove(mesh_idx as usize);
+        self.stats.update_max_quantization_error();
+
+        for node in &mut self.model.nodes {
+            node.mesh_idx = match node.mesh_idx {
+                Some(idx) if idx == mesh_idx => None,
+                Some(idx) if idx > mesh_idx => Some(idx - 1),
+                idx => idx,
+            };
+        }
+
+        self.dirty_meshes = std::mem::take(&mut self.dirty_meshes)
+            .into_iter()
+            .filter(|idx| *idx != mesh_idx)
+            .map(|idx| if idx > mesh_idx { idx - 1 } else { idx })
+            .collect();
+
+        mesh
+    }
+
+    /// Replace the material at `material_idx`, marking all meshes using it dirty
+    pub fn replace_material(&mut self, material_idx: u32, material: Material) -> Material {
+        for mesh_idx in 0..self.model.meshes.len() as u32 {
+            if self.model.meshes[mesh_idx as usize]
+                .material_indices
+                .contains(&material_idx)
+            {
+                self.mark_mesh(mesh_idx);
+            }
+        }
+
+        let previous =
+            std::mem::replace(&mut self.model.materials[material_idx as usize], material);
+        self.model.update_texture_usages();
+
+        previous
+    }
+
+    /// Same as [`Model::remove_unused_textures`], keeping the stats up to date
+    pub fn remove_unused_textures(&mut self) -> Vec<Option<u32>> {
+        let remap = self.model.remove_unused_textures();
+        self.stats.update_textures(&self.model.textures);
+
+        remap
+    }
+
+    /// Set the local transform of the node at `node_idx`, marking the world space model bounds dirty
+    pub fn set_node_transform(&mut self, node_idx: u32, transform: Mat4) {
+        self.model.nodes[node_idx as usize].transform = transform.to_cols_array();
+        self.dirty_bounds = true;
+    }
+
+    /// Same as [`Model::traverse_nodes_mut`], which leaves the model bounds up to date
+    pub fn traverse_nodes_mut<F>(&mut self, root_transform: Mat4, callback: F)
+    where
+        F: FnMut(&mut ModelNode, Mat4),
+    {
+        self.model.traverse_nodes_mut(root_transform, callback);
+        self.dirty_bounds = false;
+    }
+
+    fn mark_mesh(&mut self, mesh_idx: u32) {
+        if !self.dirty_meshes.contains(&mesh_idx) {
+            self.dirty_meshes.push(mesh_idx);
+        }
+    }
+
+    /// Grow the bounds to enclose every instance of `mesh` placed at `mesh_idx`
+    fn grow_bounds(&mut self, mesh_idx: u32, mesh: &Mesh) {
+        let transforms = self.model.instance_transforms(mesh_idx);
+        if mesh.is_empty() || transforms.is_empty() {
+            return;
+        }
+        // Zeroed bounds may not enclose anything, so they're recomputed instead
+        if self.model.bounds_min == [0.0; 3] && self.model.bounds_max == [0.0; 3] {
+            self.dirty_bounds = true;
+            return;
+        }
+
+        for transform in transforms {
+            let (min, max) = transform_bounds(mesh.bounds_min, mesh.bounds_max, transform);
+            self.model.bounds_min = Vec3::from_array(self.model.bounds_min).min(min).to_array();
+            self.model.bounds_max = Vec3::from_array(self.model.bounds_max).max(max).to_array();
+        }
+    }
+
+    /// Bounds only need to be recomputed if an instance of the removed mesh was on the boundary
+    fn shrink_bounds(&mut self, mesh_idx: u32, mesh: &Mesh) {
+        if mesh.is_empty() {
+            return;
+        }
+
+        for transform in self.model.instance_transforms(mesh_idx) {
+            let (min, max) = transform_bounds(mesh.bounds_min, mesh.bounds_max, transform);
+            if touches_boundary(
+                min.to_array(),
+                max.to_array(),
+                self.model.bounds_min,
+                self.model.bounds_max,
+            ) {
+                self.dirty_bounds = true;
+                return;
+            }
+        }
+    }
+}
+
+impl Deref for EditableModel {
+    type Target = Model;
+
+    fn deref(&self) -> &Model {
+        &self.model
+    }
+}
+
+impl From<Model> for EditableModel {
+    fn from(model: Model) -> Self {
+        Self::new(model)
+    }
+}
+
+impl Model {
+    /// Recompute the material slots referencing each texture, kept up to date by the editing functions
+    pub fn update_texture_usages(&mut self) {
+        let mut usages = vec![TextureUsages::default(); self.textures.len()];
+        for material in &self.materials {
+            for (usage, texture_idx) in TextureUsage::ALL.into_iter().zip(material.textures()) {
+                if let Some(texture_idx) = texture_idx {
+                    usages[texture_idx as usize].insert(usage);
+                }
+            }
+        }
+
+        for (texture, usages) in self.textures.iter_mut().zip(usages) {
+            texture.set_usages(usages);
+        }
+    }
+
+    /// Remove textures not referenced by any material, returns the new index of each previous texture
+    pub fn remove_unused_textures(&mut self) -> Vec<Option<u32>> {
+        let mut is_used = vec![false; self.textures.len()];
+        for material in &self.materials {
+            for texture_idx in material.textures().into_iter().flatten() {
+                is_used[texture_idx as usize] = true;
+            }
+        }
+
+        let mut remap = vec![None; self.textures.len()];
+        let mut num_used = 0;
+        for (texture_idx, is_used) in is_used.iter().enumerate() {
+            if *is_used {
+                remap[texture_idx] = Some(num_used);
+                num_used += 1;
+            }
+        }
+
+        let mut texture_idx = 0;
+        self.textures.retain(|_| {
+            texture_idx += 1;
+            is_used[texture_idx - 1]
+        });
+        for material in &mut self.materials {
+            for slot in material.textures_mut() {
+                *slot = slot.and_then(|texture_idx| remap[texture_idx as usize]);
+            }
+        }
+
+        self.update_texture_usages();
+
+        remap
+    }
+
+    /// Call `callback` for every node below the root nodes with its parent world transform, depth first.
+    /// Edits to a node's transform apply to its children, the model bounds are recomputed afterwards
+    pub fn traverse_nodes_mut<F>(&mut self, root_transform: Mat4, mut callback: F)
+    where
+        F: FnMut(&mut ModelNode, Mat4),
+    {
+        let mut stack: Vec<(u32, Mat4)> = self
+            .root_node_indices
+            .iter()
+            .rev()
+            .map(|node_idx| (*node_idx, root_transform))
+            .collect();
+        while let Some((node_idx, parent_transform)) = stack.pop() {
+            let node = &mut self.nodes[node_idx as usize];
+            callback(node, parent_transform);
+
+            let transform = parent_transform * Mat4::from_cols_array(&node.transform);
+            stack.extend(
+                node.child_node_indices
+                    .iter()
+                    .rev()
+                    .map(|child_idx| (*child_idx, transform)),
+            );
+        }
+
+        (self.bounds_min, self.bounds_max) = self.compute_world_bounds(Mat4::IDENTITY);
+    }
+
+    /// World transforms of all nodes instancing `mesh_idx`
+    fn instance_transforms(&self, mesh_idx: u32) -> Vec<Mat4> {
+        self.iter_nodes(Mat4::IDENTITY)
+            .filter(|(_, node, _)| node.mesh_idx == Some(mesh_idx))
+            .map(|(_, _, transform)| transform)
+            .collect()
+    }
+}
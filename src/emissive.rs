@@ -0,0 +1,128 @@
+//! Emissive geometry in world space, for light sampling in path tracers
+
+use glam::{Mat4, Vec3};
+
+use crate::{mesh::Mesh, Model, ModelNode};
+
+/// Rec. 709 luminance weights
+const LUMINANCE: Vec3 = Vec3::new(0.2126, 0.7152, 0.0722);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EmissiveTriangle {
+    /// World space corners
+    pub positions: [[f32; 3]; 3],
+    pub tex_coords: [[f32; 2]; 3],
+    pub mesh_idx: u32,
+    /// Index of the triangle within the mesh
+    pub triangle_idx: u32,
+    /// Model material index
+    pub material_idx: u32,
+    /// World space area
+    pub area: f32,
+    /// Area times the luminance of the material emission, emission textures are not taken into account
+    pub power: f32,
+}
+
+/// All emissive triangles of a model with a cdf for sampling them proportional to their power
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EmissiveTriangles {
+    pub triangles: Vec<EmissiveTriangle>,
+    /// Normalized inclusive prefix sum of the power of `triangles`, the last entry is 1.0
+    pub cdf: Vec<f32>,
+    pub total_power: f32,
+}
+
+impl EmissiveTriangles {
+    /// Index of the triangle picked by `u` in [0, 1) and the probability of picking it, `None` if there's no emissive power
+    pub fn sample(&self, u: f32) -> Option<(usize, f32)> {
+        if self.total_power <= 0.0 {
+            return None;
+        }
+
+        let triangle_idx = self
+            .cdf
+            .partition_point(|cdf| *cdf <= u)
+            .min(self.triangles.len() - 1);
+        Some((triangle_idx, self.pdf(triangle_idx)))
+    }
+
+    /// Probability of [`EmissiveTriangles::sample`] picking `triangle_idx`
+    pub fn pdf(&self, triangle_idx: usize) -> f32 {
+        self.triangles[triangle_idx].power / self.total_power
+    }
+}
+
+impl Model {
+    /// World space triangles of every emissive mesh instance below the root nodes, with a power weighted cdf
+    pub fn collect_emissive_triangles(&self, root_transform: Mat4) -> EmissiveTriangles {
+        let mut emissive = EmissiveTriangles::default();
+        self.traverse_nodes(root_transform, |node: &ModelNode, transform| {
+            let Some(mesh_idx) = node.mesh_idx else {
+                return;
+            };
+            let mesh = &self.meshes[mesh_idx as usize];
+            if mesh.is_emissive {
+                self.push_emissive_triangles(mesh, mesh_idx, transform, &mut emissive.triangles);
+            }
+        });
+
+        let mut total_power = 0.0;
+        emissive.cdf = emissive
+            .triangles
+            .iter()
+            .map(|triangle| {
+                total_power += triangle.power;
+                total_power
+            })
+            .collect();
+        if total_power > 0.0 {
+            for cdf in &mut emissive.cdf {
+                *cdf /= total_power;
+            }
+        }
+        emissive.total_power = total_power;
+
+        emissive
+    }
+
+    fn push_emissive_triangles(
+        &self,
+        mesh: &Mesh,
+        mesh_idx: u32,
+        transform: Mat4,
+        triangles: &mut Vec<EmissiveTriangle>,
+    ) {
+        for (triangle_idx, triangle) in mesh.indices.triangles().enumerate() {
+            let local_material_idx = mesh.triangle_material_indices[triangle_idx];
+            let material_idx = mesh.material_indices[local_material_idx as usize];
+            let radiance = Vec3::from_array(self.materials[material_idx as usize].emission);
+            if radiance.max_element() <= 0.0 {
+                continue;
+            }
+
+            let positions =
+                triangle.map(|vertex| transform.transform_point3(mesh.vertex_position(vertex)));
+            let area = (positions[1] - positions[0])
+                .cross(positions[2] - positions[0])
+                .length()
+                * 0.5;
+            if area <= 0.0 {
+                continue;
+            }
+
+            let tex_coords = triangle.map(|vertex| match &mesh.quantized_vertices {
+                Some(quantized) => quantized.vertices[vertex as usize].tex_coord,
+                None => mesh.packed_vertices[vertex as usize].tex_coord,
+            });
+            triangles.push(EmissiveTriangle {
+                positions: positions.map(|position| position.to_array()),
+                tex_coords,
+                mesh_idx,
+                triangle_idx: triangle_idx as u32,
+                material_idx,
+                area,
+                power: area * radiance.dot(LUMINANCE),
+            });
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Environment maps, converting equirectangular panoramas into cube maps
+
+use std::f32::consts::PI;
+
+use glam::{Vec3, Vec4};
+use image::{DynamicImage, Rgba32FImage};
+
+use crate::{
+    resample,
+    texture::{Texture, TextureCreateDesc},
+};
+
+/// Direction through the texel at `s`, `t` in the -1 to 1 range of cube map `face`, following the d3d and vulkan face layout
+fn face_direction(face: usize, s: f32, t: f32) -> Vec3 {
+    match face {
+        0 => Vec3::new(1.0, -t, -s),
+        1 => Vec3::new(-1.0, -t, s),
+        2 => Vec3::new(s, 1.0, t),
+        3 => Vec3::new(s, -1.0, -t),
+        4 => Vec3::new(s, -t, 1.0),
+        _ => Vec3::new(-s, -t, -1.0),
+    }
+    .normalize()
+}
+
+/// Bilinearly sample `equirect` in `direction`, with +y up and the center of the panorama facing +x
+fn sample_equirect(equirect: &Rgba32FImage, direction: Vec3) -> Vec4 {
+    let u = 0.5 + direction.z.atan2(direction.x) / (2.0 * PI);
+    let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+
+    let x = u * equirect.width() as f32 - 0.5;
+    let y = (v * equirect.height() as f32 - 0.5).clamp(0.0, equirect.height() as f32 - 1.0);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+
+    let texel = |x: f32, y: f32| {
+        // Longitude wraps around, latitude is clamped at the poles
+        let x = (x as i32).rem_euclid(equirect.width() as i32) as u32;
+        let y = (y as u32).min(equirect.height() - 1);
+        Vec4::from_array(equirect.get_pixel(x, y).0)
+    };
+
+    texel(x0, y0)
+        .lerp(texel(x0 + 1.0, y0), fx)
+        .lerp(texel(x0, y0 + 1.0).lerp(texel(x0 + 1.0, y0 + 1.0), fx), fy)
+}
+
+impl Texture {
+    /// Convert the equirectangular panorama in `desc.image` into a cube map with square faces of `face_size`,
+    /// keeping the pixel format of the panorama. The remaining options of `desc` apply to each face, precomputed mips are ignored
+    pub fn from_equirect(desc: TextureCreateDesc, face_size: u32) -> Self {
+        let equirect = desc.image.to_rgba32f();
+
+        let faces = std::array::from_fn(|face| {
+            let image = Rgba32FImage::from_fn(face_size, face_size, |x, y| {
+                let s = (x as f32 + 0.5) / face_size as f32 * 2.0 - 1.0;
+                let t = (y as f32 + 0.5) / face_size as f32 * 2.0 - 1.0;
+                image::Rgba(sample_equirect(&equirect, face_direction(face, s, t)).to_array())
+            });
+            let image: DynamicImage = resample::to_format_of(image, &desc.image);
+
+            Texture::new(TextureCreateDesc {
+                name: desc.name,
+                image,
+                mips: desc.mips,
+                max_mip_levels: desc.max_mip_levels,
+                min_mip_size: desc.min_mip_size,
+                precomputed_mips: vec![],
+                mip_filter: desc.mip_filter,
+                is_normal_map: false,
+                color_space: desc.color_space,
+                alpha_coverage_cutoff: None,
+                premultiply_alpha: false,
+                uv_offset: desc.uv_offset,
+                uv_scale: desc.uv_scale,
+                sampler: desc.sampler,
+                source_image: None,
+            })
+        });
+
+        Texture::new_cube(faces)
+    }
+}
@@ -0,0 +1,748 @@
+use std::{borrow::Cow, collections::BTreeMap, collections::HashMap, io::Cursor};
+
+use anyhow::Result;
+use glam::{Mat4, Vec3, Vec4};
+use gltf::json::{self, validation::Checked::Valid, validation::USize64};
+use image::DynamicImage;
+
+use crate::{
+    animation::{Interpolation, PropertyTarget, PropertyTrack},
+    material::AlphaMode,
+    resample,
+    texture::{AddressMode, FilterMode, SamplerDesc, Texture},
+    Model,
+};
+
+use super::{mesh_vertices, ExportSubset};
+
+/// Glb json and binary chunk under construction
+pub(crate) struct GlbWriter {
+    pub(crate) root: json::Root,
+    buffer: Vec<u8>,
+    /// KHR_animation_pointer channels and their samplers, the json types can't represent them so they're added when finishing
+    pointer_channels: Vec<(String, json::Value)>,
+}
+
+impl GlbWriter {
+    pub(crate) fn new() -> Self {
+        let mut root = json::Root::default();
+        root.asset.generator = Some(concat!("ugm ", env!("CARGO_PKG_VERSION")).to_owned());
+
+        Self {
+            root,
+            buffer: vec![],
+            pointer_channels: vec![],
+        }
+    }
+
+    pub(crate) fn push_buffer_view(
+        &mut self,
+        data: &[u8],
+        target: Option<json::buffer::Target>,
+    ) -> json::Index<json::buffer::View> {
+        let byte_offset = self.buffer.len();
+        self.buffer.extend_from_slice(data);
+        self.buffer.resize(self.buffer.len().next_multiple_of(4), 0);
+
+        self.root.push(json::buffer::View {
+            buffer: json::Index::new(0),
+            byte_length: USize64::from(data.len()),
+            byte_offset: Some(USize64::from(byte_offset)),
+            byte_stride: None,
+            extensions: Default::default(),
+            extras: Default::default(),
+            name: None,
+            target: target.map(Valid),
+        })
+    }
+
+    pub(crate) fn push_accessor<T: bytemuck::Pod>(
+        &mut self,
+        data: &[T],
+        component_type: json::accessor::ComponentType,
+        type_: json::accessor::Type,
+        target: json::buffer::Target,
+        bounds: Option<([f32; 3], [f32; 3])>,
+    ) -> json::Index<json::Accessor> {
+        let buffer_view = self.push_buffer_view(bytemuck::cast_slice(data), Some(target));
+
+        self.root.push(json::Accessor {
+            buffer_view: Some(buffer_view),
+            byte_offset: None,
+            count: USize64::from(data.len()),
+            component_type: Valid(json::accessor::GenericComponentType(component_type)),
+            extensions: Default::default(),
+            extras: Default::default(),
+            type_: Valid(type_),
+            min: bounds.map(|(min, _)| json::Value::from(Vec::from(min))),
+            max: bounds.map(|(_, max)| json::Value::from(Vec::from(max))),
+            name: None,
+            normalized: false,
+            sparse: None,
+        })
+    }
+
+    /// Float accessor without buffer view target, as used by animation samplers
+    fn push_float_accessor(
+        &mut self,
+        data: &[f32],
+        type_: json::accessor::Type,
+        bounds: Option<(f32, f32)>,
+    ) -> json::Index<json::Accessor> {
+        let buffer_view = self.push_buffer_view(bytemuck::cast_slice(data), None);
+
+        self.root.push(json::Accessor {
+            buffer_view: Some(buffer_view),
+            byte_offset: None,
+            count: USize64::from(data.len() / type_.multiplicity()),
+            component_type: Valid(json::accessor::GenericComponentType(
+                json::accessor::ComponentType::F32,
+            )),
+            extensions: Default::default(),
+            extras: Default::default(),
+            type_: Valid(type_),
+            min: bounds.map(|(min, _)| json::Value::from(vec![min])),
+            max: bounds.map(|(_, max)| json::Value::from(vec![max])),
+            name: None,
+            normalized: false,
+            sparse: None,
+        })
+    }
+
+    /// Animate the property of `track` through a KHR_animation_pointer channel, all channels share a single animation
+    pub(crate) fn push_property_track(&mut self, track: &PropertyTrack) {
+        // Input accessors require bounds
+        let bounds = track.times.iter().fold(None, |bounds, time| match bounds {
+            None => Some((*time, *time)),
+            Some((min, max)) => Some((time.min(min), time.max(max))),
+        });
+        let input = self.push_float_accessor(&track.times, json::accessor::Type::Scalar, bounds);
+        let output_type = match track.components {
+            2 => json::accessor::Type::Vec2,
+            3 => json::accessor::Type::Vec3,
+            4 => json::accessor::Type::Vec4,
+            _ => json::accessor::Type::Scalar,
+        };
+        let output = self.push_float_accessor(&track.values, output_type, None);
+
+        let interpolation = match track.interpolation {
+            Interpolation::Linear => "LINEAR",
+            Interpolation::Step => "STEP",
+            Interpolation::CubicSpline => "CUBICSPLINE",
+        };
+        let sampler = json::Value::from_iter([
+            ("input", json::Value::from(input.value())),
+            ("output", json::Value::from(output.value())),
+            ("interpolation", json::Value::from(interpolation)),
+        ]);
+        self.pointer_channels.push((track.path.pointer(), sampler));
+        self.use_extension("KHR_animation_pointer");
+    }
+
+    pub(crate) fn use_extension(&mut self, extension: &str) {
+        if !self
+            .root
+            .extensions_used
+            .iter()
+            .any(|used| used == extension)
+        {
+            self.root.extensions_used.push(extension.to_owned());
+        }
+    }
+
+    /// Embed an encoded image, e.g. png or jpeg, as texture
+    pub(crate) fn push_texture(
+        &mut self,
+        data: &[u8],
+        mime_type: &str,
+        name: &str,
+        sampler_desc: &SamplerDesc,
+    ) -> json::Index<json::Texture> {
+        let buffer_view = self.push_buffer_view(data, None);
+        let image = self.root.push(json::Image {
+            buffer_view: Some(buffer_view),
+            mime_type: Some(json::image::MimeType(mime_type.to_owned())),
+            name: Some(name.to_owned()),
+            uri: None,
+            extensions: None,
+            extras: Default::default(),
+        });
+        let sampler = self.root.push(sampler(sampler_desc));
+
+        self.root.push(json::Texture {
+            name: Some(name.to_owned()),
+            sampler: Some(sampler),
+            source: image,
+            extensions: None,
+            extras: Default::default(),
+        })
+    }
+
+    /// Add a scene with `root_nodes`, marked as default if `is_default`, and write the glb
+    pub(crate) fn finish(
+        mut self,
+        root_nodes: Vec<json::Index<json::Node>>,
+        is_default: bool,
+    ) -> Result<Vec<u8>> {
+        let scene = self.root.push(json::Scene {
+            extensions: Default::default(),
+            extras: Default::default(),
+            name: None,
+            nodes: root_nodes,
+        });
+        self.root.scene = is_default.then_some(scene);
+
+        if !self.buffer.is_empty() {
+            self.root.push(json::Buffer {
+                byte_length: USize64::from(self.buffer.len()),
+                extensions: Default::default(),
+                extras: Default::default(),
+                name: None,
+                uri: None,
+            });
+        }
+
+        let json = if self.pointer_channels.is_empty() {
+            self.root.to_string()?
+        } else {
+            let mut root = json::serialize::to_value(&self.root)?;
+            let (channels, samplers): (Vec<_>, Vec<_>) = self
+                .pointer_channels
+                .into_iter()
+                .enumerate()
+                .map(|(sampler_idx, (pointer, sampler))| {
+                    let pointer = json::Value::from_iter([("pointer", pointer)]);
+                    let target = json::Value::from_iter([
+                        ("path", json::Value::from("pointer")),
+                        (
+                            "extensions",
+                            json::Value::from_iter([("KHR_animation_pointer", pointer)]),
+                        ),
+                    ]);
+                    let channel = json::Value::from_iter([
+                        ("sampler", json::Value::from(sampler_idx)),
+                        ("target", target),
+                    ]);
+                    (channel, sampler)
+                })
+                .unzip();
+            root["animations"] = json::Value::from(vec![json::Value::from_iter([
+                ("channels", channels),
+                ("samplers", samplers),
+            ])]);
+            json::serialize::to_string(&root)?
+        };
+        let glb = gltf::binary::Glb {
+            header: gltf::binary::Header {
+                magic: *b"glTF",
+                version: 2,
+                // Computed when writing
+                length: 0,
+            },
+            json: Cow::Owned(json.into_bytes()),
+            bin: (!self.buffer.is_empty()).then_some(Cow::Owned(self.buffer)),
+        };
+
+        Ok(glb.to_vec()?)
+    }
+}
+
+struct Exporter<'a> {
+    model: &'a Model,
+    writer: GlbWriter,
+    meshes: HashMap<u32, json::Index<json::Mesh>>,
+    materials: HashMap<u32, json::Index<json::Material>>,
+    textures: HashMap<u32, Option<json::Index<json::Texture>>>,
+}
+
+pub(crate) fn export_glb(model: &Model, subset: Option<&ExportSubset>) -> Result<Vec<u8>> {
+    let mut exporter = Exporter {
+        model,
+        writer: GlbWriter::new(),
+        meshes: HashMap::new(),
+        materials: HashMap::new(),
+        textures: HashMap::new(),
+    };
+
+    let mut root_nodes = vec![];
+    match subset {
+        None => {
+            for node_idx in &model.root_node_indices {
+                root_nodes.push(exporter.export_node(*node_idx, None)?);
+            }
+        }
+        Some(ExportSubset::Nodes(node_indices)) => {
+            let parents = parent_node_indices(model);
+            for node_idx in node_indices {
+                anyhow::ensure!(
+                    (*node_idx as usize) < model.nodes.len(),
+                    "Failed to export node {}, index out of range.",
+                    node_idx
+                );
+
+                // Nodes below another selected node are already exported as part of its subtree
+                let mut ancestor = parents[*node_idx as usize];
+                let mut is_selected_subtree = false;
+                while let Some(ancestor_idx) = ancestor {
+                    is_selected_subtree |= node_indices.contains(&ancestor_idx);
+                    ancestor = parents[ancestor_idx as usize];
+                }
+                if is_selected_subtree {
+                    continue;
+                }
+
+                let mut transform = Mat4::IDENTITY;
+                let mut ancestor = Some(*node_idx);
+                while let Some(ancestor_idx) = ancestor {
+                    transform =
+                        Mat4::from_cols_array(&model.nodes[ancestor_idx as usize].transform)
+                            * transform;
+                    ancestor = parents[ancestor_idx as usize];
+                }
+
+                root_nodes.push(exporter.export_node(*node_idx, Some(transform))?);
+            }
+        }
+        Some(ExportSubset::Meshes(mesh_indices)) => {
+            for mesh_idx in mesh_indices {
+                anyhow::ensure!(
+                    (*mesh_idx as usize) < model.meshes.len(),
+                    "Failed to export mesh {}, index out of range.",
+                    mesh_idx
+                );
+
+                let mesh = exporter.export_mesh(*mesh_idx);
+                root_nodes.push(exporter.writer.root.push(json::Node {
+                    mesh: Some(mesh),
+                    name: Some(model.meshes[*mesh_idx as usize].name.clone()),
+                    ..Default::default()
+                }));
+            }
+        }
+    }
+
+    // Only tracks of exported materials are kept, pointing at their exported index
+    for track in &model.property_tracks {
+        if track.path.target != PropertyTarget::Material {
+            continue;
+        }
+        if let Some(material) = exporter.materials.get(&track.path.index) {
+            let mut track = track.clone();
+            track.path.index = material.value() as u32;
+            exporter.writer.push_property_track(&track);
+        }
+    }
+
+    exporter.writer.finish(root_nodes, true)
+}
+
+fn parent_node_indices(model: &Model) -> Vec<Option<u32>> {
+    let mut parents = vec![None; model.nodes.len()];
+    for (node_idx, node) in model.nodes.iter().enumerate() {
+        for child_idx in &node.child_node_indices {
+            parents[*child_idx as usize] = Some(node_idx as u32);
+        }
+    }
+    parents
+}
+
+impl Exporter<'_> {
+    fn export_node(
+        &mut self,
+        node_idx: u32,
+        transform: Option<Mat4>,
+    ) -> Result<json::Index<json::Node>> {
+        let node = &self.model.nodes[node_idx as usize];
+
+        let mut children = vec![];
+        for child_idx in &node.child_node_indices {
+            children.push(self.export_node(*child_idx, None)?);
+        }
+
+        let mesh = node.mesh_idx.map(|mesh_idx| self.export_mesh(mesh_idx));
+
+        let transform = transform.unwrap_or_else(|| Mat4::from_cols_array(&node.transform));
+        Ok(self.writer.root.push(json::Node {
+            children: (!children.is_empty()).then_some(children),
+            matrix: (transform != Mat4::IDENTITY).then_some(transform.to_cols_array()),
+            mesh,
+            name: Some(node.name.clone()),
+            ..Default::default()
+        }))
+    }
+
+    fn export_mesh(&mut self, mesh_idx: u32) -> json::Index<json::Mesh> {
+        if let Some(mesh) = self.meshes.get(&mesh_idx) {
+            return *mesh;
+        }
+        let model = self.model;
+        let mesh = &model.meshes[mesh_idx as usize];
+
+        let vertices = mesh_vertices(mesh);
+
+        let positions: Vec<[f32; 3]> = vertices.iter().map(|vertex| vertex.position).collect();
+        let normals: Vec<[f32; 3]> = vertices.iter().map(|vertex| vertex.normal).collect();
+        let tangents: Vec<[f32; 4]> = vertices.iter().map(|vertex| vertex.tangent).collect();
+        let tex_coords: Vec<[f32; 2]> = vertices.iter().map(|vertex| vertex.tex_coord).collect();
+
+        use json::accessor::{ComponentType, Type};
+        use json::buffer::Target;
+        use json::mesh::Semantic;
+
+        let mut attributes = BTreeMap::new();
+        attributes.insert(
+            Valid(Semantic::Positions),
+            self.writer.push_accessor(
+                &positions,
+                ComponentType::F32,
+                Type::Vec3,
+                Target::ArrayBuffer,
+                Some((mesh.bounds_min, mesh.bounds_max)),
+            ),
+        );
+        attributes.insert(
+            Valid(Semantic::Normals),
+            self.writer.push_accessor(
+                &normals,
+                ComponentType::F32,
+                Type::Vec3,
+                Target::ArrayBuffer,
+                None,
+            ),
+        );
+        attributes.insert(
+            Valid(Semantic::Tangents),
+            self.writer.push_accessor(
+                &tangents,
+                ComponentType::F32,
+                Type::Vec4,
+                Target::ArrayBuffer,
+                None,
+            ),
+        );
+        attributes.insert(
+            Valid(Semantic::TexCoords(0)),
+            self.writer.push_accessor(
+                &tex_coords,
+                ComponentType::F32,
+                Type::Vec2,
+                Target::ArrayBuffer,
+                None,
+            ),
+        );
+        if !mesh.secondary_tex_coords.is_empty() {
+            attributes.insert(
+                Valid(Semantic::TexCoords(1)),
+                self.writer.push_accessor(
+                    &mesh.secondary_tex_coords,
+                    ComponentType::F32,
+                    Type::Vec2,
+                    Target::ArrayBuffer,
+                    None,
+                ),
+            );
+        }
+
+        // One primitive per submesh keeps the draw ranges intact, older models without submeshes are grouped by material
+        let indices = mesh.indices.to_u32();
+        let ranges: Vec<(Vec<u32>, u32)> = if mesh.submeshes.is_empty() {
+            (0..mesh.material_indices.len() as u32)
+                .map(|local_material_idx| {
+                    let indices = indices
+                        .chunks_exact(3)
+                        .zip(&mesh.triangle_material_indices)
+                        .filter(|(_, triangle_material_idx)| {
+                            **triangle_material_idx == local_material_idx
+                        })
+                        .flat_map(|(triangle, _)| triangle.iter().copied())
+                        .collect();
+                    (indices, local_material_idx)
+                })
+                .collect()
+        } else {
+            mesh.submeshes
+                .iter()
+                .map(|submesh| {
+                    let start = submesh.index_offset as usize;
+                    let end = start + submesh.index_count as usize;
+                    (indices[start..end].to_vec(), submesh.material)
+                })
+                .collect()
+        };
+
+        let mut primitives = vec![];
+        for (indices, local_material_idx) in ranges {
+            if indices.is_empty() {
+                continue;
+            }
+
+            let indices = self.writer.push_accessor(
+                &indices,
+                ComponentType::U32,
+                Type::Scalar,
+                Target::ElementArrayBuffer,
+                None,
+            );
+            let material = self.export_material(mesh.material_indices[local_material_idx as usize]);
+
+            primitives.push(json::mesh::Primitive {
+                attributes: attributes.clone(),
+                extensions: Default::default(),
+                extras: Default::default(),
+                indices: Some(indices),
+                material: Some(material),
+                mode: Valid(json::mesh::Mode::Triangles),
+                targets: None,
+            });
+        }
+
+        let index = self.writer.root.push(json::Mesh {
+            extensions: Default::default(),
+            extras: Default::default(),
+            name: Some(mesh.name.clone()),
+            primitives,
+            weights: None,
+        });
+        self.meshes.insert(mesh_idx, index);
+        index
+    }
+
+    /// Export the core metallic roughness model, emission strength, transmission, ior, specular and volume absorption, and the color, metallic roughness, normal, occlusion, emission and transmission textures
+    fn export_material(&mut self, material_idx: u32) -> json::Index<json::Material> {
+        if let Some(material) = self.materials.get(&material_idx) {
+            return *material;
+        }
+        let material = &self.model.materials[material_idx as usize];
+
+        let mut texture_info = |texture: Option<u32>, tex_coord: u32| {
+            let texture_idx = texture?;
+            let index = self.export_texture(texture_idx)?;
+            let texture = &self.model.textures[texture_idx as usize];
+            Some(json::texture::Info {
+                index,
+                tex_coord,
+                extensions: self.texture_transform(texture),
+                extras: Default::default(),
+            })
+        };
+
+        let base_color_texture = texture_info(material.color_texture, material.color_tex_coord);
+        let metallic_roughness_texture = texture_info(
+            material.metallic_roughness_texture,
+            material.metallic_roughness_tex_coord,
+        );
+        let emissive_texture = texture_info(material.emission_texture, material.emission_tex_coord);
+        let transmission_texture = texture_info(
+            material.transmission_texture,
+            material.transmission_tex_coord,
+        );
+        let normal_texture =
+            texture_info(material.normal_texture, material.normal_tex_coord).map(|info| {
+                json::material::NormalTexture {
+                    index: info.index,
+                    scale: material.normal_scale,
+                    tex_coord: info.tex_coord,
+                    extensions: None,
+                    extras: Default::default(),
+                }
+            });
+        let occlusion_texture =
+            texture_info(material.occlusion_texture, material.occlusion_tex_coord).map(|info| {
+                json::material::OcclusionTexture {
+                    index: info.index,
+                    strength: json::material::StrengthFactor(material.occlusion_strength),
+                    tex_coord: info.tex_coord,
+                    extensions: None,
+                    extras: Default::default(),
+                }
+            });
+
+        // Emission above 1 is stored as emissive strength
+        let emission = Vec3::from_array(material.emission);
+        let emissive_strength = emission.max_element().max(1.0);
+        use json::extensions::material as ext;
+        let mut extensions = ext::Material::default();
+        if emissive_strength > 1.0 {
+            extensions.emissive_strength = Some(ext::EmissiveStrength {
+                emissive_strength: ext::EmissiveStrengthFactor(emissive_strength),
+            });
+            self.writer.use_extension("KHR_materials_emissive_strength");
+        }
+        if material.transmission > 0.0 || transmission_texture.is_some() {
+            extensions.transmission = Some(ext::Transmission {
+                transmission_factor: ext::TransmissionFactor(material.transmission),
+                transmission_texture,
+                ..Default::default()
+            });
+            self.writer.use_extension("KHR_materials_transmission");
+        }
+        let ior = 1.0 / material.eta;
+        if material.eta > 0.0 && (ior - 1.5).abs() > 1e-4 {
+            extensions.ior = Some(ext::Ior {
+                ior: ext::IndexOfRefraction(ior),
+                ..Default::default()
+            });
+            self.writer.use_extension("KHR_materials_ior");
+        }
+        if material.specular > 0.0 {
+            extensions.specular = Some(ext::Specular {
+                specular_factor: ext::SpecularFactor(material.specular),
+                specular_color_factor: ext::SpecularColorFactor(material.specular_tint),
+                ..Default::default()
+            });
+            self.writer.use_extension("KHR_materials_specular");
+        }
+        // Inverse of the parser, which derives absorption as `(1 - attenuation_color) / attenuation_distance`
+        let absorption = Vec3::from_array(material.absorption);
+        if absorption.max_element() > 0.0 {
+            let attenuation_distance = 1.0 / absorption.max_element();
+            extensions.volume = Some(ext::Volume {
+                attenuation_distance: ext::AttenuationDistance(attenuation_distance),
+                attenuation_color: ext::AttenuationColor(
+                    (Vec3::ONE - absorption * attenuation_distance)
+                        .clamp(Vec3::ZERO, Vec3::ONE)
+                        .to_array(),
+                ),
+                ..Default::default()
+            });
+            self.writer.use_extension("KHR_materials_volume");
+        }
+        let has_extensions = extensions.emissive_strength.is_some()
+            || extensions.transmission.is_some()
+            || extensions.ior.is_some()
+            || extensions.specular.is_some()
+            || extensions.volume.is_some();
+
+        let alpha_mode = if material.is_opaque {
+            json::material::AlphaMode::Opaque
+        } else if material.alpha_mode == AlphaMode::Blend {
+            json::material::AlphaMode::Blend
+        } else {
+            json::material::AlphaMode::Mask
+        };
+        let index = self.writer.root.push(json::Material {
+            alpha_cutoff: (alpha_mode == json::material::AlphaMode::Mask)
+                .then_some(json::material::AlphaCutoff(material.alpha_cutoff)),
+            alpha_mode: Valid(alpha_mode),
+            name: Some(material.name.clone()),
+            pbr_metallic_roughness: json::material::PbrMetallicRoughness {
+                base_color_factor: json::material::PbrBaseColorFactor(
+                    Vec3::from_array(material.color).extend(1.0).to_array(),
+                ),
+                base_color_texture,
+                metallic_factor: json::material::StrengthFactor(material.metallic),
+                roughness_factor: json::material::StrengthFactor(material.roughness),
+                metallic_roughness_texture,
+                ..Default::default()
+            },
+            normal_texture,
+            occlusion_texture,
+            emissive_texture,
+            emissive_factor: json::material::EmissiveFactor(
+                (emission / emissive_strength).to_array(),
+            ),
+            extensions: has_extensions.then_some(extensions),
+            ..Default::default()
+        });
+        self.materials.insert(material_idx, index);
+        index
+    }
+
+    fn texture_transform(&mut self, texture: &Texture) -> Option<json::extensions::texture::Info> {
+        if texture.uv_offset() == [0.0; 2] && texture.uv_scale() == [1.0; 2] {
+            return None;
+        }
+        self.writer.use_extension("KHR_texture_transform");
+
+        Some(json::extensions::texture::Info {
+            texture_transform: Some(json::extensions::texture::TextureTransform {
+                offset: json::extensions::texture::TextureTransformOffset(texture.uv_offset()),
+                rotation: json::extensions::texture::TextureTransformRotation(0.0),
+                scale: json::extensions::texture::TextureTransformScale(texture.uv_scale()),
+                tex_coord: None,
+                extras: Default::default(),
+            }),
+        })
+    }
+
+    /// Embed the texture as png, or as its source image when available. Returns `None` if the texture format can't be decoded
+    fn export_texture(&mut self, texture_idx: u32) -> Option<json::Index<json::Texture>> {
+        if let Some(texture) = self.textures.get(&texture_idx) {
+            return *texture;
+        }
+        let texture = &self.model.textures[texture_idx as usize];
+
+        let encoded = if let Some(source_image) = texture.source_image() {
+            Some((source_image.data.clone(), source_image.mime_type.clone()))
+        } else {
+            encode_png(texture).map(|png| (png, "image/png".to_owned()))
+        };
+
+        let index = encoded.map(|(data, mime_type)| {
+            self.writer
+                .push_texture(&data, &mime_type, texture.name(), &texture.sampler())
+        });
+
+        self.textures.insert(texture_idx, index);
+        index
+    }
+}
+
+fn encode_png(texture: &Texture) -> Option<Vec<u8>> {
+    let mut image = texture.to_image(0)?;
+    // Gltf expects straight alpha
+    if texture.premultiplied_alpha() {
+        image = resample::unpremultiply_alpha(image, texture.color_space());
+    }
+
+    let image = match image {
+        // Two channel normal maps store xy only
+        DynamicImage::ImageLumaA8(image) if texture.reconstruct_normal_z() => {
+            DynamicImage::ImageRgba8(image::RgbaImage::from_fn(
+                image.width(),
+                image.height(),
+                |x, y| {
+                    let [nx, ny] = image.get_pixel(x, y).0;
+                    let normal = Vec4::new(nx as f32, ny as f32, 0.0, 0.0) / 127.5 - 1.0;
+                    let nz = (1.0 - normal.x * normal.x - normal.y * normal.y)
+                        .max(0.0)
+                        .sqrt();
+                    image::Rgba([nx, ny, ((nz * 0.5 + 0.5) * 255.0).round() as u8, 255])
+                },
+            ))
+        }
+        // Png has no floating point formats
+        DynamicImage::ImageRgba32F(_) => DynamicImage::ImageRgba8(image.to_rgba8()),
+        image => image,
+    };
+
+    let mut png = Cursor::new(vec![]);
+    image.write_to(&mut png, image::ImageFormat::Png).ok()?;
+    Some(png.into_inner())
+}
+
+fn sampler(sampler: &SamplerDesc) -> json::texture::Sampler {
+    use json::texture::{MagFilter, MinFilter, WrappingMode};
+
+    let wrapping_mode = |address_mode: AddressMode| match address_mode {
+        AddressMode::ClampToEdge => WrappingMode::ClampToEdge,
+        AddressMode::Repeat => WrappingMode::Repeat,
+        AddressMode::MirrorRepeat => WrappingMode::MirroredRepeat,
+    };
+
+    json::texture::Sampler {
+        mag_filter: Some(Valid(match sampler.mag_filter {
+            FilterMode::Nearest => MagFilter::Nearest,
+            FilterMode::Linear => MagFilter::Linear,
+        })),
+        min_filter: Some(Valid(match (sampler.min_filter, sampler.mipmap_filter) {
+            (FilterMode::Nearest, FilterMode::Nearest) => MinFilter::NearestMipmapNearest,
+            (FilterMode::Nearest, FilterMode::Linear) => MinFilter::NearestMipmapLinear,
+            (FilterMode::Linear, FilterMode::Nearest) => MinFilter::LinearMipmapNearest,
+            (FilterMode::Linear, FilterMode::Linear) => MinFilter::LinearMipmapLinear,
+        })),
+        wrap_s: Valid(wrapping_mode(sampler.address_mode_u)),
+        wrap_t: Valid(wrapping_mode(sampler.address_mode_v)),
+        ..Default::default()
+    }
+}
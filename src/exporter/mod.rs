@@ -0,0 +1,47 @@
+use std::borrow::Cow;
+
+use crate::mesh::{Mesh, Vertex};
+
+#[cfg(feature = "gltf")]
+pub(crate) mod gltf;
+pub(crate) mod obj;
+
+/// Part of a model to export
+#[derive(Debug, Clone)]
+pub enum ExportSubset {
+    /// Nodes including their children, each exported as root node with its parent transforms applied
+    Nodes(Vec<u32>),
+    /// Meshes, each exported as root node with identity transform
+    Meshes(Vec<u32>),
+}
+
+/// Unpacked vertices of a mesh, preferring full precision vertices when the model was parsed losslessly
+pub(crate) fn mesh_vertices(mesh: &Mesh) -> Vec<Vertex> {
+    if !mesh.unpacked_vertices.is_empty() {
+        return mesh.unpacked_vertices.clone();
+    }
+
+    let packed_vertices = match &mesh.quantized_vertices {
+        Some(quantized) => Cow::Owned(
+            quantized
+                .vertices
+                .iter()
+                .map(|vertex| quantized.unpack(vertex))
+                .collect(),
+        ),
+        None => Cow::Borrowed(&mesh.packed_vertices),
+    };
+    packed_vertices
+        .iter()
+        .map(|vertex| Vertex {
+            position: vertex.position,
+            normal: vertex.normal.unpack().to_array(),
+            tex_coord: vertex.tex_coord,
+            tangent: vertex
+                .tangent
+                .unpack()
+                .extend(vertex.tangent_handiness)
+                .to_array(),
+        })
+        .collect()
+}
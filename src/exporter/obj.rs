@@ -0,0 +1,110 @@
+use std::io::{self, Write};
+
+use glam::{Mat3, Mat4, Vec3};
+
+use crate::Model;
+
+use super::mesh_vertices;
+
+/// Write meshes as wavefront obj, one object per mesh or mesh instance and one group per submesh material
+pub(crate) fn write_obj(
+    model: &Model,
+    writer: &mut impl Write,
+    bake_transforms: bool,
+) -> io::Result<()> {
+    let mut objects: Vec<(String, u32, Mat4)> = vec![];
+    if bake_transforms {
+        model.traverse_nodes(Mat4::IDENTITY, |node, transform| {
+            if let Some(mesh_idx) = node.mesh_idx {
+                objects.push((node.name.clone(), mesh_idx, transform));
+            }
+        });
+    } else {
+        objects.extend(
+            model
+                .meshes
+                .iter()
+                .enumerate()
+                .map(|(mesh_idx, mesh)| (mesh.name.clone(), mesh_idx as u32, Mat4::IDENTITY)),
+        );
+    }
+
+    writeln!(writer, "# ugm {}", env!("CARGO_PKG_VERSION"))?;
+
+    // Obj indices are global and one based
+    let mut first_vertex = 1;
+    for (name, mesh_idx, transform) in objects {
+        let mesh = &model.meshes[mesh_idx as usize];
+        let normal_transform = Mat3::from_mat4(transform).inverse().transpose();
+        let mirrors = transform.determinant() < 0.0;
+
+        writeln!(writer, "o {}", name)?;
+        let vertices = mesh_vertices(mesh);
+        for vertex in &vertices {
+            let [x, y, z] = transform
+                .transform_point3(Vec3::from_array(vertex.position))
+                .to_array();
+            writeln!(writer, "v {} {} {}", x, y, z)?;
+        }
+        for vertex in &vertices {
+            // Obj places the uv origin in the bottom left corner
+            writeln!(
+                writer,
+                "vt {} {}",
+                vertex.tex_coord[0],
+                1.0 - vertex.tex_coord[1]
+            )?;
+        }
+        for vertex in &vertices {
+            let [x, y, z] = (normal_transform * Vec3::from_array(vertex.normal))
+                .normalize_or_zero()
+                .to_array();
+            writeln!(writer, "vn {} {} {}", x, y, z)?;
+        }
+
+        let indices = mesh.indices.to_u32();
+        let mut write_triangles = |indices: &[u32], local_material_idx: u32| -> io::Result<()> {
+            let material = mesh
+                .material_indices
+                .get(local_material_idx as usize)
+                .map(|material_idx| model.materials[*material_idx as usize].name.as_str())
+                .unwrap_or("default");
+            writeln!(writer, "g {}_{}", name, material)?;
+            writeln!(writer, "usemtl {}", material)?;
+            for triangle in indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| triangle[i] + first_vertex);
+                let [b, c] = if mirrors { [c, b] } else { [b, c] };
+                writeln!(writer, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}")?;
+            }
+            Ok(())
+        };
+
+        if mesh.submeshes.is_empty() {
+            for local_material_idx in 0..mesh.material_indices.len() as u32 {
+                let material_indices: Vec<u32> = indices
+                    .chunks_exact(3)
+                    .zip(&mesh.triangle_material_indices)
+                    .filter(|(_, triangle_material_idx)| {
+                        **triangle_material_idx == local_material_idx
+                    })
+                    .flat_map(|(triangle, _)| triangle.iter().copied())
+                    .collect();
+                if !material_indices.is_empty() {
+                    write_triangles(&material_indices, local_material_idx)?;
+                }
+            }
+        } else {
+            for submesh in &mesh.submeshes {
+                let start = submesh.index_offset as usize;
+                write_triangles(
+                    &indices[start..start + submesh.index_count as usize],
+                    submesh.material,
+                )?;
+            }
+        }
+
+        first_vertex += vertices.len() as u32;
+    }
+
+    Ok(())
+}
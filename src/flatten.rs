@@ -0,0 +1,249 @@
+use glam::{Mat3, Mat4, Vec2, Vec3, Vec4};
+
+use crate::{
+    exporter::mesh_vertices,
+    material::Material,
+    mesh::{pack_vertices, Mesh, Vertex},
+    Model, ModelNode,
+};
+
+/// World space geometry of a single mesh instance
+struct BakedInstance {
+    name: String,
+    mesh_idx: u32,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
+impl BakedInstance {
+    fn new(name: String, mesh_idx: u32, mesh: &Mesh, transform: Mat4) -> Self {
+        let normal_transform = Mat3::from_mat4(transform).inverse().transpose();
+        let tangent_transform = Mat3::from_mat4(transform);
+        let mirrors = transform.determinant() < 0.0;
+        let handiness_sign = if mirrors { -1.0 } else { 1.0 };
+
+        let vertices = mesh_vertices(mesh)
+            .into_iter()
+            .map(|vertex| {
+                let tangent = Vec4::from_array(vertex.tangent);
+                Vertex {
+                    position: transform
+                        .transform_point3(Vec3::from_array(vertex.position))
+                        .to_array(),
+                    normal: (normal_transform * Vec3::from_array(vertex.normal))
+                        .normalize_or_zero()
+                        .to_array(),
+                    tex_coord: vertex.tex_coord,
+                    tangent: (tangent_transform * tangent.truncate())
+                        .normalize_or_zero()
+                        .extend(tangent.w * handiness_sign)
+                        .to_array(),
+                }
+            })
+            .collect();
+
+        let mut indices = mesh.indices.to_u32().into_owned();
+        if mirrors {
+            for triangle in indices.chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+
+        Self {
+            name,
+            mesh_idx,
+            vertices,
+            indices,
+        }
+    }
+}
+
+/// Vertices, indices and per vertex secondary uvs of a mesh under construction
+#[derive(Default)]
+struct MeshBuilder {
+    vertices: Vec<Vertex>,
+    secondary_tex_coords: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+    triangle_material_indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    /// Build a mesh with the storage of `source`, lossless vertices and secondary uvs are only kept if `source` has them
+    fn build(
+        self,
+        name: &str,
+        material_indices: Vec<u32>,
+        source: &Mesh,
+        materials: &[Material],
+    ) -> Mesh {
+        let (packed_vertices, quantization_error) = pack_vertices(
+            self.vertices
+                .iter()
+                .map(|vertex| Vec3::from_array(vertex.position))
+                .collect(),
+            self.vertices
+                .iter()
+                .map(|vertex| Vec3::from_array(vertex.normal))
+                .collect(),
+            self.vertices
+                .iter()
+                .map(|vertex| Vec4::from_array(vertex.tangent))
+                .collect(),
+            self.vertices
+                .iter()
+                .map(|vertex| Vec2::from_array(vertex.tex_coord))
+                .collect(),
+        );
+
+        let opaque = material_indices
+            .iter()
+            .all(|material_idx| materials[*material_idx as usize].is_opaque);
+        let is_emissive = material_indices
+            .iter()
+            .any(|material_idx| materials[*material_idx as usize].is_emissive());
+        let mut mesh = Mesh::new(
+            name,
+            packed_vertices,
+            self.triangle_material_indices,
+            material_indices,
+            self.indices,
+            opaque,
+            is_emissive,
+        );
+        mesh.quantization_error = quantization_error;
+        if !source.unpacked_vertices.is_empty() {
+            mesh.unpacked_vertices = self.vertices;
+        }
+        if !source.secondary_tex_coords.is_empty() {
+            mesh.secondary_tex_coords = self.secondary_tex_coords;
+        }
+        mesh.compute_sort_keys(materials);
+        if source.quantized_vertices.is_some() {
+            mesh.quantize_positions();
+        }
+        if source.position_stream.is_some() {
+            mesh.build_position_stream();
+        }
+
+        mesh
+    }
+}
+
+impl Model {
+    /// Bake world transforms into the geometry of every mesh instance and replace the hierarchy by one root node with identity transform per mesh.
+    /// With `merge_materials` all triangles sharing a material are merged into a single mesh instead of one mesh per instance.
+    /// Meshes not instanced by any node are removed, shadow proxies, meshlets and bvhs aren't carried over and need to be rebuilt
+    pub fn flatten(&mut self, merge_materials: bool) {
+        let mut instances = vec![];
+        self.traverse_nodes(Mat4::IDENTITY, |node, transform| {
+            if let Some(mesh_idx) = node.mesh_idx {
+                let mesh = &self.meshes[mesh_idx as usize];
+                instances.push(BakedInstance::new(
+                    node.name.clone(),
+                    mesh_idx,
+                    mesh,
+                    transform,
+                ));
+            }
+        });
+
+        let meshes: Vec<Mesh> = if merge_materials {
+            (0..self.materials.len() as u32)
+                .filter_map(|material_idx| self.merge_material_instances(&instances, material_idx))
+                .collect()
+        } else {
+            instances
+                .into_iter()
+                .map(|instance| {
+                    let source = &self.meshes[instance.mesh_idx as usize];
+                    let builder = MeshBuilder {
+                        vertices: instance.vertices,
+                        secondary_tex_coords: source.secondary_tex_coords.clone(),
+                        indices: instance.indices,
+                        triangle_material_indices: source.triangle_material_indices.clone(),
+                    };
+                    builder.build(
+                        &instance.name,
+                        source.material_indices.clone(),
+                        source,
+                        &self.materials,
+                    )
+                })
+                .collect()
+        };
+
+        self.nodes = meshes
+            .iter()
+            .enumerate()
+            .map(|(mesh_idx, mesh)| ModelNode {
+                name: mesh.name.clone(),
+                transform: Mat4::IDENTITY.to_cols_array(),
+                mesh_idx: Some(mesh_idx as u32),
+                child_node_indices: vec![],
+            })
+            .collect();
+        self.root_node_indices = (0..self.nodes.len() as u32).collect();
+        self.meshes = meshes;
+        (self.bounds_min, self.bounds_max) = self.compute_world_bounds(Mat4::IDENTITY);
+    }
+
+    /// Single mesh of all triangles of all instances using `material_idx`, `None` if there are none
+    fn merge_material_instances(
+        &self,
+        instances: &[BakedInstance],
+        material_idx: u32,
+    ) -> Option<Mesh> {
+        let mut builder = MeshBuilder::default();
+        let mut keeps_secondary_tex_coords = true;
+        let mut source = None;
+        for instance in instances {
+            let mesh = &self.meshes[instance.mesh_idx as usize];
+            let Some(local_material_idx) = mesh
+                .material_indices
+                .iter()
+                .position(|idx| *idx == material_idx)
+            else {
+                continue;
+            };
+
+            let mut vertex_remap = vec![None; instance.vertices.len()];
+            for (triangle, triangle_material_idx) in instance
+                .indices
+                .chunks_exact(3)
+                .zip(&mesh.triangle_material_indices)
+            {
+                if *triangle_material_idx as usize != local_material_idx {
+                    continue;
+                }
+
+                for vertex_idx in triangle {
+                    let merged_idx = *vertex_remap[*vertex_idx as usize].get_or_insert_with(|| {
+                        builder
+                            .vertices
+                            .push(instance.vertices[*vertex_idx as usize]);
+                        if let Some(tex_coord) = mesh.secondary_tex_coords.get(*vertex_idx as usize)
+                        {
+                            builder.secondary_tex_coords.push(*tex_coord);
+                        }
+                        builder.vertices.len() as u32 - 1
+                    });
+                    builder.indices.push(merged_idx);
+                }
+                builder.triangle_material_indices.push(0);
+            }
+
+            keeps_secondary_tex_coords &= !mesh.secondary_tex_coords.is_empty();
+            source.get_or_insert(mesh);
+        }
+
+        let source = source?;
+        if builder.indices.is_empty() {
+            return None;
+        }
+        if !keeps_secondary_tex_coords {
+            builder.secondary_tex_coords.clear();
+        }
+        let name = self.materials[material_idx as usize].name.clone();
+        Some(builder.build(&name, vec![material_idx], source, &self.materials))
+    }
+}
@@ -0,0 +1,26 @@
+use anyhow::Result;
+use image::{ImageFormat, Rgba32FImage};
+
+/// Float image format of encoded image bytes, only recognizing formats enabled through the `hdr` and `exr` features
+pub(crate) fn float_image_format(mime_type: Option<&str>, data: &[u8]) -> Option<ImageFormat> {
+    let format = match mime_type {
+        Some("image/vnd.radiance") => ImageFormat::Hdr,
+        Some("image/x-exr") => ImageFormat::OpenExr,
+        _ => image::guess_format(data).ok()?,
+    };
+
+    match format {
+        #[cfg(feature = "hdr")]
+        ImageFormat::Hdr => Some(format),
+        #[cfg(feature = "exr")]
+        ImageFormat::OpenExr => Some(format),
+        _ => None,
+    }
+}
+
+/// Decode a Radiance hdr or OpenEXR image into 32 bit float rgba, keeping values above 1.0 intact
+pub(crate) fn decode_float_image(data: &[u8], format: ImageFormat) -> Result<Rgba32FImage> {
+    let image = image::load_from_memory_with_format(data, format)?;
+
+    Ok(image.into_rgba32f())
+}
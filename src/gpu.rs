@@ -0,0 +1,772 @@
+use std::mem::offset_of;
+
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+
+use crate::{
+    material::{Material, PackedMaterial},
+    mesh::{HalfTexCoordVertex, Indices, Mesh, PackedVertex, Submesh, Vertex},
+    quantize::QuantizedVertex,
+    texture::{ColorSpace, SamplerDesc, Texture, TextureDimension, TextureFormat, TextureUsage},
+    Model,
+};
+
+/// Data type of a single vertex attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VertexFormat {
+    Float32,
+    Float32x2,
+    Float32x3,
+    Float32x4,
+    /// Octahedral encoded unit direction stored as two 15 bit unorm values in a u32, see [`PackedNormalizedXyz10`](crate::packing::PackedNormalizedXyz10)
+    OctahedralUnorm15x2,
+    Unorm16x4,
+    Float16x2,
+}
+
+impl VertexFormat {
+    pub fn size(&self) -> u64 {
+        match self {
+            Self::Float32 | Self::OctahedralUnorm15x2 | Self::Float16x2 => 4,
+            Self::Float32x2 | Self::Unorm16x4 => 8,
+            Self::Float32x3 => 12,
+            Self::Float32x4 => 16,
+        }
+    }
+
+    /// Octahedral directions are bound as `Uint32` and decoded in the shader
+    #[cfg(feature = "wgpu")]
+    pub const fn to_wgpu(&self) -> wgpu::VertexFormat {
+        match self {
+            Self::Float32 => wgpu::VertexFormat::Float32,
+            Self::Float32x2 => wgpu::VertexFormat::Float32x2,
+            Self::Float32x3 => wgpu::VertexFormat::Float32x3,
+            Self::Float32x4 => wgpu::VertexFormat::Float32x4,
+            Self::OctahedralUnorm15x2 => wgpu::VertexFormat::Uint32,
+            Self::Unorm16x4 => wgpu::VertexFormat::Unorm16x4,
+            Self::Float16x2 => wgpu::VertexFormat::Float16x2,
+        }
+    }
+}
+
+/// Meaning of a vertex attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VertexSemantic {
+    /// Position, also holds the tangent handiness in w when stored as [`VertexFormat::Unorm16x4`]
+    Position,
+    Normal,
+    TexCoord,
+    /// Tangent direction, also holds the handiness in w when stored as [`VertexFormat::Float32x4`]
+    /// or in the top bit when stored as [`VertexFormat::OctahedralUnorm15x2`] in a [`HalfTexCoordVertex`]
+    Tangent,
+    TangentHandiness,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VertexAttribute {
+    pub semantic: VertexSemantic,
+    pub format: VertexFormat,
+    pub offset: u64,
+}
+
+impl PackedVertex {
+    pub const ATTRIBUTES: [VertexAttribute; 5] = [
+        VertexAttribute {
+            semantic: VertexSemantic::Position,
+            format: VertexFormat::Float32x3,
+            offset: offset_of!(PackedVertex, position) as u64,
+        },
+        VertexAttribute {
+            semantic: VertexSemantic::Normal,
+            format: VertexFormat::OctahedralUnorm15x2,
+            offset: offset_of!(PackedVertex, normal) as u64,
+        },
+        VertexAttribute {
+            semantic: VertexSemantic::TexCoord,
+            format: VertexFormat::Float32x2,
+            offset: offset_of!(PackedVertex, tex_coord) as u64,
+        },
+        VertexAttribute {
+            semantic: VertexSemantic::Tangent,
+            format: VertexFormat::OctahedralUnorm15x2,
+            offset: offset_of!(PackedVertex, tangent) as u64,
+        },
+        VertexAttribute {
+            semantic: VertexSemantic::TangentHandiness,
+            format: VertexFormat::Float32,
+            offset: offset_of!(PackedVertex, tangent_handiness) as u64,
+        },
+    ];
+}
+
+impl HalfTexCoordVertex {
+    pub const ATTRIBUTES: [VertexAttribute; 4] = [
+        VertexAttribute {
+            semantic: VertexSemantic::Position,
+            format: VertexFormat::Float32x3,
+            offset: offset_of!(HalfTexCoordVertex, position) as u64,
+        },
+        VertexAttribute {
+            semantic: VertexSemantic::Normal,
+            format: VertexFormat::OctahedralUnorm15x2,
+            offset: offset_of!(HalfTexCoordVertex, normal) as u64,
+        },
+        VertexAttribute {
+            semantic: VertexSemantic::TexCoord,
+            format: VertexFormat::Float16x2,
+            offset: offset_of!(HalfTexCoordVertex, tex_coord) as u64,
+        },
+        VertexAttribute {
+            semantic: VertexSemantic::Tangent,
+            format: VertexFormat::OctahedralUnorm15x2,
+            offset: offset_of!(HalfTexCoordVertex, tangent) as u64,
+        },
+    ];
+}
+
+/// wgpu attributes of `attributes`, bound to consecutive shader locations starting at 0
+#[cfg(feature = "wgpu")]
+pub(crate) const fn wgpu_attributes<const N: usize>(
+    attributes: [VertexAttribute; N],
+) -> [wgpu::VertexAttribute; N] {
+    let mut wgpu_attributes = [wgpu::VertexAttribute {
+        format: wgpu::VertexFormat::Float32,
+        offset: 0,
+        shader_location: 0,
+    }; N];
+    let mut i = 0;
+    while i < N {
+        wgpu_attributes[i] = wgpu::VertexAttribute {
+            format: attributes[i].format.to_wgpu(),
+            offset: attributes[i].offset,
+            shader_location: i as u32,
+        };
+        i += 1;
+    }
+
+    wgpu_attributes
+}
+
+#[cfg(feature = "wgpu")]
+impl PackedVertex {
+    pub const WGPU_ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu_attributes(Self::ATTRIBUTES);
+    /// Vertex buffer layout binding [`PackedVertex::ATTRIBUTES`] to shader locations 0 to 4
+    pub const LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: size_of::<PackedVertex>() as u64,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &Self::WGPU_ATTRIBUTES,
+    };
+}
+
+#[cfg(feature = "wgpu")]
+impl QuantizedVertex {
+    pub const WGPU_ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu_attributes(Self::ATTRIBUTES);
+    /// Vertex buffer layout binding [`QuantizedVertex::ATTRIBUTES`] to shader locations 0 to 3
+    pub const LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: size_of::<QuantizedVertex>() as u64,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &Self::WGPU_ATTRIBUTES,
+    };
+}
+
+#[cfg(feature = "wgpu")]
+impl HalfTexCoordVertex {
+    pub const WGPU_ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu_attributes(Self::ATTRIBUTES);
+    /// Vertex buffer layout binding [`HalfTexCoordVertex::ATTRIBUTES`] to shader locations 0 to 3
+    pub const LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: size_of::<HalfTexCoordVertex>() as u64,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &Self::WGPU_ATTRIBUTES,
+    };
+}
+
+#[cfg(feature = "wgpu")]
+impl Vertex {
+    pub const WGPU_ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu_attributes(Self::ATTRIBUTES);
+    /// Vertex buffer layout binding [`Vertex::ATTRIBUTES`] to shader locations 0 to 3
+    pub const LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: size_of::<Vertex>() as u64,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &Self::WGPU_ATTRIBUTES,
+    };
+}
+
+impl Vertex {
+    pub const ATTRIBUTES: [VertexAttribute; 4] = [
+        VertexAttribute {
+            semantic: VertexSemantic::Position,
+            format: VertexFormat::Float32x3,
+            offset: offset_of!(Vertex, position) as u64,
+        },
+        VertexAttribute {
+            semantic: VertexSemantic::Normal,
+            format: VertexFormat::Float32x3,
+            offset: offset_of!(Vertex, normal) as u64,
+        },
+        VertexAttribute {
+            semantic: VertexSemantic::TexCoord,
+            format: VertexFormat::Float32x2,
+            offset: offset_of!(Vertex, tex_coord) as u64,
+        },
+        VertexAttribute {
+            semantic: VertexSemantic::Tangent,
+            format: VertexFormat::Float32x4,
+            offset: offset_of!(Vertex, tangent) as u64,
+        },
+    ];
+}
+
+/// Renderer agnostic view of a mesh, vertices are interleaved according to `attributes`
+#[derive(Debug, Clone, Copy)]
+pub struct GpuMeshData<'a> {
+    pub vertices: &'a [u8],
+    pub vertex_stride: u64,
+    pub attributes: &'static [VertexAttribute],
+    /// Triangle list
+    pub indices: &'a Indices,
+    /// Index into `material_indices` per triangle
+    pub triangle_material_indices: &'a [u32],
+    /// Index ranges per source primitive, for one draw per submesh
+    pub submeshes: &'a [Submesh],
+    /// Model material indices used by the mesh
+    pub material_indices: &'a [u32],
+    pub bounds_min: [f32; 3],
+    pub bounds_max: [f32; 3],
+    /// Transform from stored to mesh space positions, `position = offset + stored * scale`, identity unless quantized
+    pub position_offset: [f32; 3],
+    pub position_scale: [f32; 3],
+    /// Mesh space positions in [`POSITION_STREAM_ATTRIBUTE`] layout, empty unless built with [`Mesh::build_position_stream`]
+    pub position_stream: &'a [u8],
+}
+
+/// Attribute of the tightly packed positions in [`GpuMeshData::position_stream`]
+pub const POSITION_STREAM_ATTRIBUTE: VertexAttribute = VertexAttribute {
+    semantic: VertexSemantic::Position,
+    format: VertexFormat::Float32x3,
+    offset: 0,
+};
+
+impl Mesh {
+    pub fn gpu_data(&self) -> GpuMeshData<'_> {
+        let (vertices, vertex_stride, attributes, position_offset, position_scale) =
+            match &self.quantized_vertices {
+                Some(quantized) => (
+                    bytemuck::cast_slice(&quantized.vertices),
+                    size_of::<QuantizedVertex>() as u64,
+                    &QuantizedVertex::ATTRIBUTES[..],
+                    quantized.offset,
+                    quantized.scale,
+                ),
+                None => (
+                    bytemuck::cast_slice(&self.packed_vertices),
+                    size_of::<PackedVertex>() as u64,
+                    &PackedVertex::ATTRIBUTES[..],
+                    [0.0; 3],
+                    [1.0; 3],
+                ),
+            };
+
+        GpuMeshData {
+            vertices,
+            vertex_stride,
+            attributes,
+            indices: &self.indices,
+            triangle_material_indices: &self.triangle_material_indices,
+            submeshes: &self.submeshes,
+            material_indices: &self.material_indices,
+            bounds_min: self.bounds_min,
+            bounds_max: self.bounds_max,
+            position_offset,
+            position_scale,
+            position_stream: self
+                .position_stream
+                .as_deref()
+                .map(bytemuck::cast_slice)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Vertex and index buffer of a mesh, created by [`Mesh::create_wgpu_buffers`]
+#[cfg(feature = "wgpu")]
+#[derive(Debug)]
+pub struct WgpuMeshBuffers {
+    pub vertex_buffer: wgpu::Buffer,
+    /// [`QuantizedVertex::LAYOUT`] for quantized meshes, [`PackedVertex::LAYOUT`] otherwise
+    pub vertex_layout: wgpu::VertexBufferLayout<'static>,
+    pub index_buffer: wgpu::Buffer,
+    pub index_format: wgpu::IndexFormat,
+    pub index_count: u32,
+    /// Positions for depth only passes bound with [`POSITION_STREAM_LAYOUT`], `None` without a position stream
+    pub position_buffer: Option<wgpu::Buffer>,
+}
+
+#[cfg(feature = "wgpu")]
+const POSITION_STREAM_WGPU_ATTRIBUTES: [wgpu::VertexAttribute; 1] =
+    wgpu_attributes([POSITION_STREAM_ATTRIBUTE]);
+
+/// Vertex buffer layout binding [`POSITION_STREAM_ATTRIBUTE`] to shader location 0
+#[cfg(feature = "wgpu")]
+pub const POSITION_STREAM_LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+    array_stride: size_of::<[f32; 3]>() as u64,
+    step_mode: wgpu::VertexStepMode::Vertex,
+    attributes: &POSITION_STREAM_WGPU_ATTRIBUTES,
+};
+
+#[cfg(feature = "wgpu")]
+impl Mesh {
+    /// Upload the vertices and indices in their stored format, quantized positions are dequantized with [`GpuMeshData::position_offset`] and [`GpuMeshData::position_scale`] in the shader
+    pub fn create_wgpu_buffers(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> WgpuMeshBuffers {
+        let data = self.gpu_data();
+        let create_buffer = |label: &str, contents: &[u8], usage: wgpu::BufferUsages| {
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                // Writes must be a multiple of 4 bytes, u16 indices may not be
+                size: (contents.len() as u64).next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
+                usage: usage | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let mut padded = contents.to_vec();
+            padded.resize(buffer.size() as usize, 0);
+            queue.write_buffer(&buffer, 0, &padded);
+            buffer
+        };
+
+        WgpuMeshBuffers {
+            vertex_buffer: create_buffer(
+                &format!("{} vertices", self.name),
+                data.vertices,
+                wgpu::BufferUsages::VERTEX,
+            ),
+            vertex_layout: if self.quantized_vertices.is_some() {
+                QuantizedVertex::LAYOUT
+            } else {
+                PackedVertex::LAYOUT
+            },
+            index_buffer: create_buffer(
+                &format!("{} indices", self.name),
+                self.indices.as_bytes(),
+                wgpu::BufferUsages::INDEX,
+            ),
+            index_format: self.indices.wgpu_format(),
+            index_count: self.indices.len() as u32,
+            position_buffer: self.position_stream.is_some().then(|| {
+                create_buffer(
+                    &format!("{} positions", self.name),
+                    data.position_stream,
+                    wgpu::BufferUsages::VERTEX,
+                )
+            }),
+        }
+    }
+}
+
+/// Single mip level, holding all layers contiguously
+#[derive(Debug, Clone, Copy)]
+pub struct GpuMipData<'a> {
+    pub width: u32,
+    pub height: u32,
+    pub bytes_per_row: u32,
+    pub rows_per_image: u32,
+    pub data: &'a [u8],
+}
+
+/// Renderer agnostic view of a texture, ready to be copied into a gpu texture
+#[derive(Debug, Clone)]
+pub struct GpuTextureData<'a> {
+    pub name: &'a str,
+    pub format: TextureFormat,
+    /// Srgb textures should use the srgb variant of `format`
+    pub color_space: ColorSpace,
+    pub dimension: TextureDimension,
+    pub width: u32,
+    pub height: u32,
+    pub layer_count: u32,
+    pub mips: Vec<GpuMipData<'a>>,
+    pub sampler: SamplerDesc,
+    pub uv_offset: [f32; 2],
+    pub uv_scale: [f32; 2],
+    /// Normal map z must be reconstructed from x and y in the shader
+    pub reconstruct_normal_z: bool,
+    /// Rgb is premultiplied by alpha
+    pub premultiplied_alpha: bool,
+}
+
+impl Texture {
+    pub fn gpu_data(&self) -> GpuTextureData<'_> {
+        let mips = (0..self.mip_count())
+            .map(|mip| {
+                let width = (self.width() >> mip).max(1);
+                let height = (self.height() >> mip).max(1);
+                let bytes_per_row = self.format().bytes_per_row(width);
+                let rows_per_image = self.layer_data(mip, 0).len() / bytes_per_row;
+
+                GpuMipData {
+                    width,
+                    height,
+                    bytes_per_row: bytes_per_row as u32,
+                    rows_per_image: rows_per_image as u32,
+                    data: self.mip_data(mip),
+                }
+            })
+            .collect();
+
+        GpuTextureData {
+            name: self.name(),
+            format: self.format(),
+            color_space: self.color_space(),
+            dimension: self.dimension(),
+            width: self.width(),
+            height: self.height(),
+            layer_count: self.layer_count(),
+            mips,
+            sampler: self.sampler(),
+            uv_offset: self.uv_offset(),
+            uv_scale: self.uv_scale(),
+            reconstruct_normal_z: self.reconstruct_normal_z(),
+            premultiplied_alpha: self.premultiplied_alpha(),
+        }
+    }
+}
+
+/// Two dimensional model textures uploaded for a `binding_array<texture_2d<f32>>`, created by [`Model::create_wgpu_texture_array`]
+#[cfg(feature = "wgpu")]
+#[derive(Debug)]
+pub struct WgpuTextureArray {
+    pub textures: Vec<wgpu::Texture>,
+    /// Views in binding array order
+    pub views: Vec<wgpu::TextureView>,
+    /// Binding array index of each model texture, `None` for cube maps which can't be part of the array
+    pub texture_remap: Vec<Option<u32>>,
+    /// Binding array index of every slot of each material in [`Material::textures`] order, [`PackedMaterial::NO_TEXTURE`] for empty slots
+    pub material_textures: Vec<[u32; 11]>,
+}
+
+#[cfg(feature = "wgpu")]
+impl WgpuTextureArray {
+    /// Views for [`wgpu::BindingResource::TextureViewArray`]
+    pub fn view_refs(&self) -> Vec<&wgpu::TextureView> {
+        self.views.iter().collect()
+    }
+}
+
+#[cfg(feature = "wgpu")]
+impl Model {
+    /// Upload all two dimensional textures for bindless rendering, binding arrays require [`wgpu::Features::TEXTURE_BINDING_ARRAY`]
+    pub fn create_wgpu_texture_array(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> WgpuTextureArray {
+        let mut textures = vec![];
+        let mut views = vec![];
+        let texture_remap = self
+            .textures
+            .iter()
+            .map(|texture| {
+                if texture.dimension() != TextureDimension::D2 {
+                    return None;
+                }
+
+                let (wgpu_texture, view) = texture.create_wgpu_texture(
+                    wgpu::TextureUsages::TEXTURE_BINDING,
+                    device,
+                    queue,
+                );
+                textures.push(wgpu_texture);
+                views.push(view);
+                Some(views.len() as u32 - 1)
+            })
+            .collect::<Vec<_>>();
+
+        let material_textures = self
+            .materials
+            .iter()
+            .map(|material| {
+                material.textures().map(|texture_idx| {
+                    texture_idx
+                        .and_then(|texture_idx| {
+                            texture_remap.get(texture_idx as usize).copied().flatten()
+                        })
+                        .unwrap_or(PackedMaterial::NO_TEXTURE)
+                })
+            })
+            .collect();
+
+        WgpuTextureArray {
+            textures,
+            views,
+            texture_remap,
+            material_textures,
+        }
+    }
+}
+
+#[cfg(feature = "wgpu")]
+impl Model {
+    /// Upload all textures like [`Texture::create_wgpu_texture`], copying every mip from a single staging buffer with a single command encoder
+    pub fn upload_textures(
+        &self,
+        usage: wgpu::TextureUsages,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Vec<(wgpu::Texture, wgpu::TextureView)> {
+        let textures: Vec<_> = self
+            .textures
+            .iter()
+            .map(|texture| texture.create_empty_wgpu_texture(usage, device))
+            .collect();
+
+        // Buffer to texture copies require rows aligned to `COPY_BYTES_PER_ROW_ALIGNMENT`,
+        // which also keeps every mip offset aligned
+        let mut copies = vec![];
+        let mut staging_size = 0;
+        for (texture_idx, texture) in self.textures.iter().enumerate() {
+            for (mip_level, mip) in texture.gpu_data().mips.into_iter().enumerate() {
+                let padded_bytes_per_row = mip
+                    .bytes_per_row
+                    .next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+                let row_count = (mip.data.len() / mip.bytes_per_row as usize) as u64;
+                copies.push((
+                    texture_idx,
+                    mip_level as u32,
+                    staging_size,
+                    padded_bytes_per_row,
+                    mip,
+                ));
+                staging_size += padded_bytes_per_row as u64 * row_count;
+            }
+        }
+        if staging_size == 0 {
+            return textures;
+        }
+
+        let staging_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("texture staging"),
+            size: staging_size,
+            usage: wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: true,
+        });
+        {
+            let mut staging = staging_buffer.slice(..).get_mapped_range_mut();
+            for (_, _, offset, padded_bytes_per_row, mip) in &copies {
+                for (row_idx, row) in mip
+                    .data
+                    .chunks_exact(mip.bytes_per_row as usize)
+                    .enumerate()
+                {
+                    let start = *offset as usize + row_idx * *padded_bytes_per_row as usize;
+                    staging[start..start + row.len()].copy_from_slice(row);
+                }
+            }
+        }
+        staging_buffer.unmap();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("texture upload"),
+        });
+        for (texture_idx, mip_level, offset, padded_bytes_per_row, mip) in &copies {
+            encoder.copy_buffer_to_texture(
+                wgpu::TexelCopyBufferInfo {
+                    buffer: &staging_buffer,
+                    layout: wgpu::TexelCopyBufferLayout {
+                        offset: *offset,
+                        bytes_per_row: Some(*padded_bytes_per_row),
+                        rows_per_image: Some(mip.rows_per_image),
+                    },
+                },
+                wgpu::TexelCopyTextureInfo {
+                    texture: &textures[*texture_idx].0,
+                    mip_level: *mip_level,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::Extent3d {
+                    width: mip.width,
+                    height: mip.height,
+                    depth_or_array_layers: self.textures[*texture_idx].layer_count(),
+                },
+            );
+        }
+        queue.submit([encoder.finish()]);
+
+        textures
+    }
+}
+
+/// Texture bound to a material slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GpuTextureSlot {
+    pub usage: TextureUsage,
+    /// Model texture index
+    pub texture: u32,
+    pub tex_coord: u32,
+}
+
+/// Renderer agnostic view of a material, with its textures listed per slot
+#[derive(Debug, Clone)]
+pub struct GpuMaterialData<'a> {
+    pub material: &'a Material,
+    pub textures: Vec<GpuTextureSlot>,
+}
+
+impl Material {
+    pub fn gpu_data(&self) -> GpuMaterialData<'_> {
+        let textures = TextureUsage::ALL
+            .into_iter()
+            .zip(self.textures())
+            .zip(self.tex_coords())
+            .filter_map(|((usage, texture), tex_coord)| {
+                Some(GpuTextureSlot {
+                    usage,
+                    texture: texture?,
+                    tex_coord,
+                })
+            })
+            .collect();
+
+        GpuMaterialData {
+            material: self,
+            textures,
+        }
+    }
+}
+
+/// Mesh instance of a node, laid out for std430 storage buffers as `struct { transform: mat4x4<f32>, mesh_idx: u32, node_idx: u32 }`
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+#[repr(C)]
+pub struct GpuInstance {
+    /// Column major world transform
+    pub transform: [f32; 16],
+    pub mesh_idx: u32,
+    pub node_idx: u32,
+    pub _padding: [u32; 2],
+}
+
+/// Draw data of a whole model, see [`Model::gpu_scene_data`]
+#[derive(Debug, Clone)]
+pub struct GpuSceneData {
+    /// One instance per node with a mesh, in depth first order
+    pub instances: Vec<GpuInstance>,
+    /// [`PackedMaterial`] per model material, only 4 byte members so it's std430 compatible with `tex_coords` read as `array<u32, 3>`
+    pub materials: Vec<PackedMaterial>,
+}
+
+impl GpuSceneData {
+    pub fn instance_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.instances)
+    }
+
+    pub fn material_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.materials)
+    }
+
+    /// Replace material texture indices, e.g. by [`WgpuTextureArray::material_textures`]
+    pub fn remap_textures(&mut self, material_textures: &[[u32; 11]]) {
+        for (material, textures) in self.materials.iter_mut().zip(material_textures) {
+            material.textures = *textures;
+        }
+    }
+}
+
+impl Model {
+    /// Flatten the node hierarchy into mesh instances and pack all materials
+    pub fn gpu_scene_data(&self, root_transform: Mat4) -> GpuSceneData {
+        let instances = self
+            .iter_nodes(root_transform)
+            .filter_map(|(node_idx, node, transform)| {
+                Some(GpuInstance {
+                    transform: transform.to_cols_array(),
+                    mesh_idx: node.mesh_idx?,
+                    node_idx,
+                    _padding: [0; 2],
+                })
+            })
+            .collect();
+
+        GpuSceneData {
+            instances,
+            materials: self.materials.iter().map(Material::packed).collect(),
+        }
+    }
+}
+
+#[cfg(feature = "wgpu")]
+#[derive(Debug)]
+pub struct WgpuSceneBuffers {
+    /// Storage buffer of [`GpuInstance`]
+    pub instance_buffer: wgpu::Buffer,
+    pub instance_count: u32,
+    /// Storage buffer of [`PackedMaterial`]
+    pub material_buffer: wgpu::Buffer,
+    pub material_count: u32,
+}
+
+#[cfg(feature = "wgpu")]
+impl Model {
+    /// Upload [`Model::gpu_scene_data`] with an identity root transform into storage buffers.
+    /// Material texture indices are the model's, pass [`WgpuTextureArray::material_textures`] to index a binding array instead
+    pub fn create_wgpu_scene_buffers(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        material_textures: Option<&[[u32; 11]]>,
+    ) -> WgpuSceneBuffers {
+        let mut data = self.gpu_scene_data(Mat4::IDENTITY);
+        if let Some(material_textures) = material_textures {
+            data.remap_textures(material_textures);
+        }
+
+        let create_buffer = |label: &str, contents: &[u8], element_size: usize| {
+            // Empty storage buffers can't be bound, so there's always room for one element
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size: contents.len().max(element_size) as u64,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            queue.write_buffer(&buffer, 0, contents);
+            buffer
+        };
+
+        WgpuSceneBuffers {
+            instance_buffer: create_buffer(
+                "instances",
+                data.instance_bytes(),
+                size_of::<GpuInstance>(),
+            ),
+            instance_count: data.instances.len() as u32,
+            material_buffer: create_buffer(
+                "materials",
+                data.material_bytes(),
+                size_of::<PackedMaterial>(),
+            ),
+            material_count: data.materials.len() as u32,
+        }
+    }
+}
+
+/// Conversion of a [`Mesh`] into the mesh type of renderer `R`, usually implemented for the renderer's device or context
+pub trait IntoGpuMesh<R: ?Sized> {
+    type Output;
+
+    fn create_gpu_mesh(&self, renderer: &mut R) -> Self::Output;
+}
+
+/// Conversion of a [`Material`] into the material type of renderer `R`
+pub trait IntoGpuMaterial<R: ?Sized> {
+    type Output;
+
+    fn create_gpu_material(&self, renderer: &mut R) -> Self::Output;
+}
+
+/// Conversion of a [`Texture`] into the texture type of renderer `R`
+pub trait IntoGpuTexture<R: ?Sized> {
+    type Output;
+
+    fn create_gpu_texture(&self, renderer: &mut R) -> Self::Output;
+}
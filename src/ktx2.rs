@@ -0,0 +1,332 @@
+//! KTX2 container writer, see https://registry.khronos.org/KTX/specs/2.0/ktxspec.v2.html
+
+use std::io::{self, Write};
+
+use crate::texture::{
+    ColorSpace, CompressedTextureFormat, Texture, TextureFormat, UncompressedTextureFormat,
+};
+
+const IDENTIFIER: [u8; 12] = [
+    0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a,
+];
+
+const HEADER_SIZE: usize = 12 + 9 * 4 + 4 * 4 + 2 * 8;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 3 * 8;
+
+const KHR_DF_MODEL_RGBSDA: u8 = 1;
+const KHR_DF_MODEL_BC1A: u8 = 128;
+const KHR_DF_MODEL_BC4: u8 = 131;
+const KHR_DF_MODEL_BC5: u8 = 132;
+const KHR_DF_MODEL_BC6H: u8 = 133;
+const KHR_DF_MODEL_BC7: u8 = 134;
+const KHR_DF_MODEL_ETC1: u8 = 160;
+const KHR_DF_MODEL_ETC2: u8 = 161;
+
+const KHR_DF_PRIMARIES_BT709: u8 = 1;
+const KHR_DF_TRANSFER_LINEAR: u8 = 1;
+const KHR_DF_TRANSFER_SRGB: u8 = 2;
+
+const KHR_DF_SAMPLE_DATATYPE_LINEAR: u8 = 0x10;
+const KHR_DF_SAMPLE_DATATYPE_SIGNED: u8 = 0x40;
+const KHR_DF_SAMPLE_DATATYPE_FLOAT: u8 = 0x80;
+
+const KHR_DF_CHANNEL_ALPHA: u8 = 15;
+
+/// Single sample of a basic data format descriptor block
+struct Sample {
+    bit_offset: u16,
+    bit_length: u8,
+    channel: u8,
+    lower: u32,
+    upper: u32,
+}
+
+impl Sample {
+    fn unorm(channel: u8, bit_offset: u16, bit_length: u8) -> Self {
+        Self {
+            bit_offset,
+            bit_length,
+            channel,
+            lower: 0,
+            upper: u32::MAX >> (32 - bit_length as u32),
+        }
+    }
+
+    fn float(channel: u8, bit_offset: u16) -> Self {
+        Self {
+            bit_offset,
+            bit_length: 32,
+            channel: channel | KHR_DF_SAMPLE_DATATYPE_FLOAT | KHR_DF_SAMPLE_DATATYPE_SIGNED,
+            lower: (-1.0f32).to_bits(),
+            upper: 1.0f32.to_bits(),
+        }
+    }
+
+    fn block(channel: u8, bit_offset: u16, bit_length: u8) -> Self {
+        Self {
+            bit_offset,
+            bit_length,
+            channel,
+            lower: 0,
+            upper: u32::MAX,
+        }
+    }
+}
+
+fn vk_format(format: TextureFormat) -> u32 {
+    match format {
+        TextureFormat::Uncompressed(format) => match format {
+            UncompressedTextureFormat::R8Unorm => 9,
+            UncompressedTextureFormat::Rg8Unorm => 16,
+            UncompressedTextureFormat::Rgba8Unorm => 37,
+            UncompressedTextureFormat::R16Unorm => 70,
+            UncompressedTextureFormat::Rg16Unorm => 77,
+            UncompressedTextureFormat::Rgba16Unorm => 91,
+            UncompressedTextureFormat::Rgba32Float => 109,
+        },
+        TextureFormat::Compressed(format) => match format {
+            CompressedTextureFormat::Bc1RgbaUnorm => 133,
+            CompressedTextureFormat::Bc4RUnorm => 139,
+            CompressedTextureFormat::Bc5RgUnorm => 141,
+            CompressedTextureFormat::Bc6hRgbUfloat => 143,
+            CompressedTextureFormat::Bc7RgbaUnorm => 145,
+            // Etc1 blocks are valid Etc2 rgb blocks
+            CompressedTextureFormat::Etc1 | CompressedTextureFormat::Etc2Rgb8Unorm => 147,
+            CompressedTextureFormat::Etc2Rgba8Unorm => 151,
+            CompressedTextureFormat::EacR11Unorm => 153,
+            CompressedTextureFormat::EacRg11Unorm => 155,
+        },
+    }
+}
+
+/// Srgb variant of a unorm vk format, if there is one
+fn srgb_vk_format(vk_format: u32) -> Option<u32> {
+    match vk_format {
+        9 => Some(15),
+        16 => Some(22),
+        37 => Some(43),
+        133 => Some(134),
+        145 => Some(146),
+        147 => Some(148),
+        151 => Some(152),
+        _ => None,
+    }
+}
+
+/// Returns (color model, bytes per texel block, samples)
+fn format_descriptor(format: TextureFormat) -> (u8, usize, Vec<Sample>) {
+    match format {
+        TextureFormat::Uncompressed(format) => {
+            let num_channels = format.num_channels();
+            let bytes_per_channel = format.bytes_per_channel();
+            let bit_length = (bytes_per_channel * 8) as u8;
+
+            let samples = (0..num_channels)
+                .map(|i| {
+                    let channel = if i == 3 {
+                        KHR_DF_CHANNEL_ALPHA
+                    } else {
+                        i as u8
+                    };
+                    let bit_offset = (i * bytes_per_channel * 8) as u16;
+                    if format == UncompressedTextureFormat::Rgba32Float {
+                        Sample::float(channel, bit_offset)
+                    } else {
+                        Sample::unorm(channel, bit_offset, bit_length)
+                    }
+                })
+                .collect();
+
+            (
+                KHR_DF_MODEL_RGBSDA,
+                num_channels * bytes_per_channel,
+                samples,
+            )
+        }
+        TextureFormat::Compressed(format) => {
+            let (model, samples) = match format {
+                CompressedTextureFormat::Bc1RgbaUnorm => {
+                    (KHR_DF_MODEL_BC1A, vec![Sample::block(0, 0, 64)])
+                }
+                CompressedTextureFormat::Bc4RUnorm => {
+                    (KHR_DF_MODEL_BC4, vec![Sample::block(0, 0, 64)])
+                }
+                CompressedTextureFormat::Bc5RgUnorm => (
+                    KHR_DF_MODEL_BC5,
+                    vec![Sample::block(0, 0, 64), Sample::block(1, 64, 64)],
+                ),
+                CompressedTextureFormat::Bc6hRgbUfloat => {
+                    let mut sample = Sample::block(KHR_DF_SAMPLE_DATATYPE_FLOAT, 0, 128);
+                    sample.lower = 0.0f32.to_bits();
+                    sample.upper = 1.0f32.to_bits();
+                    (KHR_DF_MODEL_BC6H, vec![sample])
+                }
+                CompressedTextureFormat::Bc7RgbaUnorm => {
+                    (KHR_DF_MODEL_BC7, vec![Sample::block(0, 0, 128)])
+                }
+                CompressedTextureFormat::Etc1 => (KHR_DF_MODEL_ETC1, vec![Sample::block(0, 0, 64)]),
+                CompressedTextureFormat::Etc2Rgb8Unorm => {
+                    (KHR_DF_MODEL_ETC2, vec![Sample::block(2, 0, 64)])
+                }
+                CompressedTextureFormat::Etc2Rgba8Unorm => (
+                    KHR_DF_MODEL_ETC2,
+                    vec![
+                        Sample::block(KHR_DF_CHANNEL_ALPHA, 0, 64),
+                        Sample::block(2, 64, 64),
+                    ],
+                ),
+                CompressedTextureFormat::EacR11Unorm => {
+                    (KHR_DF_MODEL_ETC2, vec![Sample::block(0, 0, 64)])
+                }
+                CompressedTextureFormat::EacRg11Unorm => (
+                    KHR_DF_MODEL_ETC2,
+                    vec![Sample::block(0, 0, 64), Sample::block(1, 64, 64)],
+                ),
+            };
+
+            (model, format.block_size(), samples)
+        }
+    }
+}
+
+fn data_format_descriptor(format: TextureFormat, srgb: bool) -> Vec<u8> {
+    let (model, bytes_per_block, mut samples) = format_descriptor(format);
+    let transfer = if srgb {
+        // Alpha is always stored linearly
+        for sample in &mut samples {
+            if sample.channel & 0xf == KHR_DF_CHANNEL_ALPHA {
+                sample.channel |= KHR_DF_SAMPLE_DATATYPE_LINEAR;
+            }
+        }
+        KHR_DF_TRANSFER_SRGB
+    } else {
+        KHR_DF_TRANSFER_LINEAR
+    };
+    let block_dimension = match format {
+        TextureFormat::Uncompressed(_) => 0,
+        TextureFormat::Compressed(_) => 3,
+    };
+
+    let block_size = 24 + 16 * samples.len();
+    let mut dfd = Vec::with_capacity(4 + block_size);
+    dfd.extend_from_slice(&(4 + block_size as u32).to_le_bytes());
+    // Khronos vendor, basic descriptor type
+    dfd.extend_from_slice(&0u32.to_le_bytes());
+    dfd.extend_from_slice(&(2 | ((block_size as u32) << 16)).to_le_bytes());
+    dfd.extend_from_slice(&[model, KHR_DF_PRIMARIES_BT709, transfer, 0]);
+    dfd.extend_from_slice(&[block_dimension, block_dimension, 0, 0]);
+    dfd.extend_from_slice(&[bytes_per_block as u8, 0, 0, 0, 0, 0, 0, 0]);
+
+    for sample in samples {
+        dfd.extend_from_slice(&sample.bit_offset.to_le_bytes());
+        dfd.extend_from_slice(&[sample.bit_length - 1, sample.channel]);
+        dfd.extend_from_slice(&0u32.to_le_bytes());
+        dfd.extend_from_slice(&sample.lower.to_le_bytes());
+        dfd.extend_from_slice(&sample.upper.to_le_bytes());
+    }
+
+    dfd
+}
+
+fn key_value_data(entries: &[(&str, &str)]) -> Vec<u8> {
+    let mut kvd = vec![];
+    for (key, value) in entries {
+        let length = key.len() + value.len() + 2;
+        kvd.extend_from_slice(&(length as u32).to_le_bytes());
+        kvd.extend_from_slice(key.as_bytes());
+        kvd.push(0);
+        kvd.extend_from_slice(value.as_bytes());
+        kvd.push(0);
+        kvd.resize(kvd.len().next_multiple_of(4), 0);
+    }
+
+    kvd
+}
+
+fn type_size(format: TextureFormat) -> u32 {
+    match format {
+        TextureFormat::Uncompressed(format) => format.bytes_per_channel() as u32,
+        TextureFormat::Compressed(_) => 1,
+    }
+}
+
+pub(crate) fn write_ktx2(texture: &Texture, writer: &mut impl Write) -> io::Result<()> {
+    let format = texture.format();
+    let levels: Vec<&[u8]> = texture.mips().collect();
+
+    let mut vk_format = vk_format(format);
+    let srgb_vk_format =
+        srgb_vk_format(vk_format).filter(|_| texture.color_space() == ColorSpace::Srgb);
+    if let Some(srgb_vk_format) = srgb_vk_format {
+        vk_format = srgb_vk_format;
+    }
+
+    let dfd = data_format_descriptor(format, srgb_vk_format.is_some());
+    let kvd = key_value_data(&[
+        ("KTXorientation", "rd"),
+        ("KTXwriter", concat!("ugm ", env!("CARGO_PKG_VERSION"))),
+    ]);
+
+    let dfd_offset = HEADER_SIZE + levels.len() * LEVEL_INDEX_ENTRY_SIZE;
+    let kvd_offset = dfd_offset + dfd.len();
+
+    // Levels are stored smallest first, each aligned to the least common multiple of the texel block size and 4
+    let (_, bytes_per_block, _) = format_descriptor(format);
+    let alignment = bytes_per_block * 4 / gcd(bytes_per_block, 4);
+
+    let mut level_offsets = vec![0; levels.len()];
+    let mut offset = kvd_offset + kvd.len();
+    for (i, level) in levels.iter().enumerate().rev() {
+        offset = offset.next_multiple_of(alignment);
+        level_offsets[i] = offset;
+        offset += level.len();
+    }
+
+    let mut header = Vec::with_capacity(dfd_offset);
+    header.extend_from_slice(&IDENTIFIER);
+    for value in [
+        vk_format,
+        type_size(format),
+        texture.width(),
+        texture.height(),
+        0,
+        0,
+        texture.layer_count(),
+        levels.len() as u32,
+        0,
+    ] {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+    for value in [dfd_offset, dfd.len(), kvd_offset, kvd.len()] {
+        header.extend_from_slice(&(value as u32).to_le_bytes());
+    }
+    // No supercompression global data
+    header.extend_from_slice(&0u64.to_le_bytes());
+    header.extend_from_slice(&0u64.to_le_bytes());
+    for (level, level_offset) in levels.iter().zip(&level_offsets) {
+        header.extend_from_slice(&(*level_offset as u64).to_le_bytes());
+        header.extend_from_slice(&(level.len() as u64).to_le_bytes());
+        header.extend_from_slice(&(level.len() as u64).to_le_bytes());
+    }
+
+    writer.write_all(&header)?;
+    writer.write_all(&dfd)?;
+    writer.write_all(&kvd)?;
+
+    let mut offset = kvd_offset + kvd.len();
+    for (level, level_offset) in levels.iter().zip(&level_offsets).rev() {
+        writer.write_all(&vec![0; level_offset - offset])?;
+        writer.write_all(level)?;
+        offset = level_offset + level.len();
+    }
+
+    Ok(())
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
@@ -0,0 +1,101 @@
+//! Models with textures loaded on demand, for editors that open many models but display few of their textures
+
+use std::io::{Read, Seek};
+
+use anyhow::Result;
+
+use crate::{
+    chunked::ChunkedReader,
+    texture::{Texture, TextureInfo},
+    Model,
+};
+
+/// Texture of a [`LazyModel`], its metadata is always available while the texel data is only present once loaded
+#[derive(Debug, Clone)]
+pub struct TextureHandle {
+    info: TextureInfo,
+    texture: Option<Texture>,
+}
+
+impl TextureHandle {
+    pub fn info(&self) -> &TextureInfo {
+        &self.info
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.texture.is_some()
+    }
+
+    /// The texture if it has been loaded with [`LazyModel::load_texture`]
+    pub fn get(&self) -> Option<&Texture> {
+        self.texture.as_ref()
+    }
+
+    /// Drop the texel data, the texture can be loaded again later
+    pub fn unload(&mut self) {
+        self.texture = None;
+    }
+}
+
+/// Model read from a chunked container, with `model.textures` left empty and textures fetched from the reader on demand.
+/// Material texture indices refer to `textures`
+pub struct LazyModel<R> {
+    pub model: Model,
+    pub textures: Vec<TextureHandle>,
+    reader: ChunkedReader<R>,
+}
+
+impl<R: Read + Seek> LazyModel<R> {
+    /// Load texture `texture_idx` if it isn't loaded yet
+    pub fn load_texture(&mut self, texture_idx: usize) -> Result<&Texture> {
+        anyhow::ensure!(
+            texture_idx < self.textures.len(),
+            "Failed to load texture {}, index out of range.",
+            texture_idx
+        );
+
+        let handle = &mut self.textures[texture_idx];
+        if handle.texture.is_none() {
+            handle.texture = Some(self.reader.read_texture(texture_idx)?);
+        }
+        Ok(handle.texture.as_ref().unwrap())
+    }
+
+    /// Load all remaining textures and return the complete model
+    pub fn into_model(mut self) -> Result<Model> {
+        for texture_idx in 0..self.textures.len() {
+            self.load_texture(texture_idx)?;
+        }
+
+        let mut model = self.model;
+        model.textures = self
+            .textures
+            .into_iter()
+            .filter_map(|handle| handle.texture)
+            .collect();
+        Ok(model)
+    }
+}
+
+impl Model {
+    /// Read the nodes, meshes and materials of a container written by [`Model::write_chunked`], deferring all texture data to [`LazyModel::load_texture`]
+    pub fn read_lazy<R: Read + Seek>(reader: R) -> Result<LazyModel<R>> {
+        let mut reader = ChunkedReader::new(reader)?;
+        let model = reader.read_model_without_textures()?;
+        let textures = reader
+            .scene()
+            .textures
+            .iter()
+            .map(|info| TextureHandle {
+                info: info.clone(),
+                texture: None,
+            })
+            .collect();
+
+        Ok(LazyModel {
+            model,
+            textures,
+            reader,
+        })
+    }
+}
@@ -1,17 +1,77 @@
-use glam::Mat4;
-use material::Material;
+use animation::PropertyTrack;
+use glam::{Mat4, Vec3};
+use material::{Material, MaterialView};
 use mesh::Mesh;
 use speedy::{Readable, Writable};
+use stats::ModelStats;
 use texture::Texture;
 
+pub mod animation;
+pub mod atlas;
+mod bc;
+#[cfg(feature = "bevy")]
+pub mod bevy_loader;
+pub mod bvh;
+pub mod chunked;
+pub mod coordinate_system;
+mod dds;
+mod eac;
+pub mod edit;
+pub mod emissive;
+mod environment;
+pub mod exporter;
+mod flatten;
+#[cfg(feature = "gltf")]
+mod float_image;
+pub mod gpu;
+mod ktx2;
+pub mod lazy;
+pub mod lightmap;
 pub mod material;
+mod merge;
 pub mod mesh;
+pub mod meshlet;
+pub mod optimize;
+mod orm;
 pub mod packing;
 pub mod parser;
+mod placeholder;
+mod position_stream;
+pub mod quality;
+pub mod quantize;
+pub mod raycast;
+mod resample;
+pub mod serialization;
+pub mod shadow_proxy;
+pub mod stats;
+pub mod streaming;
+#[cfg(feature = "test-util")]
+pub mod test_util;
 pub mod texture;
+pub mod texture_pool;
+pub mod validation;
 
 pub use speedy;
 
+/// Bounds enclosing the bounds `min` to `max` after transforming all eight corners by `transform`
+pub(crate) fn transform_bounds(min: [f32; 3], max: [f32; 3], transform: Mat4) -> (Vec3, Vec3) {
+    let (min, max) = (Vec3::from_array(min), Vec3::from_array(max));
+    let mut bounds_min = Vec3::INFINITY;
+    let mut bounds_max = Vec3::NEG_INFINITY;
+    for corner in 0..8 {
+        let corner = Vec3::select(
+            glam::BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+            max,
+            min,
+        );
+        let corner = transform.transform_point3(corner);
+        bounds_min = bounds_min.min(corner);
+        bounds_max = bounds_max.max(corner);
+    }
+
+    (bounds_min, bounds_max)
+}
+
 #[derive(Debug, Clone, Readable, Writable)]
 pub struct ModelNode {
     pub name: String,
@@ -31,34 +91,252 @@ pub struct Model {
     pub meshes: Vec<Mesh>,
     pub materials: Vec<Material>,
     pub textures: Vec<Texture>,
+    /// Animated material properties, see [`animation`]
+    pub property_tracks: Vec<PropertyTrack>,
 }
 
 impl Model {
     #[cfg(feature = "gltf")]
     pub fn parse_glb(data: &[u8], opt: parser::ParseOptions) -> anyhow::Result<Self> {
+        parser::gltf::parse_glb(data, opt).map(|(model, _)| model)
+    }
+
+    /// Parse a glb or read a ugm container from a stream, detected by the magic at its start.
+    /// Glb files are read chunk by chunk, only holding their json and binary chunk in memory rather than the whole file.
+    /// Chunked and streamable ugm containers are read chunk by chunk too, uncompressed ugm containers are deserialized straight from the stream
+    #[cfg(feature = "gltf")]
+    pub fn from_reader<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        opt: parser::ParseOptions,
+    ) -> anyhow::Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        reader.seek(std::io::SeekFrom::Current(-4))?;
+
+        match &magic {
+            b"glTF" => parser::gltf::parse_glb_reader(reader, opt).map(|(model, _)| model),
+            magic if *magic == serialization::MAGIC => {
+                let mut header = [0; serialization::UgmHeader::SIZE];
+                reader.read_exact(&mut header)?;
+                reader.seek(std::io::SeekFrom::Current(-(header.len() as i64)))?;
+
+                let header = serialization::UgmHeader::from_bytes(&header)?;
+                if header.flags & serialization::FLAG_CHUNKED != 0 {
+                    chunked::ChunkedReader::new(reader)?.read_model()
+                } else if header.flags & serialization::FLAG_STREAMABLE != 0 {
+                    streaming::StreamableModel::read_resident(reader)?.into_resident(reader)
+                } else {
+                    Self::read_ugm(reader)
+                }
+            }
+            _ => anyhow::bail!("Failed to read model, the data is neither glb nor ugm."),
+        }
+    }
+
+    /// Same as [`Model::parse_glb`], running on a shared pool of worker threads so async asset servers can await the result without stalling their executor.
+    /// Works with any async runtime as it only relies on wakers. Progress is reported through [`parser::ParseOptions::progress`] like for blocking parsing,
+    /// dropping the task before it resolves cancels parsing through [`parser::ParseOptions::cancel`], which is created if not set
+    #[cfg(all(feature = "gltf", feature = "async"))]
+    pub fn parse_glb_async(
+        data: Vec<u8>,
+        mut opt: parser::ParseOptions,
+    ) -> parser::BlockingTask<anyhow::Result<Self>> {
+        let cancel = opt.cancel.get_or_insert_with(Default::default).clone();
+        parser::BlockingTask::spawn(move || Self::parse_glb(&data, opt), cancel)
+    }
+
+    /// Same as [`Model::parse_glb`], also returning the warnings found while parsing
+    #[cfg(feature = "gltf")]
+    pub fn parse_glb_with_report(
+        data: &[u8],
+        opt: parser::ParseOptions,
+    ) -> anyhow::Result<(Self, parser::ParseReport)> {
         parser::gltf::parse_glb(data, opt)
     }
 
+    /// Returns true if no mesh contains any geometry, the bounds of an empty model are zeroed
+    pub fn is_empty(&self) -> bool {
+        self.meshes.iter().all(Mesh::is_empty)
+    }
+
+    /// Export the whole model as binary glTF
+    #[cfg(feature = "gltf")]
+    pub fn export_glb(&self) -> anyhow::Result<Vec<u8>> {
+        exporter::gltf::export_glb(self, None)
+    }
+
+    /// Export the whole model as binary glTF into `writer`, see [`Model::export_glb`]
+    #[cfg(feature = "gltf")]
+    pub fn write_glb(&self, writer: &mut impl std::io::Write) -> anyhow::Result<()> {
+        writer.write_all(&self.export_glb()?)?;
+        Ok(())
+    }
+
+    /// Export part of the model as binary glTF, including only the materials and textures it depends on
+    #[cfg(feature = "gltf")]
+    pub fn export_glb_subset(&self, subset: &exporter::ExportSubset) -> anyhow::Result<Vec<u8>> {
+        exporter::gltf::export_glb(self, Some(subset))
+    }
+
+    /// Write all meshes as wavefront obj for debugging in any viewer, with one group per material.
+    /// When `bake_transforms` is set every mesh instance is written with its node transform applied, otherwise every mesh is written once in mesh space
+    pub fn write_obj(
+        &self,
+        writer: &mut impl std::io::Write,
+        bake_transforms: bool,
+    ) -> std::io::Result<()> {
+        exporter::obj::write_obj(self, writer, bake_transforms)
+    }
+
+    pub fn material_views(&self) -> impl Iterator<Item = MaterialView<'_>> {
+        self.materials
+            .iter()
+            .map(|material| MaterialView::new(material, &self.textures))
+    }
+
+    pub fn material_view(&self, material_idx: u32) -> Option<MaterialView<'_>> {
+        self.materials
+            .get(material_idx as usize)
+            .map(|material| MaterialView::new(material, &self.textures))
+    }
+
+    /// Computed from scratch, [`edit::EditableModel::stats`] keeps them up to date across edits instead
+    pub fn stats(&self) -> ModelStats {
+        ModelStats::new(self)
+    }
+
+    /// Indices of the nodes instancing each mesh, meshes merged by [`parser::ParseOptions::merge_duplicate_meshes`] list all nodes sharing them
+    pub fn mesh_instances(&self) -> Vec<Vec<u32>> {
+        let mut instances = vec![vec![]; self.meshes.len()];
+        for (node_idx, node) in self.nodes.iter().enumerate() {
+            if let Some(mesh_idx) = node.mesh_idx {
+                instances[mesh_idx as usize].push(node_idx as u32);
+            }
+        }
+
+        instances
+    }
+
+    /// World space bounds of all non empty mesh instances below the root nodes, zeroed if there are none.
+    /// `bounds_min` and `bounds_max` hold these bounds for an identity root transform
+    pub fn compute_world_bounds(&self, root_transform: Mat4) -> ([f32; 3], [f32; 3]) {
+        let mut bounds_min = Vec3::INFINITY;
+        let mut bounds_max = Vec3::NEG_INFINITY;
+        for (_, node, transform) in self.iter_nodes(root_transform) {
+            let Some(mesh) = node
+                .mesh_idx
+                .map(|mesh_idx| &self.meshes[mesh_idx as usize])
+            else {
+                continue;
+            };
+            if mesh.is_empty() {
+                continue;
+            }
+
+            let (min, max) = transform_bounds(mesh.bounds_min, mesh.bounds_max, transform);
+            bounds_min = bounds_min.min(min);
+            bounds_max = bounds_max.max(max);
+        }
+
+        if bounds_min.cmpgt(bounds_max).any() {
+            ([0.0; 3], [0.0; 3])
+        } else {
+            (bounds_min.to_array(), bounds_max.to_array())
+        }
+    }
+
+    /// Bounds of every node's subtree in the node's local space, `None` for subtrees without geometry and nodes below no root.
+    /// A subtree missed by a ray or outside a frustum can be skipped without visiting its meshes
+    pub fn compute_node_bounds(&self) -> Vec<Option<([f32; 3], [f32; 3])>> {
+        let mut node_bounds: Vec<Option<(Vec3, Vec3)>> = vec![None; self.nodes.len()];
+        let node_indices: Vec<u32> = self
+            .iter_nodes(Mat4::IDENTITY)
+            .map(|(node_idx, _, _)| node_idx)
+            .collect();
+
+        // Children follow their parent in depth first order, so they're done first in reverse
+        for node_idx in node_indices.into_iter().rev() {
+            let node = &self.nodes[node_idx as usize];
+            let mut bounds = node
+                .mesh_idx
+                .map(|mesh_idx| &self.meshes[mesh_idx as usize])
+                .filter(|mesh| !mesh.is_empty())
+                .map(|mesh| {
+                    (
+                        Vec3::from_array(mesh.bounds_min),
+                        Vec3::from_array(mesh.bounds_max),
+                    )
+                });
+            for child_idx in &node.child_node_indices {
+                let Some((min, max)) = node_bounds[*child_idx as usize] else {
+                    continue;
+                };
+                let child = &self.nodes[*child_idx as usize];
+                let (min, max) = transform_bounds(
+                    min.to_array(),
+                    max.to_array(),
+                    Mat4::from_cols_array(&child.transform),
+                );
+                bounds = Some(match bounds {
+                    Some((bounds_min, bounds_max)) => (bounds_min.min(min), bounds_max.max(max)),
+                    None => (min, max),
+                });
+            }
+            node_bounds[node_idx as usize] = bounds;
+        }
+
+        node_bounds
+            .into_iter()
+            .map(|bounds| bounds.map(|(min, max)| (min.to_array(), max.to_array())))
+            .collect()
+    }
+
+    /// Call `callback` for every node below the root nodes, depth first in the order of [`Model::iter_nodes`]
     pub fn traverse_nodes<F>(&self, root_transform: Mat4, mut callback: F)
     where
         F: FnMut(&ModelNode, Mat4),
     {
-        for root_node in &self.root_node_indices {
-            self.traverse_nodes_recursive(*root_node, root_transform, &mut callback);
+        for (_, node, transform) in self.iter_nodes(root_transform) {
+            callback(node, transform);
         }
     }
 
-    fn traverse_nodes_recursive<F>(&self, node: u32, parent_transform: Mat4, callback: &mut F)
-    where
-        F: FnMut(&ModelNode, Mat4),
-    {
-        let node = &self.nodes[node as usize];
-        let transform = parent_transform * Mat4::from_cols_array(&node.transform);
+    /// Iterate depth first over every node below the root nodes with its index and world transform.
+    /// Uses an explicit stack, so deep hierarchies can't overflow the call stack
+    pub fn iter_nodes(&self, root_transform: Mat4) -> NodeIter<'_> {
+        NodeIter {
+            model: self,
+            stack: self
+                .root_node_indices
+                .iter()
+                .rev()
+                .map(|node_idx| (*node_idx, root_transform))
+                .collect(),
+        }
+    }
+}
 
-        callback(node, transform);
+/// Depth first iterator over the nodes of a model, see [`Model::iter_nodes`]
+pub struct NodeIter<'a> {
+    model: &'a Model,
+    /// Nodes still to visit with their parent transform, the next node last
+    stack: Vec<(u32, Mat4)>,
+}
 
-        for child_node in &node.child_node_indices {
-            self.traverse_nodes_recursive(*child_node, transform, callback);
-        }
+impl<'a> Iterator for NodeIter<'a> {
+    type Item = (u32, &'a ModelNode, Mat4);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node_idx, parent_transform) = self.stack.pop()?;
+        let node = &self.model.nodes[node_idx as usize];
+        let transform = parent_transform * Mat4::from_cols_array(&node.transform);
+        self.stack.extend(
+            node.child_node_indices
+                .iter()
+                .rev()
+                .map(|child_idx| (*child_idx, transform)),
+        );
+
+        Some((node_idx, node, transform))
     }
 }
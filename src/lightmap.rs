@@ -0,0 +1,358 @@
+//! Chart based unwrapping of a non overlapping second uv set, for baked lighting
+
+use std::collections::{HashMap, VecDeque};
+
+use glam::{Vec2, Vec3};
+
+use crate::{
+    mesh::{Indices, Mesh},
+    Model,
+};
+
+/// Factor the texel density shrinks by each time the charts don't fit
+const SHRINK_FACTOR: f32 = 0.9;
+const MAX_PACK_ATTEMPTS: u32 = 64;
+
+#[derive(Debug, Clone, Copy)]
+pub struct LightmapUvDesc {
+    /// Expected width and height in texels of the lightmap, padding is computed in this resolution
+    pub resolution: u32,
+    /// Empty texels around each chart, preventing bleeding between charts when filtering
+    pub padding: u32,
+    /// Largest angle in radians between the normals of triangles in the same chart
+    pub max_chart_angle: f32,
+}
+
+impl Default for LightmapUvDesc {
+    fn default() -> Self {
+        Self {
+            resolution: 1024,
+            padding: 2,
+            max_chart_angle: 45f32.to_radians(),
+        }
+    }
+}
+
+/// Connected triangles projected onto the plane of their first triangle
+struct Chart {
+    triangles: Vec<usize>,
+    /// Projected position of every corner of `triangles`
+    corners: Vec<Vec2>,
+    min: Vec2,
+    size: Vec2,
+}
+
+/// Texel position of the chart's padded rect
+struct Placement {
+    x: u32,
+    y: u32,
+}
+
+fn grow_charts(positions: &[Vec3], indices: &[u32], max_chart_angle: f32) -> Vec<Chart> {
+    let num_triangles = indices.len() / 3;
+    let corner =
+        |triangle_idx: usize, corner: usize| positions[indices[triangle_idx * 3 + corner] as usize];
+    let face_normals: Vec<Vec3> = (0..num_triangles)
+        .map(|triangle_idx| {
+            (corner(triangle_idx, 1) - corner(triangle_idx, 0))
+                .cross(corner(triangle_idx, 2) - corner(triangle_idx, 0))
+                .normalize_or_zero()
+        })
+        .collect();
+
+    // Vertices split for uv or normal seams are welded so charts can grow across them
+    let mut welded_ids: HashMap<[u32; 3], u32> = HashMap::new();
+    let welded: Vec<u32> = positions
+        .iter()
+        .map(|position| {
+            let next_id = welded_ids.len() as u32;
+            *welded_ids
+                .entry(position.to_array().map(f32::to_bits))
+                .or_insert(next_id)
+        })
+        .collect();
+    let edge = |triangle: &[u32], i: usize| {
+        let (a, b) = (
+            welded[triangle[i] as usize],
+            welded[triangle[(i + 1) % 3] as usize],
+        );
+        (a.min(b), a.max(b))
+    };
+
+    let mut edge_triangles: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+    for (triangle_idx, triangle) in indices.chunks_exact(3).enumerate() {
+        for i in 0..3 {
+            edge_triangles
+                .entry(edge(triangle, i))
+                .or_default()
+                .push(triangle_idx);
+        }
+    }
+
+    // Grid used to find overlapping projected triangles, with cells about the size of a triangle
+    let cell_size = indices
+        .chunks_exact(3)
+        .map(|triangle| positions[triangle[0] as usize].distance(positions[triangle[1] as usize]))
+        .sum::<f32>()
+        / num_triangles.max(1) as f32;
+    let cell_size = if cell_size > 0.0 { cell_size } else { 1.0 };
+    let cells = |projected: &[Vec2; 3]| {
+        let min = (projected[0].min(projected[1]).min(projected[2]) / cell_size).floor();
+        let max = (projected[0].max(projected[1]).max(projected[2]) / cell_size).floor();
+        (min.y as i32..=max.y as i32)
+            .flat_map(move |y| (min.x as i32..=max.x as i32).map(move |x| (x, y)))
+    };
+
+    let min_dot = max_chart_angle.cos();
+    let mut chart_of = vec![false; num_triangles];
+    let mut charts = vec![];
+    for seed in 0..num_triangles {
+        if chart_of[seed] {
+            continue;
+        }
+        chart_of[seed] = true;
+
+        let normal = face_normals[seed];
+        let (u_axis, v_axis) = if normal == Vec3::ZERO {
+            (Vec3::X, Vec3::Y)
+        } else {
+            normal.any_orthonormal_pair()
+        };
+        let project = |triangle_idx: usize| {
+            [0, 1, 2].map(|i| {
+                let position = corner(triangle_idx, i);
+                Vec2::new(position.dot(u_axis), position.dot(v_axis))
+            })
+        };
+
+        let mut triangles = vec![];
+        let mut corners: Vec<Vec2> = vec![];
+        let mut grid: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+        let mut queue = VecDeque::from([seed]);
+        while let Some(triangle_idx) = queue.pop_front() {
+            // Curved surfaces can fold over themselves when projected, such triangles are left for later charts
+            let projected = project(triangle_idx);
+            let overlaps = cells(&projected).any(|cell| {
+                grid.get(&cell).is_some_and(|locals| {
+                    locals.iter().any(|local| {
+                        triangles_overlap(&projected, &corners[local * 3..local * 3 + 3])
+                    })
+                })
+            });
+            if overlaps {
+                chart_of[triangle_idx] = false;
+                continue;
+            }
+
+            for cell in cells(&projected) {
+                grid.entry(cell).or_default().push(triangles.len());
+            }
+            triangles.push(triangle_idx);
+            corners.extend(projected);
+
+            let triangle = &indices[triangle_idx * 3..triangle_idx * 3 + 3];
+            for i in 0..3 {
+                for neighbour in &edge_triangles[&edge(triangle, i)] {
+                    if !chart_of[*neighbour] && face_normals[*neighbour].dot(normal) >= min_dot {
+                        chart_of[*neighbour] = true;
+                        queue.push_back(*neighbour);
+                    }
+                }
+            }
+        }
+
+        let min = corners.iter().copied().fold(Vec2::INFINITY, Vec2::min);
+        let max = corners.iter().copied().fold(Vec2::NEG_INFINITY, Vec2::max);
+
+        charts.push(Chart {
+            triangles,
+            corners,
+            min,
+            size: max - min,
+        });
+    }
+
+    charts
+}
+
+/// Returns true if the interiors of two 2d triangles intersect, triangles only sharing an edge or corner don't
+fn triangles_overlap(a: &[Vec2], b: &[Vec2]) -> bool {
+    // Relative to the magnitude of the coordinates, as projections of shared edges differ by rounding
+    const RELATIVE_EPSILON: f32 = 1e-5;
+    let epsilon = a
+        .iter()
+        .chain(b)
+        .map(|point| point.abs().max_element())
+        .fold(f32::MIN_POSITIVE, f32::max)
+        * RELATIVE_EPSILON;
+
+    // Separating axis test over the edge normals of both triangles
+    for triangle in [a, b] {
+        for i in 0..3 {
+            let axis = (triangle[(i + 1) % 3] - triangle[i])
+                .perp()
+                .normalize_or_zero();
+            if axis == Vec2::ZERO {
+                continue;
+            }
+            let range = |points: &[Vec2]| {
+                points
+                    .iter()
+                    .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), point| {
+                        let d = point.dot(axis);
+                        (min.min(d), max.max(d))
+                    })
+            };
+            let (min_a, max_a) = range(a);
+            let (min_b, max_b) = range(b);
+            if max_a <= min_b + epsilon || max_b <= min_a + epsilon {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+/// Shelf pack all charts at `texels_per_unit`, `None` if they don't fit
+fn pack_charts(
+    charts: &[Chart],
+    order: &[usize],
+    texels_per_unit: f32,
+    desc: &LightmapUvDesc,
+) -> Option<Vec<Placement>> {
+    let padded_size = |chart: &Chart| {
+        let size = (chart.size * texels_per_unit).ceil();
+        (
+            size.x as u32 + desc.padding * 2,
+            size.y as u32 + desc.padding * 2,
+        )
+    };
+
+    let mut placements: Vec<Option<Placement>> = (0..charts.len()).map(|_| None).collect();
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+    for chart_idx in order {
+        let (width, height) = padded_size(&charts[*chart_idx]);
+        if width > desc.resolution {
+            return None;
+        }
+        if x + width > desc.resolution {
+            y += shelf_height;
+            (x, shelf_height) = (0, 0);
+        }
+        if y + height > desc.resolution {
+            return None;
+        }
+
+        placements[*chart_idx] = Some(Placement { x, y });
+        x += width;
+        shelf_height = shelf_height.max(height);
+    }
+
+    placements.into_iter().collect()
+}
+
+impl Mesh {
+    /// Replace `secondary_tex_coords` by a generated non overlapping unwrap, duplicating vertices on chart borders.
+    /// The mesh is left unchanged if its charts don't fit the resolution with their padding.
+    /// Meshlets are rebuilt, shadow proxies and bvhs don't reference vertices and stay valid
+    pub fn generate_lightmap_uvs(&mut self, desc: &LightmapUvDesc) {
+        let is_quantized = self.quantized_vertices.is_some();
+        self.dequantize_positions();
+        self.unwrap_lightmap_uvs(desc);
+        if is_quantized {
+            self.quantize_positions();
+        }
+    }
+
+    fn unwrap_lightmap_uvs(&mut self, desc: &LightmapUvDesc) {
+        let positions: Vec<Vec3> = self
+            .packed_vertices
+            .iter()
+            .map(|vertex| Vec3::from_array(vertex.position))
+            .collect();
+        let mut indices = self.indices.to_u32().into_owned();
+        let charts = grow_charts(&positions, &indices, desc.max_chart_angle);
+
+        // Tallest charts first keeps shelves tightly filled
+        let mut order: Vec<usize> = (0..charts.len()).collect();
+        order.sort_by(|a, b| charts[*b].size.y.total_cmp(&charts[*a].size.y));
+
+        let total_area: f32 = charts.iter().map(|chart| chart.size.x * chart.size.y).sum();
+        let mut texels_per_unit = if total_area > 0.0 {
+            desc.resolution as f32 / total_area.sqrt()
+        } else {
+            1.0
+        };
+        let mut placements = None;
+        for _ in 0..MAX_PACK_ATTEMPTS {
+            placements = pack_charts(&charts, &order, texels_per_unit, desc);
+            if placements.is_some() {
+                break;
+            }
+            texels_per_unit *= SHRINK_FACTOR;
+        }
+        let Some(placements) = placements else {
+            return;
+        };
+
+        let num_source_vertices = self.packed_vertices.len();
+        let mut tex_coords = vec![[0.0; 2]; num_source_vertices];
+        let mut vertex_chart: Vec<Option<usize>> = vec![None; num_source_vertices];
+        let mut duplicates: HashMap<(u32, usize), u32> = HashMap::new();
+        for (chart_idx, (chart, placement)) in charts.iter().zip(&placements).enumerate() {
+            let origin = Vec2::new(placement.x as f32, placement.y as f32) + desc.padding as f32;
+
+            for (corner_idx, triangle_idx) in chart
+                .triangles
+                .iter()
+                .flat_map(|triangle_idx| [*triangle_idx; 3])
+                .enumerate()
+            {
+                let index = &mut indices[triangle_idx * 3 + corner_idx % 3];
+                let vertex_idx = *index;
+
+                let target_idx = match vertex_chart[vertex_idx as usize] {
+                    None => {
+                        vertex_chart[vertex_idx as usize] = Some(chart_idx);
+                        vertex_idx
+                    }
+                    Some(other) if other == chart_idx => vertex_idx,
+                    Some(_) => *duplicates
+                        .entry((vertex_idx, chart_idx))
+                        .or_insert_with(|| {
+                            let vertex = self.packed_vertices[vertex_idx as usize];
+                            self.packed_vertices.push(vertex);
+                            if !self.unpacked_vertices.is_empty() {
+                                let vertex = self.unpacked_vertices[vertex_idx as usize];
+                                self.unpacked_vertices.push(vertex);
+                            }
+                            tex_coords.push([0.0; 2]);
+                            self.packed_vertices.len() as u32 - 1
+                        }),
+                };
+
+                let texel = origin + (chart.corners[corner_idx] - chart.min) * texels_per_unit;
+                tex_coords[target_idx as usize] = (texel / desc.resolution as f32).to_array();
+                *index = target_idx;
+            }
+        }
+
+        self.secondary_tex_coords = tex_coords;
+        self.indices = Indices::new(indices, self.packed_vertices.len());
+        self.refresh_position_stream();
+        if let Some(meshlets) = &self.meshlets {
+            self.meshlets =
+                Some(self.build_meshlets(meshlets.max_vertices, meshlets.max_triangles));
+        }
+    }
+}
+
+impl Model {
+    /// Generate lightmap uvs for every non empty mesh, see [`Mesh::generate_lightmap_uvs`]
+    pub fn generate_lightmap_uvs(&mut self, desc: &LightmapUvDesc) {
+        for mesh in self.meshes.iter_mut().filter(|mesh| !mesh.is_empty()) {
+            mesh.generate_lightmap_uvs(desc);
+        }
+    }
+}
@@ -1,5 +1,33 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Deref,
+};
+
 use speedy::{Readable, Writable};
 
+use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
+
+use crate::{
+    animation::{remap_material_tracks, PropertyTarget},
+    mesh::Mesh,
+    packing::PackedRgb9e5,
+    texture::Texture,
+    Model,
+};
+
+/// How the alpha of the base color is interpreted, mirroring the glTF alpha modes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Readable, Writable)]
+pub enum AlphaMode {
+    /// Alpha is ignored
+    #[default]
+    Opaque,
+    /// Alpha tested against [`Material::alpha_cutoff`]
+    Mask,
+    /// Alpha blended with whatever is behind
+    Blend,
+}
+
 #[derive(Debug, Clone, Readable, Writable)]
 pub struct Material {
     pub index: Option<usize>,
@@ -7,17 +35,25 @@ pub struct Material {
 
     pub color: [f32; 3],
     pub color_texture: Option<u32>,
+    pub color_tex_coord: u32,
     pub metallic: f32,
     pub roughness: f32,
     pub metallic_roughness_texture: Option<u32>,
+    pub metallic_roughness_tex_coord: u32,
     pub normal_scale: f32,
     pub normal_texture: Option<u32>,
+    pub normal_tex_coord: u32,
     pub emission: [f32; 3],
     pub emission_texture: Option<u32>,
+    pub emission_tex_coord: u32,
+    pub occlusion_strength: f32,
+    pub occlusion_texture: Option<u32>,
+    pub occlusion_tex_coord: u32,
 
     pub absorption: [f32; 3],
     pub transmission: f32,
     pub transmission_texture: Option<u32>,
+    pub transmission_tex_coord: u32,
     pub eta: f32,
 
     pub subsurface: f32,
@@ -27,16 +63,22 @@ pub struct Material {
 
     pub sheen: f32,
     pub sheen_texture: Option<u32>,
+    pub sheen_tex_coord: u32,
     pub sheen_tint: [f32; 3],
     pub sheen_tint_texture: Option<u32>,
+    pub sheen_tint_tex_coord: u32,
 
     pub clearcoat: f32,
     pub clearcoat_texture: Option<u32>,
+    pub clearcoat_tex_coord: u32,
     pub clearcoat_roughness: f32,
     pub clearcoat_roughness_texture: Option<u32>,
+    pub clearcoat_roughness_tex_coord: u32,
     pub clearcoat_normal_texture: Option<u32>,
+    pub clearcoat_normal_tex_coord: u32,
 
     pub is_opaque: bool,
+    pub alpha_mode: AlphaMode,
     pub alpha_cutoff: f32,
 }
 
@@ -47,17 +89,25 @@ impl Default for Material {
             name: "Unnamed".to_owned(),
             color: [1.0; 3],
             color_texture: None,
+            color_tex_coord: 0,
             metallic: 0.0,
             roughness: 0.5,
             metallic_roughness_texture: None,
+            metallic_roughness_tex_coord: 0,
             normal_scale: 1.0,
             normal_texture: None,
+            normal_tex_coord: 0,
             emission: [0.0; 3],
             emission_texture: None,
+            emission_tex_coord: 0,
+            occlusion_strength: 1.0,
+            occlusion_texture: None,
+            occlusion_tex_coord: 0,
 
             absorption: [0.0; 3],
             transmission: 0.0,
             transmission_texture: None,
+            transmission_tex_coord: 0,
             eta: 1.0 / 1.5,
 
             subsurface: 0.0,
@@ -67,23 +117,413 @@ impl Default for Material {
 
             sheen: 0.0,
             sheen_texture: None,
+            sheen_tex_coord: 0,
             sheen_tint: [1.0; 3],
             sheen_tint_texture: None,
+            sheen_tint_tex_coord: 0,
 
             clearcoat: 0.0,
             clearcoat_texture: None,
+            clearcoat_tex_coord: 0,
             clearcoat_roughness: 0.0,
             clearcoat_roughness_texture: None,
+            clearcoat_roughness_tex_coord: 0,
             clearcoat_normal_texture: None,
+            clearcoat_normal_tex_coord: 0,
 
             is_opaque: true,
+            alpha_mode: AlphaMode::Opaque,
             alpha_cutoff: 0.0,
         }
     }
 }
 
 impl Material {
+    pub const PIPELINE_OPAQUE: u32 = 1 << 0;
+    pub const PIPELINE_COLOR_TEXTURE: u32 = 1 << 1;
+    pub const PIPELINE_METALLIC_ROUGHNESS_TEXTURE: u32 = 1 << 2;
+    pub const PIPELINE_NORMAL_TEXTURE: u32 = 1 << 3;
+    pub const PIPELINE_EMISSION_TEXTURE: u32 = 1 << 4;
+    pub const PIPELINE_OCCLUSION_TEXTURE: u32 = 1 << 5;
+    pub const PIPELINE_TRANSMISSION_TEXTURE: u32 = 1 << 6;
+    pub const PIPELINE_TRANSMISSION: u32 = 1 << 7;
+    pub const PIPELINE_SHEEN: u32 = 1 << 8;
+    pub const PIPELINE_CLEARCOAT: u32 = 1 << 9;
+    pub const PIPELINE_EMISSIVE: u32 = 1 << 10;
+
+    /// Texture indices of all slots
+    pub fn textures(&self) -> [Option<u32>; 11] {
+        [
+            self.color_texture,
+            self.metallic_roughness_texture,
+            self.normal_texture,
+            self.emission_texture,
+            self.occlusion_texture,
+            self.transmission_texture,
+            self.sheen_texture,
+            self.sheen_tint_texture,
+            self.clearcoat_texture,
+            self.clearcoat_roughness_texture,
+            self.clearcoat_normal_texture,
+        ]
+    }
+
+    /// Tex coord set of all slots, in the same order as [`Material::textures`]
+    pub fn tex_coords(&self) -> [u32; 11] {
+        [
+            self.color_tex_coord,
+            self.metallic_roughness_tex_coord,
+            self.normal_tex_coord,
+            self.emission_tex_coord,
+            self.occlusion_tex_coord,
+            self.transmission_tex_coord,
+            self.sheen_tex_coord,
+            self.sheen_tint_tex_coord,
+            self.clearcoat_tex_coord,
+            self.clearcoat_roughness_tex_coord,
+            self.clearcoat_normal_tex_coord,
+        ]
+    }
+
+    /// Mutable texture indices of all slots, in the same order as [`Material::textures`]
+    pub fn textures_mut(&mut self) -> [&mut Option<u32>; 11] {
+        [
+            &mut self.color_texture,
+            &mut self.metallic_roughness_texture,
+            &mut self.normal_texture,
+            &mut self.emission_texture,
+            &mut self.occlusion_texture,
+            &mut self.transmission_texture,
+            &mut self.sheen_texture,
+            &mut self.sheen_tint_texture,
+            &mut self.clearcoat_texture,
+            &mut self.clearcoat_roughness_texture,
+            &mut self.clearcoat_normal_texture,
+        ]
+    }
+
+    /// Drawn blended with whatever is behind, either alpha blended or transmissive
+    pub fn is_blended(&self) -> bool {
+        !self.is_opaque && (self.alpha_mode == AlphaMode::Blend || self.transmission > 0.0)
+    }
+
     pub fn is_emissive(&self) -> bool {
         self.emission[0] > 0.0 || self.emission[1] > 0.0 || self.emission[2] > 0.0
     }
+
+    /// Bitfield of all material state that affects pipeline selection, textures bound and factors are ignored.
+    /// Bits are the `PIPELINE_*` constants, stable across versions so renderers can decode them from a [`crate::mesh::SortKey`]
+    pub fn pipeline_flags(&self) -> u32 {
+        [
+            (self.is_opaque, Self::PIPELINE_OPAQUE),
+            (self.color_texture.is_some(), Self::PIPELINE_COLOR_TEXTURE),
+            (
+                self.metallic_roughness_texture.is_some(),
+                Self::PIPELINE_METALLIC_ROUGHNESS_TEXTURE,
+            ),
+            (self.normal_texture.is_some(), Self::PIPELINE_NORMAL_TEXTURE),
+            (
+                self.emission_texture.is_some(),
+                Self::PIPELINE_EMISSION_TEXTURE,
+            ),
+            (
+                self.occlusion_texture.is_some(),
+                Self::PIPELINE_OCCLUSION_TEXTURE,
+            ),
+            (
+                self.transmission_texture.is_some(),
+                Self::PIPELINE_TRANSMISSION_TEXTURE,
+            ),
+            (self.transmission > 0.0, Self::PIPELINE_TRANSMISSION),
+            (self.sheen > 0.0, Self::PIPELINE_SHEEN),
+            (self.clearcoat > 0.0, Self::PIPELINE_CLEARCOAT),
+            (self.is_emissive(), Self::PIPELINE_EMISSIVE),
+        ]
+        .into_iter()
+        .filter(|(enabled, _)| *enabled)
+        .fold(0, |flags, (_, flag)| flags | flag)
+    }
+}
+
+/// Compact encoding of a [`Material`] for gpu material buffers, colors are stored as [`PackedRgb9e5`] and the name and index are dropped
+#[derive(Debug, Pod, Clone, Copy, Zeroable, Readable, Writable)]
+#[repr(C)]
+pub struct PackedMaterial {
+    pub color: PackedRgb9e5,
+    pub emission: PackedRgb9e5,
+    pub absorption: PackedRgb9e5,
+    pub specular_tint: PackedRgb9e5,
+    pub sheen_tint: PackedRgb9e5,
+
+    pub metallic: f32,
+    pub roughness: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    pub transmission: f32,
+    pub eta: f32,
+    pub subsurface: f32,
+    pub specular: f32,
+    pub anisotropic: f32,
+    pub sheen: f32,
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+    pub alpha_cutoff: f32,
+
+    /// Texture index per slot in the order of [`Material::textures`], [`PackedMaterial::NO_TEXTURE`] if unbound
+    pub textures: [u32; 11],
+    /// Tex coord set per slot in the order of [`Material::tex_coords`]
+    pub tex_coords: [u8; 12],
+    /// 1 if opaque, 0 otherwise
+    pub is_opaque: u32,
+    /// [`AlphaMode`] discriminant
+    pub alpha_mode: u32,
+}
+
+impl PackedMaterial {
+    pub const NO_TEXTURE: u32 = u32::MAX;
+
+    /// Decode into a full precision material, colors are only approximately restored
+    pub fn unpack(&self) -> Material {
+        let mut material = Material {
+            color: self.color.unpack().to_array(),
+            emission: self.emission.unpack().to_array(),
+            absorption: self.absorption.unpack().to_array(),
+            specular_tint: self.specular_tint.unpack().to_array(),
+            sheen_tint: self.sheen_tint.unpack().to_array(),
+            metallic: self.metallic,
+            roughness: self.roughness,
+            normal_scale: self.normal_scale,
+            occlusion_strength: self.occlusion_strength,
+            transmission: self.transmission,
+            eta: self.eta,
+            subsurface: self.subsurface,
+            specular: self.specular,
+            anisotropic: self.anisotropic,
+            sheen: self.sheen,
+            clearcoat: self.clearcoat,
+            clearcoat_roughness: self.clearcoat_roughness,
+            alpha_cutoff: self.alpha_cutoff,
+            is_opaque: self.is_opaque != 0,
+            alpha_mode: match self.alpha_mode {
+                1 => AlphaMode::Mask,
+                2 => AlphaMode::Blend,
+                _ => AlphaMode::Opaque,
+            },
+            ..Default::default()
+        };
+
+        for (texture, packed) in material.textures_mut().into_iter().zip(self.textures) {
+            *texture = (packed != Self::NO_TEXTURE).then_some(packed);
+        }
+        material.color_tex_coord = self.tex_coords[0] as u32;
+        material.metallic_roughness_tex_coord = self.tex_coords[1] as u32;
+        material.normal_tex_coord = self.tex_coords[2] as u32;
+        material.emission_tex_coord = self.tex_coords[3] as u32;
+        material.occlusion_tex_coord = self.tex_coords[4] as u32;
+        material.transmission_tex_coord = self.tex_coords[5] as u32;
+        material.sheen_tex_coord = self.tex_coords[6] as u32;
+        material.sheen_tint_tex_coord = self.tex_coords[7] as u32;
+        material.clearcoat_tex_coord = self.tex_coords[8] as u32;
+        material.clearcoat_roughness_tex_coord = self.tex_coords[9] as u32;
+        material.clearcoat_normal_tex_coord = self.tex_coords[10] as u32;
+
+        material
+    }
+}
+
+impl Material {
+    /// Compact encoding of the material, see [`PackedMaterial`]
+    pub fn packed(&self) -> PackedMaterial {
+        let mut tex_coords = [0; 12];
+        for (packed, tex_coord) in tex_coords.iter_mut().zip(self.tex_coords()) {
+            *packed = tex_coord as u8;
+        }
+
+        PackedMaterial {
+            color: PackedRgb9e5::new(Vec3::from_array(self.color)),
+            emission: PackedRgb9e5::new(Vec3::from_array(self.emission)),
+            absorption: PackedRgb9e5::new(Vec3::from_array(self.absorption)),
+            specular_tint: PackedRgb9e5::new(Vec3::from_array(self.specular_tint)),
+            sheen_tint: PackedRgb9e5::new(Vec3::from_array(self.sheen_tint)),
+            metallic: self.metallic,
+            roughness: self.roughness,
+            normal_scale: self.normal_scale,
+            occlusion_strength: self.occlusion_strength,
+            transmission: self.transmission,
+            eta: self.eta,
+            subsurface: self.subsurface,
+            specular: self.specular,
+            anisotropic: self.anisotropic,
+            sheen: self.sheen,
+            clearcoat: self.clearcoat,
+            clearcoat_roughness: self.clearcoat_roughness,
+            alpha_cutoff: self.alpha_cutoff,
+            textures: self
+                .textures()
+                .map(|texture| texture.unwrap_or(PackedMaterial::NO_TEXTURE)),
+            tex_coords,
+            is_opaque: self.is_opaque as u32,
+            alpha_mode: self.alpha_mode as u32,
+        }
+    }
+}
+
+impl Material {
+    /// Serialized parameters and texture references, equal for materials only differing in name and source index
+    fn parameter_block(&self) -> Vec<u8> {
+        Material {
+            index: None,
+            name: String::new(),
+            ..self.clone()
+        }
+        .write_to_vec()
+        .expect("Failed to serialize material parameters.")
+    }
+}
+
+/// Point `mesh` at remapped model materials, merging its local materials that now refer to the same material
+fn remap_mesh_materials(mesh: &mut Mesh, remap: &[u32]) {
+    let mut material_indices: Vec<u32> = vec![];
+    let local_remap: Vec<u32> = mesh
+        .material_indices
+        .iter()
+        .map(|material_idx| {
+            let material_idx = remap[*material_idx as usize];
+            match material_indices.iter().position(|idx| *idx == material_idx) {
+                Some(local_material_idx) => local_material_idx as u32,
+                None => {
+                    material_indices.push(material_idx);
+                    material_indices.len() as u32 - 1
+                }
+            }
+        })
+        .collect();
+
+    mesh.material_indices = material_indices;
+    for local_material_idx in &mut mesh.triangle_material_indices {
+        *local_material_idx = local_remap[*local_material_idx as usize];
+    }
+    for submesh in &mut mesh.submeshes {
+        submesh.material = local_remap[submesh.material as usize];
+    }
+    if let Some(meshlets) = &mut mesh.meshlets {
+        for meshlet in &mut meshlets.meshlets {
+            meshlet.material_idx = local_remap[meshlet.material_idx as usize];
+        }
+    }
+}
+
+impl Model {
+    /// Merge materials with identical parameters and texture references, keeping the first of each.
+    /// Names aren't compared and animated materials are never merged, returns the new index of each previous material
+    pub fn deduplicate_materials(&mut self) -> Vec<u32> {
+        let animated: HashSet<u32> = self
+            .property_tracks
+            .iter()
+            .filter(|track| track.path.target == PropertyTarget::Material)
+            .map(|track| track.path.index)
+            .collect();
+
+        let mut unique_materials: HashMap<(Vec<u8>, Option<u32>), u32> = HashMap::new();
+        let mut materials = vec![];
+        let remap: Vec<u32> = std::mem::take(&mut self.materials)
+            .into_iter()
+            .enumerate()
+            .map(|(material_idx, material)| {
+                let material_idx = material_idx as u32;
+                let animated_idx = animated.contains(&material_idx).then_some(material_idx);
+                *unique_materials
+                    .entry((material.parameter_block(), animated_idx))
+                    .or_insert_with(|| {
+                        materials.push(material);
+                        materials.len() as u32 - 1
+                    })
+            })
+            .collect();
+        self.materials = materials;
+        remap_material_tracks(&mut self.property_tracks, |material_idx| {
+            remap.get(material_idx as usize).copied()
+        });
+
+        for mesh in &mut self.meshes {
+            remap_mesh_materials(mesh, &remap);
+            mesh.compute_sort_keys(&self.materials);
+        }
+
+        remap
+    }
+}
+
+/// Texture bound to a material slot
+#[derive(Debug, Clone, Copy)]
+pub struct MaterialTexture<'a> {
+    pub index: u32,
+    pub texture: &'a Texture,
+    pub tex_coord: u32,
+}
+
+/// Material with its texture indices resolved, out of range indices resolve to `None`
+#[derive(Debug, Clone, Copy)]
+pub struct MaterialView<'a> {
+    pub material: &'a Material,
+    pub color_texture: Option<MaterialTexture<'a>>,
+    pub metallic_roughness_texture: Option<MaterialTexture<'a>>,
+    pub normal_texture: Option<MaterialTexture<'a>>,
+    pub emission_texture: Option<MaterialTexture<'a>>,
+    pub occlusion_texture: Option<MaterialTexture<'a>>,
+    pub transmission_texture: Option<MaterialTexture<'a>>,
+    pub sheen_texture: Option<MaterialTexture<'a>>,
+    pub sheen_tint_texture: Option<MaterialTexture<'a>>,
+    pub clearcoat_texture: Option<MaterialTexture<'a>>,
+    pub clearcoat_roughness_texture: Option<MaterialTexture<'a>>,
+    pub clearcoat_normal_texture: Option<MaterialTexture<'a>>,
+}
+
+impl<'a> MaterialView<'a> {
+    pub fn new(material: &'a Material, textures: &'a [Texture]) -> Self {
+        let resolve = |index: Option<u32>, tex_coord: u32| {
+            index.and_then(|index| {
+                textures.get(index as usize).map(|texture| MaterialTexture {
+                    index,
+                    texture,
+                    tex_coord,
+                })
+            })
+        };
+
+        Self {
+            material,
+            color_texture: resolve(material.color_texture, material.color_tex_coord),
+            metallic_roughness_texture: resolve(
+                material.metallic_roughness_texture,
+                material.metallic_roughness_tex_coord,
+            ),
+            normal_texture: resolve(material.normal_texture, material.normal_tex_coord),
+            emission_texture: resolve(material.emission_texture, material.emission_tex_coord),
+            occlusion_texture: resolve(material.occlusion_texture, material.occlusion_tex_coord),
+            transmission_texture: resolve(
+                material.transmission_texture,
+                material.transmission_tex_coord,
+            ),
+            sheen_texture: resolve(material.sheen_texture, material.sheen_tex_coord),
+            sheen_tint_texture: resolve(material.sheen_tint_texture, material.sheen_tint_tex_coord),
+            clearcoat_texture: resolve(material.clearcoat_texture, material.clearcoat_tex_coord),
+            clearcoat_roughness_texture: resolve(
+                material.clearcoat_roughness_texture,
+                material.clearcoat_roughness_tex_coord,
+            ),
+            clearcoat_normal_texture: resolve(
+                material.clearcoat_normal_texture,
+                material.clearcoat_normal_tex_coord,
+            ),
+        }
+    }
+}
+
+impl Deref for MaterialView<'_> {
+    type Target = Material;
+
+    fn deref(&self) -> &Self::Target {
+        self.material
+    }
 }
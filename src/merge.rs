@@ -0,0 +1,196 @@
+use glam::Mat4;
+
+use crate::{animation::remap_material_tracks, Model};
+
+impl Model {
+    /// Combine `models` into a single model, keeping the root nodes of each model as roots.
+    /// Mesh, material and texture indices are remapped and textures with identical content are shared
+    pub fn merge(models: &[Model]) -> Model {
+        let mut merged = Model {
+            root_node_indices: vec![],
+            nodes: vec![],
+            bounds_min: [0.0; 3],
+            bounds_max: [0.0; 3],
+            meshes: vec![],
+            materials: vec![],
+            textures: vec![],
+            property_tracks: vec![],
+        };
+
+        for model in models {
+            let node_offset = merged.nodes.len() as u32;
+            let mesh_offset = merged.meshes.len() as u32;
+            let material_offset = merged.materials.len() as u32;
+
+            let texture_remap: Vec<u32> = model
+                .textures
+                .iter()
+                .map(|texture| {
+                    let existing = merged
+                        .textures
+                        .iter()
+                        .position(|merged_texture| merged_texture.has_same_content(texture));
+                    existing.unwrap_or_else(|| {
+                        merged.textures.push(texture.clone());
+                        merged.textures.len() - 1
+                    }) as u32
+                })
+                .collect();
+
+            merged.root_node_indices.extend(
+                model
+                    .root_node_indices
+                    .iter()
+                    .map(|node_idx| node_idx + node_offset),
+            );
+            merged.nodes.extend(model.nodes.iter().map(|node| {
+                let mut node = node.clone();
+                node.mesh_idx = node.mesh_idx.map(|mesh_idx| mesh_idx + mesh_offset);
+                for child_idx in &mut node.child_node_indices {
+                    *child_idx += node_offset;
+                }
+                node
+            }));
+            merged.meshes.extend(model.meshes.iter().map(|mesh| {
+                let mut mesh = mesh.clone();
+                for material_idx in &mut mesh.material_indices {
+                    *material_idx += material_offset;
+                }
+                mesh
+            }));
+            merged
+                .materials
+                .extend(model.materials.iter().map(|material| {
+                    let mut material = material.clone();
+                    for slot in material.textures_mut() {
+                        *slot = slot.map(|texture_idx| texture_remap[texture_idx as usize]);
+                    }
+                    material
+                }));
+
+            let mut property_tracks = model.property_tracks.clone();
+            remap_material_tracks(&mut property_tracks, |material_idx| {
+                Some(material_idx + material_offset)
+            });
+            merged.property_tracks.extend(property_tracks);
+        }
+
+        // Sort keys embed model material indices
+        for mesh in &mut merged.meshes {
+            mesh.compute_sort_keys(&merged.materials);
+        }
+        (merged.bounds_min, merged.bounds_max) = merged.compute_world_bounds(Mat4::IDENTITY);
+        merged.update_texture_usages();
+
+        merged
+    }
+    /// Standalone model of the node at `node_idx` and its descendants, holding only the meshes, materials and textures they reference.
+    /// The node becomes the single root and keeps its local transform
+    pub fn extract_subtree(&self, node_idx: u32) -> Model {
+        let mut node_remap = vec![None; self.nodes.len()];
+        let mut node_indices = vec![];
+        let mut stack = vec![node_idx];
+        while let Some(node_idx) = stack.pop() {
+            node_remap[node_idx as usize] = Some(node_indices.len() as u32);
+            node_indices.push(node_idx);
+            stack.extend(
+                self.nodes[node_idx as usize]
+                    .child_node_indices
+                    .iter()
+                    .rev(),
+            );
+        }
+
+        let mut mesh_remap = vec![None; self.meshes.len()];
+        let mut mesh_indices = vec![];
+        for node_idx in &node_indices {
+            if let Some(mesh_idx) = self.nodes[*node_idx as usize].mesh_idx {
+                compact_index(&mut mesh_remap, &mut mesh_indices, mesh_idx);
+            }
+        }
+
+        let mut material_remap = vec![None; self.materials.len()];
+        let mut material_indices = vec![];
+        for mesh_idx in &mesh_indices {
+            for material_idx in &self.meshes[*mesh_idx as usize].material_indices {
+                compact_index(&mut material_remap, &mut material_indices, *material_idx);
+            }
+        }
+
+        let mut texture_remap = vec![None; self.textures.len()];
+        let mut texture_indices = vec![];
+        for material_idx in &material_indices {
+            for texture_idx in self.materials[*material_idx as usize]
+                .textures()
+                .into_iter()
+                .flatten()
+            {
+                compact_index(&mut texture_remap, &mut texture_indices, texture_idx);
+            }
+        }
+
+        let nodes = node_indices
+            .iter()
+            .map(|node_idx| {
+                let mut node = self.nodes[*node_idx as usize].clone();
+                node.mesh_idx = node
+                    .mesh_idx
+                    .and_then(|mesh_idx| mesh_remap[mesh_idx as usize]);
+                for child_idx in &mut node.child_node_indices {
+                    *child_idx = node_remap[*child_idx as usize].unwrap();
+                }
+                node
+            })
+            .collect();
+        let materials: Vec<_> = material_indices
+            .iter()
+            .map(|material_idx| {
+                let mut material = self.materials[*material_idx as usize].clone();
+                for slot in material.textures_mut() {
+                    *slot = slot.and_then(|texture_idx| texture_remap[texture_idx as usize]);
+                }
+                material
+            })
+            .collect();
+        let meshes: Vec<_> = mesh_indices
+            .iter()
+            .map(|mesh_idx| {
+                let mut mesh = self.meshes[*mesh_idx as usize].clone();
+                for material_idx in &mut mesh.material_indices {
+                    *material_idx = material_remap[*material_idx as usize].unwrap();
+                }
+                mesh.compute_sort_keys(&materials);
+                mesh
+            })
+            .collect();
+        let mut property_tracks = self.property_tracks.clone();
+        remap_material_tracks(&mut property_tracks, |material_idx| {
+            material_remap.get(material_idx as usize).copied().flatten()
+        });
+        let mut model = Model {
+            root_node_indices: vec![0],
+            nodes,
+            bounds_min: [0.0; 3],
+            bounds_max: [0.0; 3],
+            meshes,
+            materials,
+            textures: texture_indices
+                .iter()
+                .map(|texture_idx| self.textures[*texture_idx as usize].clone())
+                .collect(),
+            property_tracks,
+        };
+        (model.bounds_min, model.bounds_max) = model.compute_world_bounds(Mat4::IDENTITY);
+        model.update_texture_usages();
+
+        model
+    }
+}
+
+/// Assign `idx` the next compacted index if it doesn't have one yet
+fn compact_index(remap: &mut [Option<u32>], indices: &mut Vec<u32>, idx: u32) {
+    if remap[idx as usize].is_none() {
+        remap[idx as usize] = Some(indices.len() as u32);
+        indices.push(idx);
+    }
+}
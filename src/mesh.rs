@@ -1,10 +1,17 @@
-use std::hash::{DefaultHasher, Hash, Hasher};
+use std::{
+    borrow::Cow,
+    hash::{DefaultHasher, Hash, Hasher},
+};
 
 use bytemuck::{Pod, Zeroable};
 use glam::{Vec2, Vec3, Vec3Swizzles, Vec4, Vec4Swizzles};
+use half::f16;
 use speedy::{Readable, Writable};
 
-use crate::packing::PackedNormalizedXyz10;
+use crate::{
+    bvh::Bvh, material::Material, meshlet::Meshlets, packing::PackedNormalizedXyz10,
+    quantize::QuantizedVertices, shadow_proxy::ShadowProxy,
+};
 
 #[derive(Debug, Pod, Clone, Copy, Zeroable, Readable, Writable)]
 #[repr(C)]
@@ -16,17 +23,237 @@ pub struct PackedVertex {
     pub tangent_handiness: f32,
 }
 
+/// Vertex 8 bytes smaller than [`PackedVertex`] with half precision tex coords, see [`Mesh::half_tex_coord_vertices`]
+#[derive(Debug, Pod, Clone, Copy, Zeroable)]
+#[repr(C)]
+pub struct HalfTexCoordVertex {
+    pub position: [f32; 3],
+    pub normal: PackedNormalizedXyz10,
+    pub tex_coord: [f16; 2],
+    /// Tangent with the handiness stored as sign bit, see [`PackedNormalizedXyz10::sign`]
+    pub tangent: PackedNormalizedXyz10,
+}
+
+/// Full precision vertex, only stored when parsing losslessly
+#[derive(Debug, Pod, Clone, Copy, Zeroable, Readable, Writable)]
+#[repr(C)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub tex_coord: [f32; 2],
+    pub tangent: [f32; 4],
+}
+
+/// Order in which draws of a submesh should be sorted relative to the camera
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Readable, Writable)]
+pub enum DepthSortHint {
+    FrontToBack,
+    AlphaTested,
+    BackToFront,
+}
+
+/// Precomputed draw sorting key of a single submesh, a submesh being all triangles sharing a material
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Readable, Writable)]
+pub struct SortKey {
+    pub depth_sort_hint: DepthSortHint,
+    /// See [`Material::pipeline_flags`]
+    pub pipeline_flags: u32,
+    pub material_idx: u32,
+}
+
+impl SortKey {
+    pub fn new(material: &Material, material_idx: u32) -> Self {
+        let depth_sort_hint = if material.is_opaque {
+            DepthSortHint::FrontToBack
+        } else if material.is_blended() {
+            DepthSortHint::BackToFront
+        } else {
+            DepthSortHint::AlphaTested
+        };
+
+        Self {
+            depth_sort_hint,
+            pipeline_flags: material.pipeline_flags(),
+            material_idx,
+        }
+    }
+
+    /// Combine into a single radix sortable key, ordered by depth sort hint, pipeline and material
+    pub fn key(&self) -> u64 {
+        ((self.depth_sort_hint as u64) << 62)
+            | (((self.pipeline_flags as u64) & 0x3fffffff) << 32)
+            | self.material_idx as u64
+    }
+}
+
+/// Triangle list indices, stored as 16 bit when all vertices of the mesh are addressable with them
+#[derive(Debug, Clone, PartialEq, Eq, Readable, Writable)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Default for Indices {
+    fn default() -> Self {
+        Self::U16(Vec::new())
+    }
+}
+
+impl Indices {
+    /// Pick the smallest index type able to address `num_vertices` vertices
+    pub fn new(indices: Vec<u32>, num_vertices: usize) -> Self {
+        if num_vertices <= u16::MAX as usize {
+            Self::U16(indices.into_iter().map(|index| index as u16).collect())
+        } else {
+            Self::U32(indices)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::U16(indices) => indices.len(),
+            Self::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, idx: usize) -> u32 {
+        match self {
+            Self::U16(indices) => indices[idx] as u32,
+            Self::U32(indices) => indices[idx],
+        }
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = u32> + '_ {
+        (0..self.len()).map(|idx| self.get(idx))
+    }
+
+    pub fn triangle(&self, triangle_idx: usize) -> [u32; 3] {
+        [0, 1, 2].map(|corner| self.get(triangle_idx * 3 + corner))
+    }
+
+    pub fn triangles(&self) -> impl ExactSizeIterator<Item = [u32; 3]> + '_ {
+        (0..self.len() / 3).map(|triangle_idx| self.triangle(triangle_idx))
+    }
+
+    /// Indices widened to u32, only allocating for 16 bit indices
+    pub fn to_u32(&self) -> Cow<'_, [u32]> {
+        match self {
+            Self::U16(indices) => Cow::Owned(indices.iter().map(|index| *index as u32).collect()),
+            Self::U32(indices) => Cow::Borrowed(indices),
+        }
+    }
+
+    /// Size of a single index in bytes
+    pub fn index_size(&self) -> usize {
+        match self {
+            Self::U16(_) => size_of::<u16>(),
+            Self::U32(_) => size_of::<u32>(),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::U16(indices) => bytemuck::cast_slice(indices),
+            Self::U32(indices) => bytemuck::cast_slice(indices),
+        }
+    }
+
+    #[cfg(feature = "wgpu")]
+    pub fn wgpu_format(&self) -> wgpu::IndexFormat {
+        match self {
+            Self::U16(_) => wgpu::IndexFormat::Uint16,
+            Self::U32(_) => wgpu::IndexFormat::Uint32,
+        }
+    }
+}
+
+/// Contiguous range of [`Mesh::indices`] drawn with a single material, for multi draw rendering
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Readable, Writable)]
+pub struct Submesh {
+    pub index_offset: u32,
+    pub index_count: u32,
+    /// Index into [`Mesh::material_indices`]
+    pub material: u32,
+}
+
+/// Maximum angular error in radians introduced by packing normals and tangents into `PackedNormalizedXyz10`, and the optional position quantization error
+#[derive(Debug, Clone, Copy, Default, PartialEq, Readable, Writable)]
+pub struct QuantizationError {
+    pub max_normal_error: f32,
+    pub max_tangent_error: f32,
+    /// Maximum distance in mesh units introduced by [`Mesh::quantize_positions`]
+    pub max_position_error: f32,
+}
+
+/// Vertices whose tangent frame was modified by `repair_tangents`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TangentRepairReport {
+    /// Zero length or non finite tangents, replaced by a tangent perpendicular to the normal
+    pub degenerate: Vec<u32>,
+    /// Tangents not orthogonal to the normal or not unit length, re-orthogonalized
+    pub non_orthogonal: Vec<u32>,
+    /// Handedness disagreeing with the uv winding of the adjacent triangles, flipped
+    pub flipped_handedness: Vec<u32>,
+}
+
+impl TangentRepairReport {
+    pub fn is_empty(&self) -> bool {
+        self.degenerate.is_empty()
+            && self.non_orthogonal.is_empty()
+            && self.flipped_handedness.is_empty()
+    }
+
+    /// Number of distinct vertices affected by any repair
+    pub fn num_affected_vertices(&self) -> usize {
+        let mut vertices: Vec<u32> = self
+            .degenerate
+            .iter()
+            .chain(&self.non_orthogonal)
+            .chain(&self.flipped_handedness)
+            .copied()
+            .collect();
+        vertices.sort_unstable();
+        vertices.dedup();
+        vertices.len()
+    }
+}
+
 #[derive(Debug, Clone, Readable, Writable)]
 pub struct Mesh {
     pub name: String,
     pub packed_vertices: Vec<PackedVertex>,
+    /// Full precision copy of `packed_vertices`, empty unless parsed losslessly
+    pub unpacked_vertices: Vec<Vertex>,
+    /// Second uv set (TEXCOORD_1) per vertex, empty when the source mesh has none
+    pub secondary_tex_coords: Vec<[f32; 2]>,
     pub triangle_material_indices: Vec<u32>,
     pub material_indices: Vec<u32>,
-    pub indices: Vec<u32>,
+    pub sort_keys: Vec<SortKey>,
+    pub indices: Indices,
+    /// Source primitives as index ranges, together covering all of `indices`
+    pub submeshes: Vec<Submesh>,
     pub opaque: bool,
     pub is_emissive: bool,
     pub bounds_min: [f32; 3],
     pub bounds_max: [f32; 3],
+    pub quantization_error: QuantizationError,
+    /// Simplified geometry for shadow map rendering, see [`Model::generate_shadow_proxies`](crate::Model::generate_shadow_proxies)
+    pub shadow_proxy: Option<ShadowProxy>,
+    /// Clusters for mesh shader pipelines, see [`Model::build_meshlets`](crate::Model::build_meshlets)
+    pub meshlets: Option<Meshlets>,
+    /// Triangle bvh for cpu ray tracing and physics queries, see [`Model::build_bvhs`](crate::Model::build_bvhs)
+    pub bvh: Option<Bvh>,
+    /// Compact replacement of `packed_vertices`, which are empty when this is set, see [`Mesh::quantize_positions`]
+    pub quantized_vertices: Option<QuantizedVertices>,
+    /// Mesh space position per vertex for position only passes, see [`Mesh::build_position_stream`]
+    pub position_stream: Option<Vec<[f32; 3]>>,
+    /// Tangent repairs applied while parsing, not serialized
+    #[speedy(skip)]
+    pub tangent_repair: TangentRepairReport,
     id: u64,
 }
 
@@ -48,6 +275,9 @@ impl Mesh {
             bounds_min = bounds_min.min(Vec3::from_array(vertex.position));
             bounds_max = bounds_max.max(Vec3::from_array(vertex.position));
         }
+        if packed_vertices.is_empty() {
+            (bounds_min, bounds_max) = (Vec3::ZERO, Vec3::ZERO);
+        }
 
         let mut hasher = DefaultHasher::new();
         for vert in &packed_vertices {
@@ -57,17 +287,30 @@ impl Mesh {
             }
         }
         let id = hasher.finish();
+        let indices = Indices::new(indices, packed_vertices.len());
+        let submeshes = material_runs(&triangle_material_indices);
 
         Mesh {
             name: name.to_owned(),
             packed_vertices,
+            unpacked_vertices: Vec::new(),
+            secondary_tex_coords: Vec::new(),
             triangle_material_indices,
             material_indices,
+            sort_keys: Vec::new(),
             indices,
+            submeshes,
             opaque,
             is_emissive,
             bounds_min: bounds_min.to_array(),
             bounds_max: bounds_max.to_array(),
+            quantization_error: QuantizationError::default(),
+            shadow_proxy: None,
+            meshlets: None,
+            bvh: None,
+            quantized_vertices: None,
+            position_stream: None,
+            tangent_repair: TangentRepairReport::default(),
             id,
         }
     }
@@ -76,25 +319,98 @@ impl Mesh {
         Mesh {
             name: "Empty".to_owned(),
             packed_vertices: Vec::new(),
+            unpacked_vertices: Vec::new(),
+            secondary_tex_coords: Vec::new(),
             triangle_material_indices: Vec::new(),
             material_indices: Vec::new(),
-            indices: Vec::new(),
+            sort_keys: Vec::new(),
+            indices: Indices::default(),
+            submeshes: Vec::new(),
             opaque: true,
             is_emissive: false,
             bounds_min: [0.0; 3],
             bounds_max: [0.0; 3],
+            quantization_error: QuantizationError::default(),
+            shadow_proxy: None,
+            meshlets: None,
+            bvh: None,
+            quantized_vertices: None,
+            position_stream: None,
+            tangent_repair: TangentRepairReport::default(),
             id: 0,
         }
     }
 
     pub fn is_empty(&self) -> bool {
-        self.packed_vertices.is_empty()
+        self.num_vertices() == 0
+    }
+
+    /// Number of vertices, either packed or quantized
+    pub fn num_vertices(&self) -> usize {
+        match &self.quantized_vertices {
+            Some(quantized) => quantized.vertices.len(),
+            None => self.packed_vertices.len(),
+        }
+    }
+
+    /// Position of a packed or quantized vertex
+    pub fn vertex_position(&self, vertex_idx: u32) -> Vec3 {
+        match &self.quantized_vertices {
+            Some(quantized) => quantized.position(&quantized.vertices[vertex_idx as usize]),
+            None => Vec3::from_array(self.packed_vertices[vertex_idx as usize].position),
+        }
     }
 
     pub fn id(&self) -> u64 {
         self.id
     }
 
+    /// Vertices with tex coords stored as `f16`, `None` if any tex coord would move by more than `max_tex_coord_error`.
+    /// Half floats have 11 bits of precision, so tex coords beyond a few repeats of the texture usually don't fit
+    pub fn half_tex_coord_vertices(
+        &self,
+        max_tex_coord_error: f32,
+    ) -> Option<Vec<HalfTexCoordVertex>> {
+        (0..self.num_vertices())
+            .map(|vertex_idx| {
+                let vertex = match &self.quantized_vertices {
+                    Some(quantized) => quantized.unpack(&quantized.vertices[vertex_idx]),
+                    None => self.packed_vertices[vertex_idx],
+                };
+                let tex_coord = vertex.tex_coord.map(f16::from_f32);
+                let error = (Vec2::from_array(tex_coord.map(f16::to_f32))
+                    - Vec2::from_array(vertex.tex_coord))
+                .abs()
+                .max_element();
+                // Also rejects non finite tex coords
+                if error.is_nan() || error > max_tex_coord_error {
+                    return None;
+                }
+
+                Some(HalfTexCoordVertex {
+                    position: self.vertex_position(vertex_idx as u32).to_array(),
+                    normal: vertex.normal,
+                    tex_coord,
+                    tangent: vertex.tangent.with_sign(vertex.tangent_handiness),
+                })
+            })
+            .collect()
+    }
+
+    /// Replace `submeshes` by one submesh per run of triangles sharing a material, merging adjacent primitives
+    pub fn merge_submeshes(&mut self) {
+        self.submeshes = material_runs(&self.triangle_material_indices);
+    }
+
+    /// Compute a sort key for each entry in `material_indices`, must be called once all materials are final
+    pub fn compute_sort_keys(&mut self, materials: &[Material]) {
+        self.sort_keys = self
+            .material_indices
+            .iter()
+            .map(|material_idx| SortKey::new(&materials[*material_idx as usize], *material_idx))
+            .collect();
+    }
+
     #[cfg(feature = "rapier3d")]
     pub fn build_rapier3d_trimesh(&self, scale: Vec3) -> rapier3d::prelude::SharedShape {
         use rapier3d::prelude::*;
@@ -111,34 +427,95 @@ impl Mesh {
             })
             .collect();
 
-        let indices: Vec<[u32; 3]> = self
-            .indices
-            .chunks(3)
-            .map(|chunk| [chunk[0], chunk[1], chunk[2]])
-            .collect();
+        let indices: Vec<[u32; 3]> = self.indices.triangles().collect();
 
         SharedShape::trimesh(vertices, indices).expect("Failed to build rapier3d trimesh.")
     }
+
+    /// Index buffer in the format of [`Mesh::indices`], use [`Indices::wgpu_format`] when binding it
+    #[cfg(feature = "wgpu")]
+    pub fn create_wgpu_index_buffer(&self, device: &wgpu::Device) -> wgpu::Buffer {
+        use wgpu::util::DeviceExt;
+
+        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&self.name),
+            contents: self.indices.as_bytes(),
+            usage: wgpu::BufferUsages::INDEX,
+        })
+    }
+}
+
+/// Submeshes covering each run of triangles with the same material
+fn material_runs(triangle_material_indices: &[u32]) -> Vec<Submesh> {
+    let mut submeshes: Vec<Submesh> = Vec::new();
+    for (triangle_idx, material) in triangle_material_indices.iter().enumerate() {
+        match submeshes.last_mut() {
+            Some(submesh) if submesh.material == *material => submesh.index_count += 3,
+            _ => submeshes.push(Submesh {
+                index_offset: triangle_idx as u32 * 3,
+                index_count: 3,
+                material: *material,
+            }),
+        }
+    }
+
+    submeshes
 }
 
+/// Pack vertices, also returning the angular error introduced by quantizing normals and tangents
 pub fn pack_vertices(
     vertex_positions: Vec<Vec3>,
     vertex_normals: Vec<Vec3>,
     vertex_tangents: Vec<Vec4>,
     vertex_tex_coords: Vec<Vec2>,
-) -> Vec<PackedVertex> {
+) -> (Vec<PackedVertex>, QuantizationError) {
+    let angular_error = |dir: Vec3, packed: &PackedNormalizedXyz10| {
+        let dir = dir.normalize_or_zero();
+        if dir == Vec3::ZERO {
+            0.0
+        } else {
+            dir.dot(packed.unpack()).clamp(-1.0, 1.0).acos()
+        }
+    };
+
+    let mut quantization_error = QuantizationError::default();
     let mut packed_vertices = Vec::with_capacity(vertex_positions.len());
     for i in 0..vertex_positions.len() {
-        packed_vertices.push(PackedVertex {
+        let packed_vertex = PackedVertex {
             position: vertex_positions[i].to_array(),
             normal: PackedNormalizedXyz10::new(vertex_normals[i]),
             tex_coord: vertex_tex_coords[i].to_array(),
             tangent: PackedNormalizedXyz10::new(vertex_tangents[i].xyz()),
             tangent_handiness: vertex_tangents[i].w,
-        });
+        };
+
+        quantization_error.max_normal_error = quantization_error
+            .max_normal_error
+            .max(angular_error(vertex_normals[i], &packed_vertex.normal));
+        quantization_error.max_tangent_error = quantization_error.max_tangent_error.max(
+            angular_error(vertex_tangents[i].xyz(), &packed_vertex.tangent),
+        );
+
+        packed_vertices.push(packed_vertex);
     }
 
-    packed_vertices
+    (packed_vertices, quantization_error)
+}
+
+pub fn unpack_vertices(
+    vertex_positions: &[Vec3],
+    vertex_normals: &[Vec3],
+    vertex_tangents: &[Vec4],
+    vertex_tex_coords: &[Vec2],
+) -> Vec<Vertex> {
+    (0..vertex_positions.len())
+        .map(|i| Vertex {
+            position: vertex_positions[i].to_array(),
+            normal: vertex_normals[i].to_array(),
+            tex_coord: vertex_tex_coords[i].to_array(),
+            tangent: vertex_tangents[i].to_array(),
+        })
+        .collect()
 }
 
 pub fn generate_normals(positions: &[Vec3], indices: &[u32]) -> Vec<Vec3> {
@@ -162,6 +539,8 @@ pub fn generate_normals(positions: &[Vec3], indices: &[u32]) -> Vec<Vec3> {
     vertex_normals
 }
 
+/// Tangents with the handiness in w, matching MikkTSpace when the `mikktspace` feature is enabled
+/// so normal maps baked by external tools don't show seams
 pub fn generate_tangents(
     positions: &[Vec3],
     normals: &[Vec3],
@@ -172,6 +551,11 @@ pub fn generate_tangents(
         return vec![Vec4::ZERO; positions.len()];
     }
 
+    #[cfg(feature = "mikktspace")]
+    if let Some(tangents) = generate_mikktspace_tangents(positions, normals, tex_coords, indices) {
+        return tangents;
+    }
+
     // Source: 2001. http://www.terathon.com/code/tangent.html
     let mut tan1 = vec![Vec3::default(); positions.len()];
     let mut tan2 = vec![Vec3::default(); positions.len()];
@@ -244,3 +628,150 @@ pub fn generate_tangents(
 
     vertex_tangents
 }
+
+#[cfg(feature = "mikktspace")]
+struct MikktspaceGeometry<'a> {
+    positions: &'a [Vec3],
+    normals: &'a [Vec3],
+    tex_coords: &'a [Vec2],
+    indices: &'a [u32],
+    tangents: Vec<Vec4>,
+}
+
+#[cfg(feature = "mikktspace")]
+impl MikktspaceGeometry<'_> {
+    fn vertex(&self, face: usize, vert: usize) -> usize {
+        self.indices[face * 3 + vert] as usize
+    }
+}
+
+#[cfg(feature = "mikktspace")]
+impl mikktspace::Geometry for MikktspaceGeometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.positions[self.vertex(face, vert)].to_array()
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.normals[self.vertex(face, vert)].to_array()
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.tex_coords[self.vertex(face, vert)].to_array()
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        // Corners sharing a vertex share position, normal and uv, so they get the same tangent
+        let vertex = self.vertex(face, vert);
+        self.tangents[vertex] = Vec4::from_array(tangent);
+    }
+}
+
+/// Reference MikkTSpace tangents, `None` if the algorithm fails
+#[cfg(feature = "mikktspace")]
+fn generate_mikktspace_tangents(
+    positions: &[Vec3],
+    normals: &[Vec3],
+    tex_coords: &[Vec2],
+    indices: &[u32],
+) -> Option<Vec<Vec4>> {
+    let mut geometry = MikktspaceGeometry {
+        positions,
+        normals,
+        tex_coords,
+        indices,
+        tangents: vec![Vec4::ZERO; positions.len()],
+    };
+
+    mikktspace::generate_tangents(&mut geometry).then_some(geometry.tangents)
+}
+
+/// Validate tangents against their normals and the uv winding of each triangle,
+/// re-orthogonalizing, replacing degenerate tangents and fixing inconsistent handedness in place
+pub fn repair_tangents(
+    normals: &[Vec3],
+    tangents: &mut [Vec4],
+    tex_coords: &[Vec2],
+    positions: &[Vec3],
+    indices: &[u32],
+) -> TangentRepairReport {
+    const TOLERANCE: f32 = 1e-3;
+
+    let mut report = TangentRepairReport::default();
+
+    for (i, (tangent, n)) in tangents.iter_mut().zip(normals).enumerate() {
+        let n = n.normalize_or_zero();
+        if n == Vec3::ZERO {
+            continue;
+        }
+
+        let t = tangent.xyz();
+        let w = if tangent.w < 0.0 { -1.0 } else { 1.0 };
+
+        let orthogonal_t = t - n * n.dot(t);
+        let xyz = if !t.is_finite() || orthogonal_t.length_squared() < TOLERANCE * TOLERANCE {
+            report.degenerate.push(i as u32);
+            n.any_orthonormal_vector()
+        } else if n.dot(t).abs() > TOLERANCE || (t.length() - 1.0).abs() > TOLERANCE {
+            report.non_orthogonal.push(i as u32);
+            orthogonal_t.normalize()
+        } else {
+            t
+        };
+
+        *tangent = Vec4::new(xyz.x, xyz.y, xyz.z, w);
+    }
+
+    if tex_coords.len() != positions.len() {
+        return report;
+    }
+
+    // Each triangle votes for the handedness of its vertices based on its uv winding
+    let mut handedness_votes = vec![0i32; tangents.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [i0, i1, i2] = [
+            triangle[0] as usize,
+            triangle[1] as usize,
+            triangle[2] as usize,
+        ];
+
+        let e1 = positions[i1] - positions[i0];
+        let e2 = positions[i2] - positions[i0];
+        let uv1 = tex_coords[i1] - tex_coords[i0];
+        let uv2 = tex_coords[i2] - tex_coords[i0];
+
+        let uv_area = uv1.x * uv2.y - uv2.x * uv1.y;
+        if uv_area.abs() < f32::EPSILON {
+            continue;
+        }
+
+        let sdir = (e1 * uv2.y - e2 * uv1.y) / uv_area;
+        let tdir = (e2 * uv1.x - e1 * uv2.x) / uv_area;
+        let face_normal = e1.cross(e2);
+
+        let vote = if face_normal.cross(sdir).dot(tdir) < 0.0 {
+            -1
+        } else {
+            1
+        };
+        for i in [i0, i1, i2] {
+            handedness_votes[i] += vote;
+        }
+    }
+
+    for (i, (tangent, votes)) in tangents.iter_mut().zip(handedness_votes).enumerate() {
+        if votes != 0 && (votes < 0) != (tangent.w < 0.0) {
+            tangent.w = -tangent.w;
+            report.flipped_handedness.push(i as u32);
+        }
+    }
+
+    report
+}
@@ -0,0 +1,233 @@
+//! Small clusters of triangles for gpu driven mesh shader pipelines
+
+use glam::Vec3;
+use speedy::{Readable, Writable};
+
+use crate::{mesh::Mesh, Model};
+
+/// Dot product between the cone axis and triangle normals below which a meshlet is never backface culled
+const MIN_CONE_DOT: f32 = 0.1;
+
+/// Cluster of triangles sharing a single material
+#[derive(Debug, Clone, Copy, Default, PartialEq, Readable, Writable)]
+pub struct Meshlet {
+    /// Offset into [`Meshlets::vertices`]
+    pub vertex_offset: u32,
+    pub vertex_count: u32,
+    /// Offset in triangles into [`Meshlets::triangles`]
+    pub triangle_offset: u32,
+    pub triangle_count: u32,
+    /// Index into the material indices of the mesh
+    pub material_idx: u32,
+    /// Bounding sphere
+    pub center: [f32; 3],
+    pub radius: f32,
+    /// The meshlet is backfacing for a camera at `position` if `dot(normalize(cone_apex - position), cone_axis) >= cone_cutoff`
+    pub cone_apex: [f32; 3],
+    pub cone_axis: [f32; 3],
+    /// 1.0 if the meshlet can't be backface culled
+    pub cone_cutoff: f32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Readable, Writable)]
+pub struct Meshlets {
+    pub meshlets: Vec<Meshlet>,
+    /// Mesh vertex indices referenced by the meshlets
+    pub vertices: Vec<u32>,
+    /// Three indices into the meshlet's vertices per triangle
+    pub triangles: Vec<u8>,
+    pub max_vertices: u32,
+    pub max_triangles: u32,
+}
+
+/// Meshlet under construction
+struct MeshletBuilder {
+    vertices: Vec<u32>,
+    triangles: Vec<[u8; 3]>,
+}
+
+impl MeshletBuilder {
+    fn new() -> Self {
+        Self {
+            vertices: vec![],
+            triangles: vec![],
+        }
+    }
+
+    fn num_new_vertices(&self, triangle: &[u32]) -> usize {
+        triangle
+            .iter()
+            .filter(|vertex| !self.vertices.contains(vertex))
+            .count()
+    }
+
+    fn fits(&self, triangle: &[u32], max_vertices: usize, max_triangles: usize) -> bool {
+        self.triangles.len() < max_triangles
+            && self.vertices.len() + self.num_new_vertices(triangle) <= max_vertices
+    }
+
+    fn push(&mut self, triangle: &[u32]) {
+        let mut local = [0; 3];
+        for (local, vertex) in local.iter_mut().zip(triangle) {
+            *local = match self.vertices.iter().position(|other| other == vertex) {
+                Some(local) => local as u8,
+                None => {
+                    self.vertices.push(*vertex);
+                    (self.vertices.len() - 1) as u8
+                }
+            };
+        }
+        self.triangles.push(local);
+    }
+}
+
+impl Mesh {
+    /// Split the mesh into meshlets of at most `max_vertices` vertices and `max_triangles` triangles, grouping adjacent triangles of the same material
+    pub fn build_meshlets(&self, max_vertices: u32, max_triangles: u32) -> Meshlets {
+        assert!(
+            (3..=256).contains(&max_vertices) && max_triangles > 0,
+            "Meshlets need between 3 and 256 vertices and at least one triangle."
+        );
+        let (max_vertices, max_triangles) = (max_vertices as usize, max_triangles as usize);
+
+        let indices = self.indices.to_u32();
+        let num_triangles = indices.len() / 3;
+        let triangle = |triangle_idx: usize| &indices[triangle_idx * 3..triangle_idx * 3 + 3];
+
+        let mut vertex_triangles = vec![vec![]; self.packed_vertices.len()];
+        for triangle_idx in 0..num_triangles {
+            for vertex in triangle(triangle_idx) {
+                vertex_triangles[*vertex as usize].push(triangle_idx);
+            }
+        }
+
+        let mut meshlets = Meshlets {
+            max_vertices: max_vertices as u32,
+            max_triangles: max_triangles as u32,
+            ..Default::default()
+        };
+        let mut is_used = vec![false; num_triangles];
+        for material_idx in 0..self.material_indices.len() as u32 {
+            let in_material =
+                |triangle_idx: usize| self.triangle_material_indices[triangle_idx] == material_idx;
+
+            let mut builder = MeshletBuilder::new();
+            let mut next_seed = 0;
+            loop {
+                // Prefer unused triangles adjacent to the meshlet adding the fewest vertices
+                let adjacent = builder
+                    .vertices
+                    .iter()
+                    .flat_map(|vertex| &vertex_triangles[*vertex as usize])
+                    .filter(|triangle_idx| !is_used[**triangle_idx] && in_material(**triangle_idx))
+                    .min_by_key(|triangle_idx| builder.num_new_vertices(triangle(**triangle_idx)))
+                    .copied();
+                let next = adjacent.or_else(|| {
+                    while next_seed < num_triangles
+                        && (is_used[next_seed] || !in_material(next_seed))
+                    {
+                        next_seed += 1;
+                    }
+                    (next_seed < num_triangles).then_some(next_seed)
+                });
+                let Some(next) = next else {
+                    break;
+                };
+
+                if !builder.fits(triangle(next), max_vertices, max_triangles) {
+                    self.push_meshlet(&mut meshlets, builder, material_idx);
+                    builder = MeshletBuilder::new();
+                }
+                builder.push(triangle(next));
+                is_used[next] = true;
+            }
+
+            if !builder.triangles.is_empty() {
+                self.push_meshlet(&mut meshlets, builder, material_idx);
+            }
+        }
+
+        meshlets
+    }
+
+    fn push_meshlet(&self, meshlets: &mut Meshlets, builder: MeshletBuilder, material_idx: u32) {
+        let position = |local: u8| {
+            Vec3::from_array(
+                self.packed_vertices[builder.vertices[local as usize] as usize].position,
+            )
+        };
+
+        let mut bounds_min = Vec3::INFINITY;
+        let mut bounds_max = Vec3::NEG_INFINITY;
+        for local in 0..builder.vertices.len() {
+            bounds_min = bounds_min.min(position(local as u8));
+            bounds_max = bounds_max.max(position(local as u8));
+        }
+        let center = (bounds_min + bounds_max) * 0.5;
+        let radius = (0..builder.vertices.len())
+            .map(|local| center.distance(position(local as u8)))
+            .fold(0.0, f32::max);
+
+        let normals: Vec<(Vec3, Vec3)> = builder
+            .triangles
+            .iter()
+            .filter_map(|[a, b, c]| {
+                let normal = (position(*b) - position(*a))
+                    .cross(position(*c) - position(*a))
+                    .try_normalize()?;
+                Some((position(*a), normal))
+            })
+            .collect();
+        let axis = normals
+            .iter()
+            .map(|(_, normal)| *normal)
+            .sum::<Vec3>()
+            .normalize_or_zero();
+        let min_dot = normals
+            .iter()
+            .map(|(_, normal)| normal.dot(axis))
+            .fold(1.0, f32::min);
+
+        let (cone_apex, cone_axis, cone_cutoff) = if normals.is_empty() || min_dot <= MIN_CONE_DOT {
+            (center, Vec3::ZERO, 1.0)
+        } else {
+            // Move the apex back along the axis until it's behind the planes of all triangles
+            let max_t = normals
+                .iter()
+                .map(|(point, normal)| (center - *point).dot(*normal) / axis.dot(*normal))
+                .fold(0.0, f32::max);
+            (
+                center - axis * max_t,
+                axis,
+                (1.0 - min_dot * min_dot).sqrt(),
+            )
+        };
+
+        meshlets.meshlets.push(Meshlet {
+            vertex_offset: meshlets.vertices.len() as u32,
+            vertex_count: builder.vertices.len() as u32,
+            triangle_offset: (meshlets.triangles.len() / 3) as u32,
+            triangle_count: builder.triangles.len() as u32,
+            material_idx,
+            center: center.to_array(),
+            radius,
+            cone_apex: cone_apex.to_array(),
+            cone_axis: cone_axis.to_array(),
+            cone_cutoff,
+        });
+        meshlets.vertices.extend_from_slice(&builder.vertices);
+        meshlets
+            .triangles
+            .extend(builder.triangles.iter().flatten());
+    }
+}
+
+impl Model {
+    /// Build meshlets for every non empty mesh, see [`Mesh::build_meshlets`]
+    pub fn build_meshlets(&mut self, max_vertices: u32, max_triangles: u32) {
+        for mesh in &mut self.meshes {
+            mesh.meshlets =
+                (!mesh.is_empty()).then(|| mesh.build_meshlets(max_vertices, max_triangles));
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! Triangle reordering for post transform vertex cache efficiency, following Tom Forsyth's linear speed vertex cache optimisation,
+//! and for reduced overdraw, following Sander et al. "Fast Triangle Reordering for Vertex Locality and Reduced Overdraw"
+
+use std::collections::VecDeque;
+
+use glam::Vec3;
+
+use crate::{
+    mesh::{Indices, Mesh},
+    Model,
+};
+
+/// Size of the fifo cache simulated to find cluster boundaries for overdraw optimization
+const OVERDRAW_CACHE_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshOptimizeOptions {
+    /// Reorder triangles for post transform vertex cache efficiency
+    pub vertex_cache: bool,
+    /// Reorder clusters of triangles front to back to reduce overdraw.
+    /// Clusters are split while their cache miss ratio stays below this factor times the miss ratio of the whole mesh, so higher values give smaller clusters with less vertex reuse
+    pub overdraw_threshold: Option<f32>,
+}
+
+impl Default for MeshOptimizeOptions {
+    fn default() -> Self {
+        Self {
+            vertex_cache: true,
+            overdraw_threshold: Some(1.05),
+        }
+    }
+}
+
+/// Size of the simulated cache used while optimizing
+const CACHE_SIZE: usize = 32;
+const CACHE_DECAY_POWER: f32 = 1.5;
+/// Score of the vertices of the last emitted triangle, slightly lower to avoid strips going back and forth
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+fn vertex_score(cache_position: Option<usize>, remaining_valence: usize) -> f32 {
+    if remaining_valence == 0 {
+        return -1.0;
+    }
+
+    let cache_score = match cache_position {
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            (1.0 - (position - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(CACHE_DECAY_POWER)
+        }
+        None => 0.0,
+    };
+
+    cache_score + VALENCE_BOOST_SCALE * (remaining_valence as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+/// Reorder `triangles`, indices of the triangles in `indices`, in place
+fn optimize_triangles(indices: &[u32], num_vertices: usize, triangles: &mut [usize]) {
+    let triangle = |triangle_idx: usize| &indices[triangle_idx * 3..triangle_idx * 3 + 3];
+
+    let mut vertex_triangles = vec![vec![]; num_vertices];
+    for (local, triangle_idx) in triangles.iter().enumerate() {
+        for vertex in triangle(*triangle_idx) {
+            vertex_triangles[*vertex as usize].push(local);
+        }
+    }
+
+    let mut scores: Vec<f32> = vertex_triangles
+        .iter()
+        .map(|adjacent| vertex_score(None, adjacent.len()))
+        .collect();
+    let triangle_score = |scores: &[f32], local: usize| -> f32 {
+        triangle(triangles[local])
+            .iter()
+            .map(|vertex| scores[*vertex as usize])
+            .sum()
+    };
+
+    let mut is_emitted = vec![false; triangles.len()];
+    let mut order = Vec::with_capacity(triangles.len());
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut next_unemitted = 0;
+    while order.len() < triangles.len() {
+        // Best triangle touching the cache, falling back to the next triangle in the original order
+        let best = cache
+            .iter()
+            .flat_map(|vertex| &vertex_triangles[*vertex as usize])
+            .filter(|local| !is_emitted[**local])
+            .map(|local| (*local, triangle_score(&scores, *local)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(local, _)| local);
+        let best = best.unwrap_or_else(|| {
+            while is_emitted[next_unemitted] {
+                next_unemitted += 1;
+            }
+            next_unemitted
+        });
+
+        is_emitted[best] = true;
+        order.push(triangles[best]);
+
+        let vertices = triangle(triangles[best]);
+        for vertex in vertices {
+            let adjacent = &mut vertex_triangles[*vertex as usize];
+            if let Some(position) = adjacent.iter().position(|local| *local == best) {
+                adjacent.swap_remove(position);
+            }
+        }
+
+        cache.retain(|vertex| !vertices.contains(vertex));
+        cache.splice(0..0, vertices.iter().copied());
+        for (position, vertex) in cache.iter().enumerate() {
+            let position = (position < CACHE_SIZE).then_some(position);
+            scores[*vertex as usize] =
+                vertex_score(position, vertex_triangles[*vertex as usize].len());
+        }
+        cache.truncate(CACHE_SIZE);
+    }
+
+    triangles.copy_from_slice(&order);
+}
+
+/// Start of each cluster in `triangles`, a cluster starts at each triangle missing all its vertices in the cache and whenever the cache miss ratio of the current cluster drops below `max_miss_ratio`
+fn cluster_starts(indices: &[u32], triangles: &[usize], max_miss_ratio: f32) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut cache = VecDeque::with_capacity(OVERDRAW_CACHE_SIZE + 1);
+    let mut cluster_misses = 0;
+    for (local, triangle_idx) in triangles.iter().enumerate() {
+        let mut misses = 0;
+        for vertex in &indices[triangle_idx * 3..triangle_idx * 3 + 3] {
+            if !cache.contains(vertex) {
+                misses += 1;
+                cache.push_back(*vertex);
+                if cache.len() > OVERDRAW_CACHE_SIZE {
+                    cache.pop_front();
+                }
+            }
+        }
+
+        let start = *starts.last().unwrap();
+        if misses == 3 && local > start {
+            starts.push(local);
+            cluster_misses = 0;
+        }
+        cluster_misses += misses;
+
+        let start = *starts.last().unwrap();
+        let cluster_len = local + 1 - start;
+        if local + 1 < triangles.len()
+            && cluster_misses as f32 / cluster_len as f32 <= max_miss_ratio
+        {
+            // Start the next cluster with an empty cache, as it may be drawn far away from this one
+            starts.push(local + 1);
+            cluster_misses = 0;
+            cache.clear();
+        }
+    }
+
+    starts
+}
+
+/// Reorder clusters of `triangles` so clusters facing away from the center of the mesh are drawn first, occluding the clusters behind them
+fn optimize_overdraw(mesh: &Mesh, indices: &[u32], triangles: &mut [usize], threshold: f32) {
+    let position = |vertex: u32| Vec3::from_array(mesh.packed_vertices[vertex as usize].position);
+    let triangle = |triangle_idx: usize| {
+        let triangle = &indices[triangle_idx * 3..triangle_idx * 3 + 3];
+        [
+            position(triangle[0]),
+            position(triangle[1]),
+            position(triangle[2]),
+        ]
+    };
+
+    let miss_ratio = |triangles: &[usize]| {
+        let mut cache = VecDeque::with_capacity(OVERDRAW_CACHE_SIZE + 1);
+        let mut misses = 0;
+        for triangle_idx in triangles {
+            for vertex in &indices[triangle_idx * 3..triangle_idx * 3 + 3] {
+                if !cache.contains(vertex) {
+                    misses += 1;
+                    cache.push_back(*vertex);
+                    if cache.len() > OVERDRAW_CACHE_SIZE {
+                        cache.pop_front();
+                    }
+                }
+            }
+        }
+        misses as f32 / triangles.len().max(1) as f32
+    };
+    let starts = cluster_starts(indices, triangles, miss_ratio(triangles) * threshold);
+
+    let mut mesh_center = Vec3::ZERO;
+    for triangle_idx in triangles.iter() {
+        let [a, b, c] = triangle(*triangle_idx);
+        mesh_center += (a + b + c) / 3.0;
+    }
+    mesh_center /= triangles.len().max(1) as f32;
+
+    let mut clusters: Vec<(f32, &[usize])> = starts
+        .iter()
+        .zip(starts.iter().skip(1).chain([&triangles.len()]))
+        .map(|(start, end)| {
+            let cluster = &triangles[*start..*end];
+
+            // Area weighted center and normal
+            let mut center = Vec3::ZERO;
+            let mut normal = Vec3::ZERO;
+            let mut area = 0.0;
+            for triangle_idx in cluster {
+                let [a, b, c] = triangle(*triangle_idx);
+                let scaled_normal = (b - a).cross(c - a);
+                let triangle_area = scaled_normal.length();
+                center += (a + b + c) / 3.0 * triangle_area;
+                normal += scaled_normal;
+                area += triangle_area;
+            }
+            if area > 0.0 {
+                center /= area;
+            }
+
+            let sort_key = (center - mesh_center).dot(normal.normalize_or_zero());
+            (sort_key, cluster)
+        })
+        .collect();
+    clusters.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let order: Vec<usize> = clusters
+        .iter()
+        .flat_map(|(_, cluster)| cluster.iter().copied())
+        .collect();
+    triangles.copy_from_slice(&order);
+}
+
+impl Mesh {
+    /// Reorder triangles to improve post transform vertex cache hit rates and reduce overdraw, triangles stay within their submesh
+    pub fn optimize(&mut self, options: &MeshOptimizeOptions) {
+        let indices = self.indices.to_u32().into_owned();
+        let num_triangles = indices.len() / 3;
+
+        let mut order: Vec<usize> = (0..num_triangles).collect();
+        for submesh in &self.submeshes {
+            let start = submesh.index_offset as usize / 3;
+            let end = start + submesh.index_count as usize / 3;
+
+            if options.vertex_cache {
+                optimize_triangles(&indices, self.packed_vertices.len(), &mut order[start..end]);
+            }
+            if let Some(threshold) = options.overdraw_threshold {
+                optimize_overdraw(self, &indices, &mut order[start..end], threshold);
+            }
+        }
+
+        let optimized = order
+            .iter()
+            .flat_map(|triangle_idx| &indices[triangle_idx * 3..triangle_idx * 3 + 3])
+            .copied()
+            .collect();
+        self.indices = Indices::new(optimized, self.packed_vertices.len());
+    }
+
+    /// Average number of vertex shader invocations per triangle with a fifo post transform cache of `cache_size` vertices, between 0.5 and 3.0
+    pub fn average_cache_miss_ratio(&self, cache_size: usize) -> f32 {
+        let num_triangles = self.indices.len() / 3;
+        if num_triangles == 0 {
+            return 0.0;
+        }
+
+        let mut cache = VecDeque::with_capacity(cache_size + 1);
+        let mut misses = 0;
+        for vertex in self.indices.iter() {
+            if !cache.contains(&vertex) {
+                misses += 1;
+                cache.push_back(vertex);
+                if cache.len() > cache_size {
+                    cache.pop_front();
+                }
+            }
+        }
+
+        misses as f32 / num_triangles as f32
+    }
+}
+
+impl Model {
+    /// Optimize the triangle order of all meshes, see [`Mesh::optimize`]
+    pub fn optimize_meshes(&mut self, options: &MeshOptimizeOptions) {
+        for mesh in &mut self.meshes {
+            mesh.optimize(options);
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use image::{DynamicImage, RgbaImage};
+
+use crate::{
+    resample,
+    texture::{ColorSpace, MipFilter, Texture, TextureCreateDesc},
+    Model,
+};
+
+/// Pack the red channel of `occlusion` and the green and blue channels of `metallic_roughness` into one rgba8 texture,
+/// returns `None` if the textures are mapped differently or can't be decoded
+fn pack_orm(occlusion: &Texture, metallic_roughness: &Texture) -> Option<Texture> {
+    if occlusion.uv_offset() != metallic_roughness.uv_offset()
+        || occlusion.uv_scale() != metallic_roughness.uv_scale()
+    {
+        return None;
+    }
+
+    let occlusion_image = occlusion.to_image(0)?;
+    let metallic_roughness_image = metallic_roughness.to_image(0)?;
+    let width = occlusion_image
+        .width()
+        .max(metallic_roughness_image.width());
+    let height = occlusion_image
+        .height()
+        .max(metallic_roughness_image.height());
+
+    let resize = |image: DynamicImage| {
+        if image.width() == width && image.height() == height {
+            image.to_rgba8()
+        } else {
+            resample::resize(
+                &image,
+                width,
+                height,
+                MipFilter::default(),
+                ColorSpace::Linear,
+            )
+            .to_rgba8()
+        }
+    };
+    let occlusion_image = resize(occlusion_image);
+    let metallic_roughness_image = resize(metallic_roughness_image);
+
+    let image = RgbaImage::from_fn(width, height, |x, y| {
+        let occlusion = occlusion_image.get_pixel(x, y);
+        let metallic_roughness = metallic_roughness_image.get_pixel(x, y);
+        image::Rgba([
+            occlusion[0],
+            metallic_roughness[1],
+            metallic_roughness[2],
+            u8::MAX,
+        ])
+    });
+
+    Some(Texture::new(TextureCreateDesc {
+        name: Some(&format!("{}_orm", metallic_roughness.name())),
+        image: DynamicImage::ImageRgba8(image),
+        mips: metallic_roughness.mip_count() > 1,
+        max_mip_levels: None,
+        min_mip_size: None,
+        precomputed_mips: vec![],
+        mip_filter: MipFilter::default(),
+        is_normal_map: false,
+        color_space: ColorSpace::Linear,
+        alpha_coverage_cutoff: None,
+        premultiply_alpha: false,
+        uv_offset: metallic_roughness.uv_offset(),
+        uv_scale: metallic_roughness.uv_scale(),
+        sampler: metallic_roughness.sampler(),
+        source_image: None,
+    }))
+}
+
+impl Model {
+    /// Pack occlusion, roughness and metallic of each material into the red, green and blue channels of a single texture,
+    /// referenced by both the occlusion and metallic roughness slots. Textures left unused are removed, returns the indices of the packed textures
+    pub fn pack_orm_textures(&mut self) -> Vec<u32> {
+        let mut packed: HashMap<(u32, u32), Option<u32>> = HashMap::new();
+
+        for material_idx in 0..self.materials.len() {
+            let material = &self.materials[material_idx];
+            let (Some(occlusion), Some(metallic_roughness)) = (
+                material.occlusion_texture,
+                material.metallic_roughness_texture,
+            ) else {
+                continue;
+            };
+            if occlusion == metallic_roughness
+                || material.occlusion_tex_coord != material.metallic_roughness_tex_coord
+            {
+                continue;
+            }
+
+            let packed_idx = *packed
+                .entry((occlusion, metallic_roughness))
+                .or_insert_with(|| {
+                    let texture = pack_orm(
+                        &self.textures[occlusion as usize],
+                        &self.textures[metallic_roughness as usize],
+                    )?;
+                    self.textures.push(texture);
+                    Some(self.textures.len() as u32 - 1)
+                });
+
+            if let Some(packed_idx) = packed_idx {
+                let material = &mut self.materials[material_idx];
+                material.occlusion_texture = Some(packed_idx);
+                material.metallic_roughness_texture = Some(packed_idx);
+            }
+        }
+
+        let remap = self.remove_unused_textures();
+        let mut packed_indices: Vec<u32> = packed
+            .into_values()
+            .flatten()
+            .filter_map(|texture_idx| remap[texture_idx as usize])
+            .collect();
+        packed_indices.sort_unstable();
+        packed_indices
+    }
+}
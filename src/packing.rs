@@ -42,6 +42,15 @@ impl PackedRgb9e5 {
             data: e | (rgb_as_u32.z << 18) | (rgb_as_u32.y << 9) | (rgb_as_u32.x & 0x1FFu32),
         }
     }
+
+    pub fn unpack(&self) -> Vec3 {
+        let scale = f32::from_bits(((self.data >> 27) + 103) << 23);
+        Vec3::new(
+            (self.data & 0x1ff) as f32,
+            ((self.data >> 9) & 0x1ff) as f32,
+            ((self.data >> 18) & 0x1ff) as f32,
+        ) * scale
+    }
 }
 
 impl Default for PackedNormalizedXyz10 {
@@ -58,6 +67,33 @@ impl PackedNormalizedXyz10 {
 
         Self { data }
     }
+
+    pub fn unpack(&self) -> Vec3 {
+        let oct_encoded_dir = Vec2::new(
+            (self.data & 0x7fff) as f32 / 0x7fff as f32,
+            ((self.data >> 15) & 0x7fff) as f32 / 0x7fff as f32,
+        );
+        dir_oct_quad_decode(oct_encoded_dir)
+    }
+
+    /// Store the sign of `sign` in the otherwise unused top bit, ignored by [`PackedNormalizedXyz10::unpack`]
+    pub fn with_sign(mut self, sign: f32) -> Self {
+        if sign < 0.0 {
+            self.data |= 1 << 31;
+        } else {
+            self.data &= !(1 << 31);
+        }
+        self
+    }
+
+    /// -1.0 if the sign bit is set by [`PackedNormalizedXyz10::with_sign`], 1.0 otherwise
+    pub fn sign(&self) -> f32 {
+        if self.data >> 31 == 1 {
+            -1.0
+        } else {
+            1.0
+        }
+    }
 }
 
 // Inspired by https://knarkowicz.wordpress.com/2014/04/16/octahedron-normal-vector-encoding/
@@ -80,3 +116,12 @@ fn dir_oct_quad_encode(dir: Vec3) -> Vec2 {
     }
     ret_val * 0.5 + 0.5
 }
+
+fn dir_oct_quad_decode(encoded: Vec2) -> Vec3 {
+    let f = encoded * 2.0 - 1.0;
+    let mut dir = Vec3::new(f.x, f.y, 1.0 - f.x.abs() - f.y.abs());
+    let t = (-dir.z).max(0.0);
+    dir.x += if dir.x >= 0.0 { -t } else { t };
+    dir.y += if dir.y >= 0.0 { -t } else { t };
+    dir.normalize()
+}
@@ -0,0 +1,162 @@
+use std::{
+    path::PathBuf,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+use crate::{
+    coordinate_system::CoordinateSystem,
+    lightmap::LightmapUvDesc,
+    optimize::MeshOptimizeOptions,
+    shadow_proxy::ShadowProxyDesc,
+    texture::{
+        MipFilter, TextureCompression, TextureCompressionProfile, TextureCompressionQuality,
+        TextureUsage,
+    },
+};
+
+use super::{MaxTextureResolution, ParseOptions, ProgressCallback, SceneSelection};
+
+/// Texture settings applied to all textures or to the textures of one material slot, see [`ParseOptionsBuilder::texture_usage`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TextureOptions {
+    /// Textures stay uncompressed when `None`
+    pub compression: Option<TextureCompression>,
+    pub max_resolution: Option<MaxTextureResolution>,
+    pub generate_mips: bool,
+}
+
+/// Mesh processing settings
+#[derive(Debug, Default, Clone)]
+pub struct GeometryOptions {
+    pub merge_duplicate_meshes: bool,
+    pub flat_shading: bool,
+    pub lightmap_uvs: Option<LightmapUvDesc>,
+    pub optimize_meshes: Option<MeshOptimizeOptions>,
+    pub shadow_proxies: Option<ShadowProxyDesc>,
+    pub quantize_positions: bool,
+    pub position_streams: bool,
+}
+
+/// Builds [`ParseOptions`] from grouped texture and geometry settings, started by [`ParseOptions::builder`]
+#[derive(Default, Clone)]
+pub struct ParseOptionsBuilder {
+    options: ParseOptions,
+}
+
+impl ParseOptionsBuilder {
+    pub fn scene(mut self, scene: SceneSelection) -> Self {
+        self.options.scene = scene;
+        self
+    }
+
+    pub fn coordinate_system(mut self, coordinate_system: CoordinateSystem) -> Self {
+        self.options.handedness = coordinate_system.handedness;
+        self.options.up_axis = coordinate_system.up_axis;
+        self
+    }
+
+    /// Meters per unit of the source and of the parsed model, see [`ParseOptions::target_unit_scale`]
+    pub fn unit_scale(mut self, source: f32, target: f32) -> Self {
+        self.options.source_unit_scale = Some(source);
+        self.options.target_unit_scale = Some(target);
+        self
+    }
+
+    /// Settings of all textures not overridden by [`ParseOptionsBuilder::texture_usage`]
+    pub fn textures(mut self, textures: TextureOptions) -> Self {
+        self.options.texture_compression = textures.compression;
+        self.options.max_texture_resolution = textures.max_resolution;
+        self.options.generate_mips = textures.generate_mips;
+        self
+    }
+
+    /// Settings of textures referenced by the material slot `usage`, overriding [`ParseOptionsBuilder::textures`].
+    /// A `max_resolution` of `None` keeps the cap of all textures
+    pub fn texture_usage(mut self, usage: TextureUsage, textures: TextureOptions) -> Self {
+        self.options
+            .texture_compression_per_usage
+            .insert(usage, textures.compression);
+        match textures.max_resolution {
+            Some(max_resolution) => {
+                self.options
+                    .max_texture_resolution_per_usage
+                    .insert(usage, max_resolution);
+            }
+            None => {
+                self.options.max_texture_resolution_per_usage.remove(&usage);
+            }
+        }
+        self.options
+            .generate_mips_per_usage
+            .insert(usage, textures.generate_mips);
+        self
+    }
+
+    pub fn texture_compression_profile(mut self, profile: TextureCompressionProfile) -> Self {
+        self.options.texture_compression_profile = profile;
+        self
+    }
+
+    pub fn texture_compression_quality(mut self, quality: TextureCompressionQuality) -> Self {
+        self.options.texture_compression_quality = quality;
+        self
+    }
+
+    /// Mip chain limits and filter of all textures generating mips
+    pub fn mips(
+        mut self,
+        max_mip_levels: Option<u32>,
+        min_mip_size: Option<u32>,
+        mip_filter: MipFilter,
+    ) -> Self {
+        self.options.max_mip_levels = max_mip_levels;
+        self.options.min_mip_size = min_mip_size;
+        self.options.mip_filter = mip_filter;
+        self
+    }
+
+    pub fn texture_cache_dir(mut self, texture_cache_dir: PathBuf) -> Self {
+        self.options.texture_cache_dir = Some(texture_cache_dir);
+        self
+    }
+
+    pub fn geometry(mut self, geometry: GeometryOptions) -> Self {
+        self.options.merge_duplicate_meshes = geometry.merge_duplicate_meshes;
+        self.options.flat_shading = geometry.flat_shading;
+        self.options.lightmap_uvs = geometry.lightmap_uvs;
+        self.options.optimize_meshes = geometry.optimize_meshes;
+        self.options.shadow_proxies = geometry.shadow_proxies;
+        self.options.quantize_positions = geometry.quantize_positions;
+        self.options.position_streams = geometry.position_streams;
+        self
+    }
+
+    pub fn deduplicate_materials(mut self, deduplicate_materials: bool) -> Self {
+        self.options.deduplicate_materials = deduplicate_materials;
+        self
+    }
+
+    pub fn deterministic_uuids(mut self, deterministic_uuids: bool) -> Self {
+        self.options.deterministic_uuids = deterministic_uuids;
+        self
+    }
+
+    pub fn lossless(mut self, lossless: bool) -> Self {
+        self.options.lossless = lossless;
+        self
+    }
+
+    pub fn progress(mut self, progress: ProgressCallback) -> Self {
+        self.options.progress = Some(progress);
+        self
+    }
+
+    pub fn cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.options.cancel = Some(cancel);
+        self
+    }
+
+    pub fn build(self) -> ParseOptions {
+        self.options
+    }
+}
@@ -1,25 +1,267 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{Cursor, Read, Seek, SeekFrom},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
 use anyhow::Result;
 use glam::{Mat4, Quat, Vec2, Vec3, Vec4, Vec4Swizzles};
 use gltf::material::AlphaMode;
 use image::DynamicImage;
 
 use crate::{
-    material::Material,
-    mesh::{generate_normals, generate_tangents, pack_vertices, Mesh},
-    texture::{Texture, TextureCreateDesc},
+    animation::{Interpolation, PropertyPath, PropertyTarget, PropertyTrack},
+    coordinate_system::CoordinateSystem,
+    float_image,
+    material::{self, Material},
+    mesh::{
+        generate_normals, generate_tangents, pack_vertices, repair_tangents, unpack_vertices, Mesh,
+        Submesh,
+    },
+    placeholder, resample,
+    texture::{
+        AddressMode, FilterMode, SamplerDesc, SourceImage, Texture, TextureCreateDesc, TextureUsage,
+    },
     Model, ModelNode,
 };
 
-use super::ParseOptions;
+use super::{
+    parallel_map, ParseError, ParseOptions, ParseReport, ParseStage, ParseWarning, SceneSelection,
+};
+
+/// Size of the uv grid substituted for broken images
+const PLACEHOLDER_SIZE: u32 = 256;
+
+/// Glb chunk types, see the glTF specification
+const CHUNK_JSON: u32 = 0x4E4F534A;
+const CHUNK_BIN: u32 = 0x004E4942;
+
+pub(crate) fn parse_glb(data: &[u8], opt: ParseOptions) -> Result<(Model, ParseReport)> {
+    // Plain gltf json is accepted too
+    if data.starts_with(b"glTF") {
+        let glb = gltf::Glb::from_slice(data)?;
+        parse_json(&glb.json, glb.bin.map(Cow::into_owned), opt)
+    } else {
+        parse_json(data, None, opt)
+    }
+}
+
+/// Parse a glb read chunk by chunk, so only the json and binary chunk are held in memory instead of the whole file
+pub(crate) fn parse_glb_reader<R: Read + Seek>(
+    reader: &mut R,
+    opt: ParseOptions,
+) -> Result<(Model, ParseReport)> {
+    let mut header = [0; 12];
+    reader.read_exact(&mut header)?;
+    let word = |bytes: &[u8], offset: usize| {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    };
+    anyhow::ensure!(
+        header[0..4] == *b"glTF" && word(&header, 4) == 2,
+        "Failed to parse glb, the data is not a glTF 2.0 binary."
+    );
+    let length = word(&header, 8) as u64;
+
+    let mut json = None;
+    let mut blob = None;
+    let mut offset = header.len() as u64;
+    while offset < length {
+        let mut chunk_header = [0; 8];
+        reader.read_exact(&mut chunk_header)?;
+        let chunk_length = word(&chunk_header, 0) as u64;
+        offset += chunk_header.len() as u64 + chunk_length;
+
+        let chunk = match word(&chunk_header, 4) {
+            CHUNK_JSON => &mut json,
+            CHUNK_BIN => &mut blob,
+            // Unknown chunks must be ignored
+            _ => {
+                reader.seek(SeekFrom::Current(chunk_length as i64))?;
+                continue;
+            }
+        };
+        // Read through `take`, so a corrupt chunk length can't allocate more than the stream holds
+        let mut data = vec![];
+        reader.take(chunk_length).read_to_end(&mut data)?;
+        anyhow::ensure!(
+            data.len() as u64 == chunk_length,
+            "Failed to parse glb, a chunk of {} bytes is truncated.",
+            chunk_length
+        );
+        *chunk = Some(data);
+    }
+
+    let json = json.ok_or_else(|| anyhow::anyhow!("Failed to parse glb, it has no json chunk."))?;
+    parse_json(&json, blob, opt)
+}
+
+/// KHR_animation_pointer channel, split off the json before building the document
+struct PointerChannel {
+    animation_idx: usize,
+    sampler_idx: usize,
+    pointer: String,
+}
+
+/// Build the document without KHR_animation_pointer channels, as the gltf crate requires every channel to target a node
+fn parse_json(
+    json: &[u8],
+    blob: Option<Vec<u8>>,
+    opt: ParseOptions,
+) -> Result<(Model, ParseReport)> {
+    let mut root: gltf::json::Value = gltf::json::deserialize::from_slice(json)?;
+    let pointer_channels = take_pointer_channels(&mut root);
+    let document = gltf::Document::from_json(gltf::json::deserialize::from_value(root)?)?;
+    parse_document(document, blob, pointer_channels, opt)
+}
+
+/// Remove all KHR_animation_pointer channels from the json, channels without a valid sampler or pointer are dropped
+fn take_pointer_channels(root: &mut gltf::json::Value) -> Vec<PointerChannel> {
+    let mut pointer_channels = vec![];
+    let animations = root
+        .get_mut("animations")
+        .and_then(gltf::json::Value::as_array_mut);
+    for (animation_idx, animation) in animations.into_iter().flatten().enumerate() {
+        if let Some(channels) = animation
+            .get_mut("channels")
+            .and_then(gltf::json::Value::as_array_mut)
+        {
+            channels.retain(|channel| {
+                let target = &channel["target"];
+                if target["path"] != "pointer" {
+                    return true;
+                }
+
+                let pointer = target["extensions"]["KHR_animation_pointer"]["pointer"].as_str();
+                if let (Some(sampler_idx), Some(pointer)) = (channel["sampler"].as_u64(), pointer) {
+                    pointer_channels.push(PointerChannel {
+                        animation_idx,
+                        sampler_idx: sampler_idx as usize,
+                        pointer: pointer.to_owned(),
+                    });
+                }
+                false
+            });
+        }
+    }
 
-pub(crate) fn parse_glb(data: &[u8], opt: ParseOptions) -> Result<Model> {
-    let (document, buffers, images) = gltf::import_slice(data)?;
+    // Handled here, so it mustn't fail validation as unsupported required extension
+    if let Some(required) = root
+        .get_mut("extensionsRequired")
+        .and_then(gltf::json::Value::as_array_mut)
+    {
+        required.retain(|extension| extension != "KHR_animation_pointer");
+    }
+
+    pointer_channels
+}
+
+/// Import material tracks of the pointer channels, channels targeting anything else are skipped with a warning
+fn import_property_tracks(
+    document: &gltf::Document,
+    buffers: &[gltf::buffer::Data],
+    pointer_channels: Vec<PointerChannel>,
+    report: &mut ParseReport,
+) -> Vec<PropertyTrack> {
+    let mut property_tracks = vec![];
+    for channel in pointer_channels {
+        let path = PropertyPath::parse(&channel.pointer).filter(|path| {
+            path.target == PropertyTarget::Material
+                && (path.index as usize) < document.materials().len()
+        });
+        let sampler = document
+            .animations()
+            .nth(channel.animation_idx)
+            .and_then(|animation| animation.samplers().nth(channel.sampler_idx));
+        let keyframes = sampler.as_ref().and_then(|sampler| {
+            let (times, _) = read_floats(sampler.input(), buffers)?;
+            let (values, components) = read_floats(sampler.output(), buffers)?;
+            Some((times, values, components))
+        });
+
+        match (path, sampler, keyframes) {
+            (Some(path), Some(sampler), Some((times, values, components))) => {
+                property_tracks.push(PropertyTrack {
+                    path,
+                    interpolation: match sampler.interpolation() {
+                        gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                        gltf::animation::Interpolation::Step => Interpolation::Step,
+                        gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+                    },
+                    times,
+                    values,
+                    components,
+                })
+            }
+            _ => report.warnings.push(ParseWarning::SkippedPropertyTrack {
+                pointer: channel.pointer,
+            }),
+        }
+    }
+
+    property_tracks
+}
+
+/// Flattened components of a float accessor and the number of components per element
+fn read_floats(
+    accessor: gltf::Accessor,
+    buffers: &[gltf::buffer::Data],
+) -> Option<(Vec<f32>, u32)> {
+    use gltf::accessor::{DataType, Dimensions, Iter};
+
+    if accessor.data_type() != DataType::F32 {
+        return None;
+    }
+    let get_buffer_data = |buffer: gltf::Buffer| Some(&buffers[buffer.index()][..]);
+    match accessor.dimensions() {
+        Dimensions::Scalar => Some((Iter::<f32>::new(accessor, get_buffer_data)?.collect(), 1)),
+        Dimensions::Vec2 => Some((
+            Iter::<[f32; 2]>::new(accessor, get_buffer_data)?
+                .flatten()
+                .collect(),
+            2,
+        )),
+        Dimensions::Vec3 => Some((
+            Iter::<[f32; 3]>::new(accessor, get_buffer_data)?
+                .flatten()
+                .collect(),
+            3,
+        )),
+        Dimensions::Vec4 => Some((
+            Iter::<[f32; 4]>::new(accessor, get_buffer_data)?
+                .flatten()
+                .collect(),
+            4,
+        )),
+        _ => None,
+    }
+}
+
+fn parse_document(
+    document: gltf::Document,
+    blob: Option<Vec<u8>>,
+    pointer_channels: Vec<PointerChannel>,
+    opt: ParseOptions,
+) -> Result<(Model, ParseReport)> {
+    let buffers = gltf::import_buffers(&document, None, blob)?;
+    let mut report = ParseReport::default();
+    let property_tracks =
+        import_property_tracks(&document, &buffers, pointer_channels, &mut report);
+    let canonical_images = scan_images(&document, &buffers, &opt)?;
 
     let mut meshes = vec![None; document.meshes().len()];
     let mut materials = vec![Material::default(); document.materials().len()];
-    let mut textures = vec![];
 
-    let mut image_to_texture_mapping = vec![None; document.images().len()];
+    let scene = select_scene(&document, opt.scene, &mut report)?;
+
+    let (textures, image_to_texture_mapping) = process_textures(
+        scene.as_ref(),
+        &buffers,
+        &canonical_images,
+        &opt,
+        &mut report,
+    )?;
 
     if materials.is_empty() {
         materials.push(Material::default());
@@ -28,25 +270,31 @@ pub(crate) fn parse_glb(data: &[u8], opt: ParseOptions) -> Result<Model> {
     let mut root_node_indices = Vec::new();
     let mut nodes = Vec::new();
 
-    if let Some(scene) = document.default_scene() {
+    let (num_nodes, num_meshes) = scene.as_ref().map_or((0, 0), count_scene_nodes);
+    opt.report_progress(ParseStage::Nodes, 0, num_nodes);
+    opt.report_progress(ParseStage::Meshes, 0, num_meshes);
+    let canonical_meshes = if opt.merge_duplicate_meshes {
+        canonical_meshes(&document, &buffers)
+    } else {
+        (0..document.meshes().len()).collect()
+    };
+    let ctx = NodeContext {
+        buffers: &buffers,
+        image_to_texture_mapping: &image_to_texture_mapping,
+        canonical_meshes: &canonical_meshes,
+        opt: &opt,
+        num_nodes,
+        num_meshes,
+    };
+    if let Some(scene) = &scene {
         for root_node in scene.nodes() {
             root_node_indices.push(nodes.len() as u32);
-            process_nodes_recursive(
-                &document,
-                &root_node,
-                &buffers,
-                &images,
-                &mut nodes,
-                &mut textures,
-                &mut image_to_texture_mapping,
-                &mut materials,
-                &mut meshes,
-                opt,
-            );
+            process_nodes_recursive(&root_node, &ctx, &mut nodes, &mut materials, &mut meshes)?;
         }
     }
+    opt.report_progress(ParseStage::Meshes, num_meshes, num_meshes);
 
-    let meshes: Vec<Mesh> = meshes
+    let mut meshes: Vec<Mesh> = meshes
         .into_iter()
         .map(|mesh| {
             if let Some(mesh) = mesh {
@@ -57,81 +305,177 @@ pub(crate) fn parse_glb(data: &[u8], opt: ParseOptions) -> Result<Model> {
         })
         .collect();
 
-    let mut bounds_min = Vec3::INFINITY;
-    let mut bounds_max = Vec3::NEG_INFINITY;
-    for mesh in &meshes {
-        bounds_min = bounds_min.min(Vec3::from_array(mesh.bounds_min));
-        bounds_max = bounds_max.max(Vec3::from_array(mesh.bounds_max));
+    for mesh in &mut meshes {
+        mesh.compute_sort_keys(&materials);
     }
 
-    Ok(Model {
+    let mut model = Model {
         root_node_indices,
         nodes,
-        bounds_min: bounds_min.to_array(),
-        bounds_max: bounds_max.to_array(),
+        bounds_min: [0.0; 3],
+        bounds_max: [0.0; 3],
 
         meshes,
         materials,
         textures,
-    })
+        property_tracks,
+    };
+
+    (model.bounds_min, model.bounds_max) = model.compute_world_bounds(Mat4::IDENTITY);
+    if opt.deduplicate_materials {
+        model.deduplicate_materials();
+    }
+
+    opt.check_cancelled()?;
+    model.convert_coordinate_system(
+        CoordinateSystem::GLTF,
+        CoordinateSystem {
+            handedness: opt.handedness,
+            up_axis: opt.up_axis,
+        },
+    );
+    model.scale_uniformly(opt.unit_scale());
+    if let Some(lightmap_uvs) = &opt.lightmap_uvs {
+        model.generate_lightmap_uvs(lightmap_uvs);
+    }
+    if let Some(optimize_meshes) = &opt.optimize_meshes {
+        model.optimize_meshes(optimize_meshes);
+    }
+    if opt.pack_orm_textures {
+        pack_orm_textures(&mut model, &opt);
+    }
+    if let Some(shadow_proxies) = &opt.shadow_proxies {
+        model.generate_shadow_proxies(shadow_proxies);
+    }
+    if opt.quantize_positions && !opt.lossless {
+        model.quantize_positions();
+    }
+    if opt.position_streams {
+        model.build_position_streams();
+    }
+    if opt.deterministic_uuids {
+        for texture in &mut model.textures {
+            texture.set_content_uuid();
+        }
+    }
+
+    if model.is_empty() {
+        report.warnings.push(ParseWarning::EmptyModel);
+    }
+
+    Ok((model, report))
+}
+
+fn is_orm_usage(usage: &TextureUsage) -> bool {
+    matches!(
+        usage,
+        TextureUsage::Occlusion | TextureUsage::MetallicRoughness
+    )
+}
+
+/// Pack the orm textures left uncompressed by [`process_tex`] and compress the results
+fn pack_orm_textures(model: &mut Model, opt: &ParseOptions) {
+    model.pack_orm_textures();
+
+    let mut usages = vec![vec![]; model.textures.len()];
+    for material in &model.materials {
+        let slots = [
+            (material.occlusion_texture, TextureUsage::Occlusion),
+            (
+                material.metallic_roughness_texture,
+                TextureUsage::MetallicRoughness,
+            ),
+        ];
+        for (texture_idx, usage) in slots {
+            if let Some(texture_idx) = texture_idx {
+                let texture_usages: &mut Vec<TextureUsage> = &mut usages[texture_idx as usize];
+                if !texture_usages.contains(&usage) {
+                    texture_usages.push(usage);
+                }
+            }
+        }
+        for texture_idx in material.textures().into_iter().flatten() {
+            if !usages[texture_idx as usize].iter().any(is_orm_usage) {
+                // Referenced by other slots only, already compressed
+                usages[texture_idx as usize].push(TextureUsage::Color);
+            }
+        }
+    }
+
+    let textures = std::mem::take(&mut model.textures);
+    model.textures = parallel_map(
+        textures.into_iter().zip(usages).collect(),
+        |(texture, usages)| {
+            if !usages.is_empty() && usages.iter().all(is_orm_usage) {
+                compress_texture(texture, &usages, opt)
+            } else {
+                texture
+            }
+        },
+    );
+}
+
+/// Inputs shared by all nodes of the traversal
+struct NodeContext<'a> {
+    buffers: &'a [gltf::buffer::Data],
+    image_to_texture_mapping: &'a [Option<u32>],
+    /// Mesh parsed in place of each mesh, see [`canonical_meshes`]
+    canonical_meshes: &'a [usize],
+    opt: &'a ParseOptions,
+    num_nodes: usize,
+    num_meshes: usize,
 }
 
-#[allow(clippy::too_many_arguments)]
 fn process_nodes_recursive(
-    document: &gltf::Document,
     node: &gltf::Node,
-    buffers: &[gltf::buffer::Data],
-    images: &[gltf::image::Data],
+    ctx: &NodeContext,
     nodes: &mut Vec<ModelNode>,
-    internal_images: &mut Vec<Texture>,
-    image_to_texture_mapping: &mut [Option<u32>],
     materials: &mut Vec<Material>,
     meshes: &mut Vec<Option<Mesh>>,
-    opt: ParseOptions,
-) {
-    nodes.push(process_node(
-        document,
-        node,
-        buffers,
-        images,
-        internal_images,
-        image_to_texture_mapping,
-        materials,
-        meshes,
-        opt,
-    ));
+) -> Result<()> {
+    ctx.opt.check_cancelled()?;
+    nodes.push(process_node(node, ctx, materials, meshes)?);
     let node_idx = nodes.len() - 1;
+    ctx.opt
+        .report_progress(ParseStage::Nodes, nodes.len(), ctx.num_nodes);
 
     for child in node.children() {
         let child_idx = nodes.len() as u32;
         nodes[node_idx].child_node_indices.push(child_idx);
-        process_nodes_recursive(
-            document,
-            &child,
-            buffers,
-            images,
-            nodes,
-            internal_images,
-            image_to_texture_mapping,
-            materials,
-            meshes,
-            opt,
-        );
+        process_nodes_recursive(&child, ctx, nodes, materials, meshes)?;
+    }
+
+    Ok(())
+}
+
+/// Number of nodes and unique meshes reachable from the root nodes of `scene`
+fn count_scene_nodes(scene: &gltf::Scene) -> (usize, usize) {
+    let mut num_nodes = 0;
+    let mut mesh_indices = HashSet::new();
+    let mut stack: Vec<gltf::Node> = scene.nodes().collect();
+    while let Some(node) = stack.pop() {
+        num_nodes += 1;
+        if let Some(mesh) = node.mesh() {
+            mesh_indices.insert(mesh.index());
+        }
+        stack.extend(node.children());
     }
+
+    (num_nodes, mesh_indices.len())
 }
 
-#[allow(clippy::too_many_arguments)]
 fn process_node(
-    document: &gltf::Document,
     node: &gltf::Node,
-    buffers: &[gltf::buffer::Data],
-    images: &[gltf::image::Data],
-    internal_images: &mut Vec<Texture>,
-    image_to_texture_mapping: &mut [Option<u32>],
+    ctx: &NodeContext,
     materials: &mut [Material],
     meshes: &mut [Option<Mesh>],
-    opt: ParseOptions,
-) -> ModelNode {
+) -> Result<ModelNode> {
+    let NodeContext {
+        buffers,
+        image_to_texture_mapping,
+        opt,
+        ..
+    } = *ctx;
     let (translation, rotation, scale) = node.transform().decomposed();
     let translation = Vec3::new(translation[0], translation[1], translation[2]);
     let rotation = Quat::from_xyzw(rotation[0], rotation[1], rotation[2], rotation[3]);
@@ -142,26 +486,31 @@ fn process_node(
     let mut node_mesh = None;
 
     if let Some(mesh) = node.mesh() {
-        let mut mesh_idx = mesh.index();
+        let mut mesh_idx = ctx.canonical_meshes[mesh.index()];
         if meshes[mesh_idx].is_none() {
             let mut mesh_vertex_positions = vec![];
             let mut mesh_vertex_tex_coords = vec![];
+            let mut mesh_vertex_secondary_tex_coords = vec![];
             let mut mesh_vertex_normals = vec![];
             let mut mesh_vertex_tangents = vec![];
             let mut mesh_triangle_material_indices = vec![];
             let mut mesh_material_indices = vec![];
             let mut mesh_indices = vec![];
+            let mut mesh_submeshes = vec![];
             let mut opaque = true;
             let mut is_emissive = false;
+            let mesh_name = mesh.name().unwrap_or("Unnamed");
 
             for primitive in mesh.primitives() {
                 if primitive.mode() == gltf::mesh::Mode::Triangles {
                     let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
 
                     let mut vertex_positions = {
-                        let iter = reader
-                            .read_positions()
-                            .expect("Failed to process mesh node. (Vertices must have positions)");
+                        let iter = reader.read_positions().ok_or_else(|| {
+                            ParseError::MissingPositions {
+                                mesh: mesh_name.to_owned(),
+                            }
+                        })?;
 
                         iter.map(|arr| -> Vec3 { Vec3::from(arr) })
                             .collect::<Vec<_>>()
@@ -170,7 +519,9 @@ fn process_node(
                     let indices = reader
                         .read_indices()
                         .map(|read_indices| read_indices.into_u32().collect::<Vec<_>>())
-                        .expect("Failed to process mesh node. (Indices are required)");
+                        .ok_or_else(|| ParseError::MissingIndices {
+                            mesh: mesh_name.to_owned(),
+                        })?;
 
                     let mut vertex_tex_coords = if let Some(tex_coords) = reader.read_tex_coords(0)
                     {
@@ -182,6 +533,13 @@ fn process_node(
                         vec![]
                     };
 
+                    let mut vertex_secondary_tex_coords =
+                        if let Some(tex_coords) = reader.read_tex_coords(1) {
+                            tex_coords.into_f32().collect()
+                        } else {
+                            vec![[0.0; 2]; vertex_positions.len()]
+                        };
+
                     let mut vertex_normals = if let Some(normals) = reader.read_normals() {
                         normals
                             .into_iter()
@@ -208,6 +566,7 @@ fn process_node(
                         .collect::<Vec<u32>>();
                     mesh_vertex_positions.append(&mut vertex_positions);
                     mesh_vertex_tex_coords.append(&mut vertex_tex_coords);
+                    mesh_vertex_secondary_tex_coords.append(&mut vertex_secondary_tex_coords);
                     mesh_vertex_normals.append(&mut vertex_normals);
                     mesh_vertex_tangents.append(&mut vertex_tangents);
                     mesh_indices.append(&mut indices);
@@ -228,6 +587,13 @@ fn process_node(
 
                     mesh_triangle_material_indices
                         .append(&mut vec![local_material_idx; num_triangles]);
+                    if num_triangles > 0 {
+                        mesh_submeshes.push(Submesh {
+                            index_offset: mesh_indices.len() as u32 - num_triangles as u32 * 3,
+                            index_count: num_triangles as u32 * 3,
+                            material: local_material_idx,
+                        });
+                    }
 
                     let material = &mut materials[material_idx];
                     if material.index.is_none() {
@@ -251,14 +617,9 @@ fn process_node(
                         if let Some(transmission) = prim_material.transmission() {
                             material.transmission = transmission.transmission_factor();
                             if let Some(tex) = transmission.transmission_texture() {
-                                material.transmission_texture = Some(process_tex_info(
-                                    document,
-                                    images,
-                                    internal_images,
-                                    image_to_texture_mapping,
-                                    &tex,
-                                    opt,
-                                ));
+                                material.transmission_tex_coord = tex.tex_coord();
+                                material.transmission_texture =
+                                    texture_index(image_to_texture_mapping, &tex.texture());
                             }
                         }
                         material.eta = 1.0 / prim_material.ior().unwrap_or(1.5);
@@ -272,119 +633,101 @@ fn process_node(
                         if let Some(clearcoat) = prim_material.clearcoat() {
                             material.clearcoat = clearcoat.clearcoat_factor();
                             if let Some(tex) = clearcoat.clearcoat_texture() {
-                                material.clearcoat_texture = Some(process_tex_info(
-                                    document,
-                                    images,
-                                    internal_images,
-                                    image_to_texture_mapping,
-                                    &tex,
-                                    opt,
-                                ));
+                                material.clearcoat_tex_coord = tex.tex_coord();
+                                material.clearcoat_texture =
+                                    texture_index(image_to_texture_mapping, &tex.texture());
                             }
                             material.clearcoat_roughness = clearcoat.clearcoat_roughness_factor();
                             if let Some(tex) = clearcoat.clearcoat_roughness_texture() {
-                                material.clearcoat_roughness_texture = Some(process_tex_info(
-                                    document,
-                                    images,
-                                    internal_images,
-                                    image_to_texture_mapping,
-                                    &tex,
-                                    opt,
-                                ));
+                                material.clearcoat_roughness_tex_coord = tex.tex_coord();
+                                material.clearcoat_roughness_texture =
+                                    texture_index(image_to_texture_mapping, &tex.texture());
                             }
                             if let Some(tex) = clearcoat.clearcoat_normal_texture() {
-                                material.clearcoat_normal_texture = Some(process_normal_tex(
-                                    document,
-                                    images,
-                                    internal_images,
-                                    image_to_texture_mapping,
-                                    tex,
-                                    opt,
-                                ));
+                                material.clearcoat_normal_tex_coord = tex.tex_coord();
+                                material.clearcoat_normal_texture =
+                                    texture_index(image_to_texture_mapping, &tex.texture());
                             }
                         }
                         if let Some(sheen) = prim_material.sheen() {
                             material.sheen = sheen.sheen_roughness_factor();
                             if let Some(tex) = sheen.sheen_roughness_texture() {
-                                material.sheen_texture = Some(process_tex_info(
-                                    document,
-                                    images,
-                                    internal_images,
-                                    image_to_texture_mapping,
-                                    &tex,
-                                    opt,
-                                ));
+                                material.sheen_tex_coord = tex.tex_coord();
+                                material.sheen_texture =
+                                    texture_index(image_to_texture_mapping, &tex.texture());
                             }
                             material.sheen_tint = sheen.sheen_color_factor();
                             if let Some(tex) = sheen.sheen_color_texture() {
-                                material.sheen_tint_texture = Some(process_tex_info(
-                                    document,
-                                    images,
-                                    internal_images,
-                                    image_to_texture_mapping,
-                                    &tex,
-                                    opt,
-                                ));
+                                material.sheen_tint_tex_coord = tex.tex_coord();
+                                material.sheen_tint_texture =
+                                    texture_index(image_to_texture_mapping, &tex.texture());
                             }
                         }
 
                         material.alpha_cutoff = prim_material.alpha_cutoff().unwrap_or(0.5);
                         material.is_opaque = prim_material.alpha_mode() == AlphaMode::Opaque
                             || material.alpha_cutoff == 0.0;
+                        material.alpha_mode = match prim_material.alpha_mode() {
+                            AlphaMode::Opaque => material::AlphaMode::Opaque,
+                            AlphaMode::Mask => material::AlphaMode::Mask,
+                            AlphaMode::Blend => material::AlphaMode::Blend,
+                        };
 
                         if let Some(tex) = pbr.base_color_texture() {
-                            material.color_texture = Some(process_tex_info(
-                                document,
-                                images,
-                                internal_images,
-                                image_to_texture_mapping,
-                                &tex,
-                                opt,
-                            ));
+                            material.color_tex_coord = tex.tex_coord();
+                            material.color_texture =
+                                texture_index(image_to_texture_mapping, &tex.texture());
                         }
 
                         if let Some(tex) = prim_material.normal_texture() {
                             material.normal_scale = tex.scale();
-                            material.normal_texture = Some(process_normal_tex(
-                                document,
-                                images,
-                                internal_images,
-                                image_to_texture_mapping,
-                                tex,
-                                opt,
-                            ));
+                            material.normal_tex_coord = tex.tex_coord();
+                            material.normal_texture =
+                                texture_index(image_to_texture_mapping, &tex.texture());
                         }
 
                         if let Some(tex) = pbr.metallic_roughness_texture() {
-                            material.metallic_roughness_texture = Some(process_tex_info(
-                                document,
-                                images,
-                                internal_images,
-                                image_to_texture_mapping,
-                                &tex,
-                                opt,
-                            ));
+                            material.metallic_roughness_tex_coord = tex.tex_coord();
+                            material.metallic_roughness_texture =
+                                texture_index(image_to_texture_mapping, &tex.texture());
                         }
 
                         if let Some(tex) = prim_material.emissive_texture() {
-                            material.emission_texture = Some(process_tex_info(
-                                document,
-                                images,
-                                internal_images,
-                                image_to_texture_mapping,
-                                &tex,
-                                opt,
-                            ));
+                            material.emission_tex_coord = tex.tex_coord();
+                            material.emission_texture =
+                                texture_index(image_to_texture_mapping, &tex.texture());
+                        }
+
+                        if let Some(tex) = prim_material.occlusion_texture() {
+                            material.occlusion_strength = tex.strength();
+                            material.occlusion_tex_coord = tex.tex_coord();
+                            material.occlusion_texture =
+                                texture_index(image_to_texture_mapping, &tex.texture());
                         }
                     }
 
                     opaque = opaque && material.is_opaque;
                     is_emissive = is_emissive || material.is_emissive();
                 } else {
-                    panic!("Only triangles are supported.");
+                    return Err(ParseError::UnsupportedPrimitiveMode {
+                        mesh: mesh_name.to_owned(),
+                        mode: format!("{:?}", primitive.mode()),
+                    }
+                    .into());
                 }
             }
 
+            if opt.flat_shading {
+                // Every corner gets its own vertex, so generated normals equal the face normals
+                mesh_vertex_positions = split_per_corner(&mesh_vertex_positions, &mesh_indices);
+                mesh_vertex_tex_coords = split_per_corner(&mesh_vertex_tex_coords, &mesh_indices);
+                mesh_vertex_secondary_tex_coords =
+                    split_per_corner(&mesh_vertex_secondary_tex_coords, &mesh_indices);
+                mesh_vertex_normals.clear();
+                mesh_vertex_tangents.clear();
+                mesh_indices = (0..mesh_indices.len() as u32).collect();
+            }
+
             if mesh_vertex_normals.is_empty() {
                 mesh_vertex_normals = generate_normals(&mesh_vertex_positions, &mesh_indices);
             }
@@ -396,18 +739,42 @@ fn process_node(
                     &mesh_indices,
                 );
             }
+            // Lossless vertices keep the authored tangents, only the packed vertices get repaired ones
+            let authored_tangents = opt.lossless.then(|| mesh_vertex_tangents.clone());
+            let tangent_repair = repair_tangents(
+                &mesh_vertex_normals,
+                &mut mesh_vertex_tangents,
+                &mesh_vertex_tex_coords,
+                &mesh_vertex_positions,
+                &mesh_indices,
+            );
             if mesh_vertex_tex_coords.is_empty() {
                 mesh_vertex_tex_coords = vec![Vec2::ZERO; mesh_vertex_positions.len()];
             }
 
-            let packed_vertices = pack_vertices(
+            let unpacked_vertices = if let Some(authored_tangents) = authored_tangents {
+                unpack_vertices(
+                    &mesh_vertex_positions,
+                    &mesh_vertex_normals,
+                    &authored_tangents,
+                    &mesh_vertex_tex_coords,
+                )
+            } else {
+                vec![]
+            };
+
+            let (packed_vertices, quantization_error) = pack_vertices(
                 mesh_vertex_positions,
                 mesh_vertex_normals,
                 mesh_vertex_tangents,
                 mesh_vertex_tex_coords,
             );
 
-            let mesh = Mesh::new(
+            let has_secondary_tex_coords = mesh
+                .primitives()
+                .any(|primitive| primitive.get(&gltf::Semantic::TexCoords(1)).is_some());
+
+            let mut mesh = Mesh::new(
                 mesh.name().unwrap_or("Unnamed"),
                 packed_vertices,
                 mesh_triangle_material_indices,
@@ -416,6 +783,13 @@ fn process_node(
                 opaque,
                 is_emissive,
             );
+            mesh.submeshes = mesh_submeshes;
+            mesh.unpacked_vertices = unpacked_vertices;
+            mesh.tangent_repair = tangent_repair;
+            mesh.quantization_error = quantization_error;
+            if has_secondary_tex_coords {
+                mesh.secondary_tex_coords = mesh_vertex_secondary_tex_coords;
+            }
 
             if opt.merge_duplicate_meshes {
                 for (i, other_mesh) in meshes.iter().enumerate() {
@@ -429,187 +803,772 @@ fn process_node(
             }
 
             meshes[mesh_idx] = Some(mesh);
+            // Merged duplicates leave their slot empty, so this can lag behind until the stage completes
+            opt.report_progress(
+                ParseStage::Meshes,
+                meshes.iter().flatten().count().min(ctx.num_meshes),
+                ctx.num_meshes,
+            );
         }
 
         node_mesh = Some(mesh_idx as u32);
     }
 
-    ModelNode {
+    Ok(ModelNode {
         name: node.name().unwrap_or("Unnamed").to_owned(),
         transform,
         mesh_idx: node_mesh,
         child_node_indices: vec![],
+    })
+}
+
+/// Copy of `values` with one entry per index, empty if `values` is
+fn split_per_corner<T: Copy>(values: &[T], indices: &[u32]) -> Vec<T> {
+    if values.is_empty() {
+        return vec![];
     }
+
+    indices
+        .iter()
+        .map(|index| values[*index as usize])
+        .collect()
 }
 
-fn process_tex_info(
-    document: &gltf::Document,
-    images: &[gltf::image::Data],
-    internal_images: &mut Vec<Texture>,
-    image_to_texture_mapping: &mut [Option<u32>],
-    texture_info: &gltf::texture::Info,
-    opt: ParseOptions,
-) -> u32 {
-    let texture = texture_info.texture();
-    let texture_transform = texture_info.texture_transform();
-
-    process_tex(
-        document,
-        images,
-        internal_images,
-        image_to_texture_mapping,
-        texture,
-        texture_transform,
-        false,
-        opt,
-    )
+fn texture_index(
+    image_to_texture_mapping: &[Option<u32>],
+    texture: &gltf::texture::Texture,
+) -> Option<u32> {
+    image_to_texture_mapping[texture.source().index()]
 }
 
-fn process_normal_tex(
+/// Texture referenced by a material slot
+struct TextureReference<'a> {
+    usage: TextureUsage,
+    texture: gltf::texture::Texture<'a>,
+    texture_transform: Option<gltf::texture::TextureTransform<'a>>,
+}
+
+/// Image to be processed into a texture, using the sampler and transform of its first reference
+struct TextureRequest<'a> {
+    image_idx: usize,
+    reference: TextureReference<'a>,
+    usages: Vec<TextureUsage>,
+    /// Alpha cutoff of the first alpha masked material using the image as color texture
+    alpha_cutoff: Option<f32>,
+    /// Only referenced as color texture of alpha blended materials
+    blended_color: bool,
+}
+
+/// All textures referenced by the slots of `material`
+fn material_textures<'a>(material: &gltf::Material<'a>) -> Vec<TextureReference<'a>> {
+    let mut references = vec![];
+    let mut add_info = |info: Option<gltf::texture::Info<'a>>, usage: TextureUsage| {
+        if let Some(info) = info {
+            references.push(TextureReference {
+                usage,
+                texture: info.texture(),
+                texture_transform: info.texture_transform(),
+            });
+        }
+    };
+
+    let pbr = material.pbr_metallic_roughness();
+    add_info(pbr.base_color_texture(), TextureUsage::Color);
+    add_info(
+        pbr.metallic_roughness_texture(),
+        TextureUsage::MetallicRoughness,
+    );
+    add_info(material.emissive_texture(), TextureUsage::Emission);
+    if let Some(transmission) = material.transmission() {
+        add_info(
+            transmission.transmission_texture(),
+            TextureUsage::Transmission,
+        );
+    }
+    if let Some(clearcoat) = material.clearcoat() {
+        add_info(clearcoat.clearcoat_texture(), TextureUsage::Clearcoat);
+        add_info(
+            clearcoat.clearcoat_roughness_texture(),
+            TextureUsage::ClearcoatRoughness,
+        );
+    }
+    if let Some(sheen) = material.sheen() {
+        add_info(sheen.sheen_roughness_texture(), TextureUsage::Sheen);
+        add_info(sheen.sheen_color_texture(), TextureUsage::SheenTint);
+    }
+
+    if let Some(normal_tex) = material.normal_texture() {
+        references.push(TextureReference {
+            usage: TextureUsage::Normal,
+            texture: normal_tex.texture(),
+            texture_transform: normal_tex.texture_transform(),
+        });
+    }
+    if let Some(occlusion_tex) = material.occlusion_texture() {
+        references.push(TextureReference {
+            usage: TextureUsage::Occlusion,
+            texture: occlusion_tex.texture(),
+            texture_transform: occlusion_tex.texture_transform(),
+        });
+    }
+    if let Some(clearcoat) = material.clearcoat() {
+        if let Some(normal_tex) = clearcoat.clearcoat_normal_texture() {
+            references.push(TextureReference {
+                usage: TextureUsage::ClearcoatNormal,
+                texture: normal_tex.texture(),
+                texture_transform: normal_tex.texture_transform(),
+            });
+        }
+    }
+
+    references
+}
+
+/// Materials referenced by the node hierarchy below `node`, in traversal order
+fn collect_node_materials<'a>(node: &gltf::Node<'a>, materials: &mut Vec<gltf::Material<'a>>) {
+    if let Some(mesh) = node.mesh() {
+        for primitive in mesh.primitives() {
+            let material = primitive.material();
+            if !materials
+                .iter()
+                .any(|other| other.index() == material.index())
+            {
+                materials.push(material);
+            }
+        }
+    }
+
+    for child in node.children() {
+        collect_node_materials(&child, materials);
+    }
+}
+
+/// Fail with [`ParseError::TextureTooLarge`] if the image exceeds [`ParseOptions::max_source_texture_resolution`]
+fn check_source_resolution(name: &str, width: u32, height: u32, opt: &ParseOptions) -> Result<()> {
+    match opt.max_source_texture_resolution {
+        Some(max_resolution) if width.max(height) > max_resolution => {
+            Err(ParseError::TextureTooLarge {
+                image: name.to_owned(),
+                width,
+                height,
+                max_resolution,
+            }
+            .into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Encoded bytes of an embedded image or the uri of an external one
+fn encoded_source<'a>(image: &gltf::Image<'a>, buffers: &'a [gltf::buffer::Data]) -> &'a [u8] {
+    match image.source() {
+        gltf::image::Source::View { view, .. } => {
+            &buffers[view.buffer().index()][view.offset()..view.offset() + view.length()]
+        }
+        gltf::image::Source::Uri { uri, .. } => uri.as_bytes(),
+    }
+}
+
+/// Check the resolution of all images, returning the index of the first image with identical pixels for each image.
+/// Images with identical encoded source are matched without decoding, others are only decoded to compare their pixels when another image has the same size.
+/// At most one decoded image is held in memory here, images are decoded again once their texture is processed
+fn scan_images(
     document: &gltf::Document,
-    images: &[gltf::image::Data],
-    internal_images: &mut Vec<Texture>,
-    image_to_texture_mapping: &mut [Option<u32>],
-    normal_tex: gltf::material::NormalTexture,
-    opt: ParseOptions,
-) -> u32 {
-    let texture = normal_tex.texture();
-    let texture_transform = normal_tex.texture_transform();
-
-    process_tex(
-        document,
-        images,
-        internal_images,
-        image_to_texture_mapping,
-        texture,
-        texture_transform,
-        true,
-        opt,
-    )
+    buffers: &[gltf::buffer::Data],
+    opt: &ParseOptions,
+) -> Result<Vec<usize>> {
+    let num_images = document.images().len();
+    opt.report_progress(ParseStage::Images, 0, num_images);
+
+    let mut canonical_images = Vec::with_capacity(num_images);
+    // Images with a unique encoded source, with their size if it can be read from the header
+    let mut unique_images = vec![];
+    let mut by_hash: HashMap<u64, Vec<(usize, &[u8])>> = HashMap::new();
+    for (image_idx, image) in document.images().enumerate() {
+        opt.check_cancelled()?;
+        let source = encoded_source(&image, buffers);
+
+        // Only the header is read, so oversized images fail before being decoded
+        let size = match image.source() {
+            gltf::image::Source::View { .. } => image::ImageReader::new(Cursor::new(source))
+                .with_guessed_format()
+                .ok()
+                .and_then(|reader| reader.into_dimensions().ok()),
+            gltf::image::Source::Uri { .. } => None,
+        };
+        if let Some((width, height)) = size {
+            check_source_resolution(image.name().unwrap_or("Unnamed"), width, height, opt)?;
+        }
+        opt.report_progress(ParseStage::Images, image_idx + 1, num_images);
+
+        let mut hasher = DefaultHasher::new();
+        source.hash(&mut hasher);
+        let candidates = by_hash.entry(hasher.finish()).or_default();
+        if let Some((canonical_image_idx, _)) =
+            candidates.iter().find(|(_, other)| *other == source)
+        {
+            canonical_images.push(*canonical_image_idx);
+        } else {
+            candidates.push((image_idx, source));
+            canonical_images.push(image_idx);
+            unique_images.push((image, size));
+        }
+    }
+    if unique_images.len() < 2 {
+        return Ok(canonical_images);
+    }
+
+    // Images without a readable header are decoded first to learn their size
+    let mut decoded = vec![];
+    for (image, _) in unique_images.iter().filter(|(_, size)| size.is_none()) {
+        if let Some(pixel_hash) = decoded_pixel_hash(image, buffers, opt)? {
+            decoded.push((image.index(), pixel_hash));
+        }
+    }
+    let mut size_counts: HashMap<(u32, u32), usize> = HashMap::new();
+    for size in unique_images
+        .iter()
+        .filter_map(|(_, size)| *size)
+        .chain(decoded.iter().map(|(_, (size, _))| *size))
+    {
+        *size_counts.entry(size).or_default() += 1;
+    }
+    for (image, _) in unique_images
+        .iter()
+        .filter(|(_, size)| size.is_some_and(|size| size_counts[&size] > 1))
+    {
+        if let Some(pixel_hash) = decoded_pixel_hash(image, buffers, opt)? {
+            decoded.push((image.index(), pixel_hash));
+        }
+    }
+
+    decoded.sort_by_key(|(image_idx, _)| *image_idx);
+    let mut by_pixels = HashMap::new();
+    for (image_idx, (size, pixel_hash)) in decoded {
+        if size_counts[&size] > 1 {
+            canonical_images[image_idx] = *by_pixels.entry(pixel_hash).or_insert(image_idx);
+        }
+    }
+    // Exact duplicates follow the image they duplicate
+    Ok((0..num_images)
+        .map(|image_idx| canonical_images[canonical_images[image_idx]])
+        .collect())
 }
 
-#[allow(clippy::too_many_arguments)]
+/// Decoded size and digest of the pixels and their format
+type PixelHash = ((u32, u32), [u8; 32]);
+
+/// `None` for broken images, which are reported once their texture is processed
+fn decoded_pixel_hash(
+    image: &gltf::Image,
+    buffers: &[gltf::buffer::Data],
+    opt: &ParseOptions,
+) -> Result<Option<PixelHash>> {
+    opt.check_cancelled()?;
+    let data = match decode_source(image, image.name().unwrap_or("Unnamed"), buffers, opt) {
+        Ok(data) => data,
+        Err(error)
+            if matches!(
+                error.downcast_ref(),
+                Some(ParseError::TextureTooLarge { .. })
+            ) =>
+        {
+            return Err(error)
+        }
+        Err(_) => return Ok(None),
+    };
+
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[data.format as u8]);
+    hasher.update(&data.pixels);
+    Ok(Some((
+        (data.width, data.height),
+        *hasher.finalize().as_bytes(),
+    )))
+}
+
+/// Decode an image, decoding float images ourselves as the gltf crate only accepts png and jpeg.
+/// Broken images are replaced by a placeholder if enabled, returned with the warning to report
+fn import_image(
+    image: &gltf::Image,
+    buffers: &[gltf::buffer::Data],
+    opt: &ParseOptions,
+) -> Result<(gltf::image::Data, Option<ParseWarning>)> {
+    let name = image.name().unwrap_or("Unnamed");
+    match decode_source(image, name, buffers, opt) {
+        Err(error)
+            if opt.placeholder_for_broken_images
+                && !matches!(
+                    error.downcast_ref(),
+                    Some(ParseError::TextureTooLarge { .. })
+                ) =>
+        {
+            let warning = ParseWarning::BrokenImage {
+                image: name.to_owned(),
+                error: error.to_string(),
+            };
+
+            let placeholder = placeholder::uv_grid_image(PLACEHOLDER_SIZE);
+            let data = gltf::image::Data {
+                width: placeholder.width(),
+                height: placeholder.height(),
+                format: gltf::image::Format::R8G8B8A8,
+                pixels: placeholder.into_raw(),
+            };
+            Ok((data, Some(warning)))
+        }
+        data => data.map(|data| (data, None)),
+    }
+}
+
+fn decode_source(
+    image: &gltf::Image,
+    name: &str,
+    buffers: &[gltf::buffer::Data],
+    opt: &ParseOptions,
+) -> Result<gltf::image::Data> {
+    if let gltf::image::Source::View { mime_type, .. } = image.source() {
+        let data = encoded_source(image, buffers);
+        if let Some(format) = float_image::float_image_format(Some(mime_type), data) {
+            let decoded = float_image::decode_float_image(data, format)?;
+            check_source_resolution(name, decoded.width(), decoded.height(), opt)?;
+
+            return Ok(gltf::image::Data {
+                width: decoded.width(),
+                height: decoded.height(),
+                format: gltf::image::Format::R32G32B32A32FLOAT,
+                pixels: bytemuck::cast_slice(&decoded.into_raw()).to_vec(),
+            });
+        }
+    }
+
+    let data = gltf::image::Data::from_source(image.source(), None, buffers)?;
+    check_source_resolution(name, data.width, data.height, opt)?;
+
+    Ok(data)
+}
+
+/// Scene to parse according to `selection`, `None` if the file has no scenes at all
+fn select_scene<'a>(
+    document: &'a gltf::Document,
+    selection: SceneSelection,
+    report: &mut ParseReport,
+) -> Result<Option<gltf::Scene<'a>>> {
+    match selection {
+        SceneSelection::Default | SceneSelection::DefaultStrict => {
+            if let Some(scene) = document.default_scene() {
+                return Ok(Some(scene));
+            }
+            if selection == SceneSelection::DefaultStrict {
+                return Err(ParseError::MissingDefaultScene.into());
+            }
+
+            report.warnings.push(ParseWarning::MissingDefaultScene);
+            Ok(document.scenes().next())
+        }
+        SceneSelection::Index(index) => document.scenes().nth(index).map(Some).ok_or_else(|| {
+            ParseError::SceneOutOfRange {
+                index,
+                num_scenes: document.scenes().len(),
+            }
+            .into()
+        }),
+    }
+}
+
+/// Decode and process all images referenced by `scene` in parallel, returning the textures and the image to texture mapping.
+/// Each image is decoded by the worker processing its texture and dropped once the texture is done
+fn process_textures(
+    scene: Option<&gltf::Scene>,
+    buffers: &[gltf::buffer::Data],
+    canonical_images: &[usize],
+    opt: &ParseOptions,
+    report: &mut ParseReport,
+) -> Result<(Vec<Texture>, Vec<Option<u32>>)> {
+    let mut materials = vec![];
+    if let Some(scene) = scene {
+        for root_node in scene.nodes() {
+            collect_node_materials(&root_node, &mut materials);
+        }
+    }
+
+    let mut requests: Vec<TextureRequest> = vec![];
+    for material in &materials {
+        let alpha_cutoff = (material.alpha_mode() == AlphaMode::Mask)
+            .then(|| material.alpha_cutoff().unwrap_or(0.5));
+
+        for reference in material_textures(material) {
+            let image_idx = canonical_images[reference.texture.source().index()];
+            let alpha_cutoff = alpha_cutoff.filter(|_| reference.usage == TextureUsage::Color);
+            let blended_color =
+                material.alpha_mode() == AlphaMode::Blend && reference.usage == TextureUsage::Color;
+
+            if let Some(request) = requests
+                .iter_mut()
+                .find(|request| request.image_idx == image_idx)
+            {
+                if !request.usages.contains(&reference.usage) {
+                    request.usages.push(reference.usage);
+                }
+                request.alpha_cutoff = request.alpha_cutoff.or(alpha_cutoff);
+                request.blended_color &= blended_color;
+            } else {
+                requests.push(TextureRequest {
+                    image_idx,
+                    usages: vec![reference.usage],
+                    reference,
+                    alpha_cutoff,
+                    blended_color,
+                });
+            }
+        }
+    }
+
+    let mut image_to_texture_mapping = vec![None; canonical_images.len()];
+    for (texture_idx, request) in requests.iter().enumerate() {
+        image_to_texture_mapping[request.image_idx] = Some(texture_idx as u32);
+    }
+    for (image_idx, canonical_image_idx) in canonical_images.iter().enumerate() {
+        image_to_texture_mapping[image_idx] = image_to_texture_mapping[*canonical_image_idx];
+    }
+
+    let num_textures = requests.len();
+    let completed_textures = AtomicUsize::new(0);
+    opt.report_progress(ParseStage::Textures, 0, num_textures);
+    let textures = parallel_map(requests, |request| {
+        opt.check_cancelled()?;
+        let texture = process_tex(buffers, request, opt);
+        let completed = completed_textures.fetch_add(1, Ordering::Relaxed) + 1;
+        opt.report_progress(ParseStage::Textures, completed, num_textures);
+        texture
+    })
+    .into_iter()
+    .map(|texture| {
+        let (texture, warning) = texture?;
+        report.warnings.extend(warning);
+        Ok(texture)
+    })
+    .collect::<Result<Vec<_>>>()?;
+
+    Ok((textures, image_to_texture_mapping))
+}
+
+/// Raw bytes of an accessor's elements, `None` for sparse accessors and accessors without buffer view
+fn accessor_bytes(accessor: &gltf::Accessor, buffers: &[gltf::buffer::Data]) -> Option<Vec<u8>> {
+    let view = accessor.view().filter(|_| accessor.sparse().is_none())?;
+    let buffer = &buffers[view.buffer().index()];
+    let size = accessor.size();
+    let stride = view.stride().unwrap_or(size);
+    let start = view.offset() + accessor.offset();
+
+    (0..accessor.count())
+        .map(|element| buffer.get(start + element * stride..start + element * stride + size))
+        .try_fold(
+            Vec::with_capacity(accessor.count() * size),
+            |mut bytes, element| {
+                bytes.extend_from_slice(element?);
+                Some(bytes)
+            },
+        )
+}
+
+/// Accessor data and materials of all primitives, equal for meshes that parse to the same geometry
+fn mesh_content(mesh: &gltf::Mesh, buffers: &[gltf::buffer::Data]) -> Vec<u8> {
+    let mut content = vec![];
+    let push_accessor = |content: &mut Vec<u8>, accessor: gltf::Accessor| {
+        match accessor_bytes(&accessor, buffers) {
+            Some(bytes) => {
+                content.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+                content.extend_from_slice(&bytes);
+            }
+            // Never equal to the content of another accessor
+            None => content.extend_from_slice(&(u64::MAX - accessor.index() as u64).to_le_bytes()),
+        }
+    };
+
+    for primitive in mesh.primitives() {
+        content.extend_from_slice(&(primitive.mode() as u32).to_le_bytes());
+        let material = primitive
+            .material()
+            .index()
+            .map_or(u64::MAX, |idx| idx as u64);
+        content.extend_from_slice(&material.to_le_bytes());
+        for (semantic, accessor) in primitive.attributes() {
+            content.extend_from_slice(semantic.to_string().as_bytes());
+            push_accessor(&mut content, accessor);
+        }
+        if let Some(indices) = primitive.indices() {
+            content.extend_from_slice(b"indices");
+            push_accessor(&mut content, indices);
+        }
+    }
+
+    content
+}
+
+/// Index of the first mesh with identical accessor data for each mesh, so duplicates are detected before processing them
+fn canonical_meshes(document: &gltf::Document, buffers: &[gltf::buffer::Data]) -> Vec<usize> {
+    let mut by_hash: HashMap<u64, Vec<(usize, Vec<u8>)>> = HashMap::new();
+
+    document
+        .meshes()
+        .map(|mesh| {
+            let content = mesh_content(&mesh, buffers);
+            let mut hasher = DefaultHasher::new();
+            content.hash(&mut hasher);
+
+            let candidates = by_hash.entry(hasher.finish()).or_default();
+            if let Some((canonical_mesh_idx, _)) =
+                candidates.iter().find(|(_, other)| *other == content)
+            {
+                *canonical_mesh_idx
+            } else {
+                candidates.push((mesh.index(), content));
+                mesh.index()
+            }
+        })
+        .collect()
+}
+
+/// Decode the image of `request` and process it into a texture, returned with the warning of a broken image
 fn process_tex(
-    document: &gltf::Document,
-    images: &[gltf::image::Data],
-    internal_images: &mut Vec<Texture>,
-    image_to_texture_mapping: &mut [Option<u32>],
-    texture: gltf::texture::Texture,
-    texture_transform: Option<gltf::texture::TextureTransform>,
-    is_normal_map: bool,
-    opt: ParseOptions,
-) -> u32 {
+    buffers: &[gltf::buffer::Data],
+    request: TextureRequest,
+    opt: &ParseOptions,
+) -> Result<(Texture, Option<ParseWarning>)> {
+    let texture = &request.reference.texture;
     let name = texture.name().unwrap_or("Unnamed");
 
-    let (uv_offset, uv_scale) = if let Some(transform) = texture_transform {
+    let (uv_offset, uv_scale) = if let Some(transform) = &request.reference.texture_transform {
         (transform.offset(), transform.scale())
     } else {
         ([0.0; 2], [1.0; 2])
     };
+    let sampler = process_sampler(&texture.sampler());
+
+    let image_name = texture.source().name().unwrap_or(name);
+    let (image, warning) = import_image(&texture.source(), buffers, opt)?;
+    let mut image = decode_image(image_name, image)?;
+
+    let source_image = if opt.keep_source_images() {
+        source_image(&texture.source(), buffers)
+    } else {
+        None
+    };
+
+    if let Some(max_texture_resolution) = opt.max_texture_resolution(&request.usages) {
+        if max_texture_resolution < image.width() || max_texture_resolution < image.height() {
+            let scale_x = max_texture_resolution as f32 / image.width() as f32;
+            let scale_y = max_texture_resolution as f32 / image.height() as f32;
+            let min_scale = scale_x.min(scale_y);
+
+            let resized_width = (image.width() as f32 * min_scale) as u32;
+            let resized_height = (image.height() as f32 * min_scale) as u32;
+
+            image = resample::resize(
+                &image,
+                resized_width,
+                resized_height,
+                opt.resize_filter,
+                request.reference.usage.color_space(),
+            );
+        }
+    }
 
-    match texture.source().source() {
-        gltf::image::Source::View { .. } => {
-            let texture_idx = texture.index(); // TODO???
+    let mut texture = Texture::new(TextureCreateDesc {
+        name: Some(name),
+        image,
+        mips: opt.generate_mips(&request.usages),
+        max_mip_levels: opt.max_mip_levels,
+        min_mip_size: opt.min_mip_size,
+        precomputed_mips: vec![],
+        mip_filter: opt.mip_filter,
+        is_normal_map: request.reference.usage.is_normal_map(),
+        color_space: request.reference.usage.color_space(),
+        alpha_coverage_cutoff: request.alpha_cutoff.filter(|_| opt.preserve_alpha_coverage),
+        premultiply_alpha: opt.premultiply_alpha && request.blended_color,
+        uv_offset,
+        uv_scale,
+        sampler,
+        source_image,
+    });
+
+    texture.set_usages(request.usages.iter().copied().collect());
+    if opt.two_channel_normal_maps
+        && !opt.lossless
+        && request.usages.iter().all(TextureUsage::is_normal_map)
+    {
+        texture = texture.into_two_channel_normal_map();
+    }
+
+    if opt.pack_orm_textures && request.usages.iter().all(is_orm_usage) {
+        // Compressed after packing
+        return Ok((texture, warning));
+    }
 
-            let texture = document.textures().nth(texture_idx).unwrap(); // TODO ???
-            let image_idx = texture.source().index();
+    Ok((compress_texture(texture, &request.usages, opt), warning))
+}
 
-            if let Some(texture_idx) = &image_to_texture_mapping[image_idx] {
-                *texture_idx
+fn compress_texture(mut texture: Texture, usages: &[TextureUsage], opt: &ParseOptions) -> Texture {
+    if let Some(texture_compression) = opt.texture_compression(usages) {
+        let (prepared_texture, compressed_format) = texture.prepare_for_compression(
+            &texture_compression,
+            opt.texture_compression_profile,
+            usages,
+        );
+        texture = prepared_texture;
+
+        if let Some(compressed_format) = compressed_format {
+            let compressed_texture = if let Some(cache_dir) = opt.texture_cache_dir() {
+                texture.compress_cached(
+                    compressed_format,
+                    opt.texture_compression_quality,
+                    &cache_dir,
+                )
             } else {
-                let data = images[image_idx].clone();
-
-                let mut image = match data.format {
-                    gltf::image::Format::R16G16B16A16 => DynamicImage::ImageRgba16(
-                        image::ImageBuffer::from_vec(
-                            data.width,
-                            data.height,
-                            bytemuck::cast_slice(&data.pixels).to_vec(),
-                        )
-                        .unwrap(),
-                    ),
-                    gltf::image::Format::R16G16B16 => DynamicImage::ImageRgb16(
-                        image::ImageBuffer::from_vec(
-                            data.width,
-                            data.height,
-                            bytemuck::cast_slice(&data.pixels).to_vec(),
-                        )
-                        .unwrap(),
-                    ),
-                    gltf::image::Format::R16G16 => DynamicImage::ImageLuma16(
-                        image::ImageBuffer::from_vec(
-                            data.width,
-                            data.height,
-                            bytemuck::cast_slice(&data.pixels).to_vec(),
-                        )
-                        .unwrap(),
-                    ),
-                    gltf::image::Format::R16 => DynamicImage::ImageLumaA16(
-                        image::ImageBuffer::from_vec(
-                            data.width,
-                            data.height,
-                            bytemuck::cast_slice(&data.pixels).to_vec(),
-                        )
-                        .unwrap(),
-                    ),
-                    gltf::image::Format::R8G8B8A8 => DynamicImage::ImageRgba8(
-                        image::RgbaImage::from_raw(data.width, data.height, data.pixels).unwrap(),
-                    ),
-                    gltf::image::Format::R8G8B8 => DynamicImage::ImageRgb8(
-                        image::RgbImage::from_raw(data.width, data.height, data.pixels).unwrap(),
-                    ),
-                    gltf::image::Format::R8G8 => DynamicImage::ImageLumaA8(
-                        image::GrayAlphaImage::from_raw(data.width, data.height, data.pixels)
-                            .unwrap(),
-                    ),
-                    gltf::image::Format::R8 => DynamicImage::ImageLuma8(
-                        image::GrayImage::from_raw(data.width, data.height, data.pixels).unwrap(),
-                    ),
-                    _ => panic!("Unsupported image type: {:?}.", data.format),
-                };
-
-                if let Some(max_texture_resolution) = &opt.max_texture_resolution {
-                    let max_texture_resolution = max_texture_resolution.resolution();
-
-                    if max_texture_resolution < image.width()
-                        || max_texture_resolution < image.height()
-                    {
-                        let scale_x = max_texture_resolution as f32 / image.width() as f32;
-                        let scale_y = max_texture_resolution as f32 / image.height() as f32;
-                        let min_scale = scale_x.min(scale_y);
-
-                        let resized_width = (image.width() as f32 * min_scale) as u32;
-                        let resized_height = (image.height() as f32 * min_scale) as u32;
-
-                        image = image.resize_exact(
-                            resized_width,
-                            resized_height,
-                            image::imageops::FilterType::CatmullRom,
-                        );
-                    }
-                }
+                texture.compress_as(compressed_format, opt.texture_compression_quality)
+            };
 
-                let mut texture = Texture::new(TextureCreateDesc {
-                    name: Some(name),
-                    image,
-                    mips: opt.generate_mips,
-                    is_normal_map,
-                    uv_offset,
-                    uv_scale,
-                });
+            if let Some(compressed_texture) = compressed_texture {
+                texture = compressed_texture;
+            }
+        }
+    }
 
-                if let Some(texture_compression) = &opt.texture_compression {
-                    if let Some(compressed_texture) = texture.compress(texture_compression) {
-                        texture = compressed_texture;
-                    }
-                }
+    texture
+}
 
-                let texture_idx = internal_images.len() as u32;
-                internal_images.push(texture);
-                image_to_texture_mapping[image_idx] = Some(texture_idx);
-                texture_idx
+/// Original encoded bytes of an image embedded in a buffer view
+fn source_image(image: &gltf::Image, buffers: &[gltf::buffer::Data]) -> Option<SourceImage> {
+    match image.source() {
+        gltf::image::Source::View { view, mime_type } => {
+            let buffer = &buffers[view.buffer().index()];
+
+            Some(SourceImage {
+                mime_type: mime_type.to_owned(),
+                data: buffer[view.offset()..view.offset() + view.length()].to_vec(),
+            })
+        }
+        gltf::image::Source::Uri { .. } => None,
+    }
+}
+
+fn decode_image(name: &str, data: gltf::image::Data) -> Result<DynamicImage> {
+    let (width, height) = (data.width, data.height);
+    let invalid_data = || ParseError::InvalidImageData {
+        image: name.to_owned(),
+        width,
+        height,
+    };
+
+    let image = match data.format {
+        gltf::image::Format::R16G16B16A16 => DynamicImage::ImageRgba16(
+            image::ImageBuffer::from_vec(
+                data.width,
+                data.height,
+                bytemuck::cast_slice(&data.pixels).to_vec(),
+            )
+            .ok_or_else(invalid_data)?,
+        ),
+        gltf::image::Format::R16G16B16 => DynamicImage::ImageRgb16(
+            image::ImageBuffer::from_vec(
+                data.width,
+                data.height,
+                bytemuck::cast_slice(&data.pixels).to_vec(),
+            )
+            .ok_or_else(invalid_data)?,
+        ),
+        gltf::image::Format::R16G16 => DynamicImage::ImageLumaA16(
+            image::ImageBuffer::from_vec(
+                data.width,
+                data.height,
+                bytemuck::cast_slice(&data.pixels).to_vec(),
+            )
+            .ok_or_else(invalid_data)?,
+        ),
+        gltf::image::Format::R16 => DynamicImage::ImageLuma16(
+            image::ImageBuffer::from_vec(
+                data.width,
+                data.height,
+                bytemuck::cast_slice(&data.pixels).to_vec(),
+            )
+            .ok_or_else(invalid_data)?,
+        ),
+        gltf::image::Format::R8G8B8A8 => DynamicImage::ImageRgba8(
+            image::RgbaImage::from_raw(data.width, data.height, data.pixels)
+                .ok_or_else(invalid_data)?,
+        ),
+        gltf::image::Format::R8G8B8 => DynamicImage::ImageRgb8(
+            image::RgbImage::from_raw(data.width, data.height, data.pixels)
+                .ok_or_else(invalid_data)?,
+        ),
+        gltf::image::Format::R8G8 => DynamicImage::ImageLumaA8(
+            image::GrayAlphaImage::from_raw(data.width, data.height, data.pixels)
+                .ok_or_else(invalid_data)?,
+        ),
+        gltf::image::Format::R8 => DynamicImage::ImageLuma8(
+            image::GrayImage::from_raw(data.width, data.height, data.pixels)
+                .ok_or_else(invalid_data)?,
+        ),
+        gltf::image::Format::R32G32B32FLOAT => DynamicImage::ImageRgb32F(
+            image::ImageBuffer::from_vec(
+                data.width,
+                data.height,
+                bytemuck::cast_slice(&data.pixels).to_vec(),
+            )
+            .ok_or_else(invalid_data)?,
+        ),
+        gltf::image::Format::R32G32B32A32FLOAT => DynamicImage::ImageRgba32F(
+            image::ImageBuffer::from_vec(
+                data.width,
+                data.height,
+                bytemuck::cast_slice(&data.pixels).to_vec(),
+            )
+            .ok_or_else(invalid_data)?,
+        ),
+        #[allow(unreachable_patterns)]
+        _ => {
+            return Err(ParseError::UnsupportedImageFormat {
+                image: name.to_owned(),
+                format: format!("{:?}", data.format),
             }
+            .into())
+        }
+    };
+
+    Ok(image)
+}
+
+fn process_sampler(sampler: &gltf::texture::Sampler) -> SamplerDesc {
+    use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+    let address_mode = |wrapping_mode: WrappingMode| match wrapping_mode {
+        WrappingMode::ClampToEdge => AddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => AddressMode::MirrorRepeat,
+        WrappingMode::Repeat => AddressMode::Repeat,
+    };
+
+    let mag_filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => FilterMode::Nearest,
+        Some(MagFilter::Linear) | None => FilterMode::Linear,
+    };
+
+    let (min_filter, mipmap_filter) = match sampler.min_filter() {
+        Some(MinFilter::Nearest) | Some(MinFilter::NearestMipmapNearest) => {
+            (FilterMode::Nearest, FilterMode::Nearest)
+        }
+        Some(MinFilter::Linear) | Some(MinFilter::LinearMipmapNearest) => {
+            (FilterMode::Linear, FilterMode::Nearest)
         }
-        gltf::image::Source::Uri { .. } => todo!(),
+        Some(MinFilter::NearestMipmapLinear) => (FilterMode::Nearest, FilterMode::Linear),
+        Some(MinFilter::LinearMipmapLinear) | None => (FilterMode::Linear, FilterMode::Linear),
+    };
+
+    SamplerDesc {
+        address_mode_u: address_mode(sampler.wrap_s()),
+        address_mode_v: address_mode(sampler.wrap_t()),
+        mag_filter,
+        min_filter,
+        mipmap_filter,
     }
 }
@@ -1,10 +1,43 @@
+mod builder;
 #[cfg(feature = "gltf")]
 pub(crate) mod gltf;
+#[cfg(all(feature = "gltf", feature = "async"))]
+mod task;
 
-use crate::texture::TextureCompression;
+#[cfg(feature = "gltf")]
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    path::PathBuf,
+    sync::{atomic::AtomicBool, Arc},
+};
+
+pub use builder::{GeometryOptions, ParseOptionsBuilder, TextureOptions};
+#[cfg(all(feature = "gltf", feature = "async"))]
+pub use task::BlockingTask;
+
+use crate::{
+    coordinate_system::{Handedness, UpAxis},
+    lightmap::LightmapUvDesc,
+    optimize::MeshOptimizeOptions,
+    shadow_proxy::ShadowProxyDesc,
+    texture::{
+        MipFilter, TextureCompression, TextureCompressionProfile, TextureCompressionQuality,
+        TextureUsage,
+    },
+};
 
-#[derive(Clone, Copy)]
+/// Environment variable used as compressed texture cache directory when `ParseOptions::texture_cache_dir` is not set
+pub const TEXTURE_CACHE_DIR_ENV: &str = "UGM_TEXTURE_CACHE_DIR";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MaxTextureResolution {
+    Res256,
+    Res512,
     Res1024,
     Res2048,
     Res4096,
@@ -13,6 +46,8 @@ pub enum MaxTextureResolution {
 impl MaxTextureResolution {
     pub fn resolution(&self) -> u32 {
         match self {
+            Self::Res256 => 256,
+            Self::Res512 => 512,
             Self::Res1024 => 1024,
             Self::Res2048 => 2048,
             Self::Res4096 => 4096,
@@ -20,10 +55,344 @@ impl MaxTextureResolution {
     }
 }
 
-#[derive(Default, Clone, Copy)]
+/// Scene of a file to parse
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SceneSelection {
+    /// Scene marked as default by the file, falling back to the first scene with a warning if none is marked
+    #[default]
+    Default,
+    /// Scene marked as default by the file, failing to parse if none is marked
+    DefaultStrict,
+    Index(usize),
+}
+
+/// Stage of parsing reported to [`ParseOptions::progress`], in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParseStage {
+    /// Source images loaded from buffers or decoded from float image formats
+    Images,
+    /// Textures resized, mipmapped and compressed, runs in parallel so completion order is arbitrary
+    Textures,
+    Nodes,
+    /// Unique meshes converted, including normal and tangent generation
+    Meshes,
+}
+
+/// Progress of one [`ParseStage`], `completed` items out of `total`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseProgress {
+    pub stage: ParseStage,
+    pub completed: usize,
+    pub total: usize,
+}
+
+/// Callback receiving [`ParseProgress`] updates, may be called from multiple threads
+pub type ProgressCallback = Arc<dyn Fn(ParseProgress) + Send + Sync>;
+
+/// Settings of parsing, not `Copy` since 0.1.0 as it holds per usage overrides and shared handles, clone it to reuse
+#[derive(Default, Clone)]
 pub struct ParseOptions {
+    pub scene: SceneSelection,
+    /// Handedness of the parsed model, glTF is right handed
+    pub handedness: Handedness,
+    /// Up axis of the parsed model, glTF is y up
+    pub up_axis: UpAxis,
+    /// Meters per unit of the source, glTF is specified in meters so 1.0 is assumed when `None`.
+    /// Set for assets authored in other units, such as 0.01 for centimeter assets converted from fbx without rescaling
+    pub source_unit_scale: Option<f32>,
+    /// Meters per unit of the parsed model, the model is scaled through its root transforms if it differs from the source, see [`crate::Model::scale_uniformly`]
+    pub target_unit_scale: Option<f32>,
     pub texture_compression: Option<TextureCompression>,
+    /// Overrides `texture_compression` for textures referenced by these material slots, `None` leaves them uncompressed
+    pub texture_compression_per_usage: HashMap<TextureUsage, Option<TextureCompression>>,
+    pub texture_compression_profile: TextureCompressionProfile,
+    pub texture_compression_quality: TextureCompressionQuality,
+    /// Store normal maps as two channels with z reconstructed when sampling, independent of `texture_compression_profile`
+    pub two_channel_normal_maps: bool,
     pub generate_mips: bool,
+    /// Overrides `generate_mips` for textures referenced by these material slots
+    pub generate_mips_per_usage: HashMap<TextureUsage, bool>,
+    /// Maximum number of mip levels including the top level, the full chain when `None`
+    pub max_mip_levels: Option<u32>,
+    /// Smallest width or height a generated mip may have
+    pub min_mip_size: Option<u32>,
+    pub mip_filter: MipFilter,
+    /// Fail parsing when a source image is wider or higher than this, checked before decoding where possible to avoid running out of memory on huge images
+    pub max_source_texture_resolution: Option<u32>,
     pub max_texture_resolution: Option<MaxTextureResolution>,
+    /// Overrides `max_texture_resolution` for textures referenced by these material slots
+    pub max_texture_resolution_per_usage: HashMap<TextureUsage, MaxTextureResolution>,
+    /// Filter used to downscale textures exceeding their maximum resolution
+    pub resize_filter: MipFilter,
+    /// Parse meshes with identical accessor data and materials once and share them between their nodes, see [`crate::Model::mesh_instances`]
     pub merge_duplicate_meshes: bool,
+    /// Merge materials with identical parameters, see [`crate::Model::deduplicate_materials`]
+    pub deduplicate_materials: bool,
+    /// Derive texture uuids from their content instead of generating random ones, so parsing the same file twice gives identical output,
+    /// see [`crate::texture::Texture::set_content_uuid`]
+    pub deterministic_uuids: bool,
+    /// Generate lightmap uvs replacing any source TEXCOORD_1, see [`crate::mesh::Mesh::generate_lightmap_uvs`]
+    pub lightmap_uvs: Option<LightmapUvDesc>,
+    /// Split vertices per triangle and use face normals, ignoring source normals and tangents, for faceted low poly art
+    pub flat_shading: bool,
+    /// Reorder triangles for vertex cache efficiency and reduced overdraw, see [`crate::mesh::Mesh::optimize`]
+    pub optimize_meshes: Option<MeshOptimizeOptions>,
+    /// Directory used to cache compressed textures across runs
+    pub texture_cache_dir: Option<PathBuf>,
+    /// Keep full precision vertices and original image bytes, texture resizing and compression are skipped
+    pub lossless: bool,
+    /// Keep the original encoded image bytes alongside the processed texture data, implied by `lossless`
+    pub keep_source_images: bool,
+    /// Substitute images that fail to load with a uv grid placeholder, reported as [`ParseWarning::BrokenImage`], instead of failing to parse
+    pub placeholder_for_broken_images: bool,
+    /// Rescale the alpha of generated mips so alpha masked color textures keep their alpha test coverage
+    pub preserve_alpha_coverage: bool,
+    /// Premultiply rgb by alpha of color textures only used by alpha blended materials, avoiding fringes when filtering, see [`crate::texture::Texture::premultiplied_alpha`]
+    pub premultiply_alpha: bool,
+    /// Pack occlusion, roughness and metallic of each material into a single texture, see [`crate::Model::pack_orm_textures`]
+    pub pack_orm_textures: bool,
+    /// Generate shadow proxies for opaque meshes, see [`crate::Model::generate_shadow_proxies`]
+    pub shadow_proxies: Option<ShadowProxyDesc>,
+    /// Store vertex positions as 16 bit unorm relative to the mesh bounds, ignored when `lossless`, see [`crate::mesh::Mesh::quantize_positions`]
+    pub quantize_positions: bool,
+    /// Store a position only vertex stream per mesh, see [`crate::mesh::Mesh::build_position_stream`]
+    pub position_streams: bool,
+    /// Called at the start of every [`ParseStage`] and after each completed item, for loading bars in tools
+    pub progress: Option<ProgressCallback>,
+    /// Checked between images, textures and nodes, parsing fails with [`ParseError::Cancelled`] once set
+    pub cancel: Option<Arc<AtomicBool>>,
+}
+
+impl ParseOptions {
+    /// Profile for using ugm as archival format, preserving all source data
+    pub fn lossless() -> Self {
+        Self {
+            lossless: true,
+            ..Default::default()
+        }
+    }
+
+    /// Start building options from the defaults, see [`ParseOptionsBuilder`]
+    pub fn builder() -> ParseOptionsBuilder {
+        ParseOptionsBuilder::default()
+    }
+
+    /// Uniform scale converting source units to target units
+    pub fn unit_scale(&self) -> f32 {
+        match self.target_unit_scale {
+            Some(target) => self.source_unit_scale.unwrap_or(1.0) / target,
+            None => 1.0,
+        }
+    }
+
+    pub fn keep_source_images(&self) -> bool {
+        self.keep_source_images || self.lossless
+    }
+
+    /// Resolution cap of a texture referenced by `usages`, the smallest cap of all its usages
+    pub fn max_texture_resolution(&self, usages: &[TextureUsage]) -> Option<u32> {
+        if self.lossless {
+            return None;
+        }
+
+        let global = self.max_texture_resolution.as_ref();
+        if usages.is_empty() {
+            return global.map(MaxTextureResolution::resolution);
+        }
+
+        usages
+            .iter()
+            .filter_map(|usage| {
+                self.max_texture_resolution_per_usage
+                    .get(usage)
+                    .or(global)
+                    .map(MaxTextureResolution::resolution)
+            })
+            .min()
+    }
+
+    /// Compression of a texture referenced by `usages`, textures stay uncompressed if any of their usages asks for it
+    pub fn texture_compression(&self, usages: &[TextureUsage]) -> Option<TextureCompression> {
+        if self.lossless {
+            return None;
+        }
+
+        let mut compressions = usages.iter().map(|usage| {
+            self.texture_compression_per_usage
+                .get(usage)
+                .copied()
+                .unwrap_or(self.texture_compression)
+        });
+        match compressions.next() {
+            Some(first) => first.filter(|_| compressions.all(|compression| compression.is_some())),
+            None => self.texture_compression,
+        }
+    }
+
+    /// Returns true if mips are generated for a texture referenced by `usages`, which is the case if any of its usages asks for them
+    pub fn generate_mips(&self, usages: &[TextureUsage]) -> bool {
+        if usages.is_empty() {
+            return self.generate_mips;
+        }
+
+        usages.iter().any(|usage| {
+            self.generate_mips_per_usage
+                .get(usage)
+                .copied()
+                .unwrap_or(self.generate_mips)
+        })
+    }
+
+    #[cfg(feature = "gltf")]
+    pub(crate) fn check_cancelled(&self) -> Result<(), ParseError> {
+        match &self.cancel {
+            Some(cancel) if cancel.load(Ordering::Relaxed) => Err(ParseError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "gltf")]
+    pub(crate) fn report_progress(&self, stage: ParseStage, completed: usize, total: usize) {
+        if let Some(progress) = &self.progress {
+            progress(ParseProgress {
+                stage,
+                completed,
+                total,
+            });
+        }
+    }
+
+    pub fn texture_cache_dir(&self) -> Option<PathBuf> {
+        self.texture_cache_dir
+            .clone()
+            .or_else(|| std::env::var_os(TEXTURE_CACHE_DIR_ENV).map(PathBuf::from))
+    }
+}
+
+/// Errors specific to parsing, returned wrapped in an [`anyhow::Error`] that can be downcast to this type
+#[derive(Debug, thiserror::Error)]
+pub enum ParseError {
+    #[error("Unsupported image format {format} in image \"{image}\".")]
+    UnsupportedImageFormat { image: String, format: String },
+    #[error("Image \"{image}\" holds less pixel data than its {width}x{height} size requires.")]
+    InvalidImageData {
+        image: String,
+        width: u32,
+        height: u32,
+    },
+    #[error("File doesn't specify a default scene.")]
+    MissingDefaultScene,
+    #[error("Scene {index} out of range, file contains {num_scenes} scenes.")]
+    SceneOutOfRange { index: usize, num_scenes: usize },
+    #[error("Image \"{image}\" of {width}x{height} exceeds the maximum source texture resolution of {max_resolution}.")]
+    TextureTooLarge {
+        image: String,
+        width: u32,
+        height: u32,
+        max_resolution: u32,
+    },
+    #[error("Primitive of mesh \"{mesh}\" has no positions.")]
+    MissingPositions { mesh: String },
+    #[error("Primitive of mesh \"{mesh}\" has no indices.")]
+    MissingIndices { mesh: String },
+    #[error(
+        "Primitive of mesh \"{mesh}\" uses unsupported mode {mode}, only triangles are supported."
+    )]
+    UnsupportedPrimitiveMode { mesh: String, mode: String },
+    /// Parsing was cancelled through [`ParseOptions::cancel`]
+    #[error("Parsing was cancelled.")]
+    Cancelled,
+}
+
+/// Non fatal issue found while parsing
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseWarning {
+    /// No mesh contains any geometry, the model bounds are zeroed
+    EmptyModel,
+    /// File doesn't specify a default scene, the first scene was parsed instead
+    MissingDefaultScene,
+    /// Image failed to load and was replaced by a uv grid, see [`crate::texture::Texture::uv_grid`]
+    BrokenImage { image: String, error: String },
+    /// KHR_animation_pointer channel wasn't imported, only float material properties are, see [`crate::animation`]
+    SkippedPropertyTrack { pointer: String },
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EmptyModel => write!(f, "Model contains no geometry."),
+            Self::MissingDefaultScene => {
+                write!(
+                    f,
+                    "File doesn't specify a default scene, using the first scene."
+                )
+            }
+            Self::BrokenImage { image, error } => {
+                write!(
+                    f,
+                    "Image \"{}\" failed to load and was replaced by a placeholder: {}",
+                    image, error
+                )
+            }
+            Self::SkippedPropertyTrack { pointer } => {
+                write!(
+                    f,
+                    "Animation of \"{}\" was skipped, only float material properties are imported.",
+                    pointer
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ParseReport {
+    pub warnings: Vec<ParseWarning>,
+}
+
+/// Map `items` on all available cores, preserving order
+#[cfg(feature = "gltf")]
+pub(crate) fn parallel_map<T, R, F>(items: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+{
+    let num_threads = if cfg!(target_arch = "wasm32") {
+        1
+    } else {
+        std::thread::available_parallelism().map_or(1, |n| n.get())
+    }
+    .min(items.len());
+
+    if num_threads <= 1 {
+        return items.into_iter().map(f).collect();
+    }
+
+    let num_items = items.len();
+    let items: Vec<Mutex<Option<T>>> = items
+        .into_iter()
+        .map(|item| Mutex::new(Some(item)))
+        .collect();
+    let results: Vec<Mutex<Option<R>>> = (0..num_items).map(|_| Mutex::new(None)).collect();
+    let next_item = AtomicUsize::new(0);
+
+    std::thread::scope(|scope| {
+        for _ in 0..num_threads {
+            scope.spawn(|| loop {
+                let i = next_item.fetch_add(1, Ordering::Relaxed);
+                if i >= num_items {
+                    break;
+                }
+
+                let item = items[i].lock().unwrap().take().unwrap();
+                *results[i].lock().unwrap() = Some(f(item));
+            });
+        }
+    });
+
+    results
+        .into_iter()
+        .map(|result| result.into_inner().unwrap().unwrap())
+        .collect()
 }
@@ -0,0 +1,103 @@
+use std::{
+    any::Any,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex, OnceLock,
+    },
+    task::{Context, Poll, Waker},
+};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct TaskState<T> {
+    result: Option<Result<T, Box<dyn Any + Send>>>,
+    waker: Option<Waker>,
+    /// Set once the closure returned, even after its result was taken
+    finished: bool,
+}
+
+/// Threads shared by all blocking tasks, so at most the available parallelism of tasks run at once and the rest wait in queue
+fn worker_pool() -> &'static mpsc::Sender<Job> {
+    static POOL: OnceLock<mpsc::Sender<Job>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let num_workers = std::thread::available_parallelism().map_or(1, |n| n.get());
+        for _ in 0..num_workers {
+            let receiver = receiver.clone();
+            std::thread::spawn(move || loop {
+                let job = receiver.lock().unwrap().recv();
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => break,
+                }
+            });
+        }
+        sender
+    })
+}
+
+/// Future resolving to the result of a closure run on a shared pool of worker threads, so blocking work never stalls the executor polling it.
+/// Only relies on wakers, making it usable from any async runtime. Panics of the closure are resumed when polled.
+/// Dropping the task before it resolves sets its cancel flag, which the closure is expected to check
+pub struct BlockingTask<T> {
+    state: Arc<Mutex<TaskState<T>>>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl<T: Send + 'static> BlockingTask<T> {
+    pub(crate) fn spawn<F>(f: F, cancel: Arc<AtomicBool>) -> Self
+    where
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let state = Arc::new(Mutex::new(TaskState {
+            result: None,
+            waker: None,
+            finished: false,
+        }));
+
+        let thread_state = state.clone();
+        let job: Job = Box::new(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(f));
+
+            let mut state = thread_state.lock().unwrap();
+            state.result = Some(result);
+            state.finished = true;
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        });
+        worker_pool()
+            .send(job)
+            .expect("The worker pool lives as long as the process.");
+
+        Self { state, cancel }
+    }
+}
+
+impl<T> Future for BlockingTask<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.lock().unwrap();
+        match state.result.take() {
+            Some(Ok(result)) => Poll::Ready(result),
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Drop for BlockingTask<T> {
+    fn drop(&mut self) {
+        if !self.state.lock().unwrap().finished {
+            self.cancel.store(true, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Programmatically generated textures, used as placeholders for missing or broken images
+
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use crate::texture::{ColorSpace, MipFilter, Texture, TextureCreateDesc};
+
+/// Number of cells along each side of generated patterns
+const NUM_CELLS: u32 = 8;
+
+/// Uv grid pattern, red and green follow u and v, cells alternate in brightness and are outlined in white
+pub(crate) fn uv_grid_image(size: u32) -> RgbaImage {
+    let cell_size = (size / NUM_CELLS).max(1);
+
+    RgbaImage::from_fn(size, size, |x, y| {
+        if x % cell_size == 0 || y % cell_size == 0 {
+            return Rgba([255; 4]);
+        }
+
+        let brightness = if (x / cell_size + y / cell_size).is_multiple_of(2) {
+            1.0
+        } else {
+            0.6
+        };
+        let u = (x as f32 + 0.5) / size as f32;
+        let v = (y as f32 + 0.5) / size as f32;
+        Rgba([
+            (u * brightness * 255.0) as u8,
+            (v * brightness * 255.0) as u8,
+            (0.5 * brightness * 255.0) as u8,
+            255,
+        ])
+    })
+}
+
+fn generated_texture(name: &str, image: RgbaImage) -> Texture {
+    Texture::new(TextureCreateDesc {
+        name: Some(name),
+        image: DynamicImage::ImageRgba8(image),
+        mips: true,
+        max_mip_levels: None,
+        min_mip_size: None,
+        precomputed_mips: vec![],
+        mip_filter: MipFilter::Box,
+        is_normal_map: false,
+        color_space: ColorSpace::Srgb,
+        alpha_coverage_cutoff: None,
+        premultiply_alpha: false,
+        uv_offset: [0.0; 2],
+        uv_scale: [1.0; 2],
+        sampler: Default::default(),
+        source_image: None,
+    })
+}
+
+impl Texture {
+    /// Srgb checkerboard of `size` by `size` pixels with 8 by 8 cells, alternating between both `colors`
+    pub fn checkerboard(size: u32, colors: [[u8; 4]; 2]) -> Self {
+        let cell_size = (size / NUM_CELLS).max(1);
+        let image = RgbaImage::from_fn(size, size, |x, y| {
+            Rgba(colors[((x / cell_size + y / cell_size) % 2) as usize])
+        });
+
+        generated_texture("Checkerboard", image)
+    }
+
+    /// Srgb uv grid of `size` by `size` pixels, making the orientation and density of texture coordinates visible
+    pub fn uv_grid(size: u32) -> Self {
+        generated_texture("Uv grid", uv_grid_image(size))
+    }
+
+    /// Single srgb pixel of `color`
+    pub fn solid(color: [u8; 4]) -> Self {
+        generated_texture("Solid", RgbaImage::from_pixel(1, 1, Rgba(color)))
+    }
+}
@@ -0,0 +1,31 @@
+//! Tightly packed position only vertex streams for depth prepasses and shadow rendering, stored next to the interleaved vertices
+
+use crate::{mesh::Mesh, Model};
+
+impl Mesh {
+    /// Store the position of every vertex in `position_stream`, matching the positions of quantized vertices exactly.
+    /// Operations changing vertex positions or count keep an existing stream up to date
+    pub fn build_position_stream(&mut self) {
+        self.position_stream = Some(
+            (0..self.num_vertices() as u32)
+                .map(|vertex_idx| self.vertex_position(vertex_idx).to_array())
+                .collect(),
+        );
+    }
+
+    /// Rebuild `position_stream` if the mesh has one
+    pub(crate) fn refresh_position_stream(&mut self) {
+        if self.position_stream.is_some() {
+            self.build_position_stream();
+        }
+    }
+}
+
+impl Model {
+    /// Build the position stream of every mesh, see [`Mesh::build_position_stream`]
+    pub fn build_position_streams(&mut self) {
+        for mesh in &mut self.meshes {
+            mesh.build_position_stream();
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Image space comparison of textures, used to quantify the quality cost of texture compression
+
+use image::{ColorType, DynamicImage};
+
+use crate::texture::Texture;
+
+/// Side length of the square windows used by [`ssim`]
+const SSIM_WINDOW: u32 = 8;
+
+/// Difference between two versions of a single mip level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MipQuality {
+    pub mip: u32,
+    /// Peak signal to noise ratio in dB, infinite for identical mips
+    pub psnr: f32,
+    /// Structural similarity between -1.0 and 1.0, 1.0 for identical mips
+    pub ssim: f32,
+}
+
+impl Texture {
+    /// Peak signal to noise ratio in dB of the top mip against `reference`, see [`Texture::quality_against`]
+    pub fn psnr_against(&self, reference: &Texture) -> Option<f32> {
+        self.quality_against(reference)?
+            .first()
+            .map(|quality| quality.psnr)
+    }
+
+    /// Psnr and ssim of every mip present in both textures, compressed textures are decoded first.
+    /// Only the channels present in both textures are compared, so a bc5 normal map compares against the xy of its source.
+    /// Returns `None` if the sizes don't match or either texture can't be decoded
+    pub fn quality_against(&self, reference: &Texture) -> Option<Vec<MipQuality>> {
+        if self.width() != reference.width()
+            || self.height() != reference.height()
+            || self.layer_count() != reference.layer_count()
+        {
+            return None;
+        }
+
+        (0..self.mip_count().min(reference.mip_count()))
+            .map(|mip| {
+                let (width, height, num_channels, pixels, reference_pixels) =
+                    channels_in_common(&self.to_image(mip)?, &reference.to_image(mip)?);
+
+                // Hdr textures aren't bound to 1.0
+                let peak = reference_pixels.iter().copied().fold(1.0, f32::max);
+
+                Some(MipQuality {
+                    mip,
+                    psnr: psnr(&pixels, &reference_pixels, peak),
+                    ssim: ssim(
+                        &pixels,
+                        &reference_pixels,
+                        width,
+                        height,
+                        num_channels,
+                        peak,
+                    ),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Float channels of an image in their native layout, luma images have a single channel and luma alpha images two
+fn float_channels(image: &DynamicImage) -> (usize, Vec<f32>) {
+    match image.color() {
+        ColorType::L8 | ColorType::L16 => (1, image.to_luma32f().into_raw()),
+        ColorType::La8 | ColorType::La16 => (2, image.to_luma_alpha32f().into_raw()),
+        _ => (4, image.to_rgba32f().into_raw()),
+    }
+}
+
+/// Width, height, channel count and interleaved pixels of both images, restricted to their common channels
+fn channels_in_common(
+    image: &DynamicImage,
+    reference: &DynamicImage,
+) -> (u32, u32, usize, Vec<f32>, Vec<f32>) {
+    let (image_channels, image_pixels) = float_channels(image);
+    let (reference_channels, reference_pixels) = float_channels(reference);
+    let num_channels = image_channels.min(reference_channels);
+
+    let truncate = |pixels: Vec<f32>, channels: usize| {
+        if channels == num_channels {
+            pixels
+        } else {
+            pixels
+                .chunks_exact(channels)
+                .flat_map(|pixel| &pixel[..num_channels])
+                .copied()
+                .collect()
+        }
+    };
+
+    (
+        image.width(),
+        image.height(),
+        num_channels,
+        truncate(image_pixels, image_channels),
+        truncate(reference_pixels, reference_channels),
+    )
+}
+
+fn psnr(pixels: &[f32], reference: &[f32], peak: f32) -> f32 {
+    let squared_error: f64 = pixels
+        .iter()
+        .zip(reference)
+        .map(|(a, b)| ((a - b) as f64).powi(2))
+        .sum();
+    let mse = squared_error / pixels.len().max(1) as f64;
+
+    if mse == 0.0 {
+        f32::INFINITY
+    } else {
+        (10.0 * ((peak as f64).powi(2) / mse).log10()) as f32
+    }
+}
+
+/// Mean ssim over all channels and half overlapping windows
+fn ssim(
+    pixels: &[f32],
+    reference: &[f32],
+    width: u32,
+    height: u32,
+    num_channels: usize,
+    peak: f32,
+) -> f32 {
+    let c1 = (0.01 * peak as f64).powi(2);
+    let c2 = (0.03 * peak as f64).powi(2);
+
+    let window_width = SSIM_WINDOW.min(width);
+    let window_height = SSIM_WINDOW.min(height);
+    let window_starts = |size: u32, window: u32| {
+        (0..=size - window)
+            .step_by((window / 2).max(1) as usize)
+            .collect::<Vec<_>>()
+    };
+    let xs = window_starts(width, window_width);
+    let ys = window_starts(height, window_height);
+
+    let mut total = 0.0;
+    let mut num_windows = 0;
+    for channel in 0..num_channels {
+        for y in &ys {
+            for x in &xs {
+                let samples = (*y..y + window_height).flat_map(|y| {
+                    (*x..x + window_width).map(move |x| {
+                        let idx = (y * width + x) as usize * num_channels + channel;
+                        (pixels[idx] as f64, reference[idx] as f64)
+                    })
+                });
+
+                let count = (window_width * window_height) as f64;
+                let (mut sum_a, mut sum_b) = (0.0, 0.0);
+                let (mut sum_aa, mut sum_bb, mut sum_ab) = (0.0, 0.0, 0.0);
+                for (a, b) in samples {
+                    sum_a += a;
+                    sum_b += b;
+                    sum_aa += a * a;
+                    sum_bb += b * b;
+                    sum_ab += a * b;
+                }
+
+                let (mean_a, mean_b) = (sum_a / count, sum_b / count);
+                let variance_a = sum_aa / count - mean_a * mean_a;
+                let variance_b = sum_bb / count - mean_b * mean_b;
+                let covariance = sum_ab / count - mean_a * mean_b;
+
+                total += ((2.0 * mean_a * mean_b + c1) * (2.0 * covariance + c2))
+                    / ((mean_a * mean_a + mean_b * mean_b + c1) * (variance_a + variance_b + c2));
+                num_windows += 1;
+            }
+        }
+    }
+
+    (total / num_windows.max(1) as f64) as f32
+}
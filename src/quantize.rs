@@ -0,0 +1,167 @@
+//! Compact vertices with positions stored as 16 bit unorm relative to the mesh bounds, for bandwidth constrained platforms
+
+use std::mem::offset_of;
+
+use bytemuck::{Pod, Zeroable};
+use glam::Vec3;
+use speedy::{Readable, Writable};
+
+use crate::{
+    gpu::{VertexAttribute, VertexFormat, VertexSemantic},
+    mesh::{Mesh, PackedVertex},
+    packing::PackedNormalizedXyz10,
+    Model,
+};
+
+/// Largest value of a 16 bit unorm
+const UNORM16_MAX: f32 = u16::MAX as f32;
+
+/// Vertex 8 bytes smaller than [`PackedVertex`]
+#[derive(Debug, Pod, Clone, Copy, Zeroable, Readable, Writable)]
+#[repr(C)]
+pub struct QuantizedVertex {
+    /// Unorm position within the mesh bounds, w holds the tangent handiness as 0 for -1.0 and 65535 for 1.0
+    pub position: [u16; 4],
+    pub normal: PackedNormalizedXyz10,
+    pub tex_coord: [f32; 2],
+    pub tangent: PackedNormalizedXyz10,
+}
+
+impl QuantizedVertex {
+    pub const ATTRIBUTES: [VertexAttribute; 4] = [
+        VertexAttribute {
+            semantic: VertexSemantic::Position,
+            format: VertexFormat::Unorm16x4,
+            offset: offset_of!(QuantizedVertex, position) as u64,
+        },
+        VertexAttribute {
+            semantic: VertexSemantic::Normal,
+            format: VertexFormat::OctahedralUnorm15x2,
+            offset: offset_of!(QuantizedVertex, normal) as u64,
+        },
+        VertexAttribute {
+            semantic: VertexSemantic::TexCoord,
+            format: VertexFormat::Float32x2,
+            offset: offset_of!(QuantizedVertex, tex_coord) as u64,
+        },
+        VertexAttribute {
+            semantic: VertexSemantic::Tangent,
+            format: VertexFormat::OctahedralUnorm15x2,
+            offset: offset_of!(QuantizedVertex, tangent) as u64,
+        },
+    ];
+}
+
+/// Quantized vertices of a mesh together with their dequantization transform, `position = offset + unorm * scale`
+#[derive(Debug, Clone, Readable, Writable)]
+pub struct QuantizedVertices {
+    pub vertices: Vec<QuantizedVertex>,
+    pub offset: [f32; 3],
+    pub scale: [f32; 3],
+}
+
+impl QuantizedVertices {
+    /// Mesh space position of `vertex`
+    pub fn position(&self, vertex: &QuantizedVertex) -> Vec3 {
+        let unorm = Vec3::new(
+            vertex.position[0] as f32,
+            vertex.position[1] as f32,
+            vertex.position[2] as f32,
+        ) / UNORM16_MAX;
+        Vec3::from_array(self.offset) + unorm * Vec3::from_array(self.scale)
+    }
+
+    pub(crate) fn unpack(&self, vertex: &QuantizedVertex) -> PackedVertex {
+        PackedVertex {
+            position: self.position(vertex).to_array(),
+            normal: vertex.normal,
+            tex_coord: vertex.tex_coord,
+            tangent: vertex.tangent,
+            tangent_handiness: if vertex.position[3] == 0 { -1.0 } else { 1.0 },
+        }
+    }
+}
+
+impl Mesh {
+    /// Replace `packed_vertices` by [`QuantizedVertex`]es relative to the mesh bounds, stored in `quantized_vertices`.
+    /// Most processing needs `packed_vertices`, so this should be the last step before serializing, see [`Mesh::dequantize_positions`]
+    pub fn quantize_positions(&mut self) {
+        if self.quantized_vertices.is_some() {
+            return;
+        }
+
+        let offset = Vec3::from_array(self.bounds_min);
+        let scale = Vec3::from_array(self.bounds_max) - offset;
+        let unorm = |position: [f32; 3], axis: usize| {
+            if scale[axis] > 0.0 {
+                ((position[axis] - offset[axis]) / scale[axis] * UNORM16_MAX)
+                    .round()
+                    .clamp(0.0, UNORM16_MAX) as u16
+            } else {
+                0
+            }
+        };
+
+        let mut quantized = QuantizedVertices {
+            vertices: Vec::with_capacity(self.packed_vertices.len()),
+            offset: offset.to_array(),
+            scale: scale.to_array(),
+        };
+        let mut max_position_error = 0.0f32;
+        for vertex in &self.packed_vertices {
+            let quantized_vertex = QuantizedVertex {
+                position: [
+                    unorm(vertex.position, 0),
+                    unorm(vertex.position, 1),
+                    unorm(vertex.position, 2),
+                    if vertex.tangent_handiness < 0.0 {
+                        0
+                    } else {
+                        u16::MAX
+                    },
+                ],
+                normal: vertex.normal,
+                tex_coord: vertex.tex_coord,
+                tangent: vertex.tangent,
+            };
+            max_position_error = max_position_error.max(
+                quantized
+                    .position(&quantized_vertex)
+                    .distance(Vec3::from_array(vertex.position)),
+            );
+            quantized.vertices.push(quantized_vertex);
+        }
+
+        self.quantization_error.max_position_error = max_position_error;
+        self.quantized_vertices = Some(quantized);
+        self.packed_vertices = Vec::new();
+        self.refresh_position_stream();
+    }
+
+    /// Restore `packed_vertices` from `quantized_vertices`, positions keep their quantization error
+    pub fn dequantize_positions(&mut self) {
+        if let Some(quantized) = self.quantized_vertices.take() {
+            self.packed_vertices = quantized
+                .vertices
+                .iter()
+                .map(|vertex| quantized.unpack(vertex))
+                .collect();
+        }
+    }
+}
+
+impl Model {
+    /// Quantize the positions of every mesh, see [`Mesh::quantize_positions`]
+    pub fn quantize_positions(&mut self) {
+        for mesh in &mut self.meshes {
+            mesh.quantize_positions();
+        }
+    }
+
+    /// Restore full precision vertex storage of every quantized mesh, see [`Mesh::dequantize_positions`]
+    pub fn dequantize_positions(&mut self) {
+        for mesh in &mut self.meshes {
+            mesh.dequantize_positions();
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! Cpu ray intersection against meshes and models, for picking and tooling
+
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::{bvh::Bvh, mesh::Mesh, Model};
+
+/// Determinant below which a ray is considered parallel to a triangle
+const PARALLEL_EPSILON: f32 = 1e-12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    /// Doesn't need to be normalized, hit distances are in multiples of its length
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self { origin, direction }
+    }
+
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    fn transformed(&self, transform: Mat4) -> Self {
+        Self {
+            origin: transform.transform_point3(self.origin),
+            direction: transform.transform_vector3(self.direction),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub distance: f32,
+    /// Weights of the second and third triangle corner, the first is `1 - u - v`
+    pub barycentrics: Vec2,
+    /// Index of the triangle within the mesh
+    pub triangle_idx: u32,
+    /// Model material index
+    pub material_idx: u32,
+    /// Index of the hit mesh, only set by [`Model::intersect_ray`]
+    pub mesh_idx: Option<u32>,
+    /// Index of the node instancing the hit mesh, only set by [`Model::intersect_ray`]
+    pub node_idx: Option<u32>,
+}
+
+/// Two sided Möller-Trumbore intersection, returning the distance and barycentrics
+fn intersect_triangle(ray: &Ray, [a, b, c]: [Vec3; 3]) -> Option<(f32, Vec2)> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = ray.direction.cross(edge2);
+    let det = edge1.dot(p);
+    if det.abs() < PARALLEL_EPSILON {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    let s = ray.origin - a;
+    let u = s.dot(p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(edge1);
+    let v = ray.direction.dot(q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = edge2.dot(q) * inv_det;
+    (distance >= 0.0).then_some((distance, Vec2::new(u, v)))
+}
+
+/// Entry distance of the ray into the bounds, `None` if it misses or enters beyond `max_distance`
+fn intersect_bounds(
+    origin: Vec3,
+    inv_direction: Vec3,
+    min: [f32; 3],
+    max: [f32; 3],
+    max_distance: f32,
+) -> Option<f32> {
+    let t0 = (Vec3::from_array(min) - origin) * inv_direction;
+    let t1 = (Vec3::from_array(max) - origin) * inv_direction;
+    let near = t0.min(t1).max_element().max(0.0);
+    let far = t0.max(t1).min_element().min(max_distance);
+    (near <= far).then_some(near)
+}
+
+impl Mesh {
+    /// Closest hit of `ray` in mesh space, closer than `max_distance`. Traverses the bvh when present, otherwise tests all triangles
+    pub fn intersect_ray(&self, ray: &Ray, max_distance: f32) -> Option<RayHit> {
+        let mut closest: Option<(f32, Vec2, usize)> = None;
+        let mut test = |triangle_idx: usize, max_distance: &mut f32| {
+            let corners = self
+                .indices
+                .triangle(triangle_idx)
+                .map(|vertex| self.vertex_position(vertex));
+            if let Some((distance, barycentrics)) = intersect_triangle(ray, corners) {
+                if distance < *max_distance {
+                    *max_distance = distance;
+                    closest = Some((distance, barycentrics, triangle_idx));
+                }
+            }
+        };
+
+        let mut max_distance = max_distance;
+        match &self.bvh {
+            Some(bvh) if !bvh.nodes.is_empty() => {
+                traverse_bvh(bvh, ray, &mut max_distance, &mut test);
+            }
+            _ => {
+                for triangle_idx in 0..self.indices.len() / 3 {
+                    test(triangle_idx, &mut max_distance);
+                }
+            }
+        }
+
+        closest.map(|(distance, barycentrics, triangle_idx)| {
+            let local_material_idx = self.triangle_material_indices[triangle_idx];
+            RayHit {
+                distance,
+                barycentrics,
+                triangle_idx: triangle_idx as u32,
+                material_idx: self.material_indices[local_material_idx as usize],
+                mesh_idx: None,
+                node_idx: None,
+            }
+        })
+    }
+}
+
+/// Call `test` for the triangles of every leaf the ray enters, closest leaves first, `test` shrinks `max_distance` on hits
+fn traverse_bvh<F>(bvh: &Bvh, ray: &Ray, max_distance: &mut f32, test: &mut F)
+where
+    F: FnMut(usize, &mut f32),
+{
+    let inv_direction = ray.direction.recip();
+    let node_entry = |node_idx: usize, max_distance: f32| {
+        let node = &bvh.nodes[node_idx];
+        intersect_bounds(
+            ray.origin,
+            inv_direction,
+            node.bounds_min,
+            node.bounds_max,
+            max_distance,
+        )
+    };
+
+    if node_entry(0, *max_distance).is_none() {
+        return;
+    }
+    let mut stack = vec![0];
+    while let Some(node_idx) = stack.pop() {
+        let node = &bvh.nodes[node_idx];
+        if node.is_leaf() {
+            let first = node.left_first as usize;
+            for triangle_idx in &bvh.triangle_indices[first..first + node.triangle_count as usize] {
+                test(*triangle_idx as usize, max_distance);
+            }
+            continue;
+        }
+
+        let left = node.left_first as usize;
+        let mut children: Vec<(f32, usize)> = [left, left + 1]
+            .into_iter()
+            .filter_map(|child| Some((node_entry(child, *max_distance)?, child)))
+            .collect();
+        // Push the farther child first so the closer one is visited next
+        children.sort_by(|a, b| b.0.total_cmp(&a.0));
+        stack.extend(children.into_iter().map(|(_, child)| child));
+    }
+}
+
+impl Model {
+    /// Closest hit of a world space `ray` against all mesh instances below the root nodes
+    pub fn intersect_ray(&self, root_transform: Mat4, ray: &Ray) -> Option<RayHit> {
+        let mut closest: Option<RayHit> = None;
+        for (node_idx, node, transform) in self.iter_nodes(root_transform) {
+            let Some(mesh_idx) = node.mesh_idx else {
+                continue;
+            };
+
+            // Distances are preserved by transforming origin and direction alike
+            let local_ray = ray.transformed(transform.inverse());
+            let max_distance = closest.map_or(f32::INFINITY, |hit| hit.distance);
+            if let Some(hit) =
+                self.meshes[mesh_idx as usize].intersect_ray(&local_ray, max_distance)
+            {
+                closest = Some(RayHit {
+                    mesh_idx: Some(mesh_idx),
+                    node_idx: Some(node_idx),
+                    ..hit
+                });
+            }
+        }
+
+        closest
+    }
+}
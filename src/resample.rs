@@ -0,0 +1,324 @@
+//! Separable resampling for mip filters not provided by the image crate
+
+use std::collections::VecDeque;
+
+use image::{DynamicImage, Rgba32FImage};
+
+use crate::texture::{ColorSpace, MipFilter};
+
+/// Kaiser window shape parameter
+const KAISER_BETA: f32 = 4.0;
+/// Kaiser window half width in destination texels
+const KAISER_SUPPORT: f32 = 3.0;
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        let x = x * std::f32::consts::PI;
+        x.sin() / x
+    }
+}
+
+/// Zeroth order modified Bessel function of the first kind
+fn bessel_i0(x: f32) -> f32 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    for k in 1..32 {
+        term *= (x * 0.5 / k as f32).powi(2);
+        sum += term;
+        if term < sum * 1e-7 {
+            break;
+        }
+    }
+    sum
+}
+
+fn kaiser(x: f32) -> f32 {
+    let t = x / KAISER_SUPPORT;
+    if t.abs() >= 1.0 {
+        return 0.0;
+    }
+    sinc(x) * bessel_i0(KAISER_BETA * (1.0 - t * t).sqrt()) / bessel_i0(KAISER_BETA)
+}
+
+fn box_filter(x: f32) -> f32 {
+    if x.abs() <= 0.5 {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// Normalized filter weights for each destination texel, as (first source texel, weights)
+fn weights(
+    src_size: u32,
+    dst_size: u32,
+    kernel: fn(f32) -> f32,
+    support: f32,
+) -> Vec<(u32, Vec<f32>)> {
+    let scale = src_size as f32 / dst_size as f32;
+    let radius = support * scale.max(1.0);
+
+    (0..dst_size)
+        .map(|dst| {
+            let center = (dst as f32 + 0.5) * scale;
+            let first = (center - radius).floor().max(0.0) as u32;
+            let last = ((center + radius).ceil() as u32).min(src_size);
+
+            let mut weights: Vec<f32> = (first..last)
+                .map(|src| kernel((src as f32 + 0.5 - center) / scale.max(1.0)))
+                .collect();
+            let sum: f32 = weights.iter().sum();
+            if sum.abs() > f32::EPSILON {
+                for weight in &mut weights {
+                    *weight /= sum;
+                }
+            }
+
+            (first, weights)
+        })
+        .collect()
+}
+
+/// Source row converted to linear rgba and filtered horizontally
+fn filtered_row(
+    image: &DynamicImage,
+    y: u32,
+    column_weights: &[(u32, Vec<f32>)],
+    is_srgb: bool,
+) -> Vec<[f32; 4]> {
+    let mut row = image.crop_imm(0, y, image.width(), 1).into_rgba32f();
+    if is_srgb {
+        for pixel in row.pixels_mut() {
+            for value in &mut pixel.0[..3] {
+                *value = srgb_to_linear(*value);
+            }
+        }
+    }
+
+    column_weights
+        .iter()
+        .map(|(first, weights)| {
+            let mut texel = [0.0; 4];
+            for (i, weight) in weights.iter().enumerate() {
+                let src = row.get_pixel(first + i as u32, 0);
+                for c in 0..4 {
+                    texel[c] += src[c] * weight;
+                }
+            }
+            texel
+        })
+        .collect()
+}
+
+/// Separable resize that converts and filters source rows on demand, only the rows under the vertical kernel are held in full precision at once.
+/// This keeps the peak memory of resizing very large images close to the size of the source image
+fn resize_streamed(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    kernel: fn(f32) -> f32,
+    support: f32,
+    is_srgb: bool,
+) -> Rgba32FImage {
+    let column_weights = weights(image.width(), width, kernel, support);
+    let row_weights = weights(image.height(), height, kernel, support);
+
+    let mut resized = Rgba32FImage::new(width, height);
+    let mut rows: VecDeque<Vec<[f32; 4]>> = VecDeque::new();
+    let mut first_row = 0;
+    for (y, (first, weights)) in row_weights.iter().enumerate() {
+        while first_row < *first && !rows.is_empty() {
+            rows.pop_front();
+            first_row += 1;
+        }
+        if rows.is_empty() {
+            first_row = *first;
+        }
+        while first_row + (rows.len() as u32) < first + weights.len() as u32 {
+            let src_y = first_row + rows.len() as u32;
+            rows.push_back(filtered_row(image, src_y, &column_weights, is_srgb));
+        }
+
+        let mut row = vec![[0.0; 4]; width as usize];
+        for (src_row, weight) in rows.iter().zip(weights) {
+            for (texel, src) in row.iter_mut().zip(src_row) {
+                for c in 0..4 {
+                    texel[c] += src[c] * weight;
+                }
+            }
+        }
+        for (x, texel) in row.into_iter().enumerate() {
+            resized.put_pixel(x as u32, y as u32, image::Rgba(texel));
+        }
+    }
+
+    resized
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Filter provided by the image crate
+fn image_filter(filter: MipFilter) -> Option<image::imageops::FilterType> {
+    match filter {
+        MipFilter::Triangle => Some(image::imageops::FilterType::Triangle),
+        MipFilter::CatmullRom => Some(image::imageops::FilterType::CatmullRom),
+        MipFilter::Lanczos3 => Some(image::imageops::FilterType::Lanczos3),
+        MipFilter::Box | MipFilter::Kaiser => None,
+    }
+}
+
+/// Convert `resized` to the pixel format of `original`
+pub(crate) fn to_format_of(resized: Rgba32FImage, original: &DynamicImage) -> DynamicImage {
+    let resized = DynamicImage::ImageRgba32F(resized);
+
+    // Filters with negative lobes ring, clamp to the range of unorm formats
+    let clamped = |resized: DynamicImage| {
+        let mut resized = resized.into_rgba32f();
+        for value in resized.iter_mut() {
+            *value = value.clamp(0.0, 1.0);
+        }
+        DynamicImage::ImageRgba32F(resized)
+    };
+
+    match original {
+        DynamicImage::ImageRgba32F(_) => resized,
+        DynamicImage::ImageRgb32F(_) => DynamicImage::ImageRgb32F(resized.to_rgb32f()),
+        DynamicImage::ImageLuma8(_) => DynamicImage::ImageLuma8(clamped(resized).to_luma8()),
+        DynamicImage::ImageLumaA8(_) => {
+            DynamicImage::ImageLumaA8(clamped(resized).to_luma_alpha8())
+        }
+        DynamicImage::ImageRgb8(_) => DynamicImage::ImageRgb8(clamped(resized).to_rgb8()),
+        DynamicImage::ImageLuma16(_) => DynamicImage::ImageLuma16(clamped(resized).to_luma16()),
+        DynamicImage::ImageLumaA16(_) => {
+            DynamicImage::ImageLumaA16(clamped(resized).to_luma_alpha16())
+        }
+        DynamicImage::ImageRgb16(_) => DynamicImage::ImageRgb16(clamped(resized).to_rgb16()),
+        DynamicImage::ImageRgba16(_) => DynamicImage::ImageRgba16(clamped(resized).to_rgba16()),
+        _ => DynamicImage::ImageRgba8(clamped(resized).to_rgba8()),
+    }
+}
+
+/// Multiply the rgb of `image` by its alpha, in linear space for srgb unorm images
+pub(crate) fn premultiply_alpha(image: DynamicImage, color_space: ColorSpace) -> DynamicImage {
+    scale_rgb_by_alpha(image, color_space, |value, alpha| value * alpha)
+}
+
+/// Inverse of [`premultiply_alpha`], fully transparent pixels stay black
+#[cfg(feature = "gltf")]
+pub(crate) fn unpremultiply_alpha(image: DynamicImage, color_space: ColorSpace) -> DynamicImage {
+    scale_rgb_by_alpha(image, color_space, |value, alpha| {
+        if alpha > 0.0 {
+            (value / alpha).min(1.0)
+        } else {
+            0.0
+        }
+    })
+}
+
+fn scale_rgb_by_alpha<F>(image: DynamicImage, color_space: ColorSpace, scale: F) -> DynamicImage
+where
+    F: Fn(f32, f32) -> f32,
+{
+    if !image.color().has_alpha() {
+        return image;
+    }
+
+    let is_srgb =
+        color_space == ColorSpace::Srgb && !matches!(image, DynamicImage::ImageRgba32F(_));
+
+    let mut scaled = image.to_rgba32f();
+    for pixel in scaled.pixels_mut() {
+        let alpha = pixel.0[3];
+        for value in &mut pixel.0[..3] {
+            *value = if is_srgb {
+                linear_to_srgb(scale(srgb_to_linear(*value), alpha))
+            } else {
+                scale(*value, alpha)
+            };
+        }
+    }
+
+    to_format_of(scaled, &image)
+}
+
+/// Resize `image` to `width` by `height`, keeping its pixel format. Srgb unorm images are filtered in linear space to preserve their brightness
+pub(crate) fn resize(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    filter: MipFilter,
+    color_space: ColorSpace,
+) -> DynamicImage {
+    // Float images always store linear values
+    let is_srgb = color_space == ColorSpace::Srgb
+        && !matches!(
+            image,
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_)
+        );
+
+    let (kernel, support): (fn(f32) -> f32, f32) = match filter {
+        MipFilter::Box => (box_filter, 0.5),
+        MipFilter::Kaiser => (kaiser, KAISER_SUPPORT),
+        MipFilter::Triangle | MipFilter::CatmullRom | MipFilter::Lanczos3 => {
+            return resize_with_image_filter(image, width, height, filter, is_srgb);
+        }
+    };
+
+    let mut resized = resize_streamed(image, width, height, kernel, support, is_srgb);
+    if is_srgb {
+        for pixel in resized.pixels_mut() {
+            for value in &mut pixel.0[..3] {
+                *value = linear_to_srgb(value.max(0.0));
+            }
+        }
+    }
+
+    to_format_of(resized, image)
+}
+
+fn resize_with_image_filter(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    filter: MipFilter,
+    is_srgb: bool,
+) -> DynamicImage {
+    let image_filter = image_filter(filter).unwrap();
+    if !is_srgb {
+        return image.resize_exact(width, height, image_filter);
+    }
+
+    let mut linear = image.to_rgba32f();
+    for pixel in linear.pixels_mut() {
+        for value in &mut pixel.0[..3] {
+            *value = srgb_to_linear(*value);
+        }
+    }
+
+    let mut resized = DynamicImage::ImageRgba32F(linear)
+        .resize_exact(width, height, image_filter)
+        .into_rgba32f();
+    for pixel in resized.pixels_mut() {
+        for value in &mut pixel.0[..3] {
+            *value = linear_to_srgb(value.max(0.0));
+        }
+    }
+
+    to_format_of(resized, image)
+}
@@ -0,0 +1,281 @@
+//! Portable serialization of cooked models
+//!
+//! Models written by [`Model::write_to_bytes`] are loadable on any platform, regardless of the endianness of the machine that cooked them:
+//! - All multi byte values are stored in [`ENDIANNESS`] byte order, floats as their IEEE 754 bits.
+//!   This includes each channel of uncompressed texel data, block compressed texel data is a byte stream stored as is
+//! - Structs are stored field by field in declaration order without padding, using the speedy 0.8 encoding.
+//!   Collections and strings are prefixed by their u32 length, enums by a u32 variant index and `Option` by a u8 tag
+//! - Pod types that are also cast to gpu buffers have a fixed size, asserted at compile time below
+//!
+//! [`Model::write_ugm`] additionally prefixes the model with a [`UgmHeader`], letting readers reject files that aren't ugm models
+//! or were written by an incompatible version of this crate.
+//!
+//! The speedy trait functions (`read_from_buffer`, `write_to_vec`, ...) use the native endianness of the running machine,
+//! which only matches [`ENDIANNESS`] on little endian hosts. Prefer the functions in this module for assets shared between machines
+
+use std::{
+    borrow::Cow,
+    io::{Cursor, Read, Write},
+};
+
+use anyhow::Result;
+use speedy::{Endianness, Readable, Writable};
+
+use crate::{
+    chunked::ChunkedReader,
+    material::PackedMaterial,
+    mesh::{PackedVertex, Vertex},
+    packing::{PackedNormalizedXyz10, PackedRgb9e5},
+    quantize::QuantizedVertex,
+    streaming::StreamableModel,
+    Model,
+};
+
+/// Byte order of all serialized models
+pub const ENDIANNESS: Endianness = Endianness::LittleEndian;
+
+/// Identifies ugm containers, the first bytes of every file written by [`Model::write_ugm`]
+pub const MAGIC: [u8; 4] = *b"UGM\0";
+/// Version of the model encoding, bumped on every change to the serialized types. Containers of other versions are rejected
+pub const FORMAT_VERSION: u16 = 5;
+
+/// Zstd level used by [`Compression::Zstd`], favoring ratio since models are compressed once at bake time
+#[cfg(feature = "zstd")]
+const ZSTD_LEVEL: i32 = 19;
+
+/// Compression of the model following a [`UgmHeader`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compression {
+    #[default]
+    None,
+    /// Best ratio, requires the `zstd` feature
+    Zstd,
+    /// Fastest decompression, requires the `lz4` feature
+    Lz4,
+}
+
+impl Compression {
+    pub(crate) fn compress(&self, data: Vec<u8>) -> Result<Vec<u8>> {
+        match self {
+            Self::None => Ok(data),
+            #[cfg(feature = "zstd")]
+            Self::Zstd => Ok(zstd::encode_all(data.as_slice(), ZSTD_LEVEL)?),
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => Ok(lz4_flex::compress_prepend_size(&data)),
+            #[allow(unreachable_patterns)]
+            _ => anyhow::bail!(
+                "Failed to compress with {:?}, its feature isn't enabled.",
+                self
+            ),
+        }
+    }
+
+    pub(crate) fn decompress<'a>(&self, data: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+        match self {
+            Self::None => Ok(Cow::Borrowed(data)),
+            #[cfg(feature = "zstd")]
+            Self::Zstd => Ok(Cow::Owned(zstd::decode_all(data)?)),
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => Ok(Cow::Owned(lz4_flex::decompress_size_prepended(data)?)),
+            #[allow(unreachable_patterns)]
+            _ => anyhow::bail!(
+                "Failed to read {:?} compressed ugm container, its feature isn't enabled.",
+                self
+            ),
+        }
+    }
+}
+
+/// Header flag of containers written by [`Model::write_chunked`], see [`crate::chunked`]
+pub const FLAG_CHUNKED: u16 = 1;
+/// Header flag of containers written by [`Model::write_streamable_to_vec`], see [`crate::streaming`]
+pub const FLAG_STREAMABLE: u16 = 2;
+/// Header flag of texture pools written by [`crate::texture_pool::TexturePool::write_to_bytes`]
+pub const FLAG_TEXTURE_POOL: u16 = 4;
+/// Header flag of models written by [`crate::texture_pool::PooledModel::write_to_bytes`], their textures are in a separate pool
+pub const FLAG_POOLED_MODEL: u16 = 8;
+/// All flags known to this version, containers with other flags are rejected
+const KNOWN_FLAGS: u16 = FLAG_CHUNKED | FLAG_STREAMABLE | FLAG_TEXTURE_POOL | FLAG_POOLED_MODEL;
+
+/// Fixed size prefix of a ugm container, the header itself is always little endian
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UgmHeader {
+    pub version: u16,
+    /// Optional features of the encoding, such as [`FLAG_CHUNKED`] or [`FLAG_STREAMABLE`]
+    pub flags: u16,
+    /// Byte order of the model following the header
+    pub endianness: Endianness,
+    pub compression: Compression,
+}
+
+impl UgmHeader {
+    /// Size in bytes of the encoded header
+    pub const SIZE: usize = 16;
+
+    pub fn new(endianness: Endianness) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            flags: 0,
+            endianness,
+            compression: Compression::None,
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..4].copy_from_slice(&MAGIC);
+        bytes[4..6].copy_from_slice(&self.version.to_le_bytes());
+        bytes[6..8].copy_from_slice(&self.flags.to_le_bytes());
+        bytes[8] = match self.endianness {
+            Endianness::LittleEndian => 0,
+            Endianness::BigEndian => 1,
+        };
+        bytes[9] = match self.compression {
+            Compression::None => 0,
+            Compression::Zstd => 1,
+            Compression::Lz4 => 2,
+        };
+        bytes
+    }
+
+    /// Decode and validate a header, failing if `bytes` isn't a ugm container of the current [`FORMAT_VERSION`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        anyhow::ensure!(
+            bytes.len() >= Self::SIZE && bytes[0..4] == MAGIC,
+            "Failed to read ugm header, the data is not a ugm container."
+        );
+
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        anyhow::ensure!(
+            version == FORMAT_VERSION,
+            "Failed to read ugm container of format version {}, only version {} is supported. Re-export the model with a matching version of ugm.",
+            version,
+            FORMAT_VERSION
+        );
+
+        let flags = u16::from_le_bytes([bytes[6], bytes[7]]);
+        anyhow::ensure!(
+            flags & !KNOWN_FLAGS == 0,
+            "Failed to read ugm container with unknown flags {:#06x}.",
+            flags
+        );
+
+        let endianness = match bytes[8] {
+            0 => Endianness::LittleEndian,
+            1 => Endianness::BigEndian,
+            value => anyhow::bail!(
+                "Failed to read ugm container with invalid endianness {}.",
+                value
+            ),
+        };
+
+        let compression = match bytes[9] {
+            0 => Compression::None,
+            1 => Compression::Zstd,
+            2 => Compression::Lz4,
+            value => anyhow::bail!(
+                "Failed to read ugm container with unknown compression {}.",
+                value
+            ),
+        };
+
+        Ok(Self {
+            version,
+            flags,
+            endianness,
+            compression,
+        })
+    }
+}
+
+const _: () = {
+    assert!(size_of::<PackedVertex>() == 32);
+    assert!(size_of::<Vertex>() == 48);
+    assert!(size_of::<PackedRgb9e5>() == 4);
+    assert!(size_of::<PackedNormalizedXyz10>() == 4);
+    assert!(size_of::<PackedMaterial>() == 136);
+    assert!(size_of::<QuantizedVertex>() == 24);
+    assert!(align_of::<PackedVertex>() == 4);
+    assert!(align_of::<Vertex>() == 4);
+    assert!(align_of::<PackedMaterial>() == 4);
+    assert!(align_of::<QuantizedVertex>() == 4);
+};
+
+impl Model {
+    /// Serialize in the portable [`ENDIANNESS`] byte order
+    pub fn write_to_bytes(&self) -> Result<Vec<u8>> {
+        self.write_with_endianness(ENDIANNESS)
+    }
+
+    /// Deserialize a model written by [`Model::write_to_bytes`]
+    pub fn read_from_bytes(data: &[u8]) -> Result<Self> {
+        Self::read_with_endianness(data, ENDIANNESS)
+    }
+
+    /// Serialize as uncompressed ugm container, a [`UgmHeader`] followed by the model in [`ENDIANNESS`] byte order
+    pub fn write_ugm(&self, writer: &mut impl Write) -> Result<()> {
+        self.write_ugm_compressed(writer, Compression::None)
+    }
+
+    /// Serialize as ugm container with the model compressed by `compression`, which is recorded in the header
+    pub fn write_ugm_compressed(
+        &self,
+        writer: &mut impl Write,
+        compression: Compression,
+    ) -> Result<()> {
+        let header = UgmHeader {
+            compression,
+            ..UgmHeader::new(ENDIANNESS)
+        };
+        let data = compression.compress(self.write_to_bytes()?)?;
+        writer.write_all(&header.to_bytes())?;
+        writer.write_all(&data)?;
+        Ok(())
+    }
+
+    /// Deserialize a ugm container written by [`Model::write_ugm`], [`Model::write_ugm_compressed`], [`Model::write_chunked`]
+    /// or [`Model::write_streamable_to_vec`], validating its header. Streamable containers are read with all mips resident
+    /// Uncompressed models are deserialized straight from `reader`, the other containers are read into memory first
+    pub fn read_ugm(reader: &mut impl Read) -> Result<Self> {
+        let mut header_bytes = [0; UgmHeader::SIZE];
+        reader.read_exact(&mut header_bytes)?;
+        let header = UgmHeader::from_bytes(&header_bytes)?;
+        anyhow::ensure!(
+            header.flags & (FLAG_TEXTURE_POOL | FLAG_POOLED_MODEL) == 0,
+            "Failed to read ugm container, it holds a texture pool or pooled model, see `ugm::texture_pool`."
+        );
+
+        if header.flags & (FLAG_CHUNKED | FLAG_STREAMABLE) != 0 {
+            // Chunks and streamed mips are located by their offset in the container
+            let mut data = header_bytes.to_vec();
+            reader.read_to_end(&mut data)?;
+            let mut reader = Cursor::new(data);
+            return if header.flags & FLAG_CHUNKED != 0 {
+                ChunkedReader::new(reader)?.read_model()
+            } else {
+                StreamableModel::read_resident(&mut reader)?.into_resident(&mut reader)
+            };
+        }
+
+        if header.compression == Compression::None {
+            return Ok(Self::read_from_stream_buffered_with_ctx(
+                header.endianness,
+                reader,
+            )?);
+        }
+        let mut data = vec![];
+        reader.read_to_end(&mut data)?;
+        let data = header.compression.decompress(&data)?;
+        Self::read_with_endianness(&data, header.endianness)
+    }
+
+    /// Serialize with an explicit speedy context, for pipelines that pin a byte order other than [`ENDIANNESS`]
+    pub fn write_with_endianness(&self, endianness: Endianness) -> Result<Vec<u8>> {
+        Ok(self.write_to_vec_with_ctx(endianness)?)
+    }
+
+    /// Deserialize a model written with the same `endianness` by [`Model::write_with_endianness`]
+    pub fn read_with_endianness(data: &[u8], endianness: Endianness) -> Result<Self> {
+        Ok(Self::read_from_buffer_with_ctx(endianness, data)?)
+    }
+}
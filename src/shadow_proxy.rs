@@ -0,0 +1,369 @@
+//! Decimated position only meshes for rendering into shadow maps
+
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+};
+
+use glam::{DVec3, Vec3};
+use speedy::{Readable, Writable};
+
+use crate::{mesh::Mesh, Model};
+
+/// Weight of the planes keeping open edges in place, relative to the planes of the surface triangles
+const BOUNDARY_WEIGHT: f64 = 100.0;
+
+/// Simplified copy of a mesh with welded positions, only suitable for depth rendering
+#[derive(Debug, Clone, Default, Readable, Writable)]
+pub struct ShadowProxy {
+    pub positions: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ShadowProxyDesc {
+    /// Fraction of the source triangles to keep
+    pub triangle_ratio: f32,
+    /// Upper bound on the triangles kept, applied after `triangle_ratio`
+    pub max_triangles: Option<u32>,
+    /// Largest distance the surface may move, relative to the diagonal of the mesh bounds. Simplification stops early when reached
+    pub max_error: f32,
+}
+
+impl Default for ShadowProxyDesc {
+    fn default() -> Self {
+        Self {
+            triangle_ratio: 0.25,
+            max_triangles: None,
+            max_error: 0.01,
+        }
+    }
+}
+
+/// Symmetric 4x4 matrix summing squared distances to planes, upper triangle stored row by row.
+/// As every plane adds its full squared distance the error never underestimates the largest distance moved
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn from_plane(normal: DVec3, point: DVec3, weight: f64) -> Self {
+        let [a, b, c] = normal.to_array();
+        let d = -normal.dot(point);
+        Self([
+            a * a,
+            a * b,
+            a * c,
+            a * d,
+            b * b,
+            b * c,
+            b * d,
+            c * c,
+            c * d,
+            d * d,
+        ])
+        .scaled(weight)
+    }
+
+    fn scaled(mut self, weight: f64) -> Self {
+        for value in &mut self.0 {
+            *value *= weight;
+        }
+        self
+    }
+
+    fn add(&mut self, other: &Self) {
+        for (value, other) in self.0.iter_mut().zip(other.0) {
+            *value += other;
+        }
+    }
+
+    fn error(&self, p: DVec3) -> f64 {
+        let [a2, ab, ac, ad, b2, bc, bd, c2, cd, d2] = self.0;
+        let (x, y, z) = (p.x, p.y, p.z);
+        (a2 * x * x + 2.0 * ab * x * y + 2.0 * ac * x * z + 2.0 * ad * x)
+            + (b2 * y * y + 2.0 * bc * y * z + 2.0 * bd * y)
+            + (c2 * z * z + 2.0 * cd * z)
+            + d2
+    }
+}
+
+/// Candidate edge collapse, ordered cheapest first
+struct Collapse {
+    cost: f64,
+    position: DVec3,
+    vertices: [u32; 2],
+    versions: [u32; 2],
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+struct Simplifier {
+    positions: Vec<DVec3>,
+    quadrics: Vec<Quadric>,
+    versions: Vec<u32>,
+    triangles: Vec<[u32; 3]>,
+    is_removed: Vec<bool>,
+    vertex_triangles: Vec<Vec<u32>>,
+    heap: BinaryHeap<Collapse>,
+}
+
+impl Simplifier {
+    fn new(positions: Vec<DVec3>, triangles: Vec<[u32; 3]>) -> Self {
+        let mut quadrics = vec![Quadric::default(); positions.len()];
+        let mut vertex_triangles = vec![vec![]; positions.len()];
+        let mut edge_triangles: HashMap<(u32, u32), u32> = HashMap::new();
+
+        for (triangle_idx, triangle) in triangles.iter().enumerate() {
+            let [p0, p1, p2] = triangle.map(|idx| positions[idx as usize]);
+            let normal = (p1 - p0).cross(p2 - p0).normalize_or_zero();
+            let quadric = Quadric::from_plane(normal, p0, 1.0);
+
+            for (corner, vertex_idx) in triangle.iter().enumerate() {
+                quadrics[*vertex_idx as usize].add(&quadric);
+                vertex_triangles[*vertex_idx as usize].push(triangle_idx as u32);
+
+                let next = triangle[(corner + 1) % 3];
+                let edge = (*vertex_idx.min(&next), *vertex_idx.max(&next));
+                *edge_triangles.entry(edge).or_default() += 1;
+            }
+        }
+
+        // Edges of a single triangle are open, keep them from collapsing inwards to preserve the outline
+        for triangle in &triangles {
+            let [p0, p1, p2] = triangle.map(|idx| positions[idx as usize]);
+            let normal = (p1 - p0).cross(p2 - p0).normalize_or_zero();
+
+            for corner in 0..3 {
+                let (a, b) = (triangle[corner], triangle[(corner + 1) % 3]);
+                if edge_triangles[&(a.min(b), a.max(b))] != 1 {
+                    continue;
+                }
+
+                let (pa, pb) = (positions[a as usize], positions[b as usize]);
+                let edge = pb - pa;
+                let quadric = Quadric::from_plane(
+                    edge.cross(normal).normalize_or_zero(),
+                    pa,
+                    BOUNDARY_WEIGHT,
+                );
+                quadrics[a as usize].add(&quadric);
+                quadrics[b as usize].add(&quadric);
+            }
+        }
+
+        let mut simplifier = Self {
+            versions: vec![0; positions.len()],
+            is_removed: vec![false; triangles.len()],
+            positions,
+            quadrics,
+            triangles,
+            vertex_triangles,
+            heap: BinaryHeap::new(),
+        };
+        for triangle_idx in 0..simplifier.triangles.len() {
+            let triangle = simplifier.triangles[triangle_idx];
+            for corner in 0..3 {
+                let (a, b) = (triangle[corner], triangle[(corner + 1) % 3]);
+                if a < b {
+                    simplifier.push_collapse(a, b);
+                }
+            }
+        }
+
+        simplifier
+    }
+
+    fn push_collapse(&mut self, a: u32, b: u32) {
+        let mut quadric = self.quadrics[a as usize];
+        quadric.add(&self.quadrics[b as usize]);
+
+        let (pa, pb) = (self.positions[a as usize], self.positions[b as usize]);
+        let (cost, position) = [pa, pb, (pa + pb) * 0.5]
+            .into_iter()
+            .map(|position| (quadric.error(position).max(0.0), position))
+            .min_by(|x, y| x.0.total_cmp(&y.0))
+            .unwrap();
+
+        self.heap.push(Collapse {
+            cost,
+            position,
+            vertices: [a, b],
+            versions: [self.versions[a as usize], self.versions[b as usize]],
+        });
+    }
+
+    /// Returns true if moving `vertex_idx` to `position` flips a remaining triangle not shared with `other_idx`
+    fn flips(&self, vertex_idx: u32, other_idx: u32, position: DVec3) -> bool {
+        self.vertex_triangles[vertex_idx as usize]
+            .iter()
+            .filter(|triangle_idx| !self.is_removed[**triangle_idx as usize])
+            .map(|triangle_idx| self.triangles[*triangle_idx as usize])
+            .filter(|triangle| !triangle.contains(&other_idx))
+            .any(|triangle| {
+                let corners = triangle.map(|idx| self.positions[idx as usize]);
+                let moved = triangle.map(|idx| {
+                    if idx == vertex_idx {
+                        position
+                    } else {
+                        self.positions[idx as usize]
+                    }
+                });
+                let normal = |[p0, p1, p2]: [DVec3; 3]| (p1 - p0).cross(p2 - p0);
+                normal(corners).dot(normal(moved)) <= 0.0
+            })
+    }
+
+    fn collapse(&mut self, keep: u32, remove: u32, position: DVec3, num_triangles: &mut usize) {
+        for triangle_idx in std::mem::take(&mut self.vertex_triangles[remove as usize]) {
+            if self.is_removed[triangle_idx as usize] {
+                continue;
+            }
+
+            let triangle = &mut self.triangles[triangle_idx as usize];
+            if triangle.contains(&keep) {
+                self.is_removed[triangle_idx as usize] = true;
+                *num_triangles -= 1;
+            } else {
+                for vertex_idx in triangle.iter_mut() {
+                    if *vertex_idx == remove {
+                        *vertex_idx = keep;
+                    }
+                }
+                self.vertex_triangles[keep as usize].push(triangle_idx);
+            }
+        }
+
+        let removed_quadric = self.quadrics[remove as usize];
+        self.quadrics[keep as usize].add(&removed_quadric);
+        self.positions[keep as usize] = position;
+        self.versions[keep as usize] += 1;
+        self.versions[remove as usize] += 1;
+
+        let is_removed = &self.is_removed;
+        self.vertex_triangles[keep as usize]
+            .retain(|triangle_idx| !is_removed[*triangle_idx as usize]);
+
+        let mut neighbours: Vec<u32> = self.vertex_triangles[keep as usize]
+            .iter()
+            .flat_map(|triangle_idx| self.triangles[*triangle_idx as usize])
+            .filter(|vertex_idx| *vertex_idx != keep)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        for neighbour in neighbours {
+            self.push_collapse(keep.min(neighbour), keep.max(neighbour));
+        }
+    }
+
+    /// Collapse edges until at most `target_triangles` remain or the next collapse exceeds `max_error`
+    fn simplify(&mut self, target_triangles: usize, max_error: f64) {
+        let mut num_triangles = self.triangles.len();
+        let max_cost = max_error * max_error;
+
+        while num_triangles > target_triangles {
+            let Some(collapse) = self.heap.pop() else {
+                break;
+            };
+            let [a, b] = collapse.vertices;
+            if collapse.versions != [self.versions[a as usize], self.versions[b as usize]] {
+                continue;
+            }
+            if collapse.cost > max_cost {
+                break;
+            }
+            if self.flips(a, b, collapse.position) || self.flips(b, a, collapse.position) {
+                continue;
+            }
+
+            self.collapse(a, b, collapse.position, &mut num_triangles);
+        }
+    }
+
+    fn finish(self) -> ShadowProxy {
+        let mut remap = vec![None; self.positions.len()];
+        let mut proxy = ShadowProxy::default();
+
+        for (triangle, is_removed) in self.triangles.iter().zip(&self.is_removed) {
+            if *is_removed {
+                continue;
+            }
+            for vertex_idx in triangle {
+                let index = *remap[*vertex_idx as usize].get_or_insert_with(|| {
+                    proxy
+                        .positions
+                        .push(self.positions[*vertex_idx as usize].as_vec3().to_array());
+                    proxy.positions.len() as u32 - 1
+                });
+                proxy.indices.push(index);
+            }
+        }
+
+        proxy
+    }
+}
+
+impl Mesh {
+    /// Simplify the mesh to a position only proxy, welding vertices split by other attributes first
+    pub fn generate_shadow_proxy(&self, desc: &ShadowProxyDesc) -> ShadowProxy {
+        let mut positions = vec![];
+        let mut welded: HashMap<[u32; 3], u32> = HashMap::new();
+        let remap: Vec<u32> = self
+            .packed_vertices
+            .iter()
+            .map(|vertex| {
+                *welded
+                    .entry(vertex.position.map(f32::to_bits))
+                    .or_insert_with(|| {
+                        positions.push(Vec3::from_array(vertex.position).as_dvec3());
+                        positions.len() as u32 - 1
+                    })
+            })
+            .collect();
+
+        let triangles: Vec<[u32; 3]> = self
+            .indices
+            .triangles()
+            .map(|triangle| triangle.map(|vertex_idx| remap[vertex_idx as usize]))
+            .filter(|[a, b, c]| a != b && b != c && a != c)
+            .collect();
+
+        let mut target_triangles = (triangles.len() as f32 * desc.triangle_ratio) as usize;
+        if let Some(max_triangles) = desc.max_triangles {
+            target_triangles = target_triangles.min(max_triangles as usize);
+        }
+        let diagonal =
+            (Vec3::from_array(self.bounds_max) - Vec3::from_array(self.bounds_min)).length() as f64;
+
+        let mut simplifier = Simplifier::new(positions, triangles);
+        simplifier.simplify(target_triangles, desc.max_error as f64 * diagonal);
+        simplifier.finish()
+    }
+}
+
+impl Model {
+    /// Generate a shadow proxy for each opaque mesh, alpha tested and blended meshes keep casting shadows with their full geometry
+    pub fn generate_shadow_proxies(&mut self, desc: &ShadowProxyDesc) {
+        for mesh in &mut self.meshes {
+            mesh.shadow_proxy =
+                (mesh.opaque && !mesh.is_empty()).then(|| mesh.generate_shadow_proxy(desc));
+        }
+    }
+}
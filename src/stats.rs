@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+
+use speedy::{LittleEndian, Writable};
+
+use crate::{
+    mesh::{Mesh, QuantizationError},
+    texture::{Texture, TextureFormat},
+    Model,
+};
+
+#[derive(Debug, Clone)]
+pub struct MeshStats {
+    pub name: String,
+    pub num_vertices: usize,
+    pub num_triangles: usize,
+    pub quantization_error: QuantizationError,
+}
+
+impl MeshStats {
+    pub fn new(mesh: &Mesh) -> Self {
+        Self {
+            name: mesh.name.clone(),
+            num_vertices: mesh.num_vertices(),
+            num_triangles: mesh.indices.len() / 3,
+            quantization_error: mesh.quantization_error,
+        }
+    }
+}
+
+/// Summary of a model's contents, meant for inspecting cooked assets
+#[derive(Debug, Clone, Default)]
+pub struct ModelStats {
+    pub num_nodes: usize,
+    /// Number of nodes on the longest path from a root node to a leaf, 0 without nodes
+    pub max_node_depth: usize,
+    pub num_materials: usize,
+    pub num_textures: usize,
+    /// Size of all texture data including mips
+    pub texture_bytes: usize,
+    /// Size of all texture data including mips per texture format
+    pub texture_bytes_per_format: HashMap<TextureFormat, usize>,
+    pub meshes: Vec<MeshStats>,
+    /// Largest quantization error over all meshes
+    pub max_quantization_error: QuantizationError,
+}
+
+impl ModelStats {
+    pub fn new(model: &Model) -> Self {
+        let mut stats = Self {
+            num_nodes: model.nodes.len(),
+            max_node_depth: max_node_depth(model),
+            num_materials: model.materials.len(),
+            num_textures: 0,
+            texture_bytes: 0,
+            texture_bytes_per_format: HashMap::new(),
+            meshes: model.meshes.iter().map(MeshStats::new).collect(),
+            max_quantization_error: QuantizationError::default(),
+        };
+        stats.update_textures(&model.textures);
+        stats.update_max_quantization_error();
+        stats
+    }
+
+    pub(crate) fn update_textures(&mut self, textures: &[Texture]) {
+        self.num_textures = textures.len();
+        self.texture_bytes = textures.iter().map(|texture| texture.data().len()).sum();
+        self.texture_bytes_per_format.clear();
+        for texture in textures {
+            *self
+                .texture_bytes_per_format
+                .entry(texture.format())
+                .or_default() += texture.data().len();
+        }
+    }
+
+    /// Vertices of all meshes, instances aren't counted separately
+    pub fn num_vertices(&self) -> usize {
+        self.meshes.iter().map(|mesh| mesh.num_vertices).sum()
+    }
+
+    /// Triangles of all meshes, instances aren't counted separately
+    pub fn num_triangles(&self) -> usize {
+        self.meshes.iter().map(|mesh| mesh.num_triangles).sum()
+    }
+
+    pub(crate) fn update_max_quantization_error(&mut self) {
+        self.max_quantization_error = QuantizationError::default();
+        for mesh in &self.meshes {
+            self.max_quantization_error.max_normal_error = self
+                .max_quantization_error
+                .max_normal_error
+                .max(mesh.quantization_error.max_normal_error);
+            self.max_quantization_error.max_tangent_error = self
+                .max_quantization_error
+                .max_tangent_error
+                .max(mesh.quantization_error.max_tangent_error);
+            self.max_quantization_error.max_position_error = self
+                .max_quantization_error
+                .max_position_error
+                .max(mesh.quantization_error.max_position_error);
+        }
+    }
+}
+
+fn max_node_depth(model: &Model) -> usize {
+    let mut max_depth = 0;
+    let mut stack: Vec<(u32, usize)> = model
+        .root_node_indices
+        .iter()
+        .map(|node_idx| (*node_idx, 1))
+        .collect();
+    while let Some((node_idx, depth)) = stack.pop() {
+        max_depth = max_depth.max(depth);
+        stack.extend(
+            model.nodes[node_idx as usize]
+                .child_node_indices
+                .iter()
+                .map(|child_idx| (*child_idx, depth + 1)),
+        );
+    }
+
+    max_depth
+}
+
+/// Kind of data a serialized section holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SectionKind {
+    /// Node hierarchy, bounds and the length prefixes of the mesh, material, texture and property track tables
+    Table,
+    Mesh,
+    Material,
+    Texture,
+    PropertyTrack,
+}
+
+/// Bytes a part of the model contributes to its serialized form
+#[derive(Debug, Clone)]
+pub struct SectionSize {
+    pub kind: SectionKind,
+    pub name: String,
+    pub bytes: usize,
+}
+
+pub(crate) fn serialized_size<T: Writable<LittleEndian>>(value: &T) -> usize {
+    value
+        .bytes_needed()
+        .expect("Failed to measure serialized size.")
+}
+
+impl Model {
+    /// Size of each table, mesh, material, texture and property track in serialization order, adding up to the length of the serialized model
+    pub fn serialized_layout(&self) -> Vec<SectionSize> {
+        let table = |name: &str, bytes: usize| SectionSize {
+            kind: SectionKind::Table,
+            name: name.to_owned(),
+            bytes,
+        };
+        // Length prefix of a serialized vec
+        let len_size = serialized_size(&0u32);
+
+        let mut sections = vec![
+            table(
+                "root_node_indices",
+                serialized_size(&self.root_node_indices),
+            ),
+            table("nodes", serialized_size(&self.nodes)),
+            table(
+                "bounds",
+                serialized_size(&self.bounds_min) + serialized_size(&self.bounds_max),
+            ),
+            table("meshes", len_size),
+        ];
+        sections.extend(self.meshes.iter().map(|mesh| SectionSize {
+            kind: SectionKind::Mesh,
+            name: mesh.name.clone(),
+            bytes: serialized_size(mesh),
+        }));
+        sections.push(table("materials", len_size));
+        sections.extend(self.materials.iter().map(|material| SectionSize {
+            kind: SectionKind::Material,
+            name: material.name.clone(),
+            bytes: serialized_size(material),
+        }));
+        sections.push(table("textures", len_size));
+        sections.extend(self.textures.iter().map(|texture| SectionSize {
+            kind: SectionKind::Texture,
+            name: texture.name().to_owned(),
+            bytes: serialized_size(texture),
+        }));
+        sections.push(table("property_tracks", len_size));
+        sections.extend(self.property_tracks.iter().map(|track| SectionSize {
+            kind: SectionKind::PropertyTrack,
+            name: track.path.pointer(),
+            bytes: serialized_size(track),
+        }));
+
+        sections
+    }
+}
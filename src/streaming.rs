@@ -0,0 +1,187 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use anyhow::Result;
+use speedy::{Readable, Writable};
+
+use crate::{
+    serialization::{Compression, UgmHeader, ENDIANNESS, FLAG_STREAMABLE},
+    stats::serialized_size,
+    Model,
+};
+
+/// Location of a mip level that isn't resident, `offset` is relative to the start of the streamable container
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Readable, Writable)]
+pub struct StreamedMip {
+    pub width: u32,
+    pub height: u32,
+    pub offset: u64,
+    pub size: u64,
+}
+
+/// Model with only its smallest mips resident, larger mips are streamed in on demand from the same file
+///
+/// A streamable container starts with a [`UgmHeader`] flagged [`FLAG_STREAMABLE`], followed by the little endian u64 size of the serialized `StreamableModel`,
+/// the `StreamableModel` itself and finally the data of all streamed mips
+#[derive(Debug, Clone, Readable, Writable)]
+pub struct StreamableModel {
+    pub model: Model,
+    /// Streamed mips per texture, largest first
+    pub streamed_mips: Vec<Vec<StreamedMip>>,
+    /// Stream position of the start of the container, set by [`StreamableModel::read_resident`]
+    #[speedy(skip)]
+    pub base_offset: u64,
+}
+
+impl StreamableModel {
+    /// Read the model with its resident mips, the container starts at the current position of `reader`
+    pub fn read_resident<R: Read + Seek>(reader: &mut R) -> Result<Self> {
+        let base_offset = reader.stream_position()?;
+        let mut header = [0; UgmHeader::SIZE];
+        reader.read_exact(&mut header)?;
+        let header = UgmHeader::from_bytes(&header)?;
+        anyhow::ensure!(
+            header.flags & FLAG_STREAMABLE != 0,
+            "Failed to read streamable ugm container, the container isn't streamable."
+        );
+        anyhow::ensure!(
+            header.compression == Compression::None,
+            "Failed to read streamable ugm container, streamable containers can't be compressed."
+        );
+
+        let mut size = [0; 8];
+        reader.read_exact(&mut size)?;
+        let size = u64::from_le_bytes(size);
+        let mut data = vec![];
+        reader.take(size).read_to_end(&mut data)?;
+        anyhow::ensure!(
+            data.len() as u64 == size,
+            "Failed to read streamable ugm container, it is truncated."
+        );
+
+        let mut streamable = Self::read_from_buffer_with_ctx(header.endianness, &data)?;
+        anyhow::ensure!(
+            streamable.streamed_mips.len() == streamable.model.textures.len(),
+            "Failed to read streamable ugm container, it lists streamed mips of {} textures but holds {}.",
+            streamable.streamed_mips.len(),
+            streamable.model.textures.len()
+        );
+        streamable.base_offset = base_offset;
+        Ok(streamable)
+    }
+
+    pub fn is_fully_resident(&self) -> bool {
+        self.streamed_mips.iter().all(Vec::is_empty)
+    }
+
+    /// Stream in up to `count` of the next larger mips of texture `texture_idx`, returns the number of mips loaded
+    pub fn stream_mips<R: Read + Seek>(
+        &mut self,
+        texture_idx: usize,
+        count: u32,
+        reader: &mut R,
+    ) -> Result<u32> {
+        anyhow::ensure!(
+            texture_idx < self.model.textures.len(),
+            "Failed to stream mips of texture {}, the model has {} textures.",
+            texture_idx,
+            self.model.textures.len()
+        );
+        let streamed_mips = &mut self.streamed_mips[texture_idx];
+        let texture = &mut self.model.textures[texture_idx];
+
+        let mut loaded = 0;
+        while loaded < count {
+            let Some(mip) = streamed_mips.pop() else {
+                break;
+            };
+
+            let mut data = vec![];
+            reader.seek(SeekFrom::Start(self.base_offset + mip.offset))?;
+            reader.take(mip.size).read_to_end(&mut data)?;
+            anyhow::ensure!(
+                data.len() as u64 == mip.size,
+                "Failed to stream mip of texture {}, the container is truncated.",
+                texture_idx
+            );
+
+            texture.prepend_mip(&data, mip.width, mip.height);
+            loaded += 1;
+        }
+
+        Ok(loaded)
+    }
+
+    /// Stream in all remaining mips of every texture, returns the fully resident model
+    pub fn into_resident<R: Read + Seek>(mut self, reader: &mut R) -> Result<Model> {
+        for texture_idx in 0..self.model.textures.len() {
+            self.stream_mips(texture_idx, u32::MAX, reader)?;
+        }
+        Ok(self.model)
+    }
+}
+
+impl Model {
+    /// Serialize as a [`StreamableModel`], keeping the smallest `resident_mips` mips of each texture resident
+    pub fn write_streamable_to_vec(&self, resident_mips: u32) -> Result<Vec<u8>> {
+        let mut textures = Vec::with_capacity(self.textures.len());
+        let mut streamed_data = Vec::with_capacity(self.textures.len());
+        for texture in &self.textures {
+            let streamed_count = texture.mip_count().saturating_sub(resident_mips.max(1));
+            let (resident, streamed) = texture.split_largest_mips(streamed_count);
+            textures.push(resident);
+            streamed_data.push(streamed);
+        }
+
+        let mut streamable = StreamableModel {
+            model: Model {
+                root_node_indices: self.root_node_indices.clone(),
+                nodes: self.nodes.clone(),
+                bounds_min: self.bounds_min,
+                bounds_max: self.bounds_max,
+                meshes: self.meshes.clone(),
+                materials: self.materials.clone(),
+                textures,
+                property_tracks: self.property_tracks.clone(),
+            },
+            streamed_mips: self
+                .textures
+                .iter()
+                .zip(&streamed_data)
+                .map(|(texture, mips)| {
+                    mips.iter()
+                        .enumerate()
+                        .map(|(mip, data)| StreamedMip {
+                            width: (texture.width() >> mip).max(1),
+                            height: (texture.height() >> mip).max(1),
+                            offset: 0,
+                            size: data.len() as u64,
+                        })
+                        .collect()
+                })
+                .collect(),
+            base_offset: 0,
+        };
+
+        // Offsets don't change the serialized size, so they're assigned after measuring it
+        let mut offset = (UgmHeader::SIZE + 8 + serialized_size(&streamable)) as u64;
+        for streamed_mip in streamable.streamed_mips.iter_mut().flatten() {
+            streamed_mip.offset = offset;
+            offset += streamed_mip.size;
+        }
+
+        let header = UgmHeader {
+            flags: FLAG_STREAMABLE,
+            ..UgmHeader::new(ENDIANNESS)
+        };
+        let head = streamable.write_to_vec_with_ctx(ENDIANNESS)?;
+        let mut data = Vec::with_capacity(offset as usize);
+        data.extend_from_slice(&header.to_bytes());
+        data.extend_from_slice(&(head.len() as u64).to_le_bytes());
+        data.extend_from_slice(&head);
+        for mip in streamed_data.iter().flatten() {
+            data.extend_from_slice(mip);
+        }
+
+        Ok(data)
+    }
+}
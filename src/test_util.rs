@@ -0,0 +1,307 @@
+//! Builder for minimal in-memory glb files, covering parser edge cases without committing binary fixtures
+
+use std::{collections::BTreeMap, io::Cursor};
+
+use anyhow::Result;
+use glam::{Mat4, Vec3};
+use gltf::json::{self, validation::Checked::Valid};
+use image::DynamicImage;
+
+use crate::{animation::PropertyTrack, exporter::gltf::GlbWriter, texture::SamplerDesc};
+
+/// Metallic roughness material, texture indices refer to images added by [`GlbBuilder::add_image`]
+#[derive(Debug, Clone)]
+pub struct SyntheticMaterial {
+    pub color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emission: [f32; 3],
+    pub color_texture: Option<u32>,
+    pub metallic_roughness_texture: Option<u32>,
+    pub normal_texture: Option<u32>,
+    pub emission_texture: Option<u32>,
+    pub occlusion_texture: Option<u32>,
+    /// Alpha mask cutoff, opaque when `None`
+    pub alpha_cutoff: Option<f32>,
+    /// Alpha blended, `alpha_cutoff` is ignored when set
+    pub blend: bool,
+}
+
+impl Default for SyntheticMaterial {
+    fn default() -> Self {
+        Self {
+            color: [1.0; 4],
+            metallic: 0.0,
+            roughness: 1.0,
+            emission: [0.0; 3],
+            color_texture: None,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            emission_texture: None,
+            occlusion_texture: None,
+            alpha_cutoff: None,
+            blend: false,
+        }
+    }
+}
+
+/// Triangle list mesh, empty attributes are omitted from the glb
+#[derive(Debug, Clone, Default)]
+pub struct SyntheticMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub tangents: Vec<[f32; 4]>,
+    pub tex_coords: Vec<[f32; 2]>,
+    /// Non-indexed when `None`
+    pub indices: Option<Vec<u32>>,
+    /// Material added by [`GlbBuilder::add_material`], the glTF default material when `None`
+    pub material: Option<u32>,
+}
+
+impl SyntheticMesh {
+    /// Single counter clockwise triangle in the xy plane
+    pub fn triangle() -> Self {
+        Self {
+            positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            tex_coords: vec![[0.0, 1.0], [1.0, 1.0], [0.0, 0.0]],
+            indices: Some(vec![0, 1, 2]),
+            ..Default::default()
+        }
+    }
+}
+
+/// Builds a glb with a single scene, all nodes added are root nodes of that scene
+pub struct GlbBuilder {
+    writer: GlbWriter,
+    textures: Vec<json::Index<json::Texture>>,
+    materials: Vec<json::Index<json::Material>>,
+    meshes: Vec<json::Index<json::Mesh>>,
+    nodes: Vec<json::Index<json::Node>>,
+}
+
+impl Default for GlbBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GlbBuilder {
+    pub fn new() -> Self {
+        Self {
+            writer: GlbWriter::new(),
+            textures: vec![],
+            materials: vec![],
+            meshes: vec![],
+            nodes: vec![],
+        }
+    }
+
+    /// Embed `image` as png texture with the default sampler, returns its texture index
+    pub fn add_image(&mut self, image: &DynamicImage) -> Result<u32> {
+        let mut png = Cursor::new(vec![]);
+        image.write_to(&mut png, image::ImageFormat::Png)?;
+
+        Ok(self.add_encoded_image(png.get_ref(), "image/png"))
+    }
+
+    /// Embed already encoded image bytes with the default sampler, returns its texture index
+    pub fn add_encoded_image(&mut self, data: &[u8], mime_type: &str) -> u32 {
+        let name = format!("Image{}", self.textures.len());
+        self.textures.push(self.writer.push_texture(
+            data,
+            mime_type,
+            &name,
+            &SamplerDesc::default(),
+        ));
+        self.textures.len() as u32 - 1
+    }
+
+    /// Returns the material index
+    pub fn add_material(&mut self, material: SyntheticMaterial) -> u32 {
+        let texture_info = |texture: Option<u32>| {
+            texture.map(|texture| json::texture::Info {
+                index: self.textures[texture as usize],
+                tex_coord: 0,
+                extensions: None,
+                extras: Default::default(),
+            })
+        };
+
+        let pbr_metallic_roughness = json::material::PbrMetallicRoughness {
+            base_color_factor: json::material::PbrBaseColorFactor(material.color),
+            base_color_texture: texture_info(material.color_texture),
+            metallic_factor: json::material::StrengthFactor(material.metallic),
+            roughness_factor: json::material::StrengthFactor(material.roughness),
+            metallic_roughness_texture: texture_info(material.metallic_roughness_texture),
+            ..Default::default()
+        };
+        let normal_texture =
+            texture_info(material.normal_texture).map(|info| json::material::NormalTexture {
+                index: info.index,
+                scale: 1.0,
+                tex_coord: 0,
+                extensions: None,
+                extras: Default::default(),
+            });
+        let occlusion_texture =
+            texture_info(material.occlusion_texture).map(|info| json::material::OcclusionTexture {
+                index: info.index,
+                strength: json::material::StrengthFactor(1.0),
+                tex_coord: 0,
+                extensions: None,
+                extras: Default::default(),
+            });
+        let emissive_texture = texture_info(material.emission_texture);
+
+        self.materials.push(
+            self.writer.root.push(json::Material {
+                alpha_cutoff: material
+                    .alpha_cutoff
+                    .filter(|_| !material.blend)
+                    .map(json::material::AlphaCutoff),
+                alpha_mode: Valid(if material.blend {
+                    json::material::AlphaMode::Blend
+                } else if material.alpha_cutoff.is_some() {
+                    json::material::AlphaMode::Mask
+                } else {
+                    json::material::AlphaMode::Opaque
+                }),
+                name: Some(format!("Material{}", self.materials.len())),
+                pbr_metallic_roughness,
+                normal_texture,
+                occlusion_texture,
+                emissive_texture,
+                emissive_factor: json::material::EmissiveFactor(material.emission),
+                ..Default::default()
+            }),
+        );
+        self.materials.len() as u32 - 1
+    }
+
+    /// Returns the mesh index
+    pub fn add_mesh(&mut self, mesh: SyntheticMesh) -> u32 {
+        use json::accessor::{ComponentType, Type};
+        use json::buffer::Target;
+        use json::mesh::Semantic;
+
+        let (bounds_min, bounds_max) = mesh.positions.iter().fold(
+            (Vec3::INFINITY, Vec3::NEG_INFINITY),
+            |(min, max), position| {
+                let position = Vec3::from_array(*position);
+                (min.min(position), max.max(position))
+            },
+        );
+
+        let mut attributes = BTreeMap::new();
+        attributes.insert(
+            Valid(Semantic::Positions),
+            self.writer.push_accessor(
+                &mesh.positions,
+                ComponentType::F32,
+                Type::Vec3,
+                Target::ArrayBuffer,
+                Some((bounds_min.to_array(), bounds_max.to_array())),
+            ),
+        );
+        if !mesh.normals.is_empty() {
+            attributes.insert(
+                Valid(Semantic::Normals),
+                self.writer.push_accessor(
+                    &mesh.normals,
+                    ComponentType::F32,
+                    Type::Vec3,
+                    Target::ArrayBuffer,
+                    None,
+                ),
+            );
+        }
+        if !mesh.tangents.is_empty() {
+            attributes.insert(
+                Valid(Semantic::Tangents),
+                self.writer.push_accessor(
+                    &mesh.tangents,
+                    ComponentType::F32,
+                    Type::Vec4,
+                    Target::ArrayBuffer,
+                    None,
+                ),
+            );
+        }
+        if !mesh.tex_coords.is_empty() {
+            attributes.insert(
+                Valid(Semantic::TexCoords(0)),
+                self.writer.push_accessor(
+                    &mesh.tex_coords,
+                    ComponentType::F32,
+                    Type::Vec2,
+                    Target::ArrayBuffer,
+                    None,
+                ),
+            );
+        }
+
+        let indices = mesh.indices.as_ref().map(|indices| {
+            self.writer.push_accessor(
+                indices,
+                ComponentType::U32,
+                Type::Scalar,
+                Target::ElementArrayBuffer,
+                None,
+            )
+        });
+
+        let primitive = json::mesh::Primitive {
+            attributes,
+            extensions: Default::default(),
+            extras: Default::default(),
+            indices,
+            material: mesh
+                .material
+                .map(|material| self.materials[material as usize]),
+            mode: Valid(json::mesh::Mode::Triangles),
+            targets: None,
+        };
+
+        self.meshes.push(self.writer.root.push(json::Mesh {
+            extensions: Default::default(),
+            extras: Default::default(),
+            name: Some(format!("Mesh{}", self.meshes.len())),
+            primitives: vec![primitive],
+            weights: None,
+        }));
+        self.meshes.len() as u32 - 1
+    }
+
+    /// Add a root node, returns the node index
+    pub fn add_node(&mut self, mesh: Option<u32>, transform: Mat4) -> u32 {
+        self.nodes.push(self.writer.root.push(json::Node {
+            matrix: (transform != Mat4::IDENTITY).then_some(transform.to_cols_array()),
+            mesh: mesh.map(|mesh| self.meshes[mesh as usize]),
+            name: Some(format!("Node{}", self.nodes.len())),
+            ..Default::default()
+        }));
+        self.nodes.len() as u32 - 1
+    }
+
+    /// Animate a property through a KHR_animation_pointer channel, the path indexes materials and nodes returned by this builder
+    pub fn add_property_track(&mut self, track: &PropertyTrack) {
+        self.writer.push_property_track(track);
+    }
+
+    /// Glb containing only `mesh` instanced by a single node
+    pub fn single_mesh(mesh: SyntheticMesh) -> Result<Vec<u8>> {
+        let mut builder = Self::new();
+        let mesh = builder.add_mesh(mesh);
+        builder.add_node(Some(mesh), Mat4::IDENTITY);
+        builder.build()
+    }
+
+    pub fn build(self) -> Result<Vec<u8>> {
+        self.writer.finish(self.nodes, true)
+    }
+
+    /// Same as [`GlbBuilder::build`], but without marking the scene as default
+    pub fn build_without_default_scene(self) -> Result<Vec<u8>> {
+        self.writer.finish(self.nodes, false)
+    }
+}
@@ -1,36 +1,242 @@
+#[cfg(any(feature = "intel_tex_2", feature = "wgpu"))]
+use std::borrow::Cow;
+#[cfg(feature = "wgpu")]
+use std::collections::HashMap;
+use std::{
+    hash::Hash,
+    io::{self, Write},
+    path::Path,
+};
+
 use glam::Vec3;
+#[cfg(feature = "intel_tex_2")]
 use half::f16;
-use image::DynamicImage;
-use speedy::{Readable, Writable};
+use image::{DynamicImage, GenericImage};
+use speedy::{Context, Readable, Reader, Writable, Writer};
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Readable, Writable)]
+#[cfg(feature = "intel_tex_2")]
+use crate::eac::EacMode;
+use crate::{bc, dds, eac, ktx2, resample, serialization::ENDIANNESS};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Readable, Writable)]
 pub enum TextureFormat {
     Uncompressed(UncompressedTextureFormat),
     Compressed(CompressedTextureFormat),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TextureCompression {
     Bc,
     Etc1,
+    Etc2,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureCompressionProfile {
+    /// Pick compressed formats based on the number of channels only
+    #[default]
+    Channels,
+    /// Pick compressed formats based on the material slots a texture is referenced by
+    Quality,
+    /// Same as `Quality`, but opaque color textures use a smaller rgb only format
+    Size,
+}
+
+/// Downsample filter used for mip generation, trading sharpness against ringing
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MipFilter {
+    /// Average of the covered texels, soft without ringing
+    Box,
+    Triangle,
+    #[default]
+    CatmullRom,
+    Lanczos3,
+    /// Kaiser windowed sinc, sharpest with the most ringing
+    Kaiser,
+}
+
+/// Trade off between compression time and quality
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Readable, Writable)]
+pub enum TextureCompressionQuality {
+    #[default]
+    Fast,
+    Balanced,
+    Slow,
+}
+
+impl TextureCompressionQuality {
+    #[cfg(feature = "intel_tex_2")]
+    fn bc6h_settings(&self) -> intel_tex_2::bc6h::EncodeSettings {
+        match self {
+            Self::Fast => intel_tex_2::bc6h::very_fast_settings(),
+            Self::Balanced => intel_tex_2::bc6h::basic_settings(),
+            Self::Slow => intel_tex_2::bc6h::slow_settings(),
+        }
+    }
+
+    #[cfg(feature = "intel_tex_2")]
+    fn bc7_settings(&self) -> intel_tex_2::bc7::EncodeSettings {
+        match self {
+            Self::Fast => intel_tex_2::bc7::alpha_ultra_fast_settings(),
+            Self::Balanced => intel_tex_2::bc7::alpha_basic_settings(),
+            Self::Slow => intel_tex_2::bc7::alpha_slow_settings(),
+        }
+    }
+}
+
+/// Material slot a texture is referenced by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureUsage {
+    Color,
+    MetallicRoughness,
+    Normal,
+    Emission,
+    Occlusion,
+    Transmission,
+    Sheen,
+    SheenTint,
+    Clearcoat,
+    ClearcoatRoughness,
+    ClearcoatNormal,
+}
+
+impl TextureUsage {
+    /// All slots, in the same order as [`Material::textures`](crate::material::Material::textures)
+    pub const ALL: [TextureUsage; 11] = [
+        Self::Color,
+        Self::MetallicRoughness,
+        Self::Normal,
+        Self::Emission,
+        Self::Occlusion,
+        Self::Transmission,
+        Self::Sheen,
+        Self::SheenTint,
+        Self::Clearcoat,
+        Self::ClearcoatRoughness,
+        Self::ClearcoatNormal,
+    ];
+
+    pub fn is_normal_map(&self) -> bool {
+        matches!(self, Self::Normal | Self::ClearcoatNormal)
+    }
+
+    pub fn is_color(&self) -> bool {
+        matches!(self, Self::Color | Self::Emission | Self::SheenTint)
+    }
+
+    pub fn color_space(&self) -> ColorSpace {
+        if self.is_color() {
+            ColorSpace::Srgb
+        } else {
+            ColorSpace::Linear
+        }
+    }
+
+    /// Channel sampled by single channel slots
+    pub fn mask_channel(&self) -> Option<usize> {
+        match self {
+            Self::Occlusion | Self::Transmission | Self::Clearcoat => Some(0),
+            Self::ClearcoatRoughness => Some(1),
+            Self::Sheen => Some(3),
+            _ => None,
+        }
+    }
+}
+
+/// Set of material slots a texture is referenced by
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Readable, Writable)]
+pub struct TextureUsages(u16);
+
+impl TextureUsages {
+    pub fn contains(&self, usage: TextureUsage) -> bool {
+        self.0 & (1 << usage as u16) != 0
+    }
+
+    pub fn insert(&mut self, usage: TextureUsage) {
+        self.0 |= 1 << usage as u16;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = TextureUsage> {
+        let usages = *self;
+        TextureUsage::ALL
+            .into_iter()
+            .filter(move |usage| usages.contains(*usage))
+    }
+
+    /// Referenced by a slot storing color, requiring srgb
+    pub fn is_color(&self) -> bool {
+        self.iter().any(|usage| usage.is_color())
+    }
+
+    /// Referenced by a normal map slot
+    pub fn is_normal_map(&self) -> bool {
+        self.iter().any(|usage| usage.is_normal_map())
+    }
+}
+
+impl FromIterator<TextureUsage> for TextureUsages {
+    fn from_iter<T: IntoIterator<Item = TextureUsage>>(iter: T) -> Self {
+        let mut usages = Self::default();
+        for usage in iter {
+            usages.insert(usage);
+        }
+        usages
+    }
+}
+
+/// Shape of a texture, cube maps store their six faces as layers in +x, -x, +y, -y, +z, -z order
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Readable, Writable)]
+pub enum TextureDimension {
+    #[default]
+    D2,
+    Cube,
+}
+
+impl TextureDimension {
+    pub fn layer_count(&self) -> u32 {
+        match self {
+            Self::D2 => 1,
+            Self::Cube => 6,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Readable, Writable)]
+/// Color space texel values are stored in
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Readable, Writable)]
+pub enum ColorSpace {
+    #[default]
+    Linear,
+    Srgb,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Readable, Writable)]
 pub enum UncompressedTextureFormat {
     R8Unorm,
     Rg8Unorm,
     Rgba8Unorm,
+    R16Unorm,
+    Rg16Unorm,
+    Rgba16Unorm,
     Rgba32Float,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Readable, Writable)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Readable, Writable)]
 pub enum CompressedTextureFormat {
+    Bc1RgbaUnorm,
     Bc4RUnorm,
     Bc5RgUnorm,
     Bc7RgbaUnorm,
     Bc6hRgbUfloat,
     Etc1,
+    Etc2Rgb8Unorm,
+    Etc2Rgba8Unorm,
+    EacR11Unorm,
+    EacRg11Unorm,
 }
 
 impl TextureFormat {
@@ -53,9 +259,9 @@ impl TextureFormat {
 impl UncompressedTextureFormat {
     pub fn num_channels(&self) -> usize {
         match self {
-            Self::R8Unorm => 1,
-            Self::Rg8Unorm => 2,
-            Self::Rgba8Unorm => 4,
+            Self::R8Unorm | Self::R16Unorm => 1,
+            Self::Rg8Unorm | Self::Rg16Unorm => 2,
+            Self::Rgba8Unorm | Self::Rgba16Unorm => 4,
             Self::Rgba32Float => 4,
         }
     }
@@ -63,6 +269,7 @@ impl UncompressedTextureFormat {
     pub fn bytes_per_channel(&self) -> usize {
         match self {
             Self::R8Unorm | Self::Rg8Unorm | Self::Rgba8Unorm => size_of::<u8>(),
+            Self::R16Unorm | Self::Rg16Unorm | Self::Rgba16Unorm => size_of::<u16>(),
             Self::Rgba32Float => size_of::<f32>(),
         }
     }
@@ -79,12 +286,28 @@ impl UncompressedTextureFormat {
             TextureCompression::Bc => match self {
                 Self::R8Unorm => Some(&CompressedTextureFormat::Bc4RUnorm),
                 Self::Rg8Unorm => Some(&CompressedTextureFormat::Bc5RgUnorm),
-                Self::Rgba8Unorm => Some(&CompressedTextureFormat::Bc7RgbaUnorm),
+                Self::Rgba8Unorm | Self::Rgba16Unorm => {
+                    Some(&CompressedTextureFormat::Bc7RgbaUnorm)
+                }
                 Self::Rgba32Float => Some(&CompressedTextureFormat::Bc6hRgbUfloat),
+                // Single and dual channel 16 bit textures are usually height data, keep full precision
+                Self::R16Unorm | Self::Rg16Unorm => None,
             },
             TextureCompression::Etc1 => match self {
-                Self::Rgba8Unorm => Some(&CompressedTextureFormat::Etc1),
-                Self::Rg8Unorm | Self::Rgba32Float | Self::R8Unorm => None,
+                Self::Rgba8Unorm | Self::Rgba16Unorm => Some(&CompressedTextureFormat::Etc1),
+                Self::Rg8Unorm
+                | Self::Rgba32Float
+                | Self::R8Unorm
+                | Self::R16Unorm
+                | Self::Rg16Unorm => None,
+            },
+            TextureCompression::Etc2 => match self {
+                Self::R8Unorm => Some(&CompressedTextureFormat::EacR11Unorm),
+                Self::Rg8Unorm => Some(&CompressedTextureFormat::EacRg11Unorm),
+                Self::Rgba8Unorm | Self::Rgba16Unorm => {
+                    Some(&CompressedTextureFormat::Etc2Rgba8Unorm)
+                }
+                Self::Rgba32Float | Self::R16Unorm | Self::Rg16Unorm => None,
             },
         }
     }
@@ -95,7 +318,10 @@ impl UncompressedTextureFormat {
             Self::R8Unorm => wgpu::TextureFormat::R8Unorm,
             Self::Rg8Unorm => wgpu::TextureFormat::Rg8Unorm,
             Self::Rgba8Unorm => wgpu::TextureFormat::Rgba8Unorm,
-            _ => panic!("Failed to convert {:?} to wgpu format.", self),
+            Self::R16Unorm => wgpu::TextureFormat::R16Unorm,
+            Self::Rg16Unorm => wgpu::TextureFormat::Rg16Unorm,
+            Self::Rgba16Unorm => wgpu::TextureFormat::Rgba16Unorm,
+            Self::Rgba32Float => wgpu::TextureFormat::Rgba32Float,
         }
     }
 }
@@ -103,9 +329,10 @@ impl UncompressedTextureFormat {
 impl CompressedTextureFormat {
     pub fn block_size(&self) -> usize {
         match self {
-            Self::Bc4RUnorm => 8,
+            Self::Bc1RgbaUnorm | Self::Bc4RUnorm => 8,
             Self::Bc5RgUnorm | Self::Bc7RgbaUnorm | Self::Bc6hRgbUfloat => 16,
-            Self::Etc1 => 4,
+            Self::Etc1 | Self::Etc2Rgb8Unorm | Self::EacR11Unorm => 8,
+            Self::Etc2Rgba8Unorm | Self::EacRg11Unorm => 16,
         }
     }
 
@@ -116,58 +343,604 @@ impl CompressedTextureFormat {
     #[cfg(feature = "wgpu")]
     pub fn to_wgpu(&self) -> wgpu::TextureFormat {
         match self {
+            Self::Bc1RgbaUnorm => wgpu::TextureFormat::Bc1RgbaUnorm,
             Self::Bc4RUnorm => wgpu::TextureFormat::Bc4RUnorm,
             Self::Bc5RgUnorm => wgpu::TextureFormat::Bc5RgUnorm,
             Self::Bc7RgbaUnorm => wgpu::TextureFormat::Bc7RgbaUnorm,
             Self::Bc6hRgbUfloat => wgpu::TextureFormat::Bc6hRgbUfloat,
-            Self::Etc1 => wgpu::TextureFormat::Etc2Rgb8Unorm,
+            Self::Etc1 | Self::Etc2Rgb8Unorm => wgpu::TextureFormat::Etc2Rgb8Unorm,
+            Self::Etc2Rgba8Unorm => wgpu::TextureFormat::Etc2Rgba8Unorm,
+            Self::EacR11Unorm => wgpu::TextureFormat::EacR11Unorm,
+            Self::EacRg11Unorm => wgpu::TextureFormat::EacRg11Unorm,
+        }
+    }
+
+    /// Whether a device with `features` can sample the format
+    #[cfg(feature = "wgpu")]
+    pub fn is_supported_by(&self, features: wgpu::Features) -> bool {
+        match self {
+            Self::Bc1RgbaUnorm
+            | Self::Bc4RUnorm
+            | Self::Bc5RgUnorm
+            | Self::Bc7RgbaUnorm
+            | Self::Bc6hRgbUfloat => features.contains(wgpu::Features::TEXTURE_COMPRESSION_BC),
+            Self::Etc1
+            | Self::Etc2Rgb8Unorm
+            | Self::Etc2Rgba8Unorm
+            | Self::EacR11Unorm
+            | Self::EacRg11Unorm => features.contains(wgpu::Features::TEXTURE_COMPRESSION_ETC2),
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Readable, Writable)]
+pub enum AddressMode {
+    ClampToEdge,
+    Repeat,
+    MirrorRepeat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Readable, Writable)]
+pub enum FilterMode {
+    Nearest,
+    Linear,
+}
+
+/// Sampling state of a texture as authored in the source asset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Readable, Writable)]
+pub struct SamplerDesc {
+    pub address_mode_u: AddressMode,
+    pub address_mode_v: AddressMode,
+    pub mag_filter: FilterMode,
+    pub min_filter: FilterMode,
+    pub mipmap_filter: FilterMode,
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        Self {
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::Repeat,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+        }
+    }
+}
+
+impl AddressMode {
+    #[cfg(feature = "wgpu")]
+    pub fn to_wgpu(&self) -> wgpu::AddressMode {
+        match self {
+            Self::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+            Self::Repeat => wgpu::AddressMode::Repeat,
+            Self::MirrorRepeat => wgpu::AddressMode::MirrorRepeat,
+        }
+    }
+}
+
+impl FilterMode {
+    #[cfg(feature = "wgpu")]
+    pub fn to_wgpu(&self) -> wgpu::FilterMode {
+        match self {
+            Self::Nearest => wgpu::FilterMode::Nearest,
+            Self::Linear => wgpu::FilterMode::Linear,
+        }
+    }
+}
+
+impl SamplerDesc {
+    #[cfg(feature = "wgpu")]
+    pub fn to_wgpu<'a>(&self, label: Option<&'a str>) -> wgpu::SamplerDescriptor<'a> {
+        wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: self.address_mode_u.to_wgpu(),
+            address_mode_v: self.address_mode_v.to_wgpu(),
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: self.mag_filter.to_wgpu(),
+            min_filter: self.min_filter.to_wgpu(),
+            mipmap_filter: self.mipmap_filter.to_wgpu(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Samplers shared by all textures with the same [`SamplerDesc`]
+#[cfg(feature = "wgpu")]
+#[derive(Debug, Default)]
+pub struct WgpuSamplerCache {
+    samplers: HashMap<SamplerDesc, wgpu::Sampler>,
+}
+
+#[cfg(feature = "wgpu")]
+impl WgpuSamplerCache {
+    /// Sampler of `desc`, created on first use
+    pub fn get(&mut self, device: &wgpu::Device, desc: SamplerDesc) -> &wgpu::Sampler {
+        self.samplers
+            .entry(desc)
+            .or_insert_with(|| device.create_sampler(&desc.to_wgpu(None)))
+    }
+
+    pub fn get_for_texture(&mut self, device: &wgpu::Device, texture: &Texture) -> &wgpu::Sampler {
+        self.get(device, texture.sampler)
+    }
+
+    pub fn len(&self) -> usize {
+        self.samplers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samplers.is_empty()
+    }
+}
+
+/// Encoded image as stored in the source asset, e.g. png or jpeg
+#[derive(Debug, Clone, Readable, Writable)]
+pub struct SourceImage {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
 pub struct TextureCreateDesc<'a> {
     pub name: Option<&'a str>,
     pub image: image::DynamicImage,
     pub mips: bool,
+    /// Maximum number of mip levels including the top level, the full chain when `None`
+    pub max_mip_levels: Option<u32>,
+    /// Smallest width or height a generated mip may have
+    pub min_mip_size: Option<u32>,
+    /// Externally generated mips below the top level, each half the size of the previous, used instead of generating mips
+    pub precomputed_mips: Vec<DynamicImage>,
+    pub mip_filter: MipFilter,
     pub is_normal_map: bool,
+    pub color_space: ColorSpace,
+    /// Rescale the alpha of each mip to keep the coverage of alpha testing at this cutoff, ignored for images without alpha
+    pub alpha_coverage_cutoff: Option<f32>,
+    /// Multiply rgb by alpha before generating mips, ignored for images without alpha
+    pub premultiply_alpha: bool,
     pub uv_offset: [f32; 2],
     pub uv_scale: [f32; 2],
+    pub sampler: SamplerDesc,
+    pub source_image: Option<SourceImage>,
 }
 
-#[derive(Debug, Clone, Readable, Writable)]
+/// Normalized alpha values of an rgba image
+fn alpha_values(image: &DynamicImage) -> Option<Vec<f32>> {
+    match image {
+        DynamicImage::ImageRgba8(image) => Some(
+            image
+                .pixels()
+                .map(|pixel| pixel[3] as f32 / u8::MAX as f32)
+                .collect(),
+        ),
+        DynamicImage::ImageRgba16(image) => Some(
+            image
+                .pixels()
+                .map(|pixel| pixel[3] as f32 / u16::MAX as f32)
+                .collect(),
+        ),
+        DynamicImage::ImageRgba32F(image) => Some(image.pixels().map(|pixel| pixel[3]).collect()),
+        _ => None,
+    }
+}
+
+fn scale_alpha(image: &mut DynamicImage, scale: f32) {
+    match image {
+        DynamicImage::ImageRgba8(image) => {
+            for pixel in image.pixels_mut() {
+                pixel[3] = (pixel[3] as f32 * scale).round().min(u8::MAX as f32) as u8;
+            }
+        }
+        DynamicImage::ImageRgba16(image) => {
+            for pixel in image.pixels_mut() {
+                pixel[3] = (pixel[3] as f32 * scale).round().min(u16::MAX as f32) as u16;
+            }
+        }
+        DynamicImage::ImageRgba32F(image) => {
+            for pixel in image.pixels_mut() {
+                pixel[3] = (pixel[3] * scale).min(1.0);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Fraction of texels passing the alpha test at `alpha_cutoff`
+fn alpha_coverage(alpha_values: &[f32], alpha_cutoff: f32) -> f32 {
+    let passed = alpha_values
+        .iter()
+        .filter(|alpha| **alpha >= alpha_cutoff)
+        .count();
+    passed as f32 / alpha_values.len().max(1) as f32
+}
+
+/// Scale the alpha of each mip so the same fraction of texels passes the alpha test as in the top mip
+fn preserve_alpha_coverage(mipmaps: &mut [DynamicImage], alpha_cutoff: f32) {
+    let Some(top_alpha_values) = alpha_values(&mipmaps[0]) else {
+        return;
+    };
+    let target_coverage = alpha_coverage(&top_alpha_values, alpha_cutoff);
+
+    for mip in mipmaps.iter_mut().skip(1) {
+        let Some(alpha_values) = alpha_values(mip) else {
+            return;
+        };
+
+        // Binary search the cutoff resulting in the target coverage, coverage decreases with the cutoff
+        let mut min_cutoff = 0.0;
+        let mut max_cutoff = 1.0;
+        for _ in 0..16 {
+            let cutoff = (min_cutoff + max_cutoff) * 0.5;
+            if alpha_coverage(&alpha_values, cutoff) > target_coverage {
+                min_cutoff = cutoff;
+            } else {
+                max_cutoff = cutoff;
+            }
+        }
+
+        let cutoff = (min_cutoff + max_cutoff) * 0.5;
+        if cutoff > 0.0 {
+            scale_alpha(mip, alpha_cutoff / cutoff);
+        }
+    }
+}
+
+/// Bumped whenever an encoder changes its output, so textures cached by older versions are compressed again
+const TEXTURE_CACHE_VERSION: u32 = 1;
+
+/// Everything the output of [`Texture::compress_as`] depends on, stored in every cached texture and compared when reading it
+#[derive(Debug, PartialEq, Eq, Readable, Writable)]
+struct TextureCacheKey {
+    cache_version: u32,
+    crate_version: String,
+    width: u32,
+    height: u32,
+    dimension: TextureDimension,
+    format: TextureFormat,
+    mip_offsets: Vec<u64>,
+    data_len: u64,
+    data_hash: [u8; 32],
+    compressed_format: CompressedTextureFormat,
+    quality: TextureCompressionQuality,
+}
+
+impl TextureCacheKey {
+    fn new(
+        texture: &Texture,
+        compressed_format: CompressedTextureFormat,
+        quality: TextureCompressionQuality,
+    ) -> Self {
+        Self {
+            cache_version: TEXTURE_CACHE_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION").to_owned(),
+            width: texture.width,
+            height: texture.height,
+            dimension: texture.dimension,
+            format: texture.format,
+            mip_offsets: texture.mip_offsets.clone(),
+            data_len: texture.data.len() as u64,
+            data_hash: *blake3::hash(&texture.data).as_bytes(),
+            compressed_format,
+            quality,
+        }
+    }
+
+    /// Hash of the whole key, stable across toolchains and platforms
+    fn file_name(&self) -> Option<String> {
+        let key = self.write_to_vec_with_ctx(ENDIANNESS).ok()?;
+        Some(format!("{}.ugmtex", blake3::hash(&key).to_hex()))
+    }
+}
+
+#[derive(Readable, Writable)]
+struct CachedCompressedTexture {
+    key: TextureCacheKey,
+    format: TextureFormat,
+    data: Vec<u8>,
+    mip_offsets: Vec<u64>,
+}
+
+/// Concatenate `mips` into a single buffer, returns the buffer and the offset of each mip
+fn pack_mips<M: AsRef<[u8]>>(mips: &[M]) -> (Vec<u8>, Vec<u64>) {
+    let size = mips.iter().map(|mip| mip.as_ref().len()).sum();
+    let mut data = Vec::with_capacity(size);
+    let mut mip_offsets = Vec::with_capacity(mips.len());
+    for mip in mips {
+        mip_offsets.push(data.len() as u64);
+        data.extend_from_slice(mip.as_ref());
+    }
+
+    (data, mip_offsets)
+}
+
+/// Number of pixel rows compressed at once, a multiple of the block size of all compressed formats
+#[cfg(feature = "intel_tex_2")]
+const COMPRESSION_STRIP_ROWS: u32 = 256;
+
+/// Block compress a surface of `width` by `height` pixels, blocks are stored row by row
+#[cfg(feature = "intel_tex_2")]
+fn compress_surface(
+    compressed_format: CompressedTextureFormat,
+    quality: TextureCompressionQuality,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    bytes_per_pixel: u32,
+) -> Vec<u8> {
+    match compressed_format {
+        CompressedTextureFormat::Bc1RgbaUnorm => {
+            let surface = intel_tex_2::RgbaSurface {
+                width,
+                height,
+                stride: width * bytes_per_pixel,
+                data,
+            };
+
+            intel_tex_2::bc1::compress_blocks(&surface)
+        }
+        CompressedTextureFormat::Bc4RUnorm => {
+            let surface = intel_tex_2::RSurface {
+                width,
+                height,
+                stride: width * bytes_per_pixel,
+                data,
+            };
+
+            intel_tex_2::bc4::compress_blocks(&surface)
+        }
+        CompressedTextureFormat::Bc5RgUnorm => {
+            let surface = intel_tex_2::RgSurface {
+                width,
+                height,
+                stride: width * bytes_per_pixel,
+                data,
+            };
+
+            intel_tex_2::bc5::compress_blocks(&surface)
+        }
+        CompressedTextureFormat::Bc6hRgbUfloat => {
+            let f32_data = bytemuck::cast_slice(data);
+            let f16_data: Vec<f16> = f32_data.iter().copied().map(f16::from_f32).collect();
+
+            let surface = intel_tex_2::RgbaSurface {
+                width,
+                height,
+                stride: width * bytes_per_pixel,
+                data: bytemuck::cast_slice(&f16_data),
+            };
+
+            intel_tex_2::bc6h::compress_blocks(&quality.bc6h_settings(), &surface)
+        }
+        CompressedTextureFormat::Bc7RgbaUnorm => {
+            let surface = intel_tex_2::RgbaSurface {
+                width,
+                height,
+                stride: width * bytes_per_pixel,
+                data,
+            };
+
+            intel_tex_2::bc7::compress_blocks(&quality.bc7_settings(), &surface)
+        }
+        CompressedTextureFormat::Etc1 | CompressedTextureFormat::Etc2Rgb8Unorm => {
+            let surface = intel_tex_2::RgbaSurface {
+                width,
+                height,
+                stride: width * bytes_per_pixel,
+                data,
+            };
+
+            intel_tex_2::etc1::compress_blocks(
+                intel_tex_2::etc1::EncodeSettings {
+                    fast_skip_threshold: 6,
+                },
+                &surface,
+            )
+        }
+        CompressedTextureFormat::Etc2Rgba8Unorm => {
+            let surface = intel_tex_2::RgbaSurface {
+                width,
+                height,
+                stride: width * bytes_per_pixel,
+                data,
+            };
+
+            // Etc1 blocks are valid Etc2 color blocks, each is prefixed by an Eac alpha block
+            let color_blocks = intel_tex_2::etc1::compress_blocks(
+                intel_tex_2::etc1::EncodeSettings {
+                    fast_skip_threshold: 6,
+                },
+                &surface,
+            );
+            let alpha_blocks = eac::compress_channel(data, width, height, 4, 3, EacMode::Alpha8);
+
+            alpha_blocks
+                .iter()
+                .zip(color_blocks.chunks_exact(8))
+                .flat_map(|(alpha, color)| alpha.iter().chain(color).copied())
+                .collect()
+        }
+        CompressedTextureFormat::EacR11Unorm => {
+            eac::compress_channel(data, width, height, 1, 0, EacMode::Unorm11).concat()
+        }
+        CompressedTextureFormat::EacRg11Unorm => {
+            let r_blocks = eac::compress_channel(data, width, height, 2, 0, EacMode::Unorm11);
+            let g_blocks = eac::compress_channel(data, width, height, 2, 1, EacMode::Unorm11);
+
+            r_blocks
+                .iter()
+                .zip(&g_blocks)
+                .flat_map(|(r, g)| r.iter().chain(g).copied())
+                .collect()
+        }
+    }
+}
+
+/// Metadata of a [`Texture`] without its texel data
+#[derive(Debug, Clone, PartialEq, Readable, Writable)]
+pub struct TextureInfo {
+    pub name: String,
+    pub uuid: Uuid,
+    pub width: u32,
+    pub height: u32,
+    pub dimension: TextureDimension,
+    pub format: TextureFormat,
+    pub color_space: ColorSpace,
+    pub mip_count: u32,
+    /// Size of the texel data of all mips in bytes
+    pub data_size: u64,
+    pub sampler: SamplerDesc,
+    pub usages: TextureUsages,
+}
+
+/// Serialized field by field like a derived speedy type, with the channels of uncompressed texel data converted to the byte order of the context
+#[derive(Debug, Clone)]
 pub struct Texture {
     name: String,
     uuid: Uuid,
     width: u32,
     height: u32,
-    mip_count: u32,
+    dimension: TextureDimension,
     format: TextureFormat,
-    data: Vec<Vec<u8>>,
+    color_space: ColorSpace,
+    /// All mips stored contiguously, largest first, each mip holding all of its layers
+    data: Vec<u8>,
+    mip_offsets: Vec<u64>,
     uv_offset: [f32; 2],
     uv_scale: [f32; 2],
+    sampler: SamplerDesc,
+    reconstruct_normal_z: bool,
+    premultiplied_alpha: bool,
+    /// Material slots referencing the texture, see [`Model::update_texture_usages`](crate::Model::update_texture_usages)
+    usages: TextureUsages,
+    source_image: Option<SourceImage>,
+}
+
+/// Reverse the bytes of each channel of uncompressed texel data, block compressed data is a byte stream that never needs swapping
+fn swap_texel_bytes(format: TextureFormat, data: &mut [u8]) {
+    if let TextureFormat::Uncompressed(format) = format {
+        if format.bytes_per_channel() > 1 {
+            for channel in data.chunks_exact_mut(format.bytes_per_channel()) {
+                channel.reverse();
+            }
+        }
+    }
+}
+
+impl<'a, C: Context> Readable<'a, C> for Texture {
+    fn read_from<R: Reader<'a, C>>(reader: &mut R) -> Result<Self, C::Error> {
+        let name = reader.read_value()?;
+        let uuid = reader.read_value()?;
+        let width = reader.read_value()?;
+        let height = reader.read_value()?;
+        let dimension = reader.read_value()?;
+        let format = reader.read_value()?;
+        let color_space = reader.read_value()?;
+        let mut data: Vec<u8> = reader.read_value()?;
+        if reader.endianness().conversion_necessary() {
+            swap_texel_bytes(format, &mut data);
+        }
+
+        Ok(Self {
+            name,
+            uuid,
+            width,
+            height,
+            dimension,
+            format,
+            color_space,
+            data,
+            mip_offsets: reader.read_value()?,
+            uv_offset: reader.read_value()?,
+            uv_scale: reader.read_value()?,
+            sampler: reader.read_value()?,
+            reconstruct_normal_z: reader.read_value()?,
+            premultiplied_alpha: reader.read_value()?,
+            usages: reader.read_value()?,
+            source_image: reader.read_value()?,
+        })
+    }
+}
+
+impl<C: Context> Writable<C> for Texture {
+    fn write_to<T: ?Sized + Writer<C>>(&self, writer: &mut T) -> Result<(), C::Error> {
+        writer.write_value(&self.name)?;
+        writer.write_value(&self.uuid)?;
+        writer.write_value(&self.width)?;
+        writer.write_value(&self.height)?;
+        writer.write_value(&self.dimension)?;
+        writer.write_value(&self.format)?;
+        writer.write_value(&self.color_space)?;
+        if writer.endianness().conversion_necessary() {
+            let mut data = self.data.clone();
+            swap_texel_bytes(self.format, &mut data);
+            writer.write_value(&data)?;
+        } else {
+            writer.write_value(&self.data)?;
+        }
+        writer.write_value(&self.mip_offsets)?;
+        writer.write_value(&self.uv_offset)?;
+        writer.write_value(&self.uv_scale)?;
+        writer.write_value(&self.sampler)?;
+        writer.write_value(&self.reconstruct_normal_z)?;
+        writer.write_value(&self.premultiplied_alpha)?;
+        writer.write_value(&self.usages)?;
+        writer.write_value(&self.source_image)
+    }
 }
 
 impl Texture {
     pub fn new(desc: TextureCreateDesc) -> Self {
-        let converted_image = match desc.image {
-            DynamicImage::ImageRgba16(_) => DynamicImage::ImageRgba8(desc.image.to_rgba8()),
-            DynamicImage::ImageRgb16(_) => DynamicImage::ImageRgba8(desc.image.to_rgba8()),
-            DynamicImage::ImageLumaA16(_) => DynamicImage::ImageLumaA8(desc.image.to_luma_alpha8()),
-            DynamicImage::ImageLuma16(_) => DynamicImage::ImageLuma8(desc.image.to_luma8()),
-            DynamicImage::ImageRgb8(_) => DynamicImage::ImageRgba8(desc.image.to_rgba8()),
-            _ => desc.image,
+        let convert = |image: DynamicImage| match image {
+            // Normal map mips are generated from rgba8
+            _ if desc.is_normal_map && !matches!(image, DynamicImage::ImageRgba8(_)) => {
+                DynamicImage::ImageRgba8(image.to_rgba8())
+            }
+            DynamicImage::ImageRgb16(_) => DynamicImage::ImageRgba16(image.to_rgba16()),
+            DynamicImage::ImageRgb8(_) => DynamicImage::ImageRgba8(image.to_rgba8()),
+            DynamicImage::ImageRgb32F(_) => DynamicImage::ImageRgba32F(image.to_rgba32f()),
+            _ if desc.premultiply_alpha => resample::premultiply_alpha(image, desc.color_space),
+            _ => image,
+        };
+
+        let max_mip_levels = if desc.mips || !desc.precomputed_mips.is_empty() {
+            desc.max_mip_levels.unwrap_or(u32::MAX).max(1) as usize
+        } else {
+            1
         };
+        let min_mip_size = desc.min_mip_size.unwrap_or(1).max(1);
 
-        let mut mipmaps = vec![converted_image];
-        while mipmaps.last().unwrap().width() > 1 && mipmaps.last().unwrap().height() > 1 {
+        let premultiplied_alpha = desc.premultiply_alpha && desc.image.color().has_alpha();
+        let mut mipmaps = vec![convert(desc.image)];
+        for mip in desc.precomputed_mips.into_iter().take(max_mip_levels - 1) {
+            let last = mipmaps.last().unwrap();
+            assert!(
+                mip.width() == (last.width() / 2).max(1)
+                    && mip.height() == (last.height() / 2).max(1),
+                "Precomputed mip {} must be half the size of the previous mip.",
+                mipmaps.len()
+            );
+            mipmaps.push(convert(mip));
+        }
+        let is_precomputed = mipmaps.len() > 1;
+
+        while !is_precomputed
+            && mipmaps.len() < max_mip_levels
+            && mipmaps.last().unwrap().width() > 1
+            && mipmaps.last().unwrap().height() > 1
+        {
             let next_width = (mipmaps.last().unwrap().width() / 2).max(1);
             let next_height = (mipmaps.last().unwrap().height() / 2).max(1);
+            if next_width.min(next_height) < min_mip_size {
+                break;
+            }
 
             let next = if !desc.is_normal_map {
-                mipmaps.last().unwrap().resize_exact(
+                resample::resize(
+                    mipmaps.last().unwrap(),
                     next_width,
                     next_height,
-                    image::imageops::FilterType::CatmullRom,
+                    desc.mip_filter,
+                    desc.color_space,
                 )
             } else if let DynamicImage::ImageRgba8(img) = mipmaps.last().unwrap() {
                 let mut next_data = vec![0; (next_width * next_height * 4) as usize];
@@ -208,6 +981,13 @@ impl Texture {
             mipmaps.push(next);
         }
 
+        if let Some(alpha_cutoff) = desc
+            .alpha_coverage_cutoff
+            .filter(|_| !desc.is_normal_map && !is_precomputed)
+        {
+            preserve_alpha_coverage(&mut mipmaps, alpha_cutoff);
+        }
+
         let format = match &mipmaps[0] {
             DynamicImage::ImageRgba32F(_) => {
                 TextureFormat::Uncompressed(UncompressedTextureFormat::Rgba32Float)
@@ -215,6 +995,15 @@ impl Texture {
             DynamicImage::ImageRgba8(_) => {
                 TextureFormat::Uncompressed(UncompressedTextureFormat::Rgba8Unorm)
             }
+            DynamicImage::ImageRgba16(_) => {
+                TextureFormat::Uncompressed(UncompressedTextureFormat::Rgba16Unorm)
+            }
+            DynamicImage::ImageLumaA16(_) => {
+                TextureFormat::Uncompressed(UncompressedTextureFormat::Rg16Unorm)
+            }
+            DynamicImage::ImageLuma16(_) => {
+                TextureFormat::Uncompressed(UncompressedTextureFormat::R16Unorm)
+            }
             DynamicImage::ImageLumaA8(_) => {
                 TextureFormat::Uncompressed(UncompressedTextureFormat::Rg8Unorm)
             }
@@ -224,24 +1013,155 @@ impl Texture {
             _ => panic!(),
         };
 
-        let mut data = Vec::new();
-        for mip in &mipmaps {
-            data.push(mip.as_bytes().to_vec());
-        }
+        let mip_bytes: Vec<&[u8]> = mipmaps.iter().map(|mip| mip.as_bytes()).collect();
+        let (data, mip_offsets) = pack_mips(&mip_bytes);
 
         Self {
             name: desc.name.unwrap_or("Unnamed").to_owned(),
             uuid: Uuid::new_v4(),
             width: mipmaps[0].width(),
             height: mipmaps[0].height(),
-            mip_count: mipmaps.len() as u32,
+            dimension: TextureDimension::D2,
             format,
+            color_space: desc.color_space,
             data,
+            mip_offsets,
             uv_offset: desc.uv_offset,
             uv_scale: desc.uv_scale,
+            sampler: desc.sampler,
+            reconstruct_normal_z: false,
+            premultiplied_alpha,
+            usages: TextureUsages::default(),
+            source_image: desc.source_image,
+        }
+    }
+
+    /// Combine six square 2d textures of equal size, format and mip count into a cube map, named and sampled like the first face
+    pub fn new_cube(faces: [Texture; 6]) -> Self {
+        let [first, ..] = &faces;
+        assert!(
+            first.width == first.height,
+            "Cube map faces must be square."
+        );
+        for face in &faces {
+            assert!(
+                face.dimension == TextureDimension::D2
+                    && face.width == first.width
+                    && face.height == first.height
+                    && face.format == first.format
+                    && face.mip_count() == first.mip_count(),
+                "Cube map faces must be 2d textures of equal size, format and mip count."
+            );
+        }
+
+        let mips: Vec<Vec<u8>> = (0..first.mip_count())
+            .map(|mip| {
+                faces
+                    .iter()
+                    .flat_map(|face| face.mip_data(mip))
+                    .copied()
+                    .collect()
+            })
+            .collect();
+        let (data, mip_offsets) = pack_mips(&mips);
+
+        Self {
+            name: first.name.clone(),
+            uuid: Uuid::new_v4(),
+            width: first.width,
+            height: first.height,
+            dimension: TextureDimension::Cube,
+            format: first.format,
+            color_space: first.color_space,
+            data,
+            mip_offsets,
+            uv_offset: first.uv_offset,
+            uv_scale: first.uv_scale,
+            sampler: first.sampler,
+            reconstruct_normal_z: first.reconstruct_normal_z,
+            premultiplied_alpha: first.premultiplied_alpha,
+            usages: first.usages,
+            source_image: None,
         }
     }
 
+    pub fn info(&self) -> TextureInfo {
+        TextureInfo {
+            name: self.name.clone(),
+            uuid: self.uuid,
+            width: self.width,
+            height: self.height,
+            dimension: self.dimension,
+            format: self.format,
+            color_space: self.color_space,
+            mip_count: self.mip_count(),
+            data_size: self.data.len() as u64,
+            sampler: self.sampler,
+            usages: self.usages,
+        }
+    }
+
+    /// Returns true if both textures hold the same texel data sampled the same way, ignoring name, uuid and usages
+    pub fn has_same_content(&self, other: &Texture) -> bool {
+        self.width == other.width
+            && self.height == other.height
+            && self.dimension == other.dimension
+            && self.format == other.format
+            && self.color_space == other.color_space
+            && self.mip_offsets == other.mip_offsets
+            && self.uv_offset == other.uv_offset
+            && self.uv_scale == other.uv_scale
+            && self.sampler == other.sampler
+            && self.reconstruct_normal_z == other.reconstruct_normal_z
+            && self.premultiplied_alpha == other.premultiplied_alpha
+            && self.data == other.data
+    }
+
+    /// Hash of everything compared by [`Texture::has_same_content`], the same on every platform and toolchain
+    pub fn content_hash(&self) -> u64 {
+        let digest = self.content_digest();
+        u64::from_le_bytes(digest[..8].try_into().unwrap())
+    }
+
+    /// Blake3 of everything compared by [`Texture::has_same_content`] serialized in [`ENDIANNESS`] byte order
+    fn content_digest(&self) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        let content = (
+            (self.width, self.height, self.dimension, self.format),
+            (
+                self.color_space,
+                &self.mip_offsets,
+                self.uv_offset,
+                self.uv_scale,
+            ),
+            (
+                self.sampler,
+                self.reconstruct_normal_z,
+                self.premultiplied_alpha,
+            ),
+        );
+        hasher.update(
+            &content
+                .write_to_vec_with_ctx(ENDIANNESS)
+                .expect("Texture content is always serializable."),
+        );
+
+        if ENDIANNESS.conversion_necessary() {
+            let mut data = self.data.clone();
+            swap_texel_bytes(self.format, &mut data);
+            hasher.update(&data);
+        } else {
+            hasher.update(&self.data);
+        }
+        *hasher.finalize().as_bytes()
+    }
+
+    /// Replace the random uuid by a version 8 uuid derived from the content, so identical textures get identical uuids on every machine
+    pub fn set_content_uuid(&mut self) {
+        let digest = self.content_digest();
+        self.uuid = uuid::Builder::from_custom_bytes(digest[..16].try_into().unwrap()).into_uuid();
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -258,14 +1178,97 @@ impl Texture {
         self.height
     }
 
+    pub fn dimension(&self) -> TextureDimension {
+        self.dimension
+    }
+
+    pub fn layer_count(&self) -> u32 {
+        self.dimension.layer_count()
+    }
+
     pub fn format(&self) -> TextureFormat {
         self.format
     }
 
-    pub fn data(&self) -> &[Vec<u8>] {
+    pub fn color_space(&self) -> ColorSpace {
+        self.color_space
+    }
+
+    /// Data of all mips, use [`Texture::mip_data`] to access a single mip
+    pub fn data(&self) -> &[u8] {
         &self.data
     }
 
+    pub fn mip_count(&self) -> u32 {
+        self.mip_offsets.len() as u32
+    }
+
+    /// Data of mip level `mip`, panics if out of range
+    pub fn mip_data(&self, mip: u32) -> &[u8] {
+        let start = self.mip_offsets[mip as usize] as usize;
+        let end = self
+            .mip_offsets
+            .get(mip as usize + 1)
+            .map_or(self.data.len(), |offset| *offset as usize);
+        &self.data[start..end]
+    }
+
+    /// Data of `layer` within mip level `mip`, panics if out of range
+    pub fn layer_data(&self, mip: u32, layer: u32) -> &[u8] {
+        assert!(layer < self.layer_count(), "Layer {} out of range.", layer);
+        let data = self.mip_data(mip);
+        let layer_size = data.len() / self.layer_count() as usize;
+        &data[layer as usize * layer_size..(layer as usize + 1) * layer_size]
+    }
+
+    /// Data of each mip level, largest first
+    pub fn mips(&self) -> impl ExactSizeIterator<Item = &[u8]> + '_ {
+        (0..self.mip_count()).map(|mip| self.mip_data(mip))
+    }
+
+    /// Split off the `count` largest mips, returns a texture made of the remaining mips along with the split off mip data
+    pub(crate) fn split_largest_mips(&self, count: u32) -> (Texture, Vec<&[u8]>) {
+        assert!(count < self.mip_count(), "At least one mip must remain.");
+        let start = self.mip_offsets[count as usize];
+
+        let remaining = Texture {
+            name: self.name.clone(),
+            uuid: self.uuid,
+            width: (self.width >> count).max(1),
+            height: (self.height >> count).max(1),
+            dimension: self.dimension,
+            format: self.format,
+            color_space: self.color_space,
+            data: self.data[start as usize..].to_vec(),
+            mip_offsets: self.mip_offsets[count as usize..]
+                .iter()
+                .map(|offset| offset - start)
+                .collect(),
+            uv_offset: self.uv_offset,
+            uv_scale: self.uv_scale,
+            sampler: self.sampler,
+            reconstruct_normal_z: self.reconstruct_normal_z,
+            premultiplied_alpha: self.premultiplied_alpha,
+            usages: self.usages,
+            source_image: self.source_image.clone(),
+        };
+        let split = (0..count).map(|mip| self.mip_data(mip)).collect();
+
+        (remaining, split)
+    }
+
+    /// Add `mip` as the new largest mip of size `width` by `height`, inverse of [`Texture::split_largest_mips`]
+    pub(crate) fn prepend_mip(&mut self, mip: &[u8], width: u32, height: u32) {
+        let mip_size = mip.len() as u64;
+        self.data.splice(0..0, mip.iter().copied());
+        for offset in &mut self.mip_offsets {
+            *offset += mip_size;
+        }
+        self.mip_offsets.insert(0, 0);
+        self.width = width;
+        self.height = height;
+    }
+
     pub fn uv_offset(&self) -> [f32; 2] {
         self.uv_offset
     }
@@ -274,143 +1277,531 @@ impl Texture {
         self.uv_scale
     }
 
-    #[cfg(feature = "intel_tex_2")]
+    pub fn sampler(&self) -> SamplerDesc {
+        self.sampler
+    }
+
+    /// Normal map stored as xy only, z must be reconstructed when sampling
+    pub fn reconstruct_normal_z(&self) -> bool {
+        self.reconstruct_normal_z
+    }
+
+    /// Rgb is premultiplied by alpha, the texture should be blended as such
+    pub fn premultiplied_alpha(&self) -> bool {
+        self.premultiplied_alpha
+    }
+
+    /// Material slots referencing the texture
+    pub fn usages(&self) -> TextureUsages {
+        self.usages
+    }
+
+    pub(crate) fn set_usages(&mut self, usages: TextureUsages) {
+        self.usages = usages;
+    }
+
+    pub fn source_image(&self) -> Option<&SourceImage> {
+        self.source_image.as_ref()
+    }
+
+    /// Whether all pixels are fully opaque, textures without alpha channel are always opaque
+    pub fn is_opaque(&self) -> bool {
+        match self.format {
+            TextureFormat::Uncompressed(UncompressedTextureFormat::Rgba8Unorm) => self
+                .mip_data(0)
+                .chunks_exact(4)
+                .all(|pixel| pixel[3] == u8::MAX),
+            TextureFormat::Uncompressed(UncompressedTextureFormat::Rgba16Unorm) => self
+                .mip_data(0)
+                .chunks_exact(8)
+                .all(|pixel| pixel[6] == u8::MAX && pixel[7] == u8::MAX),
+            TextureFormat::Uncompressed(UncompressedTextureFormat::Rgba32Float) => {
+                self.mip_data(0).chunks_exact(16).all(|pixel| {
+                    f32::from_ne_bytes([pixel[12], pixel[13], pixel[14], pixel[15]]) >= 1.0
+                })
+            }
+            TextureFormat::Uncompressed(_) => true,
+            TextureFormat::Compressed(_) => false,
+        }
+    }
+
+    /// Decode mip level `mip` to an image, layers are stacked vertically.
+    /// Returns `None` for out of range mips and formats without decoder (etc and eac)
+    pub fn to_image(&self, mip: u32) -> Option<DynamicImage> {
+        if mip >= self.mip_count() {
+            return None;
+        }
+        let width = (self.width >> mip).max(1);
+        let height = (self.height >> mip).max(1);
+
+        if self.layer_count() == 1 {
+            return self.decode_layer(self.mip_data(mip), width, height);
+        }
+
+        let mut image = None;
+        for layer in 0..self.layer_count() {
+            let layer_image = self.decode_layer(self.layer_data(mip, layer), width, height)?;
+            image
+                .get_or_insert_with(|| {
+                    DynamicImage::new(width, height * self.layer_count(), layer_image.color())
+                })
+                .copy_from(&layer_image, 0, height * layer)
+                .ok()?;
+        }
+
+        image
+    }
+
+    fn decode_layer(&self, data: &[u8], width: u32, height: u32) -> Option<DynamicImage> {
+        let image = match self.format {
+            TextureFormat::Uncompressed(format) => match format {
+                UncompressedTextureFormat::R8Unorm => DynamicImage::ImageLuma8(
+                    image::GrayImage::from_raw(width, height, data.to_vec())?,
+                ),
+                UncompressedTextureFormat::Rg8Unorm => DynamicImage::ImageLumaA8(
+                    image::GrayAlphaImage::from_raw(width, height, data.to_vec())?,
+                ),
+                UncompressedTextureFormat::Rgba8Unorm => DynamicImage::ImageRgba8(
+                    image::RgbaImage::from_raw(width, height, data.to_vec())?,
+                ),
+                UncompressedTextureFormat::R16Unorm => {
+                    DynamicImage::ImageLuma16(image::ImageBuffer::from_raw(
+                        width,
+                        height,
+                        bytemuck::pod_collect_to_vec(data),
+                    )?)
+                }
+                UncompressedTextureFormat::Rg16Unorm => {
+                    DynamicImage::ImageLumaA16(image::ImageBuffer::from_raw(
+                        width,
+                        height,
+                        bytemuck::pod_collect_to_vec(data),
+                    )?)
+                }
+                UncompressedTextureFormat::Rgba16Unorm => {
+                    DynamicImage::ImageRgba16(image::ImageBuffer::from_raw(
+                        width,
+                        height,
+                        bytemuck::pod_collect_to_vec(data),
+                    )?)
+                }
+                UncompressedTextureFormat::Rgba32Float => {
+                    DynamicImage::ImageRgba32F(image::ImageBuffer::from_raw(
+                        width,
+                        height,
+                        bytemuck::pod_collect_to_vec(data),
+                    )?)
+                }
+            },
+            TextureFormat::Compressed(format) => match format {
+                CompressedTextureFormat::Bc1RgbaUnorm => DynamicImage::ImageRgba8(
+                    image::RgbaImage::from_raw(width, height, bc::decode_bc1(data, width, height))?,
+                ),
+                CompressedTextureFormat::Bc4RUnorm => DynamicImage::ImageLuma8(
+                    image::GrayImage::from_raw(width, height, bc::decode_bc4(data, width, height))?,
+                ),
+                CompressedTextureFormat::Bc5RgUnorm => {
+                    DynamicImage::ImageLumaA8(image::GrayAlphaImage::from_raw(
+                        width,
+                        height,
+                        bc::decode_bc5(data, width, height),
+                    )?)
+                }
+                CompressedTextureFormat::Bc6hRgbUfloat => {
+                    DynamicImage::ImageRgba32F(image::Rgba32FImage::from_raw(
+                        width,
+                        height,
+                        bc::decode_bc6h(data, width, height),
+                    )?)
+                }
+                CompressedTextureFormat::Bc7RgbaUnorm => DynamicImage::ImageRgba8(
+                    image::RgbaImage::from_raw(width, height, bc::decode_bc7(data, width, height))?,
+                ),
+                CompressedTextureFormat::EacR11Unorm => {
+                    DynamicImage::ImageLuma16(image::ImageBuffer::from_raw(
+                        width,
+                        height,
+                        eac::decode_r11(data, width, height),
+                    )?)
+                }
+                CompressedTextureFormat::EacRg11Unorm => {
+                    DynamicImage::ImageLumaA16(image::ImageBuffer::from_raw(
+                        width,
+                        height,
+                        eac::decode_rg11(data, width, height),
+                    )?)
+                }
+                CompressedTextureFormat::Etc1
+                | CompressedTextureFormat::Etc2Rgb8Unorm
+                | CompressedTextureFormat::Etc2Rgba8Unorm => return None,
+            },
+        };
+
+        Some(image)
+    }
+
+    /// Decode all mips of a compressed texture, returns `None` if the texture isn't compressed or its format has no decoder
+    pub fn decompress(&self) -> Option<Self> {
+        let TextureFormat::Compressed(_) = self.format else {
+            return None;
+        };
+
+        let mut mips = Vec::with_capacity(self.mip_count() as usize);
+        let mut format = None;
+        for mip in 0..self.mip_count() {
+            let image = self.to_image(mip)?;
+            format = Some(match &image {
+                DynamicImage::ImageLuma8(_) => UncompressedTextureFormat::R8Unorm,
+                DynamicImage::ImageLumaA8(_) => UncompressedTextureFormat::Rg8Unorm,
+                DynamicImage::ImageLuma16(_) => UncompressedTextureFormat::R16Unorm,
+                DynamicImage::ImageLumaA16(_) => UncompressedTextureFormat::Rg16Unorm,
+                DynamicImage::ImageRgba32F(_) => UncompressedTextureFormat::Rgba32Float,
+                _ => UncompressedTextureFormat::Rgba8Unorm,
+            });
+            mips.push(image.into_bytes());
+        }
+        let (data, mip_offsets) = pack_mips(&mips);
+
+        Some(Self {
+            name: self.name.clone(),
+            uuid: Uuid::new_v4(),
+            width: self.width,
+            height: self.height,
+            dimension: self.dimension,
+            format: TextureFormat::Uncompressed(format?),
+            color_space: self.color_space,
+            data,
+            mip_offsets,
+            uv_offset: self.uv_offset,
+            uv_scale: self.uv_scale,
+            sampler: self.sampler,
+            reconstruct_normal_z: self.reconstruct_normal_z,
+            premultiplied_alpha: self.premultiplied_alpha,
+            usages: self.usages,
+            source_image: self.source_image.clone(),
+        })
+    }
+
+    /// Write the texture including all mips as KTX2 container
+    pub fn write_ktx2(&self, writer: &mut impl Write) -> io::Result<()> {
+        ktx2::write_ktx2(self, writer)
+    }
+
+    /// Write the texture including all mips as DDS file with DX10 header, etc and eac formats are not supported
+    pub fn write_dds(&self, writer: &mut impl Write) -> io::Result<()> {
+        dds::write_dds(self, writer)
+    }
+
+    /// Strip blue and alpha of an rgba8 normal map, compressing to two channel formats such as Bc5 with z reconstructed when sampling. Other textures are returned as is
+    pub fn into_two_channel_normal_map(self) -> Self {
+        if self.format != TextureFormat::Uncompressed(UncompressedTextureFormat::Rgba8Unorm) {
+            return self;
+        }
+
+        let mut texture = self.extract_channels(&[0, 1]);
+        texture.reconstruct_normal_z = true;
+        texture
+    }
+
+    /// Keep only `channels` of an rgba8 texture, resulting in an r8 or rg8 texture
+    fn extract_channels(mut self, channels: &[usize]) -> Self {
+        debug_assert_eq!(
+            self.format,
+            TextureFormat::Uncompressed(UncompressedTextureFormat::Rgba8Unorm)
+        );
+
+        let format = match channels.len() {
+            1 => UncompressedTextureFormat::R8Unorm,
+            2 => UncompressedTextureFormat::Rg8Unorm,
+            _ => panic!("Only 1 or 2 channels can be extracted."),
+        };
+
+        // Offsets scale with the channel count as every mip is tightly packed
+        self.data = self
+            .data
+            .chunks_exact(4)
+            .flat_map(|pixel| channels.iter().map(|channel| pixel[*channel]))
+            .collect();
+        for offset in &mut self.mip_offsets {
+            *offset = *offset / 4 * channels.len() as u64;
+        }
+        self.format = TextureFormat::Uncompressed(format);
+        self.uuid = Uuid::new_v4();
+        self
+    }
+
+    /// Prepare the texture for compression and pick its compressed format, based on the material slots it's referenced by
+    pub fn prepare_for_compression(
+        self,
+        texture_compression: &TextureCompression,
+        profile: TextureCompressionProfile,
+        usages: &[TextureUsage],
+    ) -> (Self, Option<CompressedTextureFormat>) {
+        let TextureFormat::Uncompressed(uncompressed_format) = self.format else {
+            return (self, None);
+        };
+        if profile == TextureCompressionProfile::Channels || usages.is_empty() {
+            return (
+                self,
+                uncompressed_format
+                    .try_as_compressed(texture_compression)
+                    .copied(),
+            );
+        }
+
+        let all_usages = |f: fn(&TextureUsage) -> bool| usages.iter().all(f);
+        let can_compress = |format: UncompressedTextureFormat| {
+            format.try_as_compressed(texture_compression).is_some()
+        };
+
+        let texture = if uncompressed_format != UncompressedTextureFormat::Rgba8Unorm {
+            self
+        } else if all_usages(TextureUsage::is_normal_map)
+            && can_compress(UncompressedTextureFormat::Rg8Unorm)
+        {
+            self.into_two_channel_normal_map()
+        } else if all_usages(|usage| usage.mask_channel() == Some(0))
+            && can_compress(UncompressedTextureFormat::R8Unorm)
+        {
+            self.extract_channels(&[0])
+        } else {
+            self
+        };
+
+        let TextureFormat::Uncompressed(uncompressed_format) = texture.format else {
+            unreachable!()
+        };
+        let mut compressed_format = uncompressed_format
+            .try_as_compressed(texture_compression)
+            .copied();
+
+        if profile == TextureCompressionProfile::Size
+            && compressed_format.is_some()
+            && all_usages(TextureUsage::is_color)
+            && texture.is_opaque()
+        {
+            compressed_format = Some(match texture_compression {
+                TextureCompression::Bc => CompressedTextureFormat::Bc1RgbaUnorm,
+                TextureCompression::Etc1 => CompressedTextureFormat::Etc1,
+                TextureCompression::Etc2 => CompressedTextureFormat::Etc2Rgb8Unorm,
+            });
+        }
+
+        (texture, compressed_format)
+    }
+
     pub fn compress(&self, texture_compression: &TextureCompression) -> Option<Self> {
         if let TextureFormat::Uncompressed(uncompressed_format) = self.format() {
             if let Some(compressed_format) =
                 uncompressed_format.try_as_compressed(texture_compression)
             {
-                let mut compressed_data = Vec::new();
-
-                let mut mip_width = self.width;
-                let mut mip_height = self.height;
-                for data in &self.data {
-                    let compressed_mip_data = match compressed_format {
-                        CompressedTextureFormat::Bc4RUnorm => {
-                            let surface = intel_tex_2::RSurface {
-                                width: mip_width,
-                                height: mip_height,
-                                stride: mip_width
-                                    * (uncompressed_format.num_channels()
-                                        * uncompressed_format.bytes_per_channel())
-                                        as u32,
-                                data,
-                            };
-
-                            intel_tex_2::bc4::compress_blocks(&surface)
-                        }
-                        CompressedTextureFormat::Bc5RgUnorm => {
-                            let surface = intel_tex_2::RgSurface {
-                                width: mip_width,
-                                height: mip_height,
-                                stride: mip_width
-                                    * (uncompressed_format.num_channels()
-                                        * uncompressed_format.bytes_per_channel())
-                                        as u32,
-                                data,
-                            };
-
-                            intel_tex_2::bc5::compress_blocks(&surface)
-                        }
-                        CompressedTextureFormat::Bc6hRgbUfloat => {
-                            let f32_data = bytemuck::cast_slice(data);
-                            let f16_data: Vec<f16> =
-                                f32_data.iter().copied().map(f16::from_f32).collect();
-
-                            let surface = intel_tex_2::RgbaSurface {
-                                width: mip_width,
-                                height: mip_height,
-                                stride: mip_width
-                                    * (uncompressed_format.num_channels()
-                                        * uncompressed_format.bytes_per_channel())
-                                        as u32,
-                                data: bytemuck::cast_slice(&f16_data),
-                            };
-
-                            intel_tex_2::bc6h::compress_blocks(
-                                &intel_tex_2::bc6h::very_fast_settings(),
-                                &surface,
-                            )
-                        }
-                        CompressedTextureFormat::Bc7RgbaUnorm => {
-                            let surface = intel_tex_2::RgbaSurface {
-                                width: mip_width,
-                                height: mip_height,
-                                stride: mip_width
-                                    * (uncompressed_format.num_channels()
-                                        * uncompressed_format.bytes_per_channel())
-                                        as u32,
-                                data,
-                            };
-
-                            intel_tex_2::bc7::compress_blocks(
-                                &intel_tex_2::bc7::alpha_ultra_fast_settings(),
-                                &surface,
-                            )
-                        }
-                        CompressedTextureFormat::Etc1 => {
-                            let surface = intel_tex_2::RgbaSurface {
-                                width: mip_width,
-                                height: mip_height,
-                                stride: mip_width
-                                    * (uncompressed_format.num_channels()
-                                        * uncompressed_format.bytes_per_channel())
-                                        as u32,
-                                data,
-                            };
-
-                            intel_tex_2::etc1::compress_blocks(
-                                intel_tex_2::etc1::EncodeSettings {
-                                    fast_skip_threshold: 6,
-                                },
-                                &surface,
-                            )
-                        }
-                    };
+                return self.compress_as(*compressed_format, TextureCompressionQuality::default());
+            }
+        }
 
-                    compressed_data.push(compressed_mip_data);
+        None
+    }
 
-                    mip_width = (mip_width / 2).max(1);
-                    mip_height = (mip_height / 2).max(1);
-                    if mip_width < compressed_format.block_size() as u32
-                        || mip_height < compressed_format.block_size() as u32
-                    {
-                        break;
+    #[cfg(feature = "intel_tex_2")]
+    pub fn compress_as(
+        &self,
+        compressed_format: CompressedTextureFormat,
+        quality: TextureCompressionQuality,
+    ) -> Option<Self> {
+        if let TextureFormat::Uncompressed(uncompressed_format) = self.format() {
+            let mut compressed_data = Vec::new();
+
+            // 16 bit unorm textures are quantized to 8 bits before block compression
+            let is_16_bit = uncompressed_format.bytes_per_channel() == size_of::<u16>();
+            let bytes_per_pixel = if is_16_bit {
+                uncompressed_format.num_channels()
+            } else {
+                uncompressed_format.num_channels() * uncompressed_format.bytes_per_channel()
+            } as u32;
+
+            let mut mip_width = self.width;
+            let mut mip_height = self.height;
+            for mip_data in self.mips() {
+                let mut compressed_mip_data = vec![];
+                let row_size = mip_data.len() / (self.layer_count() * mip_height) as usize;
+                for layer_data in
+                    mip_data.chunks_exact(mip_data.len() / self.layer_count() as usize)
+                {
+                    // Compressed in strips of block rows, bounding the size of intermediate copies for very large textures
+                    for data in layer_data.chunks(COMPRESSION_STRIP_ROWS as usize * row_size) {
+                        let strip_height = (data.len() / row_size) as u32;
+                        let data: Cow<[u8]> = if is_16_bit {
+                            Cow::Owned(
+                                data.chunks_exact(2)
+                                    .map(|c| (u16::from_ne_bytes([c[0], c[1]]) >> 8) as u8)
+                                    .collect(),
+                            )
+                        } else {
+                            Cow::Borrowed(data)
+                        };
+
+                        compressed_mip_data.extend_from_slice(&compress_surface(
+                            compressed_format,
+                            quality,
+                            data.as_ref(),
+                            mip_width,
+                            strip_height,
+                            bytes_per_pixel,
+                        ));
                     }
                 }
+                compressed_data.push(compressed_mip_data);
 
+                mip_width = (mip_width / 2).max(1);
+                mip_height = (mip_height / 2).max(1);
+                if mip_width < compressed_format.block_size() as u32
+                    || mip_height < compressed_format.block_size() as u32
+                {
+                    break;
+                }
+            }
+
+            let (data, mip_offsets) = pack_mips(&compressed_data);
+
+            return Some(Self {
+                name: self.name.clone(),
+                uuid: Uuid::new_v4(),
+                width: self.width,
+                height: self.height,
+                dimension: self.dimension,
+                format: TextureFormat::Compressed(compressed_format),
+                color_space: self.color_space,
+                data,
+                mip_offsets,
+                uv_offset: self.uv_offset,
+                uv_scale: self.uv_scale,
+                sampler: self.sampler,
+                reconstruct_normal_z: self.reconstruct_normal_z,
+                premultiplied_alpha: self.premultiplied_alpha,
+                usages: self.usages,
+                source_image: self.source_image.clone(),
+            });
+        }
+
+        None
+    }
+
+    #[cfg(not(feature = "intel_tex_2"))]
+    pub fn compress_as(
+        &self,
+        _compressed_format: CompressedTextureFormat,
+        _quality: TextureCompressionQuality,
+    ) -> Option<Self> {
+        panic!("Failed to compress texture, this requires the 'intel_tex_2' crate feature.");
+    }
+
+    /// Compress the texture, reusing compressed mips from `cache_dir` when the same source pixels and settings were compressed before.
+    /// Cached textures are keyed by a stable hash, so the cache can be shared between runs, processes and machines.
+    /// The returned texture keeps the uuid of the source texture
+    pub fn compress_cached(
+        &self,
+        compressed_format: CompressedTextureFormat,
+        quality: TextureCompressionQuality,
+        cache_dir: &Path,
+    ) -> Option<Self> {
+        let key = TextureCacheKey::new(self, compressed_format, quality);
+        let Some(file_name) = key.file_name() else {
+            return self.compress_as(compressed_format, quality);
+        };
+        let cache_path = cache_dir.join(file_name);
+
+        if let Ok(cached) =
+            CachedCompressedTexture::read_from_file_with_ctx(ENDIANNESS, &cache_path)
+        {
+            if cached.key == key {
                 return Some(Self {
                     name: self.name.clone(),
-                    uuid: Uuid::new_v4(),
+                    uuid: self.uuid,
                     width: self.width,
                     height: self.height,
-                    mip_count: compressed_data.len() as u32,
-                    format: TextureFormat::Compressed(*compressed_format),
-                    data: compressed_data,
+                    dimension: self.dimension,
+                    format: cached.format,
+                    color_space: self.color_space,
+                    data: cached.data,
+                    mip_offsets: cached.mip_offsets,
                     uv_offset: self.uv_offset,
                     uv_scale: self.uv_scale,
+                    sampler: self.sampler,
+                    reconstruct_normal_z: self.reconstruct_normal_z,
+                    premultiplied_alpha: self.premultiplied_alpha,
+                    usages: self.usages,
+                    source_image: self.source_image.clone(),
                 });
             }
         }
 
-        None
-    }
+        let mut compressed = self.compress_as(compressed_format, quality)?;
+        compressed.uuid = self.uuid;
 
-    #[cfg(not(feature = "intel_tex_2"))]
-    pub fn compress(&self, texture_compression: &TextureCompression) -> Option<Self> {
-        panic!("Failed to compress texture, this requires the 'intel_tex_2' crate feature.");
+        // Written to a unique temporary file first, so concurrent readers never see a partially written texture.
+        // Failing to write the cache only costs time on the next run
+        let cached = CachedCompressedTexture {
+            key,
+            format: compressed.format,
+            data: compressed.data.clone(),
+            mip_offsets: compressed.mip_offsets.clone(),
+        };
+        let temp_path = cache_path.with_extension(format!("{}.tmp", Uuid::new_v4().simple()));
+        let cached = std::fs::create_dir_all(cache_dir).is_ok()
+            && cached
+                .write_to_file_with_ctx(ENDIANNESS, &temp_path)
+                .is_ok()
+            && std::fs::rename(&temp_path, &cache_path).is_ok();
+        if !cached {
+            let _ = std::fs::remove_file(&temp_path);
+        }
+
+        Some(compressed)
     }
 
     #[cfg(feature = "wgpu")]
     pub fn create_wgpu_texture(
         &self,
         usage: wgpu::TextureUsages,
-        srgb: bool,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let (texture, texture_view) = self.create_empty_wgpu_texture(usage, device);
+
+        for (i, mip) in self.gpu_data().mips.iter().enumerate() {
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: i as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                mip.data,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(mip.bytes_per_row),
+                    rows_per_image: Some(mip.rows_per_image),
+                },
+                wgpu::Extent3d {
+                    width: mip.width,
+                    height: mip.height,
+                    depth_or_array_layers: self.layer_count(),
+                },
+            );
+        }
+
+        (texture, texture_view)
+    }
+
+    /// Texture and view matching the format and size of the texture, without uploading any data
+    #[cfg(feature = "wgpu")]
+    pub(crate) fn create_empty_wgpu_texture(
+        &self,
+        usage: wgpu::TextureUsages,
+        device: &wgpu::Device,
     ) -> (wgpu::Texture, wgpu::TextureView) {
         let mut format = self.format.to_wgpu();
-        if srgb {
+        if self.color_space == ColorSpace::Srgb {
             format = format.add_srgb_suffix();
         }
 
@@ -418,9 +1809,9 @@ impl Texture {
             size: wgpu::Extent3d {
                 width: self.width,
                 height: self.height,
-                depth_or_array_layers: 1,
+                depth_or_array_layers: self.layer_count(),
             },
-            mip_level_count: self.mip_count,
+            mip_level_count: self.mip_count(),
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format,
@@ -429,39 +1820,57 @@ impl Texture {
             view_formats: &[],
         });
         let texture_view = texture.create_view(&wgpu::TextureViewDescriptor {
-            dimension: Some(wgpu::TextureViewDimension::D2),
+            dimension: Some(match self.dimension {
+                TextureDimension::D2 => wgpu::TextureViewDimension::D2,
+                TextureDimension::Cube => wgpu::TextureViewDimension::Cube,
+            }),
             ..Default::default()
         });
 
-        let mut mip_width = self.width;
-        let mut mip_height = self.height;
-        for i in 0..self.mip_count {
-            let bytes_per_row = self.format.bytes_per_row(mip_width);
+        (texture, texture_view)
+    }
 
-            queue.write_texture(
-                wgpu::TexelCopyTextureInfo {
-                    texture: &texture,
-                    mip_level: i,
-                    origin: wgpu::Origin3d::ZERO,
-                    aspect: wgpu::TextureAspect::All,
-                },
-                &self.data[i as usize],
-                wgpu::TexelCopyBufferLayout {
-                    offset: 0,
-                    bytes_per_row: Some(bytes_per_row as u32),
-                    rows_per_image: None,
-                },
-                wgpu::Extent3d {
-                    width: mip_width,
-                    height: mip_height,
-                    depth_or_array_layers: 1,
-                },
-            );
+    /// The texture in a representation a device with `features` can sample: unchanged if the device supports the stored format,
+    /// otherwise transcoded to another block compression the device supports or decompressed as last resort.
+    /// Returns `None` if the stored format is unsupported and has no decoder, ASTC isn't a transcode target as there's no ASTC encoder
+    #[cfg(feature = "wgpu")]
+    pub fn for_wgpu_features(
+        &self,
+        features: wgpu::Features,
+        quality: TextureCompressionQuality,
+    ) -> Option<Cow<'_, Self>> {
+        let TextureFormat::Compressed(format) = self.format else {
+            return Some(Cow::Borrowed(self));
+        };
+        if format.is_supported_by(features) {
+            return Some(Cow::Borrowed(self));
+        }
 
-            mip_width = (mip_width / 2).max(1);
-            mip_height = (mip_height / 2).max(1);
+        let decompressed = self.decompress()?;
+        #[cfg(feature = "intel_tex_2")]
+        if let TextureFormat::Uncompressed(uncompressed_format) = decompressed.format {
+            for texture_compression in [TextureCompression::Bc, TextureCompression::Etc2] {
+                let Some(compressed_format) =
+                    uncompressed_format.try_as_compressed(&texture_compression)
+                else {
+                    continue;
+                };
+                if compressed_format.is_supported_by(features) {
+                    return decompressed
+                        .compress_as(*compressed_format, quality)
+                        .map(Cow::Owned);
+                }
+            }
         }
+        #[cfg(not(feature = "intel_tex_2"))]
+        let _ = quality;
 
-        (texture, texture_view)
+        Some(Cow::Owned(decompressed))
+    }
+
+    /// Sampler with the wrap and filter modes of the source asset, see [`WgpuSamplerCache`] to share samplers between textures
+    #[cfg(feature = "wgpu")]
+    pub fn create_wgpu_sampler(&self, device: &wgpu::Device) -> wgpu::Sampler {
+        device.create_sampler(&self.sampler.to_wgpu(Some(&self.name)))
     }
 }
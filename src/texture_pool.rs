@@ -0,0 +1,177 @@
+//! Textures shared between models, for asset libraries where many models sample the same trim sheets or atlases
+//!
+//! A [`TexturePool`] stores every distinct texture once, keyed by its content. Models added to the pool become [`PooledModel`]s,
+//! which reference their textures by uuid and are serialized without texel data.
+//! Both are written as ugm containers, flagged [`FLAG_TEXTURE_POOL`] and [`FLAG_POOLED_MODEL`] so neither is mistaken for the other or for a model
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use speedy::{Endianness, Readable, Writable};
+use uuid::Uuid;
+
+use crate::{
+    serialization::{UgmHeader, ENDIANNESS, FLAG_POOLED_MODEL, FLAG_TEXTURE_POOL},
+    texture::Texture,
+    Model,
+};
+
+/// Distinct textures of any number of models
+#[derive(Debug, Clone, Default)]
+pub struct TexturePool {
+    textures: Vec<Texture>,
+    by_uuid: HashMap<Uuid, usize>,
+    /// Candidates with the same content hash, compared with [`Texture::has_same_content`]
+    by_content_hash: HashMap<u64, Vec<usize>>,
+}
+
+/// Model with `model.textures` left empty, material texture indices refer to `texture_uuids` resolved through a [`TexturePool`]
+#[derive(Debug, Clone, Readable, Writable)]
+pub struct PooledModel {
+    pub model: Model,
+    pub texture_uuids: Vec<Uuid>,
+}
+
+impl TexturePool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+
+    pub fn textures(&self) -> &[Texture] {
+        &self.textures
+    }
+
+    pub fn get(&self, uuid: Uuid) -> Option<&Texture> {
+        self.by_uuid
+            .get(&uuid)
+            .map(|texture_idx| &self.textures[*texture_idx])
+    }
+
+    /// Add `texture` unless the pool holds one with the same content, returns the uuid of the pooled texture.
+    /// New textures get a uuid derived from their content, see [`Texture::set_content_uuid`]
+    pub fn insert(&mut self, mut texture: Texture) -> Uuid {
+        let content_hash = texture.content_hash();
+        let candidates = self.by_content_hash.entry(content_hash).or_default();
+        if let Some(texture_idx) = candidates
+            .iter()
+            .find(|texture_idx| self.textures[**texture_idx].has_same_content(&texture))
+        {
+            return self.textures[*texture_idx].uuid();
+        }
+
+        texture.set_content_uuid();
+        let uuid = texture.uuid();
+        candidates.push(self.textures.len());
+        self.by_uuid.insert(uuid, self.textures.len());
+        self.textures.push(texture);
+        uuid
+    }
+
+    /// Move the textures of `model` into the pool
+    pub fn share(&mut self, mut model: Model) -> PooledModel {
+        let texture_uuids = std::mem::take(&mut model.textures)
+            .into_iter()
+            .map(|texture| self.insert(texture))
+            .collect();
+
+        PooledModel {
+            model,
+            texture_uuids,
+        }
+    }
+
+    /// Standalone model with copies of the pooled textures it references, fails if any of them isn't in the pool
+    pub fn resolve(&self, pooled: &PooledModel) -> Result<Model> {
+        let mut model = pooled.model.clone();
+        model.textures = pooled
+            .texture_uuids
+            .iter()
+            .map(|uuid| {
+                self.get(*uuid).cloned().ok_or_else(|| {
+                    anyhow::anyhow!("Failed to resolve texture {}, it isn't in the pool.", uuid)
+                })
+            })
+            .collect::<Result<_>>()?;
+        model.update_texture_usages();
+        Ok(model)
+    }
+
+    /// Serialize as ugm container flagged [`FLAG_TEXTURE_POOL`], holding all textures in [`ENDIANNESS`] byte order
+    pub fn write_to_bytes(&self) -> Result<Vec<u8>> {
+        write_container(&self.textures, FLAG_TEXTURE_POOL)
+    }
+
+    /// Deserialize a pool written by [`TexturePool::write_to_bytes`], validating its header
+    pub fn read_from_bytes(data: &[u8]) -> Result<Self> {
+        let textures: Vec<Texture> = read_container(data, FLAG_TEXTURE_POOL, "texture pool")?;
+
+        let mut pool = Self::default();
+        for (texture_idx, texture) in textures.iter().enumerate() {
+            pool.by_uuid.insert(texture.uuid(), texture_idx);
+            pool.by_content_hash
+                .entry(texture.content_hash())
+                .or_default()
+                .push(texture_idx);
+        }
+        pool.textures = textures;
+        Ok(pool)
+    }
+}
+
+impl PooledModel {
+    /// Serialize as ugm container flagged [`FLAG_POOLED_MODEL`] in [`ENDIANNESS`] byte order, without any texel data
+    pub fn write_to_bytes(&self) -> Result<Vec<u8>> {
+        write_container(self, FLAG_POOLED_MODEL)
+    }
+
+    /// Deserialize a model written by [`PooledModel::write_to_bytes`], validating its header
+    pub fn read_from_bytes(data: &[u8]) -> Result<Self> {
+        read_container(data, FLAG_POOLED_MODEL, "pooled model")
+    }
+}
+
+fn write_container<T: Writable<Endianness>>(value: &T, flags: u16) -> Result<Vec<u8>> {
+    let header = UgmHeader {
+        flags,
+        ..UgmHeader::new(ENDIANNESS)
+    };
+    let mut data = header.to_bytes().to_vec();
+    value.write_to_stream_with_ctx(ENDIANNESS, &mut data)?;
+    Ok(data)
+}
+
+fn read_container<T: for<'a> Readable<'a, Endianness>>(
+    data: &[u8],
+    flag: u16,
+    kind: &str,
+) -> Result<T> {
+    let header = UgmHeader::from_bytes(data)?;
+    anyhow::ensure!(
+        header.flags & flag != 0,
+        "Failed to read {}, the ugm container holds something else.",
+        kind
+    );
+
+    let data = header.compression.decompress(&data[UgmHeader::SIZE..])?;
+    Ok(T::read_from_buffer_with_ctx(header.endianness, &data)?)
+}
+
+impl Model {
+    /// Parse a glb and move its textures into `pool`, textures already in the pool aren't stored again
+    #[cfg(feature = "gltf")]
+    pub fn parse_glb_pooled(
+        data: &[u8],
+        opt: crate::parser::ParseOptions,
+        pool: &mut TexturePool,
+    ) -> Result<PooledModel> {
+        Ok(pool.share(Self::parse_glb(data, opt)?))
+    }
+}
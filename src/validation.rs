@@ -0,0 +1,315 @@
+//! Structural checks of models and meshes, letting pipelines reject bad assets before they crash downstream consumers
+
+use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+
+use crate::{mesh::Mesh, Model};
+
+/// Largest absolute cosine between a tangent and its normal still considered perpendicular, allows for packing error
+const MAX_TANGENT_NORMAL_COSINE: f32 = 0.01;
+/// Distance relative to the bounds extent a position may lie outside its bounds, allows for rounding error
+const BOUNDS_TOLERANCE: f32 = 1e-4;
+
+/// Problems found by [`Model::validate`], nodes, meshes and materials are referenced by index
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModelValidationReport {
+    /// Root node indices past the end of the nodes
+    pub out_of_range_root_nodes: Vec<u32>,
+    /// Nodes with a child index past the end of the nodes
+    pub out_of_range_child_nodes: Vec<u32>,
+    /// Nodes with a mesh index past the end of the meshes
+    pub out_of_range_node_meshes: Vec<u32>,
+    /// Meshes with a material index past the end of the materials
+    pub out_of_range_mesh_materials: Vec<u32>,
+    /// Materials with a texture index past the end of the textures
+    pub out_of_range_material_textures: Vec<u32>,
+    /// Nodes that are their own ancestor
+    pub cyclic_nodes: Vec<u32>,
+    /// Nodes referenced more than once as root or child, which are traversed once per reference
+    pub shared_nodes: Vec<u32>,
+    /// Meshes with non finite bounds or vertices outside their bounds
+    pub invalid_mesh_bounds: Vec<u32>,
+    /// The model bounds are non finite or don't enclose all mesh instances, only checked for a valid hierarchy
+    pub invalid_model_bounds: bool,
+    /// Reports of all meshes failing [`Mesh::validate`]
+    pub meshes: Vec<(u32, MeshValidationReport)>,
+}
+
+impl ModelValidationReport {
+    pub fn is_valid(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Model {
+    /// Check all index references, the node hierarchy, bounds and every mesh for inconsistencies
+    pub fn validate(&self) -> ModelValidationReport {
+        let mut report = ModelValidationReport::default();
+        let num_nodes = self.nodes.len();
+
+        let mut reference_counts = vec![0u32; num_nodes];
+        for node_idx in &self.root_node_indices {
+            match reference_counts.get_mut(*node_idx as usize) {
+                Some(count) => *count += 1,
+                None => report.out_of_range_root_nodes.push(*node_idx),
+            }
+        }
+        for (node_idx, node) in self.nodes.iter().enumerate() {
+            for child_idx in &node.child_node_indices {
+                match reference_counts.get_mut(*child_idx as usize) {
+                    Some(count) => *count += 1,
+                    None => report.out_of_range_child_nodes.push(node_idx as u32),
+                }
+            }
+            if node
+                .mesh_idx
+                .is_some_and(|mesh_idx| mesh_idx as usize >= self.meshes.len())
+            {
+                report.out_of_range_node_meshes.push(node_idx as u32);
+            }
+        }
+        report.out_of_range_child_nodes.dedup();
+        report.shared_nodes = (0..num_nodes as u32)
+            .filter(|node_idx| reference_counts[*node_idx as usize] > 1)
+            .collect();
+        report.cyclic_nodes = self.cyclic_nodes();
+
+        for (mesh_idx, mesh) in self.meshes.iter().enumerate() {
+            if mesh
+                .material_indices
+                .iter()
+                .any(|material_idx| *material_idx as usize >= self.materials.len())
+            {
+                report.out_of_range_mesh_materials.push(mesh_idx as u32);
+            }
+            if !mesh.has_valid_bounds() {
+                report.invalid_mesh_bounds.push(mesh_idx as u32);
+            }
+
+            let mesh_report = mesh.validate();
+            if !mesh_report.is_valid() {
+                report.meshes.push((mesh_idx as u32, mesh_report));
+            }
+        }
+        for (material_idx, material) in self.materials.iter().enumerate() {
+            if material
+                .textures()
+                .into_iter()
+                .flatten()
+                .any(|texture_idx| texture_idx as usize >= self.textures.len())
+            {
+                report
+                    .out_of_range_material_textures
+                    .push(material_idx as u32);
+            }
+        }
+
+        // Traversal isn't possible with dangling references or cycles
+        let valid_hierarchy = report.out_of_range_root_nodes.is_empty()
+            && report.out_of_range_child_nodes.is_empty()
+            && report.out_of_range_node_meshes.is_empty()
+            && report.cyclic_nodes.is_empty();
+        if valid_hierarchy {
+            let (bounds_min, bounds_max) = (
+                Vec3::from_array(self.bounds_min),
+                Vec3::from_array(self.bounds_max),
+            );
+            let (world_min, world_max) = self.compute_world_bounds(Mat4::IDENTITY);
+            let tolerance = (bounds_max - bounds_min).max_element() * BOUNDS_TOLERANCE;
+            report.invalid_model_bounds = !bounds_min.is_finite()
+                || !bounds_max.is_finite()
+                || Vec3::from_array(world_min)
+                    .cmplt(bounds_min - tolerance)
+                    .any()
+                || Vec3::from_array(world_max)
+                    .cmpgt(bounds_max + tolerance)
+                    .any();
+        }
+
+        report
+    }
+
+    /// Nodes on a cycle of child references, found by a depth first search from every node
+    fn cyclic_nodes(&self) -> Vec<u32> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            Unvisited,
+            OnStack,
+            Done,
+        }
+
+        let mut states = vec![State::Unvisited; self.nodes.len()];
+        let mut is_cyclic = vec![false; self.nodes.len()];
+        for start_idx in 0..self.nodes.len() {
+            if states[start_idx] != State::Unvisited {
+                continue;
+            }
+
+            // Node index and the position of its next child to visit
+            let mut stack = vec![(start_idx, 0)];
+            states[start_idx] = State::OnStack;
+            while let Some((node_idx, child_pos)) = stack.last_mut() {
+                let node_idx = *node_idx;
+                let Some(child_idx) = self.nodes[node_idx].child_node_indices.get(*child_pos)
+                else {
+                    states[node_idx] = State::Done;
+                    stack.pop();
+                    continue;
+                };
+                *child_pos += 1;
+
+                let child_idx = *child_idx as usize;
+                match states.get(child_idx) {
+                    Some(State::Unvisited) => {
+                        states[child_idx] = State::OnStack;
+                        stack.push((child_idx, 0));
+                    }
+                    Some(State::OnStack) => {
+                        // Every node on the stack from the child onwards is part of the cycle
+                        let cycle_start =
+                            stack.iter().position(|(idx, _)| *idx == child_idx).unwrap();
+                        for (idx, _) in &stack[cycle_start..] {
+                            is_cyclic[*idx] = true;
+                        }
+                    }
+                    Some(State::Done) | None => {}
+                }
+            }
+        }
+
+        (0..self.nodes.len() as u32)
+            .filter(|node_idx| is_cyclic[*node_idx as usize])
+            .collect()
+    }
+}
+
+impl Mesh {
+    /// Whether the bounds are finite and enclose every vertex
+    fn has_valid_bounds(&self) -> bool {
+        let (bounds_min, bounds_max) = (
+            Vec3::from_array(self.bounds_min),
+            Vec3::from_array(self.bounds_max),
+        );
+        if !bounds_min.is_finite() || !bounds_max.is_finite() {
+            return false;
+        }
+
+        let tolerance = (bounds_max - bounds_min).max_element() * BOUNDS_TOLERANCE;
+        (0..self.num_vertices() as u32).all(|vertex_idx| {
+            let position = self.vertex_position(vertex_idx);
+            // Non finite positions are reported by `Mesh::validate`
+            !position.is_finite()
+                || (position.cmpge(bounds_min - tolerance).all()
+                    && position.cmple(bounds_max + tolerance).all())
+        })
+    }
+}
+
+/// Problems found by [`Mesh::validate`], vertices and triangles are referenced by index
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MeshValidationReport {
+    /// Positions in `indices` holding a vertex index past the end of the vertices
+    pub out_of_range_indices: Vec<u32>,
+    /// The index count is not a multiple of three
+    pub incomplete_triangle: bool,
+    /// Vertices with a nan or infinite position
+    pub non_finite_positions: Vec<u32>,
+    /// Vertices with a zero length or non finite normal
+    pub zero_length_normals: Vec<u32>,
+    /// Vertices with a tangent that isn't perpendicular to the normal
+    pub tangent_normal_mismatches: Vec<u32>,
+    /// Triangle count and `triangle_material_indices` count, if they differ
+    pub material_count_mismatch: Option<(usize, usize)>,
+    /// Triangles whose material index is out of range of `material_indices`
+    pub invalid_triangle_materials: Vec<u32>,
+}
+
+impl MeshValidationReport {
+    pub fn is_valid(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Mesh {
+    /// Check indices, vertices and material assignments for inconsistencies, full precision vertices are checked when present
+    pub fn validate(&self) -> MeshValidationReport {
+        let mut report = MeshValidationReport::default();
+        let num_vertices = self.num_vertices();
+
+        for (idx, index) in self.indices.iter().enumerate() {
+            if index as usize >= num_vertices {
+                report.out_of_range_indices.push(idx as u32);
+            }
+        }
+        report.incomplete_triangle = !self.indices.len().is_multiple_of(3);
+
+        let vertices: Vec<(Vec3, Vec3, Vec4)> = if !self.unpacked_vertices.is_empty() {
+            self.unpacked_vertices
+                .iter()
+                .map(|vertex| {
+                    (
+                        Vec3::from_array(vertex.position),
+                        Vec3::from_array(vertex.normal),
+                        Vec4::from_array(vertex.tangent),
+                    )
+                })
+                .collect()
+        } else if let Some(quantized) = &self.quantized_vertices {
+            quantized
+                .vertices
+                .iter()
+                .map(|vertex| {
+                    (
+                        quantized.position(vertex),
+                        vertex.normal.unpack(),
+                        vertex.tangent.unpack().extend(1.0),
+                    )
+                })
+                .collect()
+        } else {
+            self.packed_vertices
+                .iter()
+                .map(|vertex| {
+                    (
+                        Vec3::from_array(vertex.position),
+                        vertex.normal.unpack(),
+                        vertex.tangent.unpack().extend(vertex.tangent_handiness),
+                    )
+                })
+                .collect()
+        };
+
+        for (vertex_idx, (position, normal, tangent)) in vertices.iter().enumerate() {
+            if !position.is_finite() {
+                report.non_finite_positions.push(vertex_idx as u32);
+            }
+
+            let normal_length = normal.length();
+            if !normal_length.is_finite() || normal_length < f32::EPSILON {
+                report.zero_length_normals.push(vertex_idx as u32);
+                continue;
+            }
+
+            let tangent = tangent.xyz();
+            if tangent != Vec3::ZERO
+                && (!tangent.is_finite()
+                    || (normal / normal_length).dot(tangent.normalize()).abs()
+                        > MAX_TANGENT_NORMAL_COSINE)
+            {
+                report.tangent_normal_mismatches.push(vertex_idx as u32);
+            }
+        }
+
+        let num_triangles = self.indices.len() / 3;
+        if self.triangle_material_indices.len() != num_triangles {
+            report.material_count_mismatch =
+                Some((num_triangles, self.triangle_material_indices.len()));
+        }
+        for (triangle_idx, material_idx) in self.triangle_material_indices.iter().enumerate() {
+            if *material_idx as usize >= self.material_indices.len() {
+                report.invalid_triangle_materials.push(triangle_idx as u32);
+            }
+        }
+
+        report
+    }
+}
@@ -2,10 +2,23 @@
 mod tests {
     use std::hint::black_box;
 
+    use glam::{Mat4, Vec2, Vec3, Vec4};
     use speedy::{Readable, Writable};
     use ugm::{
-        parser::{MaxTextureResolution, ParseOptions},
-        texture::TextureCompression,
+        edit::EditableModel,
+        exporter::ExportSubset,
+        material::{AlphaMode, Material},
+        mesh::{pack_vertices, repair_tangents, DepthSortHint, Indices, Mesh, SortKey},
+        optimize::MeshOptimizeOptions,
+        parser::{GeometryOptions, MaxTextureResolution, ParseError, ParseOptions, TextureOptions},
+        shadow_proxy::ShadowProxyDesc,
+        stats::SectionKind,
+        texture::{
+            ColorSpace, CompressedTextureFormat, MipFilter, Texture, TextureCompression,
+            TextureCompressionProfile, TextureCreateDesc, TextureDimension, TextureFormat,
+            TextureUsage,
+        },
+        texture_pool::{PooledModel, TexturePool},
         Model,
     };
 
@@ -34,6 +47,441 @@ mod tests {
         let _ = Model::read_from_buffer(&serialized).unwrap();
     }
 
+    #[test]
+    fn sixteen_bit_indices() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        for mesh in &model.meshes {
+            let is_16_bit = mesh.packed_vertices.len() <= u16::MAX as usize;
+            assert_eq!(matches!(mesh.indices, Indices::U16(_)), is_16_bit);
+            assert_eq!(
+                mesh.indices.as_bytes().len(),
+                mesh.indices.len() * mesh.indices.index_size()
+            );
+        }
+
+        let indices: Vec<u32> = (0..70_000).collect();
+        assert!(matches!(
+            Indices::new(indices.clone(), 70_000),
+            Indices::U32(_)
+        ));
+        let indices = Indices::new(indices[..3000].to_vec(), 3000);
+        assert!(matches!(indices, Indices::U16(_)));
+        assert_eq!(indices.triangle(999), [2997, 2998, 2999]);
+        assert!(indices.iter().eq(0..3000));
+    }
+
+    #[test]
+    fn quantized_positions() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+        let mut quantized = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                quantize_positions: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(quantized.write_to_bytes().unwrap().len() < model.write_to_bytes().unwrap().len());
+
+        for mesh in quantized.meshes.iter().filter(|mesh| !mesh.is_empty()) {
+            assert!(mesh.packed_vertices.is_empty());
+            assert_eq!(mesh.gpu_data().vertex_stride, 24);
+        }
+
+        quantized.dequantize_positions();
+        for (mesh, quantized) in model.meshes.iter().zip(&quantized.meshes) {
+            let extent = Vec3::from_array(mesh.bounds_max) - Vec3::from_array(mesh.bounds_min);
+            let max_error = quantized.quantization_error.max_position_error;
+            assert!(max_error <= extent.length() / u16::MAX as f32);
+
+            assert_eq!(mesh.packed_vertices.len(), quantized.packed_vertices.len());
+            for (vertex, quantized) in mesh.packed_vertices.iter().zip(&quantized.packed_vertices) {
+                let error = Vec3::from_array(vertex.position)
+                    .distance(Vec3::from_array(quantized.position));
+                assert!(error <= max_error + 1e-6);
+                assert_eq!(
+                    vertex.tangent_handiness.signum(),
+                    quantized.tangent_handiness
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn parse_glb_async() {
+        use std::{
+            future::Future,
+            sync::{
+                atomic::{AtomicBool, Ordering},
+                mpsc, Arc, Mutex,
+            },
+            task::{Context, Poll, Wake, Waker},
+            thread::Thread,
+        };
+
+        struct ThreadWaker(Thread);
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let cancel = Arc::new(AtomicBool::new(false));
+        let mut task = Box::pin(Model::parse_glb_async(
+            model_bytes.to_vec(),
+            ParseOptions {
+                cancel: Some(cancel.clone()),
+                ..Default::default()
+            },
+        ));
+
+        let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let model = loop {
+            match task.as_mut().poll(&mut cx) {
+                Poll::Ready(model) => break model.unwrap(),
+                Poll::Pending => std::thread::park(),
+            }
+        };
+        drop(task);
+        assert!(!cancel.load(Ordering::Relaxed));
+
+        let expected = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+        assert_eq!(model.meshes.len(), expected.meshes.len());
+        assert_eq!(model.textures.len(), expected.textures.len());
+
+        // Dropping a pending task cancels it, parsing is held at its first progress report until then
+        let (started_sender, started) = mpsc::channel();
+        let (resume, resumed) = mpsc::channel::<()>();
+        let (started_sender, resumed) = (Mutex::new(started_sender), Mutex::new(resumed));
+        let task = Model::parse_glb_async(
+            model_bytes.to_vec(),
+            ParseOptions {
+                cancel: Some(cancel.clone()),
+                progress: Some(Arc::new(move |_| {
+                    if started_sender.lock().unwrap().send(()).is_ok() {
+                        let _ = resumed.lock().unwrap().recv();
+                    }
+                })),
+                ..Default::default()
+            },
+        );
+        started.recv().unwrap();
+        drop(task);
+        assert!(cancel.load(Ordering::Relaxed));
+        drop(started);
+        drop(resume);
+    }
+
+    #[test]
+    fn parse_progress() {
+        use std::sync::{Arc, Mutex};
+        use ugm::parser::{ParseProgress, ParseStage};
+
+        let reports: Arc<Mutex<Vec<ParseProgress>>> = Arc::default();
+        let progress_reports = reports.clone();
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                progress: Some(Arc::new(move |progress| {
+                    progress_reports.lock().unwrap().push(progress)
+                })),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let reports = reports.lock().unwrap();
+        for stage in [
+            ParseStage::Images,
+            ParseStage::Textures,
+            ParseStage::Nodes,
+            ParseStage::Meshes,
+        ] {
+            let stage_reports: Vec<_> = reports.iter().filter(|r| r.stage == stage).collect();
+            assert_eq!(stage_reports.first().unwrap().completed, 0);
+            let last = stage_reports.last().unwrap();
+            assert_eq!(last.completed, last.total);
+        }
+        let nodes = reports.iter().rev().find(|r| r.stage == ParseStage::Nodes);
+        assert_eq!(nodes.unwrap().total, model.nodes.len());
+    }
+
+    #[test]
+    fn parse_cancellation() {
+        use std::sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        };
+        use ugm::parser::{ParseError, ParseStage};
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let progress_cancel = cancel.clone();
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let error = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                // Cancel as soon as the first texture is done
+                progress: Some(Arc::new(move |progress| {
+                    if progress.stage == ParseStage::Textures && progress.completed > 0 {
+                        progress_cancel.store(true, Ordering::Relaxed);
+                    }
+                })),
+                cancel: Some(cancel),
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ParseError>(),
+            Some(ParseError::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn parse_from_reader() {
+        use std::io::Cursor;
+        use ugm::serialization::Compression;
+
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let expected = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+        let model =
+            Model::from_reader(&mut Cursor::new(model_bytes), ParseOptions::default()).unwrap();
+        assert_eq!(model.nodes.len(), expected.nodes.len());
+        for (mesh, expected) in model.meshes.iter().zip(&expected.meshes) {
+            assert_eq!(mesh.indices, expected.indices);
+            assert_eq!(
+                bytemuck::cast_slice::<_, u8>(&mesh.packed_vertices),
+                bytemuck::cast_slice::<_, u8>(&expected.packed_vertices)
+            );
+        }
+
+        // Containers don't need to start at the beginning of the stream
+        let mut container = vec![0; 5];
+        expected
+            .write_chunked(&mut container, Compression::None)
+            .unwrap();
+        let mut reader = Cursor::new(&container);
+        reader.set_position(5);
+        let model = Model::from_reader(&mut reader, ParseOptions::default()).unwrap();
+        assert_eq!(model.meshes.len(), expected.meshes.len());
+    }
+
+    #[test]
+    fn portable_serialization() {
+        use ugm::speedy::Endianness;
+
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        let serialized = model.write_to_bytes().unwrap();
+        let deserialized = Model::read_from_bytes(&serialized).unwrap();
+        assert_eq!(deserialized.write_to_bytes().unwrap(), serialized);
+
+        let big_endian = model.write_with_endianness(Endianness::BigEndian).unwrap();
+        assert_ne!(big_endian, serialized);
+        let deserialized = Model::read_with_endianness(&big_endian, Endianness::BigEndian).unwrap();
+        assert_eq!(deserialized.write_to_bytes().unwrap(), serialized);
+    }
+
+    #[test]
+    fn portable_texel_data() {
+        use ugm::{speedy::Endianness, texture::UncompressedTextureFormat};
+
+        let image = image::ImageBuffer::from_fn(4, 4, |x, y| {
+            let value = 0x1200 + (y * 4 + x) as u16;
+            image::Rgba([value, value + 0x100, value + 0x200, u16::MAX])
+        });
+        let texture = Texture::new(TextureCreateDesc {
+            name: None,
+            image: image::DynamicImage::ImageRgba16(image),
+            mips: false,
+            mip_filter: Default::default(),
+            max_mip_levels: None,
+            min_mip_size: None,
+            precomputed_mips: vec![],
+            is_normal_map: false,
+            color_space: ColorSpace::Linear,
+            alpha_coverage_cutoff: None,
+            premultiply_alpha: false,
+            uv_offset: [0.0; 2],
+            uv_scale: [1.0; 2],
+            sampler: Default::default(),
+            source_image: None,
+        });
+        assert_eq!(
+            texture.format(),
+            TextureFormat::Uncompressed(UncompressedTextureFormat::Rgba16Unorm)
+        );
+
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let mut model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+        model.textures.push(texture.clone());
+
+        // Each channel is stored in the requested byte order, regardless of the host
+        let channels: &[u16] = bytemuck::cast_slice(texture.data());
+        let texel_bytes = |to_bytes: fn(u16) -> [u8; 2]| -> Vec<u8> {
+            channels
+                .iter()
+                .flat_map(|channel| to_bytes(*channel))
+                .collect()
+        };
+        for (endianness, to_bytes) in [
+            (
+                Endianness::BigEndian,
+                u16::to_be_bytes as fn(u16) -> [u8; 2],
+            ),
+            (Endianness::LittleEndian, u16::to_le_bytes),
+        ] {
+            let serialized = model.write_with_endianness(endianness).unwrap();
+            let expected = texel_bytes(to_bytes);
+            assert!(serialized
+                .windows(expected.len())
+                .any(|window| window == expected));
+
+            let deserialized = Model::read_with_endianness(&serialized, endianness).unwrap();
+            let round_tripped = deserialized.textures.last().unwrap();
+            assert_eq!(round_tripped.format(), texture.format());
+            assert_eq!(round_tripped.data(), texture.data());
+        }
+    }
+
+    #[test]
+    fn ugm_container() {
+        use ugm::serialization::{UgmHeader, FORMAT_VERSION};
+
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        let mut container = vec![];
+        model.write_ugm(&mut container).unwrap();
+        let deserialized = Model::read_ugm(&mut container.as_slice()).unwrap();
+        assert_eq!(
+            deserialized.write_to_bytes().unwrap(),
+            model.write_to_bytes().unwrap()
+        );
+
+        assert!(Model::read_ugm(&mut model.write_to_bytes().unwrap().as_slice()).is_err());
+
+        #[cfg(feature = "zstd")]
+        {
+            let mut compressed = vec![];
+            model
+                .write_ugm_compressed(&mut compressed, ugm::serialization::Compression::Zstd)
+                .unwrap();
+            let deserialized = Model::read_ugm(&mut compressed.as_slice()).unwrap();
+            assert_eq!(
+                deserialized.write_to_bytes().unwrap(),
+                model.write_to_bytes().unwrap()
+            );
+        }
+
+        #[cfg(feature = "lz4")]
+        {
+            let mut compressed = vec![];
+            model
+                .write_ugm_compressed(&mut compressed, ugm::serialization::Compression::Lz4)
+                .unwrap();
+            assert_eq!(
+                UgmHeader::from_bytes(&compressed).unwrap().compression,
+                ugm::serialization::Compression::Lz4
+            );
+            let deserialized = Model::read_ugm(&mut compressed.as_slice()).unwrap();
+            assert_eq!(
+                deserialized.write_to_bytes().unwrap(),
+                model.write_to_bytes().unwrap()
+            );
+        }
+
+        container[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        let error = UgmHeader::from_bytes(&container).unwrap_err();
+        assert!(error.to_string().contains("format version"));
+    }
+
+    #[test]
+    fn chunked_container() {
+        use std::io::Cursor;
+        use ugm::{
+            chunked::ChunkedReader,
+            serialization::{Compression, UgmHeader, ENDIANNESS},
+        };
+
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        let mut container = vec![];
+        model
+            .write_chunked(&mut container, Compression::None)
+            .unwrap();
+
+        let mut reader = ChunkedReader::new(Cursor::new(&container)).unwrap();
+        assert_eq!(reader.scene().nodes.len(), model.nodes.len());
+        assert_eq!(reader.mesh_count(), model.meshes.len());
+        let mesh_idx = model.meshes.len() - 1;
+        assert_eq!(
+            reader.read_mesh(mesh_idx).unwrap().write_to_vec().unwrap(),
+            model.meshes[mesh_idx].write_to_vec().unwrap()
+        );
+        assert!(reader.read_texture(model.textures.len()).is_err());
+
+        let deserialized = Model::read_ugm(&mut container.as_slice()).unwrap();
+        assert_eq!(
+            deserialized.write_to_bytes().unwrap(),
+            model.write_to_bytes().unwrap()
+        );
+
+        // Corrupt sizes fail before allocating
+        let table_start = UgmHeader::SIZE + 8;
+        let mut table = reader.table().clone();
+        table.meshes[0].size = u64::MAX / 2;
+        let table = table.write_to_vec_with_ctx(ENDIANNESS).unwrap();
+        let mut corrupt = container.clone();
+        corrupt[table_start..table_start + table.len()].copy_from_slice(&table);
+        let mut reader = ChunkedReader::new(Cursor::new(&corrupt)).unwrap();
+        assert!(reader.read_mesh(0).is_err());
+        assert!(reader.read_mesh(1).is_ok());
+
+        let mut corrupt = container.clone();
+        corrupt[UgmHeader::SIZE..table_start].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(ChunkedReader::new(Cursor::new(&corrupt)).is_err());
+    }
+
+    #[test]
+    fn lazy_textures() {
+        use std::io::Cursor;
+        use ugm::serialization::Compression;
+
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+        let mut container = vec![];
+        model
+            .write_chunked(&mut container, Compression::None)
+            .unwrap();
+
+        let mut lazy = Model::read_lazy(Cursor::new(&container)).unwrap();
+        assert!(lazy.model.textures.is_empty());
+        assert_eq!(lazy.model.meshes.len(), model.meshes.len());
+        assert_eq!(lazy.textures.len(), model.textures.len());
+        assert!(lazy.textures.iter().all(|handle| !handle.is_loaded()));
+        assert_eq!(lazy.textures[0].info(), &model.textures[0].info());
+
+        let texture = lazy.load_texture(0).unwrap();
+        assert_eq!(texture.data(), model.textures[0].data());
+        assert!(lazy.textures[0].is_loaded() && !lazy.textures[1].is_loaded());
+
+        let loaded = lazy.into_model().unwrap();
+        assert_eq!(
+            loaded.write_to_bytes().unwrap(),
+            model.write_to_bytes().unwrap()
+        );
+    }
+
     #[test]
     fn bc_texture_compression() {
         let model_bytes = include_bytes!("ToyCar.glb");
@@ -44,6 +492,7 @@ mod tests {
                 generate_mips: false,
                 max_texture_resolution: Some(MaxTextureResolution::Res1024),
                 merge_duplicate_meshes: true,
+                ..Default::default()
             },
         )
         .unwrap();
@@ -56,39 +505,3245 @@ mod tests {
     }
 
     #[test]
-    fn mip_generation() {
+    fn bc_texture_compression_cache() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let cache_dir = std::env::temp_dir().join(format!(
+            "ugm_texture_cache_test_{}",
+            uuid::Uuid::new_v4().simple()
+        ));
+        let opt = ParseOptions {
+            texture_compression: Some(TextureCompression::Bc),
+            texture_cache_dir: Some(cache_dir.clone()),
+            ..Default::default()
+        };
+        let cached_files = || {
+            let mut files: Vec<_> = std::fs::read_dir(&cache_dir)
+                .unwrap()
+                .map(|entry| {
+                    let entry = entry.unwrap();
+                    (
+                        entry.file_name(),
+                        entry.metadata().unwrap().modified().unwrap(),
+                    )
+                })
+                .collect();
+            files.sort();
+            files
+        };
+
+        let model = Model::parse_glb(model_bytes, opt.clone()).unwrap();
+        let files = cached_files();
+        assert!(!files.is_empty() && files.len() <= model.textures.len());
+        assert!(files
+            .iter()
+            .all(|(name, _)| name.to_string_lossy().ends_with(".ugmtex")));
+
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        let cached_model = Model::parse_glb(model_bytes, opt).unwrap();
+        // Cache hits don't rewrite their files
+        assert_eq!(cached_files(), files);
+
+        for (texture, cached_texture) in model.textures.iter().zip(&cached_model.textures) {
+            assert_eq!(texture.format(), cached_texture.format());
+            assert_eq!(texture.data(), cached_texture.data());
+        }
+
+        std::fs::remove_dir_all(&cache_dir).unwrap();
+    }
+
+    #[test]
+    fn usage_based_texture_compression() {
         let model_bytes = include_bytes!("ToyCar.glb");
         let model = Model::parse_glb(
             model_bytes,
             ParseOptions {
-                texture_compression: None,
-                generate_mips: true,
+                texture_compression: Some(TextureCompression::Bc),
+                texture_compression_profile: TextureCompressionProfile::Size,
                 ..Default::default()
             },
         )
         .unwrap();
 
-        let serialized = model.write_to_vec().unwrap();
-        let compression_rate = serialized.len() as f32 / model_bytes.len() as f32;
-        println!("Compression rate: {}", compression_rate);
+        for material in &model.materials {
+            if let Some(normal_texture) = material.normal_texture {
+                assert!(model.textures[normal_texture as usize].reconstruct_normal_z());
+            }
+        }
     }
 
     #[test]
-    fn bc_compressed_mips() {
+    fn texture_color_space() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        for material in &model.materials {
+            if let Some(color_texture) = material.color_texture {
+                assert_eq!(
+                    model.textures[color_texture as usize].color_space(),
+                    ColorSpace::Srgb
+                );
+            }
+            if let Some(normal_texture) = material.normal_texture {
+                assert_eq!(
+                    model.textures[normal_texture as usize].color_space(),
+                    ColorSpace::Linear
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn texture_usages() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        for material in &model.materials {
+            for (usage, texture_idx) in TextureUsage::ALL.into_iter().zip(material.textures()) {
+                if let Some(texture_idx) = texture_idx {
+                    assert!(model.textures[texture_idx as usize]
+                        .usages()
+                        .contains(usage));
+                }
+            }
+        }
+
+        let (material_idx, material) = model
+            .materials
+            .iter()
+            .enumerate()
+            .find(|(_, material)| material.normal_texture.is_some())
+            .unwrap();
+        let normal_texture = material.normal_texture.unwrap() as usize;
+        let material = ugm::material::Material {
+            normal_texture: None,
+            ..material.clone()
+        };
+        let mut model = EditableModel::new(model);
+        model.replace_material(material_idx as u32, material);
+        let is_normal_map = model
+            .materials
+            .iter()
+            .any(|material| material.normal_texture == Some(normal_texture as u32));
+        assert_eq!(
+            model.textures[normal_texture].usages().is_normal_map(),
+            is_normal_map
+        );
+    }
+
+    #[test]
+    fn two_channel_normal_maps() {
         let model_bytes = include_bytes!("ToyCar.glb");
         let model = Model::parse_glb(
             model_bytes,
             ParseOptions {
                 texture_compression: Some(TextureCompression::Bc),
-                generate_mips: true,
+                two_channel_normal_maps: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        for material in &model.materials {
+            if let Some(normal_texture) = material.normal_texture {
+                let texture = &model.textures[normal_texture as usize];
+                assert!(texture.reconstruct_normal_z());
+                assert_eq!(
+                    texture.format(),
+                    TextureFormat::Compressed(CompressedTextureFormat::Bc5RgUnorm)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn per_usage_max_texture_resolution() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                max_texture_resolution: Some(MaxTextureResolution::Res1024),
+                max_texture_resolution_per_usage: [(
+                    TextureUsage::Normal,
+                    MaxTextureResolution::Res256,
+                )]
+                .into(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        for material in &model.materials {
+            if let Some(normal_texture) = material.normal_texture {
+                let texture = &model.textures[normal_texture as usize];
+                assert!(texture.width() <= 256 && texture.height() <= 256);
+            }
+            if let Some(color_texture) = material.color_texture {
+                let texture = &model.textures[color_texture as usize];
+                assert!(texture.width() <= 1024 && texture.height() <= 1024);
+            }
+        }
+    }
+
+    #[test]
+    fn max_source_texture_resolution() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let error = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                max_source_texture_resolution: Some(512),
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref::<ParseError>(),
+            Some(ParseError::TextureTooLarge {
+                max_resolution: 512,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn srgb_downscale_preserves_brightness() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                max_texture_resolution: Some(MaxTextureResolution::Res256),
+                keep_source_images: true,
                 ..Default::default()
             },
         )
         .unwrap();
 
+        let mean_linear = |image: &image::RgbaImage| {
+            let srgb_to_linear = |value: u8| (value as f32 / 255.0).powf(2.2);
+            let sum: f32 = image
+                .pixels()
+                .map(|pixel| {
+                    pixel.0[..3]
+                        .iter()
+                        .map(|value| srgb_to_linear(*value))
+                        .sum::<f32>()
+                })
+                .sum();
+            sum / (image.width() * image.height() * 3) as f32
+        };
+
+        for material in &model.materials {
+            let Some(color_texture) = material.color_texture else {
+                continue;
+            };
+            let texture = &model.textures[color_texture as usize];
+            let source = image::load_from_memory(&texture.source_image().unwrap().data)
+                .unwrap()
+                .to_rgba8();
+            if source.width() <= texture.width() {
+                continue;
+            }
+
+            let downscaled = texture.to_image(0).unwrap().to_rgba8();
+            let source_mean = mean_linear(&source);
+            let downscaled_mean = mean_linear(&downscaled);
+            assert!((source_mean - downscaled_mean).abs() < source_mean * 0.02);
+        }
+    }
+
+    #[test]
+    fn lossless_round_trip() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::lossless()).unwrap();
+
         let serialized = model.write_to_vec().unwrap();
-        let compression_rate = serialized.len() as f32 / model_bytes.len() as f32;
-        println!("Compression rate: {}", compression_rate);
+        let deserialized = Model::read_from_buffer(&serialized).unwrap();
+
+        for (mesh, deserialized_mesh) in model.meshes.iter().zip(&deserialized.meshes) {
+            assert_eq!(mesh.packed_vertices.len(), mesh.unpacked_vertices.len());
+            assert_eq!(
+                bytemuck::cast_slice::<_, u8>(&mesh.unpacked_vertices),
+                bytemuck::cast_slice::<_, u8>(&deserialized_mesh.unpacked_vertices)
+            );
+        }
+
+        // Authored tangents are stored as is, only the packed vertices are repaired
+        #[cfg(feature = "test-util")]
+        {
+            use ugm::test_util::{GlbBuilder, SyntheticMesh};
+
+            let source_tangents = vec![
+                [1.0, 0.0, 0.0, 1.0],
+                [1.0, 0.0, 0.5, 1.0],
+                [0.0, 0.0, 1.0, -1.0],
+            ];
+            let glb = GlbBuilder::single_mesh(SyntheticMesh {
+                normals: vec![[0.0, 0.0, 1.0]; 3],
+                tangents: source_tangents.clone(),
+                ..SyntheticMesh::triangle()
+            })
+            .unwrap();
+            let model = Model::parse_glb(&glb, ParseOptions::lossless()).unwrap();
+            let mesh = &model.meshes[0];
+            assert!(!mesh.tangent_repair.non_orthogonal.is_empty());
+            assert_eq!(
+                mesh.unpacked_vertices
+                    .iter()
+                    .map(|vertex| vertex.tangent)
+                    .collect::<Vec<_>>(),
+                source_tangents
+            );
+        }
+
+        for texture in &deserialized.textures {
+            let source_image = texture.source_image().unwrap();
+            assert!(model_bytes
+                .windows(source_image.data.len())
+                .any(|window| window == source_image.data));
+        }
+    }
+
+    #[test]
+    fn tangent_repair() {
+        let positions = [Vec3::ZERO, Vec3::X, Vec3::Y];
+        let normals = [Vec3::Z; 3];
+        let tex_coords = [Vec2::ZERO, Vec2::X, Vec2::Y];
+        let indices = [0, 1, 2];
+        let mut tangents = [
+            Vec4::new(1.0, 0.0, 0.0, 1.0),
+            Vec4::new(1.0, 0.0, 0.5, 1.0),
+            Vec4::new(0.0, 0.0, 0.0, -1.0),
+        ];
+
+        let report = repair_tangents(&normals, &mut tangents, &tex_coords, &positions, &indices);
+        assert_eq!(report.non_orthogonal, vec![1]);
+        assert_eq!(report.degenerate, vec![2]);
+        assert_eq!(report.flipped_handedness, vec![2]);
+
+        for tangent in tangents {
+            assert!(tangent.truncate().dot(Vec3::Z).abs() < 1e-5);
+            assert!((tangent.truncate().length() - 1.0).abs() < 1e-5);
+            assert_eq!(tangent.w, 1.0);
+        }
+    }
+
+    #[test]
+    fn quantization_error_stats() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        let stats = model.stats();
+        assert_eq!(stats.meshes.len(), model.meshes.len());
+        assert!(stats.max_quantization_error.max_normal_error < 0.001);
+        assert!(stats.max_quantization_error.max_tangent_error < 0.001);
+    }
+
+    #[test]
+    fn shadow_proxy() {
+        // Flat grid, all interior vertices can be collapsed without moving the surface
+        let size = 17;
+        let mut positions = vec![];
+        let mut indices = vec![];
+        for y in 0..size {
+            for x in 0..size {
+                positions.push(Vec3::new(x as f32, y as f32, 0.0));
+                if x + 1 < size && y + 1 < size {
+                    let i = y * size + x;
+                    indices.extend([i, i + 1, i + size, i + 1, i + size + 1, i + size]);
+                }
+            }
+        }
+        let num_vertices = positions.len();
+        let (packed_vertices, _) = pack_vertices(
+            positions,
+            vec![Vec3::Z; num_vertices],
+            vec![Vec4::X; num_vertices],
+            vec![Vec2::ZERO; num_vertices],
+        );
+        let num_triangles = indices.len() / 3;
+        let mesh = Mesh::new(
+            "Grid",
+            packed_vertices,
+            vec![0; num_triangles],
+            vec![0],
+            indices,
+            true,
+            false,
+        );
+
+        let proxy = mesh.generate_shadow_proxy(&ShadowProxyDesc {
+            triangle_ratio: 0.05,
+            ..Default::default()
+        });
+        assert!(proxy.indices.len() / 3 <= num_triangles / 20);
+        // Open edges keep the outline in place
+        for corner in [[0.0, 0.0], [16.0, 0.0], [0.0, 16.0], [16.0, 16.0]] {
+            assert!(proxy
+                .positions
+                .iter()
+                .any(|position| position[..2] == corner));
+        }
+        for position in &proxy.positions {
+            assert_eq!(position[2], 0.0);
+        }
+
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                shadow_proxies: Some(ShadowProxyDesc::default()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        for mesh in model.meshes.iter().filter(|mesh| mesh.opaque) {
+            let proxy = mesh.shadow_proxy.as_ref().unwrap();
+            assert!(proxy.indices.len() <= mesh.indices.len());
+        }
+    }
+
+    #[test]
+    fn serialized_layout() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        let layout = model.serialized_layout();
+        assert_eq!(
+            layout.iter().map(|section| section.bytes).sum::<usize>(),
+            model.write_to_vec().unwrap().len()
+        );
+        assert_eq!(
+            layout
+                .iter()
+                .filter(|section| section.kind == SectionKind::Texture)
+                .count(),
+            model.textures.len()
+        );
+    }
+
+    #[test]
+    fn renderer_adapter() {
+        use ugm::{
+            gpu::{IntoGpuMaterial, IntoGpuMesh, IntoGpuTexture, VertexSemantic},
+            mesh::PackedVertex,
+        };
+
+        #[derive(Default)]
+        struct Renderer {
+            vertex_bytes: usize,
+            texture_bytes: usize,
+            bound_textures: usize,
+        }
+
+        impl IntoGpuMesh<Renderer> for Mesh {
+            type Output = usize;
+
+            fn create_gpu_mesh(&self, renderer: &mut Renderer) -> usize {
+                let data = self.gpu_data();
+                assert_eq!(data.vertices.len() as u64 % data.vertex_stride, 0);
+                renderer.vertex_bytes += data.vertices.len();
+                data.indices.len() / 3
+            }
+        }
+
+        impl IntoGpuMaterial<Renderer> for Material {
+            type Output = ();
+
+            fn create_gpu_material(&self, renderer: &mut Renderer) {
+                renderer.bound_textures += self.gpu_data().textures.len();
+            }
+        }
+
+        impl IntoGpuTexture<Renderer> for Texture {
+            type Output = ();
+
+            fn create_gpu_texture(&self, renderer: &mut Renderer) {
+                let data = self.gpu_data();
+                for mip in &data.mips {
+                    assert_eq!(
+                        mip.data.len(),
+                        (mip.bytes_per_row * mip.rows_per_image * data.layer_count) as usize
+                    );
+                    renderer.texture_bytes += mip.data.len();
+                }
+            }
+        }
+
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        let mut renderer = Renderer::default();
+        let triangles: usize = model
+            .meshes
+            .iter()
+            .map(|mesh| mesh.create_gpu_mesh(&mut renderer))
+            .sum();
+        model
+            .materials
+            .iter()
+            .for_each(|material| material.create_gpu_material(&mut renderer));
+        model
+            .textures
+            .iter()
+            .for_each(|texture| texture.create_gpu_texture(&mut renderer));
+
+        let last_attribute = PackedVertex::ATTRIBUTES.last().unwrap();
+        assert_eq!(last_attribute.semantic, VertexSemantic::TangentHandiness);
+        assert_eq!(
+            last_attribute.offset + last_attribute.format.size(),
+            size_of::<PackedVertex>() as u64
+        );
+        assert_eq!(
+            triangles,
+            model
+                .meshes
+                .iter()
+                .map(|mesh| mesh.indices.len() / 3)
+                .sum::<usize>()
+        );
+        assert_eq!(
+            renderer.texture_bytes,
+            model
+                .textures
+                .iter()
+                .map(|texture| texture.data().len())
+                .sum::<usize>()
+        );
+        assert_eq!(
+            renderer.bound_textures,
+            model
+                .materials
+                .iter()
+                .map(|material| material.textures().iter().flatten().count())
+                .sum::<usize>()
+        );
+        assert!(renderer.vertex_bytes > 0);
+    }
+
+    #[test]
+    fn streamable_mips() {
+        use ugm::streaming::StreamableModel;
+
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                generate_mips: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let data = model.write_streamable_to_vec(2).unwrap();
+
+        let mut reader = std::io::Cursor::new(&data);
+        let mut streamable = StreamableModel::read_resident(&mut reader).unwrap();
+        assert!(streamable
+            .model
+            .textures
+            .iter()
+            .all(|texture| texture.mip_count() <= 2));
+        assert!(!streamable.is_fully_resident());
+
+        for texture_idx in 0..model.textures.len() {
+            while streamable.stream_mips(texture_idx, 1, &mut reader).unwrap() > 0 {}
+        }
+        assert!(streamable.is_fully_resident());
+        for (streamed, original) in streamable.model.textures.iter().zip(&model.textures) {
+            assert_eq!(streamed.width(), original.width());
+            assert_eq!(streamed.mip_count(), original.mip_count());
+            assert_eq!(streamed.data(), original.data());
+        }
+        assert!(streamable
+            .stream_mips(model.textures.len(), 1, &mut reader)
+            .is_err());
+    }
+
+    #[test]
+    fn streamable_mips_at_offset() {
+        use std::io::{Seek, SeekFrom};
+        use ugm::streaming::StreamableModel;
+
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                generate_mips: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // Streamable container embedded in a larger archive
+        let mut archive = b"archive header".to_vec();
+        let base_offset = archive.len() as u64;
+        archive.extend(model.write_streamable_to_vec(1).unwrap());
+        archive.extend_from_slice(b"trailing entry");
+
+        let mut reader = std::io::Cursor::new(&archive);
+        reader.seek(SeekFrom::Start(base_offset)).unwrap();
+        let streamable = StreamableModel::read_resident(&mut reader).unwrap();
+        assert_eq!(streamable.base_offset, base_offset);
+        let streamed = streamable.into_resident(&mut reader).unwrap();
+        for (streamed, original) in streamed.textures.iter().zip(&model.textures) {
+            assert_eq!(streamed.mip_count(), original.mip_count());
+            assert_eq!(streamed.data(), original.data());
+        }
+
+        // Plain ugm readers load streamable containers with all mips resident
+        reader.seek(SeekFrom::Start(base_offset)).unwrap();
+        let read = Model::from_reader(&mut reader, ParseOptions::default()).unwrap();
+        assert_eq!(
+            read.textures.last().unwrap().data(),
+            model.textures.last().unwrap().data()
+        );
+        let read = Model::read_ugm(&mut &archive[base_offset as usize..]).unwrap();
+        assert_eq!(read.textures.len(), model.textures.len());
+        assert_eq!(read.textures[0].data(), model.textures[0].data());
+
+        assert!(StreamableModel::read_resident(&mut std::io::Cursor::new(
+            model.write_to_bytes().unwrap()
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn packed_material() {
+        let material = Material {
+            color: [0.8, 0.25, 0.1],
+            emission: [12.0, 3.5, 0.0],
+            sheen_tint: [0.5, 0.5, 0.5],
+            normal_texture: Some(3),
+            normal_tex_coord: 1,
+            is_opaque: false,
+            ..Default::default()
+        };
+
+        let unpacked = material.packed().unpack();
+        for (packed, full) in [
+            (unpacked.color, material.color),
+            (unpacked.emission, material.emission),
+            (unpacked.sheen_tint, material.sheen_tint),
+            (unpacked.specular_tint, material.specular_tint),
+        ] {
+            let max = full.iter().copied().fold(0.0, f32::max);
+            for (packed, full) in packed.iter().zip(full) {
+                assert!((packed - full).abs() <= max / 256.0);
+            }
+        }
+        assert_eq!(unpacked.textures(), material.textures());
+        assert_eq!(unpacked.tex_coords(), material.tex_coords());
+        assert!(!unpacked.is_opaque);
+    }
+
+    #[test]
+    fn bvh() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let mut model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+        model.build_bvhs();
+
+        for mesh in model.meshes.iter().filter(|mesh| !mesh.is_empty()) {
+            let bvh = mesh.bvh.as_ref().unwrap();
+            let num_triangles = mesh.indices.len() / 3;
+
+            let mut triangle_indices = bvh.triangle_indices.clone();
+            triangle_indices.sort_unstable();
+            assert!(triangle_indices.iter().copied().eq(0..num_triangles as u32));
+
+            // Every triangle is reachable through exactly one leaf and inside the bounds of all its ancestors
+            let mut num_leaf_triangles = 0;
+            let mut stack = vec![(0, Vec3::NEG_INFINITY, Vec3::INFINITY)];
+            while let Some((node_idx, parent_min, parent_max)) = stack.pop() {
+                let node = &bvh.nodes[node_idx];
+                let (min, max) = (
+                    Vec3::from_array(node.bounds_min),
+                    Vec3::from_array(node.bounds_max),
+                );
+                assert!(min.cmpge(parent_min).all() && max.cmple(parent_max).all());
+
+                if node.is_leaf() {
+                    let first = node.left_first as usize;
+                    for triangle_idx in
+                        &bvh.triangle_indices[first..][..node.triangle_count as usize]
+                    {
+                        for vertex in mesh.indices.triangle(*triangle_idx as usize) {
+                            let position =
+                                Vec3::from_array(mesh.packed_vertices[vertex as usize].position);
+                            assert!(position.cmpge(min).all() && position.cmple(max).all());
+                        }
+                    }
+                    num_leaf_triangles += node.triangle_count as usize;
+                } else {
+                    let left = node.left_first as usize;
+                    stack.extend([(left, min, max), (left + 1, min, max)]);
+                }
+            }
+            assert_eq!(num_leaf_triangles, num_triangles);
+            assert!(bvh.nodes.len() > 1 || num_triangles <= 2);
+        }
+
+        let deserialized = Model::read_from_bytes(&model.write_to_bytes().unwrap()).unwrap();
+        for (mesh, deserialized) in model.meshes.iter().zip(&deserialized.meshes) {
+            assert_eq!(mesh.bvh, deserialized.bvh);
+        }
+    }
+
+    #[test]
+    fn submeshes() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let mut model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        for mesh in &mut model.meshes {
+            let mut index_offset = 0;
+            for submesh in &mesh.submeshes {
+                assert_eq!(submesh.index_offset, index_offset);
+                let triangles = submesh.index_offset as usize / 3..;
+                assert!(mesh.triangle_material_indices[triangles]
+                    [..submesh.index_count as usize / 3]
+                    .iter()
+                    .all(|material| *material == submesh.material));
+                index_offset += submesh.index_count;
+            }
+            assert_eq!(index_offset as usize, mesh.indices.len());
+
+            let num_submeshes = mesh.submeshes.len();
+            mesh.merge_submeshes();
+            assert!(mesh.submeshes.len() <= num_submeshes);
+            assert!(mesh
+                .submeshes
+                .windows(2)
+                .all(|pair| pair[0].material != pair[1].material));
+        }
+    }
+
+    #[test]
+    fn coordinate_system_conversion() {
+        use ugm::coordinate_system::{Handedness, UpAxis};
+
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+        let converted = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                handedness: Handedness::Left,
+                up_axis: UpAxis::Z,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // Left handed z up swaps y and z, mirroring the geometry
+        let convert = |p: Vec3| Vec3::new(p.x, p.z, p.y);
+        for (mesh, converted) in model.meshes.iter().zip(&converted.meshes) {
+            for (vertex, converted) in mesh.packed_vertices.iter().zip(&converted.packed_vertices) {
+                let position = convert(Vec3::from_array(vertex.position));
+                assert!(position.abs_diff_eq(Vec3::from_array(converted.position), 1e-5));
+                assert!(
+                    convert(vertex.normal.unpack()).abs_diff_eq(converted.normal.unpack(), 1e-3)
+                );
+                assert_eq!(vertex.tangent_handiness, -converted.tangent_handiness);
+            }
+            for triangle_idx in 0..mesh.indices.len() / 3 {
+                let [a, b, c] = mesh.indices.triangle(triangle_idx);
+                assert_eq!(converted.indices.triangle(triangle_idx), [a, c, b]);
+            }
+        }
+
+        let mut world_positions = vec![];
+        model.traverse_nodes(Mat4::IDENTITY, |_, transform| {
+            world_positions.push(convert(transform.transform_point3(Vec3::ONE)));
+        });
+        let mut converted_world_positions = vec![];
+        converted.traverse_nodes(Mat4::IDENTITY, |_, transform| {
+            converted_world_positions.push(transform.transform_point3(Vec3::ONE));
+        });
+        for (a, b) in world_positions.iter().zip(&converted_world_positions) {
+            assert!(a.abs_diff_eq(*b, 1e-4));
+        }
+        assert_eq!(
+            Vec3::from_array(converted.bounds_min),
+            convert(Vec3::from_array(model.bounds_min))
+        );
+    }
+
+    #[test]
+    fn flat_shading() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                flat_shading: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        for mesh in model.meshes.iter().filter(|mesh| !mesh.is_empty()) {
+            assert_eq!(mesh.packed_vertices.len(), mesh.indices.len());
+            for [a, b, c] in mesh.indices.triangles() {
+                let [pa, pb, pc] = [a, b, c]
+                    .map(|vertex| Vec3::from_array(mesh.packed_vertices[vertex as usize].position));
+                let Some(face_normal) = (pb - pa).cross(pc - pa).try_normalize() else {
+                    continue;
+                };
+                for vertex in [a, b, c] {
+                    let normal = mesh.packed_vertices[vertex as usize].normal.unpack();
+                    assert!(normal.dot(face_normal) > 0.999);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mesh_validation() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+        for mesh in &model.meshes {
+            let report = mesh.validate();
+            assert!(report.is_valid(), "{:?}", report);
+        }
+
+        let mut mesh = model.meshes[0].clone();
+        let num_vertices = mesh.packed_vertices.len() as u32;
+        let mut indices = mesh.indices.to_u32().into_owned();
+        indices[1] = num_vertices;
+        mesh.indices = Indices::U32(indices);
+        mesh.packed_vertices[0].position[1] = f32::NAN;
+        mesh.triangle_material_indices[0] = mesh.material_indices.len() as u32;
+        mesh.triangle_material_indices.pop();
+
+        let report = mesh.validate();
+        assert!(!report.is_valid());
+        assert_eq!(report.out_of_range_indices, vec![1]);
+        assert_eq!(report.non_finite_positions, vec![0]);
+        assert_eq!(report.invalid_triangle_materials, vec![0]);
+        let num_triangles = mesh.indices.len() / 3;
+        assert_eq!(
+            report.material_count_mismatch,
+            Some((num_triangles, num_triangles - 1))
+        );
+    }
+
+    #[test]
+    fn lightmap_uvs() {
+        use ugm::lightmap::LightmapUvDesc;
+
+        let desc = LightmapUvDesc {
+            resolution: 256,
+            ..Default::default()
+        };
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                lightmap_uvs: Some(desc),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        for mesh in model.meshes.iter().filter(|mesh| !mesh.is_empty()) {
+            assert_eq!(mesh.secondary_tex_coords.len(), mesh.packed_vertices.len());
+            assert!(mesh.validate().is_valid());
+
+            // Texel centers are covered by at most one triangle
+            let size = desc.resolution as f32;
+            let mut coverage = vec![0u32; (desc.resolution * desc.resolution) as usize];
+            for triangle in mesh.indices.triangles() {
+                let [a, b, c] = triangle.map(|vertex| {
+                    Vec2::from_array(mesh.secondary_tex_coords[vertex as usize]) * size
+                });
+                for uv in [a, b, c] {
+                    assert!(uv.cmpge(Vec2::ZERO).all() && uv.cmple(Vec2::splat(size)).all());
+                }
+
+                let area = (b - a).perp_dot(c - a);
+                if area.abs() < 1e-6 {
+                    continue;
+                }
+                let min = a.min(b).min(c).floor();
+                let max = a.max(b).max(c).ceil();
+                for y in min.y as u32..max.y as u32 {
+                    for x in min.x as u32..max.x as u32 {
+                        let p = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                        let weights = [
+                            (c - b).perp_dot(p - b),
+                            (a - c).perp_dot(p - c),
+                            (b - a).perp_dot(p - a),
+                        ];
+                        if weights.iter().all(|weight| weight / area > 1e-3) {
+                            coverage[(y * desc.resolution + x) as usize] += 1;
+                        }
+                    }
+                }
+            }
+            assert!(coverage.iter().all(|count| *count <= 1));
+        }
+    }
+
+    #[test]
+    fn emissive_triangles() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let mut model =
+            EditableModel::new(Model::parse_glb(model_bytes, ParseOptions::default()).unwrap());
+        let set_emission = |model: &mut EditableModel, emission: [f32; 3]| {
+            for material_idx in 0..model.materials.len() as u32 {
+                let mut material = model.materials[material_idx as usize].clone();
+                material.emission = if material_idx == 0 {
+                    emission
+                } else {
+                    [0.0; 3]
+                };
+                model.replace_material(material_idx, material);
+            }
+            model.update_derived();
+        };
+
+        set_emission(&mut model, [0.0; 3]);
+        assert!(model
+            .collect_emissive_triangles(Mat4::IDENTITY)
+            .sample(0.5)
+            .is_none());
+        set_emission(&mut model, [2.0, 1.0, 0.5]);
+
+        let emissive = model.collect_emissive_triangles(Mat4::from_scale(Vec3::splat(2.0)));
+        assert!(!emissive.triangles.is_empty());
+        assert!(emissive
+            .triangles
+            .iter()
+            .all(|triangle| triangle.material_idx == 0 && triangle.area > 0.0));
+        assert!(emissive.cdf.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(*emissive.cdf.last().unwrap(), 1.0);
+
+        let (triangle_idx, pdf) = emissive.sample(0.5).unwrap();
+        assert!(emissive.cdf[triangle_idx] > 0.5);
+        assert!(triangle_idx == 0 || emissive.cdf[triangle_idx - 1] <= 0.5);
+        assert_eq!(
+            pdf,
+            emissive.triangles[triangle_idx].power / emissive.total_power
+        );
+    }
+
+    #[test]
+    fn raycast() {
+        use ugm::raycast::Ray;
+
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let mut model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+        let brute_force = model.clone();
+        model.build_bvhs();
+
+        let (mesh_idx, mesh) = model
+            .meshes
+            .iter()
+            .enumerate()
+            .find(|(_, mesh)| !mesh.is_empty())
+            .unwrap();
+        let num_triangles = mesh.indices.len() / 3;
+        for triangle_idx in (0..num_triangles).step_by(num_triangles / 50 + 1) {
+            let [a, b, c] = mesh
+                .indices
+                .triangle(triangle_idx)
+                .map(|vertex| mesh.vertex_position(vertex));
+            let Some(normal) = (b - a).cross(c - a).try_normalize() else {
+                continue;
+            };
+            let target = (a + b + c) / 3.0;
+            let ray = Ray::new(target + normal, -normal);
+
+            let hit = mesh.intersect_ray(&ray, f32::INFINITY).unwrap();
+            assert!(hit.distance <= 1.0 + 1e-4);
+            let expected = brute_force.meshes[mesh_idx]
+                .intersect_ray(&ray, f32::INFINITY)
+                .unwrap();
+            assert_eq!(hit.distance, expected.distance);
+            assert_eq!(hit.material_idx, expected.material_idx);
+        }
+
+        let miss = Ray::new(Vec3::from_array(model.bounds_max) + 1.0, Vec3::ONE);
+        assert!(model.intersect_ray(Mat4::IDENTITY, &miss).is_none());
+
+        // Rays towards a triangle of an instance hit it, also when the whole model is moved
+        let mut instance = None;
+        model.traverse_nodes(Mat4::IDENTITY, |node, transform| {
+            if node.mesh_idx == Some(mesh_idx as u32) && instance.is_none() {
+                instance = Some(transform);
+            }
+        });
+        let transform = instance.unwrap();
+        let [a, b, c] = mesh
+            .indices
+            .triangle(0)
+            .map(|vertex| transform.transform_point3(mesh.vertex_position(vertex)));
+        let target = (a + b + c) / 3.0;
+        let normal = (b - a).cross(c - a).normalize();
+        let origin = target + normal * 0.01;
+        let hit = model
+            .intersect_ray(Mat4::IDENTITY, &Ray::new(origin, -normal))
+            .unwrap();
+        assert!(hit.distance <= 0.01 + 1e-4);
+        let offset = Vec3::new(10.0, 0.0, 0.0);
+        let moved = model
+            .intersect_ray(
+                Mat4::from_translation(offset),
+                &Ray::new(origin + offset, -normal),
+            )
+            .unwrap();
+        assert!((hit.distance - moved.distance).abs() < 1e-4);
+        assert_eq!(hit.triangle_idx, moved.triangle_idx);
+        assert!(hit.mesh_idx.is_some() && hit.node_idx.is_some());
+    }
+
+    #[test]
+    fn meshlets() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let mut model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+        model.build_meshlets(64, 124);
+
+        for mesh in model.meshes.iter().filter(|mesh| !mesh.is_empty()) {
+            let meshlets = mesh.meshlets.as_ref().unwrap();
+
+            let mut triangles = vec![];
+            for meshlet in &meshlets.meshlets {
+                assert!(meshlet.vertex_count <= 64 && meshlet.triangle_count <= 124);
+
+                let vertices = &meshlets.vertices[meshlet.vertex_offset as usize..]
+                    [..meshlet.vertex_count as usize];
+                for vertex in vertices {
+                    let position =
+                        Vec3::from_array(mesh.packed_vertices[*vertex as usize].position);
+                    assert!(
+                        position.distance(Vec3::from_array(meshlet.center))
+                            <= meshlet.radius * 1.001 + 1e-6
+                    );
+                }
+
+                let start = meshlet.triangle_offset as usize * 3;
+                let end = start + meshlet.triangle_count as usize * 3;
+                for triangle in meshlets.triangles[start..end].chunks_exact(3) {
+                    let mut triangle: Vec<u32> = triangle
+                        .iter()
+                        .map(|local| vertices[*local as usize])
+                        .collect();
+                    triangle.push(meshlet.material_idx);
+                    triangles.push(triangle);
+                }
+            }
+
+            let mut expected: Vec<Vec<u32>> = mesh
+                .indices
+                .triangles()
+                .zip(&mesh.triangle_material_indices)
+                .map(|(triangle, material_idx)| {
+                    let mut triangle = triangle.to_vec();
+                    triangle.push(*material_idx);
+                    triangle
+                })
+                .collect();
+            triangles.sort();
+            expected.sort();
+            assert_eq!(triangles, expected);
+        }
+    }
+
+    #[test]
+    fn mesh_optimization() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+        let optimized = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                optimize_meshes: Some(MeshOptimizeOptions::default()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        for (mesh, optimized) in model.meshes.iter().zip(&optimized.meshes) {
+            assert!(optimized.average_cache_miss_ratio(16) <= mesh.average_cache_miss_ratio(16));
+            assert_eq!(
+                optimized.triangle_material_indices,
+                mesh.triangle_material_indices
+            );
+
+            let triangles = |mesh: &Mesh| {
+                let mut triangles: Vec<(u32, Vec<u32>)> = mesh
+                    .triangle_material_indices
+                    .iter()
+                    .copied()
+                    .zip(mesh.indices.triangles().map(|triangle| triangle.to_vec()))
+                    .collect();
+                triangles.sort();
+                triangles
+            };
+            assert_eq!(triangles(optimized), triangles(mesh));
+        }
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn overdraw_cluster_order() {
+        use ugm::{
+            mesh::Submesh,
+            test_util::{GlbBuilder, SyntheticMesh},
+        };
+
+        // Disjoint quads facing +z at shuffled depths, each quad becomes its own cluster
+        let depths = [3.0, 0.0, 5.0, 1.0, 7.0, 2.0, 6.0, 4.0];
+        let mut positions = vec![];
+        let mut indices = vec![];
+        for (quad, z) in depths.iter().enumerate() {
+            let x = quad as f32 * 2.0;
+            let first = positions.len() as u32;
+            positions.extend([
+                [x, 0.0, *z],
+                [x + 1.0, 0.0, *z],
+                [x + 1.0, 1.0, *z],
+                [x, 1.0, *z],
+            ]);
+            indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+        }
+        let glb = GlbBuilder::single_mesh(SyntheticMesh {
+            positions,
+            indices: Some(indices),
+            ..Default::default()
+        })
+        .unwrap();
+        let model = Model::parse_glb(&glb, ParseOptions::default()).unwrap();
+
+        // Half of the quads in a second submesh with its own material
+        let mut mesh = model.meshes[0].clone();
+        let half = mesh.indices.len() as u32 / 2;
+        mesh.submeshes = vec![
+            Submesh {
+                index_offset: 0,
+                index_count: half,
+                material: 0,
+            },
+            Submesh {
+                index_offset: half,
+                index_count: half,
+                material: 1,
+            },
+        ];
+        mesh.material_indices = vec![0, 1];
+        mesh.triangle_material_indices = (0..mesh.indices.len() as u32 / 3)
+            .map(|triangle_idx| (triangle_idx >= half / 3) as u32)
+            .collect();
+
+        let mut optimized = mesh.clone();
+        optimized.optimize(&MeshOptimizeOptions {
+            vertex_cache: false,
+            overdraw_threshold: Some(1.05),
+        });
+
+        let triangles = |mesh: &Mesh| -> Vec<(u32, [u32; 3])> {
+            mesh.triangle_material_indices
+                .iter()
+                .copied()
+                .zip(mesh.indices.triangles())
+                .collect()
+        };
+        let (mut before, after) = (triangles(&mesh), triangles(&optimized));
+        let mut sorted_after = after.clone();
+        before.sort();
+        sorted_after.sort();
+        assert_eq!(sorted_after, before);
+
+        let depth =
+            |triangle: [u32; 3]| optimized.packed_vertices[triangle[0] as usize].position[2];
+        for submesh in after.chunks(after.len() / 2) {
+            // Triangles stay in their submesh and keep its material
+            assert!(submesh
+                .iter()
+                .all(|(material, _)| *material == submesh[0].0));
+            let submesh_depths: Vec<f32> = submesh
+                .iter()
+                .map(|(_, triangle)| depth(*triangle))
+                .collect();
+            // Clusters facing away from the center are drawn first, so the farthest out along +z come first
+            assert!(submesh_depths.windows(2).all(|pair| pair[0] >= pair[1]));
+            assert!(submesh_depths.chunks(2).all(|quad| quad[0] == quad[1]));
+        }
+        let first_depths: Vec<f32> = [0, after.len() / 2]
+            .into_iter()
+            .map(|triangle_idx| depth(after[triangle_idx].1))
+            .collect();
+        assert_eq!(first_depths, [5.0, 7.0]);
+    }
+
+    #[test]
+    fn incremental_edits() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let mut model =
+            EditableModel::new(Model::parse_glb(model_bytes, ParseOptions::default()).unwrap());
+        model.update_derived();
+        let (bounds_min, bounds_max) = (model.bounds_min, model.bounds_max);
+
+        let mesh_idx = model.add_mesh(model.meshes[0].clone());
+        assert_eq!(
+            model.bounds_max, bounds_max,
+            "Meshes without instance are out of bounds"
+        );
+        assert_eq!(model.stats().meshes.len(), model.meshes.len());
+        model.remove_mesh(mesh_idx);
+        assert_eq!(model.stats().meshes.len(), model.meshes.len());
+
+        let mesh_idx = model.nodes.iter().find_map(|node| node.mesh_idx).unwrap();
+        let mut mesh = model.meshes[mesh_idx as usize].clone();
+        mesh.bounds_min = (Vec3::from_array(mesh.bounds_min) - 1000.0).to_array();
+        mesh.bounds_max = (Vec3::from_array(mesh.bounds_max) + 1000.0).to_array();
+        let previous = model.replace_mesh(mesh_idx, mesh);
+        assert!(model.bounds_max[0] > bounds_max[0]);
+
+        model.replace_mesh(mesh_idx, previous);
+        assert!(model.is_dirty());
+        model.update_derived();
+        assert!(!model.is_dirty());
+        assert_eq!(model.bounds_min, bounds_min);
+        assert_eq!(model.bounds_max, bounds_max);
+
+        let material_idx = model.meshes[0].material_indices[0];
+        let mut material = model.materials[material_idx as usize].clone();
+        material.emission = [1.0; 3];
+        model.replace_material(material_idx, material);
+        model.update_derived();
+        assert!(model.meshes[0].is_emissive);
+    }
+
+    #[test]
+    fn mip_generation() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                texture_compression: None,
+                generate_mips: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let serialized = model.write_to_vec().unwrap();
+        let compression_rate = serialized.len() as f32 / model_bytes.len() as f32;
+        println!("Compression rate: {}", compression_rate);
+    }
+
+    #[test]
+    fn alpha_coverage_mips() {
+        let image = image::RgbaImage::from_fn(64, 64, |x, y| {
+            let alpha = (x as f32 * 0.3).sin() * (y as f32 * 0.2).sin() * 0.5 + 0.5;
+            image::Rgba([255, 255, 255, (alpha * 255.0) as u8])
+        });
+        let coverage = |alpha: &[u8]| {
+            let passed = alpha
+                .chunks_exact(4)
+                .filter(|pixel| pixel[3] >= 179)
+                .count();
+            passed as f32 / (alpha.len() / 4) as f32
+        };
+
+        let texture = Texture::new(TextureCreateDesc {
+            name: None,
+            image: image::DynamicImage::ImageRgba8(image),
+            mips: true,
+            mip_filter: Default::default(),
+            max_mip_levels: None,
+            min_mip_size: None,
+            precomputed_mips: vec![],
+            is_normal_map: false,
+            color_space: ColorSpace::Srgb,
+            alpha_coverage_cutoff: Some(0.7),
+            premultiply_alpha: false,
+            uv_offset: [0.0; 2],
+            uv_scale: [1.0; 2],
+            sampler: Default::default(),
+            source_image: None,
+        });
+
+        let top_coverage = coverage(texture.mip_data(0));
+        for mip in texture.mips().skip(1).take(4) {
+            assert!((coverage(mip) - top_coverage).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn mip_filters() {
+        let image = image::RgbaImage::from_fn(16, 16, |x, y| {
+            let value = if (x + y) % 2 == 0 { 255 } else { 0 };
+            image::Rgba([value, value, value, 255])
+        });
+
+        for mip_filter in [
+            MipFilter::Box,
+            MipFilter::Triangle,
+            MipFilter::CatmullRom,
+            MipFilter::Lanczos3,
+            MipFilter::Kaiser,
+        ] {
+            let texture = Texture::new(TextureCreateDesc {
+                name: None,
+                image: image::DynamicImage::ImageRgba8(image.clone()),
+                mips: true,
+                mip_filter,
+                max_mip_levels: None,
+                min_mip_size: None,
+                precomputed_mips: vec![],
+                is_normal_map: false,
+                color_space: ColorSpace::Linear,
+                alpha_coverage_cutoff: None,
+                premultiply_alpha: false,
+                uv_offset: [0.0; 2],
+                uv_scale: [1.0; 2],
+                sampler: Default::default(),
+                source_image: None,
+            });
+            assert_eq!(texture.mip_count(), 5);
+
+            // Averaging a checkerboard results in uniform gray
+            if mip_filter == MipFilter::Box {
+                assert!(texture
+                    .mip_data(1)
+                    .chunks_exact(4)
+                    .all(|pixel| pixel[0].abs_diff(128) <= 1));
+            }
+        }
+    }
+
+    #[test]
+    fn mip_chain_control() {
+        let desc = |max_mip_levels, min_mip_size, precomputed_mips| TextureCreateDesc {
+            name: None,
+            image: image::DynamicImage::ImageRgba8(image::RgbaImage::new(64, 32)),
+            mips: true,
+            max_mip_levels,
+            min_mip_size,
+            precomputed_mips,
+            mip_filter: Default::default(),
+            is_normal_map: false,
+            color_space: ColorSpace::Linear,
+            alpha_coverage_cutoff: None,
+            premultiply_alpha: false,
+            uv_offset: [0.0; 2],
+            uv_scale: [1.0; 2],
+            sampler: Default::default(),
+            source_image: None,
+        };
+
+        assert_eq!(Texture::new(desc(None, None, vec![])).mip_count(), 6);
+        assert_eq!(Texture::new(desc(Some(4), None, vec![])).mip_count(), 4);
+        assert_eq!(Texture::new(desc(None, Some(8), vec![])).mip_count(), 3);
+
+        let precomputed_mips = vec![image::DynamicImage::ImageRgba8(
+            image::RgbaImage::from_pixel(32, 16, image::Rgba([255; 4])),
+        )];
+        let texture = Texture::new(desc(None, None, precomputed_mips));
+        assert_eq!(texture.mip_count(), 2);
+        assert!(texture.mip_data(1).iter().all(|value| *value == 255));
+    }
+
+    #[test]
+    fn premultiplied_alpha() {
+        // Opaque red next to transparent blue, which must not bleed into the mips
+        let image = image::RgbaImage::from_fn(2, 2, |x, _| {
+            if x == 0 {
+                image::Rgba([255, 0, 0, 255])
+            } else {
+                image::Rgba([0, 0, 255, 0])
+            }
+        });
+        let texture = Texture::new(TextureCreateDesc {
+            name: None,
+            image: image::DynamicImage::ImageRgba8(image),
+            mips: true,
+            max_mip_levels: None,
+            min_mip_size: None,
+            precomputed_mips: vec![],
+            mip_filter: MipFilter::Box,
+            is_normal_map: false,
+            color_space: ColorSpace::Linear,
+            alpha_coverage_cutoff: None,
+            premultiply_alpha: true,
+            uv_offset: [0.0; 2],
+            uv_scale: [1.0; 2],
+            sampler: Default::default(),
+            source_image: None,
+        });
+
+        assert!(texture.premultiplied_alpha());
+        assert_eq!(&texture.mip_data(0)[4..8], &[0, 0, 0, 0]);
+        let [r, g, b, a] = texture.mip_data(1).try_into().unwrap();
+        assert_eq!((g, b), (0, 0));
+        assert!(r.abs_diff(a) <= 1);
+    }
+
+    #[test]
+    fn bc_compressed_mips() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                texture_compression: Some(TextureCompression::Bc),
+                generate_mips: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let serialized = model.write_to_vec().unwrap();
+        let compression_rate = serialized.len() as f32 / model_bytes.len() as f32;
+        println!("Compression rate: {}", compression_rate);
+    }
+
+    #[test]
+    fn bc_texture_decompression() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                texture_compression: Some(TextureCompression::Bc),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        for texture in &model.textures {
+            let image = texture.to_image(0).unwrap();
+            assert_eq!(image.width(), texture.width());
+            assert_eq!(image.height(), texture.height());
+
+            let decompressed = texture.decompress().unwrap();
+            assert!(matches!(
+                decompressed.format(),
+                TextureFormat::Uncompressed(_)
+            ));
+            assert_eq!(decompressed.mip_count(), texture.mip_count());
+        }
+    }
+
+    #[test]
+    fn compression_quality() {
+        // Minimum quality of bc compressed textures, fails when a change degrades compression quality
+        const MIN_PSNR: f32 = 30.0;
+        const MIN_SSIM: f32 = 0.9;
+
+        let gray = |value: u8| {
+            Texture::new(TextureCreateDesc {
+                name: None,
+                image: image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+                    16,
+                    16,
+                    image::Rgba([value, value, value, 255]),
+                )),
+                mips: false,
+                max_mip_levels: None,
+                min_mip_size: None,
+                precomputed_mips: vec![],
+                mip_filter: MipFilter::Box,
+                is_normal_map: false,
+                color_space: ColorSpace::Linear,
+                alpha_coverage_cutoff: None,
+                premultiply_alpha: false,
+                uv_offset: [0.0; 2],
+                uv_scale: [1.0; 2],
+                sampler: Default::default(),
+                source_image: None,
+            })
+        };
+        // Off by one in rgb, alpha is identical
+        let expected_psnr = 10.0 * (255.0f32.powi(2) / 0.75).log10();
+        let psnr = gray(129).psnr_against(&gray(128)).unwrap();
+        assert!((psnr - expected_psnr).abs() < 0.01);
+
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                generate_mips: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        for texture in &model.textures {
+            let identical = texture.quality_against(texture).unwrap();
+            assert!(identical
+                .iter()
+                .all(|quality| quality.psnr.is_infinite() && quality.ssim == 1.0));
+
+            let compressed = texture.compress(&TextureCompression::Bc).unwrap();
+            let qualities = compressed.quality_against(texture).unwrap();
+            assert_eq!(qualities.len(), compressed.mip_count() as usize);
+            println!("{}: {:?}", texture.name(), qualities[0]);
+            assert!(compressed.psnr_against(texture).unwrap() >= MIN_PSNR);
+            assert!(qualities[0].ssim >= MIN_SSIM);
+        }
+    }
+
+    #[test]
+    fn ktx2_and_dds_export() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                texture_compression: Some(TextureCompression::Bc),
+                generate_mips: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        for texture in &model.textures {
+            let mut ktx2 = vec![];
+            texture.write_ktx2(&mut ktx2).unwrap();
+
+            assert_eq!(&ktx2[..12], b"\xabKTX 20\xbb\r\n\x1a\n");
+            let level_count = u32::from_le_bytes(ktx2[40..44].try_into().unwrap());
+            assert_eq!(level_count, texture.mip_count());
+
+            let data_size = texture.data().len();
+            assert!(ktx2.len() > data_size);
+
+            let mut dds = vec![];
+            texture.write_dds(&mut dds).unwrap();
+            assert_eq!(&dds[..4], b"DDS ");
+            assert_eq!(dds.len(), 4 + 124 + 20 + data_size);
+        }
+    }
+
+    #[test]
+    fn equirect_to_cube_map() {
+        // Red sky above blue ground
+        let equirect = image::RgbaImage::from_fn(64, 32, |_, y| {
+            if y < 16 {
+                image::Rgba([255, 0, 0, 255])
+            } else {
+                image::Rgba([0, 0, 255, 255])
+            }
+        });
+        let texture = Texture::from_equirect(
+            TextureCreateDesc {
+                name: Some("Sky"),
+                image: image::DynamicImage::ImageRgba8(equirect),
+                mips: true,
+                max_mip_levels: None,
+                min_mip_size: None,
+                precomputed_mips: vec![],
+                mip_filter: MipFilter::default(),
+                is_normal_map: false,
+                color_space: ColorSpace::Srgb,
+                alpha_coverage_cutoff: None,
+                premultiply_alpha: false,
+                uv_offset: [0.0; 2],
+                uv_scale: [1.0; 2],
+                sampler: Default::default(),
+                source_image: None,
+            },
+            8,
+        );
+        assert_eq!(texture.dimension(), TextureDimension::Cube);
+        assert_eq!(texture.mip_count(), 4);
+        assert_eq!(texture.data().len(), 6 * (64 + 16 + 4 + 1) * 4);
+
+        let image = texture.to_image(0).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (8, 48));
+        let face_color = |face: u32, x: u32, y: u32| image.get_pixel(x, face * 8 + y).0;
+        assert_eq!(face_color(2, 4, 4), [255, 0, 0, 255]);
+        assert_eq!(face_color(3, 4, 4), [0, 0, 255, 255]);
+        // Side faces show the horizon, sky at the top
+        assert_eq!(face_color(0, 4, 0), [255, 0, 0, 255]);
+        assert_eq!(face_color(0, 4, 7), [0, 0, 255, 255]);
+
+        let mut ktx2 = vec![];
+        texture.write_ktx2(&mut ktx2).unwrap();
+        let face_count = u32::from_le_bytes(ktx2[36..40].try_into().unwrap());
+        assert_eq!(face_count, 6);
+
+        let mut dds = vec![];
+        texture.write_dds(&mut dds).unwrap();
+        assert_eq!(dds.len(), 4 + 124 + 20 + texture.data().len());
+    }
+
+    #[test]
+    fn export_glb_subset() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        let glb = model
+            .export_glb_subset(&ExportSubset::Meshes(vec![0]))
+            .unwrap();
+        let exported = Model::parse_glb(&glb, ParseOptions::default()).unwrap();
+        assert_eq!(exported.meshes.len(), 1);
+        assert_eq!(
+            exported.meshes[0].indices.len(),
+            model.meshes[0].indices.len()
+        );
+        assert!(exported.materials.len() <= model.materials.len());
+
+        let glb = model.export_glb().unwrap();
+        let exported = Model::parse_glb(&glb, ParseOptions::default()).unwrap();
+        assert_eq!(exported.nodes.len(), model.nodes.len());
+    }
+
+    #[test]
+    fn glb_round_trip() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let mut model = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                quantize_positions: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        model.materials[0].transmission = 0.5;
+        model.materials[0].eta = 1.0 / 1.33;
+        model.materials[0].absorption = [0.5, 0.25, 0.0];
+
+        let mut glb = vec![];
+        model.write_glb(&mut glb).unwrap();
+        let exported = Model::parse_glb(&glb, ParseOptions::default()).unwrap();
+
+        assert_eq!(exported.meshes.len(), model.meshes.len());
+        for (exported_mesh, mesh) in exported.meshes.iter().zip(&model.meshes) {
+            assert_eq!(exported_mesh.num_vertices(), mesh.num_vertices());
+            assert_eq!(exported_mesh.indices.len(), mesh.indices.len());
+            assert_eq!(exported_mesh.submeshes.len(), mesh.submeshes.len());
+        }
+
+        let material = exported
+            .materials
+            .iter()
+            .find(|material| material.name == model.materials[0].name)
+            .unwrap();
+        assert_eq!(material.transmission, 0.5);
+        assert!((material.eta - 1.0 / 1.33).abs() < 1e-5);
+        for (exported, absorption) in material.absorption.iter().zip([0.5, 0.25, 0.0]) {
+            assert!((exported - absorption).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn obj_export() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        let count_lines = |obj: &[u8], prefix: &str| {
+            String::from_utf8_lossy(obj)
+                .lines()
+                .filter(|line| line.starts_with(prefix))
+                .count()
+        };
+
+        let mut obj = vec![];
+        model.write_obj(&mut obj, false).unwrap();
+        let num_vertices: usize = model.meshes.iter().map(|mesh| mesh.num_vertices()).sum();
+        let num_triangles: usize = model.meshes.iter().map(|mesh| mesh.indices.len() / 3).sum();
+        assert_eq!(count_lines(&obj, "v "), num_vertices);
+        assert_eq!(count_lines(&obj, "f "), num_triangles);
+
+        let mut num_instances = 0;
+        model.traverse_nodes(Mat4::IDENTITY, |node, _| {
+            num_instances += node.mesh_idx.is_some() as usize;
+        });
+        let mut baked = vec![];
+        model.write_obj(&mut baked, true).unwrap();
+        assert_eq!(count_lines(&baked, "o "), num_instances);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn parse_errors() {
+        use ugm::{
+            parser::ParseError,
+            test_util::{GlbBuilder, SyntheticMesh},
+        };
+
+        let glb = GlbBuilder::single_mesh(SyntheticMesh {
+            indices: None,
+            ..SyntheticMesh::triangle()
+        })
+        .unwrap();
+        let error = Model::parse_glb(&glb, ParseOptions::default()).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ParseError>(),
+            Some(ParseError::MissingIndices { .. })
+        ));
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn parse_synthetic_glb() {
+        use ugm::test_util::{GlbBuilder, SyntheticMaterial, SyntheticMesh};
+
+        let mut builder = GlbBuilder::new();
+        let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            4,
+            4,
+            image::Rgba([255, 0, 0, 255]),
+        ));
+        let color_texture = builder.add_image(&image).unwrap();
+        let material = builder.add_material(SyntheticMaterial {
+            color_texture: Some(color_texture),
+            ..Default::default()
+        });
+        let mesh = builder.add_mesh(SyntheticMesh {
+            material: Some(material),
+            ..SyntheticMesh::triangle()
+        });
+        builder.add_node(Some(mesh), glam::Mat4::from_translation(Vec3::X));
+        builder.add_node(Some(mesh), glam::Mat4::IDENTITY);
+
+        let model = Model::parse_glb(&builder.build().unwrap(), ParseOptions::default()).unwrap();
+        assert_eq!(model.nodes.len(), 2);
+        assert_eq!(model.textures.len(), 1);
+        assert_eq!(model.meshes[0].indices.len(), 3);
+
+        // Normals, tangents and tex coords are generated when missing
+        let glb = GlbBuilder::single_mesh(SyntheticMesh {
+            tex_coords: vec![],
+            ..SyntheticMesh::triangle()
+        })
+        .unwrap();
+        let model = Model::parse_glb(&glb, ParseOptions::default()).unwrap();
+        assert_eq!(model.meshes[0].packed_vertices.len(), 3);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn parse_16_bit_images() {
+        use ugm::{
+            test_util::{GlbBuilder, SyntheticMaterial, SyntheticMesh},
+            texture::UncompressedTextureFormat,
+        };
+
+        let luma: Vec<u16> = (0..16).map(|i| i * 4369).collect();
+        let luma_alpha: Vec<u16> = (0..32).map(|i| u16::MAX - i * 2000).collect();
+
+        let mut builder = GlbBuilder::new();
+        let occlusion = builder
+            .add_image(&image::DynamicImage::ImageLuma16(
+                image::ImageBuffer::from_raw(4, 4, luma.clone()).unwrap(),
+            ))
+            .unwrap();
+        let metallic_roughness = builder
+            .add_image(&image::DynamicImage::ImageLumaA16(
+                image::ImageBuffer::from_raw(4, 4, luma_alpha.clone()).unwrap(),
+            ))
+            .unwrap();
+        let material = builder.add_material(SyntheticMaterial {
+            occlusion_texture: Some(occlusion),
+            metallic_roughness_texture: Some(metallic_roughness),
+            ..Default::default()
+        });
+        let mesh = builder.add_mesh(SyntheticMesh {
+            material: Some(material),
+            ..SyntheticMesh::triangle()
+        });
+        builder.add_node(Some(mesh), glam::Mat4::IDENTITY);
+
+        let model = Model::parse_glb(&builder.build().unwrap(), ParseOptions::default()).unwrap();
+        let material = &model.materials[0];
+        let occlusion = &model.textures[material.occlusion_texture.unwrap() as usize];
+        let metallic_roughness =
+            &model.textures[material.metallic_roughness_texture.unwrap() as usize];
+
+        assert_eq!(
+            occlusion.format(),
+            TextureFormat::Uncompressed(UncompressedTextureFormat::R16Unorm)
+        );
+        assert_eq!(occlusion.data(), bytemuck::cast_slice::<u16, u8>(&luma));
+        assert_eq!(
+            metallic_roughness.format(),
+            TextureFormat::Uncompressed(UncompressedTextureFormat::Rg16Unorm)
+        );
+        assert_eq!(
+            metallic_roughness.data(),
+            bytemuck::cast_slice::<u16, u8>(&luma_alpha)
+        );
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn pack_orm_textures() {
+        use ugm::test_util::{GlbBuilder, SyntheticMaterial, SyntheticMesh};
+
+        let mut builder = GlbBuilder::new();
+        let occlusion = builder
+            .add_image(&image::DynamicImage::ImageRgba8(
+                image::RgbaImage::from_pixel(8, 8, image::Rgba([100, 0, 0, 255])),
+            ))
+            .unwrap();
+        let metallic_roughness = builder
+            .add_image(&image::DynamicImage::ImageRgba8(
+                image::RgbaImage::from_pixel(4, 4, image::Rgba([0, 150, 200, 255])),
+            ))
+            .unwrap();
+        let material = builder.add_material(SyntheticMaterial {
+            occlusion_texture: Some(occlusion),
+            metallic_roughness_texture: Some(metallic_roughness),
+            ..Default::default()
+        });
+        let mesh = builder.add_mesh(SyntheticMesh {
+            material: Some(material),
+            ..SyntheticMesh::triangle()
+        });
+        builder.add_node(Some(mesh), glam::Mat4::IDENTITY);
+
+        let model = Model::parse_glb(
+            &builder.build().unwrap(),
+            ParseOptions {
+                pack_orm_textures: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(model.textures.len(), 1);
+        assert_eq!(model.materials[0].occlusion_texture, Some(0));
+        assert_eq!(model.materials[0].metallic_roughness_texture, Some(0));
+
+        let image = model.textures[0].to_image(0).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (8, 8));
+        assert_eq!(image.get_pixel(3, 5).0, [100, 150, 200, 255]);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn deduplicate_identical_images() {
+        use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+        use ugm::test_util::{GlbBuilder, SyntheticMaterial, SyntheticMesh};
+
+        let image = image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(8, 8, |x, y| {
+            image::Rgba([x as u8 * 32, y as u8 * 32, 128, 255])
+        }));
+        let other = image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            8,
+            8,
+            image::Rgba([10, 20, 30, 255]),
+        ));
+        let encode = |image: &image::DynamicImage, compression, filter| {
+            let mut png = vec![];
+            image
+                .write_with_encoder(PngEncoder::new_with_quality(&mut png, compression, filter))
+                .unwrap();
+            png
+        };
+        let fast = encode(&image, CompressionType::Fast, FilterType::NoFilter);
+        let best = encode(&image, CompressionType::Best, FilterType::Paeth);
+        assert_ne!(fast, best);
+
+        // Two encodings of the same pixels, an exact copy and a different image of the same size
+        let mut builder = GlbBuilder::new();
+        let textures = [
+            builder.add_encoded_image(&fast, "image/png"),
+            builder.add_encoded_image(&best, "image/png"),
+            builder.add_encoded_image(&fast, "image/png"),
+            builder.add_image(&other).unwrap(),
+        ];
+        for texture in textures {
+            let material = builder.add_material(SyntheticMaterial {
+                normal_texture: Some(texture),
+                ..Default::default()
+            });
+            let mesh = builder.add_mesh(SyntheticMesh {
+                material: Some(material),
+                ..SyntheticMesh::triangle()
+            });
+            builder.add_node(Some(mesh), glam::Mat4::IDENTITY);
+        }
+
+        let model = Model::parse_glb(&builder.build().unwrap(), ParseOptions::default()).unwrap();
+        assert_eq!(model.textures.len(), 2);
+        assert_eq!(
+            model
+                .materials
+                .iter()
+                .map(|material| material.normal_texture)
+                .collect::<Vec<_>>(),
+            vec![Some(0), Some(0), Some(0), Some(1)]
+        );
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn large_source_texture() {
+        use std::sync::{Arc, Mutex};
+        use ugm::{
+            parser::ParseStage,
+            test_util::{GlbBuilder, SyntheticMaterial, SyntheticMesh},
+        };
+
+        // Taller than a single compression strip, with a partial last strip
+        let (width, height) = (2048, 1088);
+        let image =
+            image::DynamicImage::ImageRgba8(image::RgbaImage::from_fn(width, height, |x, y| {
+                image::Rgba([x as u8, y as u8, (x ^ y) as u8, 255])
+            }));
+        let mut builder = GlbBuilder::new();
+        let texture = builder.add_image(&image).unwrap();
+        let unused = builder.add_image(&image.fliph()).unwrap();
+        let material = builder.add_material(SyntheticMaterial {
+            color_texture: Some(texture),
+            ..Default::default()
+        });
+        let mesh = builder.add_mesh(SyntheticMesh {
+            material: Some(material),
+            ..SyntheticMesh::triangle()
+        });
+        builder.add_node(Some(mesh), glam::Mat4::IDENTITY);
+        let glb = builder.build().unwrap();
+        assert_eq!(unused, 1);
+
+        // Oversized images fail from their header, before any texture is processed
+        let stages: Arc<Mutex<Vec<ParseStage>>> = Arc::default();
+        let progress_stages = stages.clone();
+        let error = Model::parse_glb(
+            &glb,
+            ParseOptions {
+                max_source_texture_resolution: Some(1024),
+                progress: Some(Arc::new(move |progress| {
+                    progress_stages.lock().unwrap().push(progress.stage)
+                })),
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ParseError>(),
+            Some(ParseError::TextureTooLarge {
+                width: 2048,
+                height: 1088,
+                max_resolution: 1024,
+                ..
+            })
+        ));
+        assert!(!stages.lock().unwrap().contains(&ParseStage::Textures));
+
+        let model = Model::parse_glb(
+            &glb,
+            ParseOptions {
+                max_source_texture_resolution: Some(width),
+                texture_compression: Some(TextureCompression::Bc),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(model.textures.len(), 1);
+        let texture = &model.textures[0];
+        assert_eq!((texture.width(), texture.height()), (width, height));
+        #[cfg(feature = "intel_tex_2")]
+        {
+            assert!(matches!(texture.format(), TextureFormat::Compressed(_)));
+            assert_eq!(
+                texture.mip_data(0).len(),
+                (width / 4 * height / 4 * 16) as usize
+            );
+        }
+    }
+
+    #[cfg(all(feature = "test-util", feature = "hdr"))]
+    #[test]
+    fn hdr_emissive_texture() {
+        use ugm::{
+            test_util::{GlbBuilder, SyntheticMaterial, SyntheticMesh},
+            texture::UncompressedTextureFormat,
+        };
+
+        let image = image::DynamicImage::ImageRgb32F(image::Rgb32FImage::from_pixel(
+            8,
+            8,
+            image::Rgb([4.0, 2.0, 0.5]),
+        ));
+        let mut hdr = std::io::Cursor::new(vec![]);
+        image.write_to(&mut hdr, image::ImageFormat::Hdr).unwrap();
+
+        let mut builder = GlbBuilder::new();
+        let texture = builder.add_encoded_image(hdr.get_ref(), "image/vnd.radiance");
+        let material = builder.add_material(SyntheticMaterial {
+            emission_texture: Some(texture),
+            ..Default::default()
+        });
+        let mesh = builder.add_mesh(SyntheticMesh {
+            material: Some(material),
+            ..SyntheticMesh::triangle()
+        });
+        builder.add_node(Some(mesh), glam::Mat4::IDENTITY);
+        let glb = builder.build().unwrap();
+
+        let model = Model::parse_glb(
+            &glb,
+            ParseOptions {
+                texture_compression: None,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let texture = &model.textures[0];
+        assert_eq!(
+            texture.format(),
+            TextureFormat::Uncompressed(UncompressedTextureFormat::Rgba32Float)
+        );
+        let pixel = texture.to_image(0).unwrap().to_rgba32f().get_pixel(0, 0).0;
+        assert_eq!(pixel, [4.0, 2.0, 0.5, 1.0]);
+
+        let model = Model::parse_glb(
+            &glb,
+            ParseOptions {
+                texture_compression: Some(TextureCompression::Bc),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            model.textures[0].format(),
+            TextureFormat::Compressed(CompressedTextureFormat::Bc6hRgbUfloat)
+        );
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn broken_image_placeholder() {
+        use ugm::{
+            parser::ParseWarning,
+            test_util::{GlbBuilder, SyntheticMaterial, SyntheticMesh},
+        };
+
+        let mut builder = GlbBuilder::new();
+        let texture = builder.add_encoded_image(b"not a png", "image/png");
+        let material = builder.add_material(SyntheticMaterial {
+            color_texture: Some(texture),
+            ..Default::default()
+        });
+        let mesh = builder.add_mesh(SyntheticMesh {
+            material: Some(material),
+            ..SyntheticMesh::triangle()
+        });
+        builder.add_node(Some(mesh), glam::Mat4::IDENTITY);
+        let glb = builder.build().unwrap();
+
+        assert!(Model::parse_glb(&glb, ParseOptions::default()).is_err());
+
+        let (model, report) = Model::parse_glb_with_report(
+            &glb,
+            ParseOptions {
+                placeholder_for_broken_images: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(matches!(
+            report.warnings.as_slice(),
+            [ParseWarning::BrokenImage { .. }]
+        ));
+
+        let placeholder = Texture::uv_grid(256);
+        assert_eq!(model.textures[0].width(), placeholder.width());
+        assert_eq!(model.textures[0].mip_data(0), placeholder.mip_data(0));
+
+        let checkerboard = Texture::checkerboard(16, [[0, 0, 0, 255], [255; 4]]);
+        assert_eq!(&checkerboard.mip_data(0)[..4], &[0, 0, 0, 255]);
+        assert_eq!(&checkerboard.mip_data(0)[8..12], &[255; 4]);
+        assert_eq!(Texture::solid([1, 2, 3, 4]).mip_data(0), &[1, 2, 3, 4]);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn empty_model() {
+        use ugm::{parser::ParseWarning, test_util::GlbBuilder};
+
+        let mut builder = GlbBuilder::new();
+        builder.add_node(None, glam::Mat4::IDENTITY);
+        let (model, report) =
+            Model::parse_glb_with_report(&builder.build().unwrap(), ParseOptions::default())
+                .unwrap();
+
+        assert!(model.is_empty());
+        assert_eq!(report.warnings, vec![ParseWarning::EmptyModel]);
+        assert_eq!((model.bounds_min, model.bounds_max), ([0.0; 3], [0.0; 3]));
+
+        let mut model = EditableModel::new(model);
+        model.add_mesh(Mesh::empty());
+        model.update_derived();
+        assert!(model.is_empty());
+        assert_eq!((model.bounds_min, model.bounds_max), ([0.0; 3], [0.0; 3]));
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn missing_default_scene() {
+        use ugm::{
+            parser::{ParseWarning, SceneSelection},
+            test_util::{GlbBuilder, SyntheticMesh},
+        };
+
+        let mut builder = GlbBuilder::new();
+        let mesh = builder.add_mesh(SyntheticMesh::triangle());
+        builder.add_node(Some(mesh), glam::Mat4::IDENTITY);
+        let glb = builder.build_without_default_scene().unwrap();
+
+        let (model, report) = Model::parse_glb_with_report(&glb, ParseOptions::default()).unwrap();
+        assert_eq!(model.root_node_indices.len(), 1);
+        assert_eq!(report.warnings, vec![ParseWarning::MissingDefaultScene]);
+
+        let strict = ParseOptions {
+            scene: SceneSelection::DefaultStrict,
+            ..Default::default()
+        };
+        assert!(Model::parse_glb(&glb, strict).is_err());
+
+        let out_of_range = ParseOptions {
+            scene: SceneSelection::Index(1),
+            ..Default::default()
+        };
+        assert!(Model::parse_glb(&glb, out_of_range).is_err());
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn texture_atlas() {
+        use ugm::{
+            atlas::TextureAtlasDesc,
+            test_util::{GlbBuilder, SyntheticMaterial, SyntheticMesh},
+        };
+
+        let colors = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]];
+        let mut builder = GlbBuilder::new();
+        for (i, color) in colors.iter().enumerate() {
+            let texture = builder
+                .add_image(&image::DynamicImage::ImageRgba8(
+                    image::RgbaImage::from_pixel(4, 4, image::Rgba(*color)),
+                ))
+                .unwrap();
+            let material = builder.add_material(SyntheticMaterial {
+                color_texture: Some(texture),
+                ..Default::default()
+            });
+            let mut mesh = SyntheticMesh {
+                material: Some(material),
+                ..SyntheticMesh::triangle()
+            };
+            // Last material tiles its texture and can't be atlased
+            if i == 2 {
+                mesh.tex_coords = vec![[0.0, 3.0], [3.0, 3.0], [0.0, 0.0]];
+            }
+            let mesh = builder.add_mesh(mesh);
+            builder.add_node(Some(mesh), glam::Mat4::IDENTITY);
+        }
+
+        let mut model =
+            Model::parse_glb(&builder.build().unwrap(), ParseOptions::default()).unwrap();
+        let atlases = model.build_texture_atlas(&TextureAtlasDesc {
+            atlas_size: 64,
+            max_texture_size: 16,
+            padding: 2,
+        });
+        assert_eq!(atlases.len(), 1);
+        assert_eq!(model.textures.len(), 2);
+
+        let atlas = model.textures[atlases[0] as usize]
+            .to_image(0)
+            .unwrap()
+            .to_rgba8();
+        for (mesh, color) in model.meshes.iter().zip(&colors[..2]) {
+            let material = &model.materials[mesh.material_indices[0] as usize];
+            assert_eq!(material.color_texture, Some(atlases[0]));
+
+            let center = mesh
+                .packed_vertices
+                .iter()
+                .map(|vertex| Vec2::from_array(vertex.tex_coord))
+                .sum::<Vec2>()
+                / 3.0;
+            let texel = center * atlas.width() as f32;
+            assert_eq!(atlas.get_pixel(texel.x as u32, texel.y as u32).0, *color);
+        }
+        assert_eq!(
+            model.meshes[2].packed_vertices[0].tex_coord,
+            [0.0, 3.0],
+            "Tiling uvs are left as is"
+        );
+    }
+
+    #[test]
+    fn merge_models() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        let merged = Model::merge(&[model.clone(), model.clone()]);
+        assert_eq!(merged.nodes.len(), model.nodes.len() * 2);
+        assert_eq!(merged.meshes.len(), model.meshes.len() * 2);
+        assert_eq!(merged.materials.len(), model.materials.len() * 2);
+        assert_eq!(
+            merged.textures.len(),
+            model.textures.len(),
+            "Identical textures are shared"
+        );
+        assert_eq!(merged.bounds_min, model.bounds_min);
+        assert_eq!(merged.bounds_max, model.bounds_max);
+
+        let num_nodes = model.nodes.len() as u32;
+        let num_materials = model.materials.len();
+        for (node_idx, node) in model.nodes.iter().enumerate() {
+            let merged_node = &merged.nodes[node_idx + model.nodes.len()];
+            assert_eq!(
+                merged_node.mesh_idx,
+                node.mesh_idx
+                    .map(|mesh_idx| mesh_idx + model.meshes.len() as u32)
+            );
+            assert!(node
+                .child_node_indices
+                .iter()
+                .map(|child_idx| child_idx + num_nodes)
+                .eq(merged_node.child_node_indices.iter().copied()));
+        }
+        for material_idx in 0..num_materials {
+            assert_eq!(
+                merged.materials[material_idx].textures(),
+                merged.materials[material_idx + num_materials].textures()
+            );
+        }
+    }
+
+    #[test]
+    fn extract_subtree() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        let node_idx = model
+            .nodes
+            .iter()
+            .position(|node| node.mesh_idx.is_some() && node.child_node_indices.is_empty())
+            .unwrap();
+        let node = &model.nodes[node_idx];
+        let mesh = &model.meshes[node.mesh_idx.unwrap() as usize];
+
+        let subtree = model.extract_subtree(node_idx as u32);
+        assert_eq!(subtree.root_node_indices, vec![0]);
+        assert_eq!(subtree.nodes.len(), 1);
+        assert_eq!(subtree.nodes[0].name, node.name);
+        assert_eq!(subtree.nodes[0].mesh_idx, Some(0));
+        assert_eq!(subtree.meshes.len(), 1);
+        assert_eq!(subtree.materials.len(), mesh.material_indices.len());
+        let transform = Mat4::from_cols_array(&node.transform);
+        for vertex_idx in 0..mesh.num_vertices() as u32 {
+            let position = transform.transform_point3(mesh.vertex_position(vertex_idx));
+            assert!(position
+                .cmpge(Vec3::from_array(subtree.bounds_min) - 1e-4)
+                .all());
+            assert!(position
+                .cmple(Vec3::from_array(subtree.bounds_max) + 1e-4)
+                .all());
+        }
+
+        // Every index of the extracted model is in range
+        let mut num_nodes = 0;
+        subtree.traverse_nodes(Mat4::IDENTITY, |node, _| {
+            num_nodes += 1;
+            if let Some(mesh_idx) = node.mesh_idx {
+                let mesh = &subtree.meshes[mesh_idx as usize];
+                for material_idx in &mesh.material_indices {
+                    let material = &subtree.materials[*material_idx as usize];
+                    for texture_idx in material.textures().into_iter().flatten() {
+                        assert!((texture_idx as usize) < subtree.textures.len());
+                    }
+                }
+            }
+        });
+        assert_eq!(num_nodes, subtree.nodes.len());
+    }
+
+    #[test]
+    fn flatten() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        let mut instances = vec![];
+        model.traverse_nodes(Mat4::IDENTITY, |node, transform| {
+            if let Some(mesh_idx) = node.mesh_idx {
+                instances.push((mesh_idx, transform));
+            }
+        });
+        let num_triangles: usize = instances
+            .iter()
+            .map(|(mesh_idx, _)| model.meshes[*mesh_idx as usize].indices.len() / 3)
+            .sum();
+
+        let mut flattened = model.clone();
+        flattened.flatten(false);
+        assert_eq!(flattened.meshes.len(), instances.len());
+        assert_eq!(flattened.root_node_indices.len(), instances.len());
+        for (node, (mesh_idx, transform)) in flattened.nodes.iter().zip(&instances) {
+            assert_eq!(node.transform, Mat4::IDENTITY.to_cols_array());
+            let baked = &flattened.meshes[node.mesh_idx.unwrap() as usize];
+            let position =
+                transform.transform_point3(model.meshes[*mesh_idx as usize].vertex_position(0));
+            assert!(baked.vertex_position(0).distance(position) < 1e-4);
+        }
+
+        let mut merged = model.clone();
+        merged.flatten(true);
+        assert!(merged.meshes.len() <= model.materials.len());
+        for mesh in &merged.meshes {
+            assert_eq!(mesh.material_indices.len(), 1);
+        }
+        let merged_triangles: usize = merged
+            .meshes
+            .iter()
+            .map(|mesh| mesh.indices.len() / 3)
+            .sum();
+        assert_eq!(merged_triangles, num_triangles);
+    }
+
+    #[test]
+    fn iter_nodes() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let mut model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        let mut traversed = vec![];
+        model.traverse_nodes(Mat4::IDENTITY, |node, transform| {
+            traversed.push((node.name.clone(), transform));
+        });
+        assert!(model
+            .iter_nodes(Mat4::IDENTITY)
+            .map(|(_, node, transform)| (node.name.clone(), transform))
+            .eq(traversed));
+
+        let (node_idx, node, _) = model
+            .iter_nodes(Mat4::IDENTITY)
+            .find(|(_, node, _)| node.mesh_idx.is_some())
+            .unwrap();
+        assert!(std::ptr::eq(node, &model.nodes[node_idx as usize]));
+
+        // A hierarchy deep enough to overflow a recursive traversal
+        let depth = 200_000;
+        let first = model.nodes.len() as u32;
+        for i in 0..depth {
+            model.nodes.push(ugm::ModelNode {
+                name: format!("Chain {}", i),
+                transform: Mat4::from_translation(Vec3::X).to_cols_array(),
+                mesh_idx: None,
+                child_node_indices: if i + 1 < depth {
+                    vec![first + i + 1]
+                } else {
+                    vec![]
+                },
+            });
+        }
+        model.root_node_indices.push(first);
+        let (_, _, transform) = model.iter_nodes(Mat4::IDENTITY).last().unwrap();
+        assert_eq!(transform.w_axis.x, depth as f32);
+    }
+
+    #[test]
+    fn traverse_nodes_mut() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let mut model =
+            EditableModel::new(Model::parse_glb(model_bytes, ParseOptions::default()).unwrap());
+
+        let mut before = vec![];
+        model.traverse_nodes(Mat4::IDENTITY, |node, transform| {
+            before.push(transform);
+            assert!(!node.name.starts_with("Edited"));
+        });
+
+        // Scaling the roots scales every world transform
+        let scale = Mat4::from_scale(Vec3::splat(2.0));
+        model.traverse_nodes_mut(Mat4::IDENTITY, |node, parent_transform| {
+            if parent_transform == Mat4::IDENTITY {
+                node.transform = (scale * Mat4::from_cols_array(&node.transform)).to_cols_array();
+            }
+            node.name = format!("Edited {}", node.name);
+        });
+        assert!(!model.is_dirty());
+
+        let mut after = vec![];
+        model.traverse_nodes(Mat4::IDENTITY, |node, transform| {
+            after.push(transform);
+            assert!(node.name.starts_with("Edited"));
+        });
+        for (before, after) in before.iter().zip(&after) {
+            assert!((scale * *before).abs_diff_eq(*after, 1e-4));
+        }
+    }
+
+    #[test]
+    fn world_bounds() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+        assert_eq!(
+            model.compute_world_bounds(Mat4::IDENTITY),
+            (model.bounds_min, model.bounds_max)
+        );
+
+        // Bounds enclose every transformed vertex
+        let bounds_min = Vec3::from_array(model.bounds_min) - 1e-4;
+        let bounds_max = Vec3::from_array(model.bounds_max) + 1e-4;
+        model.traverse_nodes(Mat4::IDENTITY, |node, transform| {
+            let Some(mesh_idx) = node.mesh_idx else {
+                return;
+            };
+            let mesh = &model.meshes[mesh_idx as usize];
+            for vertex_idx in 0..mesh.num_vertices() as u32 {
+                let position = transform.transform_point3(mesh.vertex_position(vertex_idx));
+                assert!(position.cmpge(bounds_min).all() && position.cmple(bounds_max).all());
+            }
+        });
+
+        let offset = Vec3::new(10.0, 0.0, -5.0);
+        let (moved_min, moved_max) = model.compute_world_bounds(Mat4::from_translation(offset));
+        assert!(Vec3::from_array(moved_min)
+            .abs_diff_eq(Vec3::from_array(model.bounds_min) + offset, 1e-4));
+        assert!(Vec3::from_array(moved_max)
+            .abs_diff_eq(Vec3::from_array(model.bounds_max) + offset, 1e-4));
+    }
+
+    #[test]
+    fn node_bounds() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        let node_bounds = model.compute_node_bounds();
+        assert_eq!(node_bounds.len(), model.nodes.len());
+
+        // Root bounds in world space add up to the model bounds
+        let mut bounds_min = Vec3::INFINITY;
+        let mut bounds_max = Vec3::NEG_INFINITY;
+        for root_idx in &model.root_node_indices {
+            let Some((min, max)) = node_bounds[*root_idx as usize] else {
+                continue;
+            };
+            let transform = Mat4::from_cols_array(&model.nodes[*root_idx as usize].transform);
+            for corner in 0..8 {
+                let corner = Vec3::new(
+                    if corner & 1 == 0 { min[0] } else { max[0] },
+                    if corner & 2 == 0 { min[1] } else { max[1] },
+                    if corner & 4 == 0 { min[2] } else { max[2] },
+                );
+                bounds_min = bounds_min.min(transform.transform_point3(corner));
+                bounds_max = bounds_max.max(transform.transform_point3(corner));
+            }
+        }
+        assert!(bounds_min.abs_diff_eq(Vec3::from_array(model.bounds_min), 1e-4));
+        assert!(bounds_max.abs_diff_eq(Vec3::from_array(model.bounds_max), 1e-4));
+
+        for (node, bounds) in model.nodes.iter().zip(&node_bounds) {
+            if let Some(mesh_idx) = node.mesh_idx {
+                let mesh = &model.meshes[mesh_idx as usize];
+                let (min, max) = bounds.unwrap();
+                assert!(Vec3::from_array(mesh.bounds_min)
+                    .cmpge(Vec3::from_array(min))
+                    .all());
+                assert!(Vec3::from_array(mesh.bounds_max)
+                    .cmple(Vec3::from_array(max))
+                    .all());
+            }
+        }
+    }
+
+    #[test]
+    fn unit_scale() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        let centimeters = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                target_unit_scale: Some(0.01),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(Vec3::from_array(centimeters.bounds_max)
+            .abs_diff_eq(Vec3::from_array(model.bounds_max) * 100.0, 1e-2));
+        assert_eq!(
+            centimeters.meshes[0].vertex_position(0),
+            model.meshes[0].vertex_position(0),
+            "Scale is applied to root transforms"
+        );
+
+        let same_units = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                source_unit_scale: Some(0.01),
+                target_unit_scale: Some(0.01),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(same_units.bounds_max, model.bounds_max);
+    }
+
+    #[test]
+    fn parse_options_builder() {
+        let opt = ParseOptions::builder()
+            .textures(TextureOptions {
+                compression: Some(TextureCompression::Bc),
+                max_resolution: Some(MaxTextureResolution::Res512),
+                generate_mips: true,
+            })
+            .texture_usage(
+                TextureUsage::Normal,
+                TextureOptions {
+                    compression: None,
+                    max_resolution: Some(MaxTextureResolution::Res256),
+                    generate_mips: false,
+                },
+            )
+            .geometry(GeometryOptions {
+                quantize_positions: true,
+                ..Default::default()
+            })
+            .build();
+        assert_eq!(
+            opt.texture_compression(&[TextureUsage::Color]),
+            Some(TextureCompression::Bc)
+        );
+        assert_eq!(
+            opt.texture_compression(&[TextureUsage::Color, TextureUsage::Normal]),
+            None
+        );
+        assert!(!opt.generate_mips(&[TextureUsage::Normal]));
+        assert_eq!(
+            opt.max_texture_resolution(&[TextureUsage::Normal]),
+            Some(256)
+        );
+
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, opt).unwrap();
+        assert!(model
+            .meshes
+            .iter()
+            .all(|mesh| mesh.quantized_vertices.is_some()));
+        for texture in &model.textures {
+            let is_normal_map = texture.usages().contains(TextureUsage::Normal);
+            assert_eq!(texture.mip_count() == 1, is_normal_map);
+            assert_eq!(
+                matches!(texture.format(), TextureFormat::Compressed(_)),
+                !is_normal_map
+            );
+            assert!(texture.width() <= if is_normal_map { 256 } else { 512 });
+        }
+    }
+
+    #[test]
+    fn deduplicate_materials() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let mut model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        let num_materials = model.materials.len() as u32;
+        let copies: Vec<Material> = model
+            .materials
+            .iter()
+            .map(|material| Material {
+                name: format!("{} copy", material.name),
+                ..material.clone()
+            })
+            .collect();
+        model.materials.extend(copies);
+        for mesh in model.meshes.iter_mut().step_by(2) {
+            for material_idx in &mut mesh.material_indices {
+                *material_idx += num_materials;
+            }
+        }
+
+        let remap = model.deduplicate_materials();
+        assert_eq!(model.materials.len() as u32, num_materials);
+        assert!((0..num_materials)
+            .all(|material_idx| remap[(material_idx + num_materials) as usize] == material_idx));
+        for mesh in &model.meshes {
+            assert!(mesh
+                .material_indices
+                .iter()
+                .all(|material_idx| *material_idx < num_materials));
+            assert!(mesh
+                .sort_keys
+                .iter()
+                .zip(&mesh.material_indices)
+                .all(|(sort_key, material_idx)| sort_key.material_idx == *material_idx));
+        }
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn mesh_instances() {
+        use ugm::test_util::{GlbBuilder, SyntheticMesh};
+
+        let mut builder = GlbBuilder::new();
+        let first = builder.add_mesh(SyntheticMesh::triangle());
+        let duplicate = builder.add_mesh(SyntheticMesh::triangle());
+        let mut moved = SyntheticMesh::triangle();
+        for position in &mut moved.positions {
+            position[2] += 1.0;
+        }
+        let moved = builder.add_mesh(moved);
+        for mesh in [first, duplicate, moved] {
+            builder.add_node(Some(mesh), Mat4::IDENTITY);
+        }
+        let glb = builder.build().unwrap();
+
+        let model = Model::parse_glb(
+            &glb,
+            ParseOptions {
+                merge_duplicate_meshes: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let instances = model.mesh_instances();
+        assert_eq!(instances[first as usize], vec![0, 1]);
+        assert!(instances[duplicate as usize].is_empty());
+        assert_eq!(instances[moved as usize], vec![2]);
+
+        let model = Model::parse_glb(&glb, ParseOptions::default()).unwrap();
+        assert!(model.mesh_instances().iter().all(|nodes| nodes.len() == 1));
+    }
+
+    #[cfg(feature = "wgpu")]
+    #[test]
+    fn wgpu_vertex_layouts() {
+        use ugm::{mesh::PackedVertex, quantize::QuantizedVertex};
+
+        let layout = PackedVertex::LAYOUT;
+        assert_eq!(layout.array_stride, size_of::<PackedVertex>() as u64);
+        assert_eq!(
+            layout
+                .attributes
+                .iter()
+                .map(|attribute| (
+                    attribute.shader_location,
+                    attribute.offset,
+                    attribute.format
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, 0, wgpu::VertexFormat::Float32x3),
+                (1, 12, wgpu::VertexFormat::Uint32),
+                (2, 16, wgpu::VertexFormat::Float32x2),
+                (3, 24, wgpu::VertexFormat::Uint32),
+                (4, 28, wgpu::VertexFormat::Float32),
+            ]
+        );
+
+        let layout = QuantizedVertex::LAYOUT;
+        assert_eq!(layout.array_stride, 24);
+        assert_eq!(layout.attributes[0].format, wgpu::VertexFormat::Unorm16x4);
+    }
+
+    #[test]
+    fn gpu_scene_data() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        let data = model.gpu_scene_data(Mat4::IDENTITY);
+        assert_eq!(size_of::<ugm::gpu::GpuInstance>(), 80);
+        assert_eq!(size_of::<ugm::material::PackedMaterial>() % 4, 0);
+        assert_eq!(data.materials.len(), model.materials.len());
+        assert_eq!(
+            data.material_bytes().len(),
+            model.materials.len() * size_of::<ugm::material::PackedMaterial>()
+        );
+
+        let mut expected = vec![];
+        model.traverse_nodes(Mat4::IDENTITY, |node, transform| {
+            if let Some(mesh_idx) = node.mesh_idx {
+                expected.push((mesh_idx, transform.to_cols_array()));
+            }
+        });
+        assert_eq!(
+            data.instances
+                .iter()
+                .map(|instance| (instance.mesh_idx, instance.transform))
+                .collect::<Vec<_>>(),
+            expected
+        );
+        for instance in &data.instances {
+            assert_eq!(
+                model.nodes[instance.node_idx as usize].mesh_idx,
+                Some(instance.mesh_idx)
+            );
+        }
+    }
+
+    #[cfg(feature = "intel_tex_2")]
+    #[test]
+    fn eac_round_trip() {
+        use ugm::texture::{TextureCompressionQuality, UncompressedTextureFormat};
+
+        // A constant block followed by a block spanning the full range
+        let value = |x: u32, y: u32| {
+            if x < 4 {
+                200
+            } else {
+                ((x - 4 + y * 4) * 17) as u8
+            }
+        };
+        let round_trip = |image: image::DynamicImage, format| {
+            let texture = Texture::new(TextureCreateDesc {
+                name: None,
+                image,
+                mips: false,
+                mip_filter: Default::default(),
+                max_mip_levels: None,
+                min_mip_size: None,
+                precomputed_mips: vec![],
+                is_normal_map: false,
+                color_space: ColorSpace::Linear,
+                alpha_coverage_cutoff: None,
+                premultiply_alpha: false,
+                uv_offset: [0.0; 2],
+                uv_scale: [1.0; 2],
+                sampler: Default::default(),
+                source_image: None,
+            })
+            .compress_as(format, TextureCompressionQuality::default())
+            .unwrap();
+            assert_eq!(texture.format(), TextureFormat::Compressed(format));
+            texture.decompress().unwrap()
+        };
+        let max_error = |decoded: &[u16], expected: &[u8]| {
+            decoded
+                .iter()
+                .zip(expected)
+                .map(|(decoded, expected)| (*decoded as i32 - *expected as i32 * 257).abs())
+                .max()
+                .unwrap()
+        };
+
+        let r = image::GrayImage::from_fn(8, 4, |x, y| image::Luma([value(x, y)]));
+        let decoded = round_trip(
+            image::DynamicImage::ImageLuma8(r.clone()),
+            CompressedTextureFormat::EacR11Unorm,
+        );
+        assert_eq!(
+            decoded.format(),
+            TextureFormat::Uncompressed(UncompressedTextureFormat::R16Unorm)
+        );
+        let decoded: Vec<u16> = bytemuck::pod_collect_to_vec(decoded.data());
+        let (constant, gradient): (Vec<_>, Vec<_>) = (0..32).partition(|i| i % 8 < 4);
+        let select = |values: &[u16], indices: &[usize]| -> Vec<u16> {
+            indices.iter().map(|i| values[*i]).collect()
+        };
+        let select_source =
+            |indices: &[usize]| -> Vec<u8> { indices.iter().map(|i| r.as_raw()[*i]).collect() };
+        // Constant blocks are exact up to 11 bit rounding, full range blocks are within half a step of their 8 levels
+        assert!(max_error(&select(&decoded, &constant), &select_source(&constant)) <= 64);
+        assert!(max_error(&select(&decoded, &gradient), &select_source(&gradient)) <= 4500);
+
+        let rg = image::GrayAlphaImage::from_fn(8, 4, |x, y| {
+            image::LumaA([value(x, y), 255 - value(x, y)])
+        });
+        let decoded = round_trip(
+            image::DynamicImage::ImageLumaA8(rg.clone()),
+            CompressedTextureFormat::EacRg11Unorm,
+        );
+        assert_eq!(
+            decoded.format(),
+            TextureFormat::Uncompressed(UncompressedTextureFormat::Rg16Unorm)
+        );
+        let decoded: Vec<u16> = bytemuck::pod_collect_to_vec(decoded.data());
+        assert!(max_error(&decoded, rg.as_raw()) <= 4500);
+    }
+
+    #[cfg(all(feature = "wgpu", feature = "intel_tex_2"))]
+    #[test]
+    fn wgpu_format_selection() {
+        use ugm::texture::{TextureCompressionQuality, UncompressedTextureFormat};
+
+        let image = image::RgbaImage::from_fn(16, 16, |x, y| {
+            image::Rgba([(x * 16) as u8, (y * 16) as u8, 128, 255])
+        });
+        let texture = Texture::new(TextureCreateDesc {
+            name: None,
+            image: image::DynamicImage::ImageRgba8(image),
+            mips: false,
+            mip_filter: Default::default(),
+            max_mip_levels: None,
+            min_mip_size: None,
+            precomputed_mips: vec![],
+            is_normal_map: false,
+            color_space: ColorSpace::Srgb,
+            alpha_coverage_cutoff: None,
+            premultiply_alpha: false,
+            uv_offset: [0.0; 2],
+            uv_scale: [1.0; 2],
+            sampler: Default::default(),
+            source_image: None,
+        })
+        .compress_as(
+            CompressedTextureFormat::Bc7RgbaUnorm,
+            TextureCompressionQuality::default(),
+        )
+        .unwrap();
+
+        let select = |features| {
+            texture
+                .for_wgpu_features(features, TextureCompressionQuality::default())
+                .unwrap()
+                .format()
+        };
+        assert_eq!(
+            select(wgpu::Features::TEXTURE_COMPRESSION_BC),
+            TextureFormat::Compressed(CompressedTextureFormat::Bc7RgbaUnorm)
+        );
+        assert_eq!(
+            select(wgpu::Features::TEXTURE_COMPRESSION_ETC2),
+            TextureFormat::Compressed(CompressedTextureFormat::Etc2Rgba8Unorm)
+        );
+        assert_eq!(
+            select(wgpu::Features::empty()),
+            TextureFormat::Uncompressed(UncompressedTextureFormat::Rgba8Unorm)
+        );
+
+        let etc2 = texture
+            .for_wgpu_features(
+                wgpu::Features::TEXTURE_COMPRESSION_ETC2,
+                TextureCompressionQuality::default(),
+            )
+            .unwrap()
+            .into_owned();
+        assert!(etc2
+            .for_wgpu_features(
+                wgpu::Features::empty(),
+                TextureCompressionQuality::default()
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn packed_round_trip() {
+        use ugm::packing::{PackedNormalizedXyz10, PackedRgb9e5};
+
+        for rgb in [
+            Vec3::ZERO,
+            Vec3::ONE,
+            Vec3::new(0.25, 0.5, 0.75),
+            Vec3::new(12.0, 0.1, 3.5),
+            Vec3::new(1000.0, 0.0, 65000.0),
+        ] {
+            let unpacked = PackedRgb9e5::new(rgb).unpack();
+            // 9 bit mantissas share the exponent of the largest channel
+            let tolerance = rgb.max_element() / 256.0 + f32::EPSILON;
+            assert!((unpacked - rgb).abs().max_element() <= tolerance);
+        }
+        assert_eq!(PackedRgb9e5::new(Vec3::splat(-1.0)).unpack(), Vec3::ZERO);
+
+        for i in 0..1000 {
+            let theta = i as f32 * 0.618 * std::f32::consts::TAU;
+            let z = i as f32 / 999.0 * 2.0 - 1.0;
+            let r = (1.0 - z * z).sqrt();
+            let dir = Vec3::new(r * theta.cos(), r * theta.sin(), z);
+
+            let unpacked = PackedNormalizedXyz10::new(dir).unpack();
+            assert!((unpacked.length() - 1.0).abs() < 1e-5);
+            assert!(unpacked.dot(dir) > 0.99999);
+        }
+    }
+
+    #[test]
+    fn half_tex_coord_vertices() {
+        use ugm::mesh::HalfTexCoordVertex;
+
+        assert_eq!(size_of::<HalfTexCoordVertex>(), 24);
+
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+        for mesh in &model.meshes {
+            let vertices = mesh.half_tex_coord_vertices(1.0 / 1024.0).unwrap();
+            assert_eq!(vertices.len(), mesh.packed_vertices.len());
+            for (vertex, packed) in vertices.iter().zip(&mesh.packed_vertices) {
+                assert_eq!(vertex.position, packed.position);
+                assert_eq!(vertex.tangent.sign(), packed.tangent_handiness.signum());
+                assert!(vertex.tangent.unpack().dot(packed.tangent.unpack()) > 0.9999);
+                for axis in 0..2 {
+                    assert!(
+                        (vertex.tex_coord[axis].to_f32() - packed.tex_coord[axis]).abs()
+                            <= 1.0 / 1024.0
+                    );
+                }
+            }
+        }
+
+        let mut mesh = model.meshes[0].clone();
+        mesh.packed_vertices[0].tex_coord = [4096.5, 0.0];
+        assert!(mesh.half_tex_coord_vertices(1.0 / 1024.0).is_none());
+    }
+
+    #[test]
+    fn position_streams() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(
+            model_bytes,
+            ParseOptions {
+                quantize_positions: true,
+                position_streams: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        for mesh in &model.meshes {
+            let positions = mesh.position_stream.as_ref().unwrap();
+            assert_eq!(positions.len(), mesh.num_vertices());
+            for (vertex_idx, position) in positions.iter().enumerate() {
+                assert_eq!(
+                    *position,
+                    mesh.vertex_position(vertex_idx as u32).to_array()
+                );
+            }
+            assert_eq!(mesh.gpu_data().position_stream.len(), positions.len() * 12);
+        }
+
+        let deserialized = Model::read_from_buffer(&model.write_to_vec().unwrap()).unwrap();
+        assert_eq!(
+            deserialized.meshes[0].position_stream,
+            model.meshes[0].position_stream
+        );
+
+        let mut converted = model.clone();
+        converted.meshes[0].change_basis(glam::Mat3::from_diagonal(Vec3::new(-1.0, 1.0, 1.0)));
+        let mesh = &converted.meshes[0];
+        assert_eq!(
+            mesh.position_stream.as_ref().unwrap()[0],
+            mesh.vertex_position(0).to_array()
+        );
+    }
+
+    #[test]
+    fn model_stats() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let mut model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        let stats = model.stats();
+        assert_eq!(
+            stats.num_triangles(),
+            model
+                .meshes
+                .iter()
+                .map(|mesh| mesh.indices.len() / 3)
+                .sum::<usize>()
+        );
+        assert_eq!(
+            stats.num_vertices(),
+            model.meshes.iter().map(Mesh::num_vertices).sum::<usize>()
+        );
+        assert_eq!(
+            stats.texture_bytes_per_format.values().sum::<usize>(),
+            stats.texture_bytes
+        );
+        assert_eq!(
+            stats.texture_bytes_per_format
+                [&TextureFormat::Uncompressed(ugm::texture::UncompressedTextureFormat::Rgba8Unorm)],
+            stats.texture_bytes
+        );
+        assert!(stats.max_node_depth >= 1);
+
+        // A chain below the first root deepens the hierarchy by its length
+        let root_idx = model.root_node_indices[0];
+        let mut parent_idx = root_idx;
+        for _ in 0..10 {
+            let mut node = model.nodes[root_idx as usize].clone();
+            node.mesh_idx = None;
+            node.child_node_indices.clear();
+            model.nodes.push(node);
+            let node_idx = model.nodes.len() as u32 - 1;
+            model.nodes[parent_idx as usize]
+                .child_node_indices
+                .push(node_idx);
+            parent_idx = node_idx;
+        }
+        assert_eq!(model.stats().max_node_depth, stats.max_node_depth.max(11));
+    }
+
+    #[test]
+    fn validate_model() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+        let report = model.validate();
+        assert!(report.is_valid(), "{:?}", report);
+
+        let mut broken = model.clone();
+        let num_nodes = broken.nodes.len() as u32;
+        broken.root_node_indices.push(num_nodes);
+        broken.nodes[0].mesh_idx = Some(broken.meshes.len() as u32);
+        broken.meshes[0].material_indices[0] = broken.materials.len() as u32;
+        broken.materials[1].color_texture = Some(broken.textures.len() as u32);
+        broken.meshes[1].bounds_max = broken.meshes[1].bounds_min;
+        let report = broken.validate();
+        assert_eq!(report.out_of_range_root_nodes, vec![num_nodes]);
+        assert_eq!(report.out_of_range_node_meshes, vec![0]);
+        assert_eq!(report.out_of_range_mesh_materials, vec![0]);
+        assert_eq!(report.out_of_range_material_textures, vec![1]);
+        assert_eq!(report.invalid_mesh_bounds, vec![1]);
+        assert!(report.cyclic_nodes.is_empty());
+
+        // Root 0 -> child -> grandchild -> child
+        let mut cyclic = model.clone();
+        let root_idx = cyclic.root_node_indices[0];
+        let mut node = cyclic.nodes[root_idx as usize].clone();
+        node.child_node_indices.clear();
+        cyclic.nodes.push(node.clone());
+        cyclic.nodes.push(node);
+        let (child_idx, grandchild_idx) = (num_nodes, num_nodes + 1);
+        cyclic.nodes[root_idx as usize]
+            .child_node_indices
+            .push(child_idx);
+        cyclic.nodes[child_idx as usize]
+            .child_node_indices
+            .push(grandchild_idx);
+        cyclic.nodes[grandchild_idx as usize]
+            .child_node_indices
+            .push(child_idx);
+        let report = cyclic.validate();
+        assert_eq!(report.cyclic_nodes, vec![child_idx, grandchild_idx]);
+        assert_eq!(report.shared_nodes, vec![child_idx]);
+        assert!(!report.invalid_model_bounds);
+
+        let mut moved = model.clone();
+        moved.bounds_max = moved.bounds_min;
+        assert!(moved.validate().invalid_model_bounds);
+    }
+
+    #[test]
+    fn deterministic_uuids() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let parse = |deterministic_uuids| {
+            Model::parse_glb(
+                model_bytes,
+                ParseOptions {
+                    deterministic_uuids,
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+            .write_to_bytes()
+            .unwrap()
+        };
+
+        assert_eq!(parse(true), parse(true));
+        assert_ne!(parse(false), parse(false));
+
+        let model = Model::read_from_bytes(&parse(true)).unwrap();
+        for texture in &model.textures {
+            let mut copy = texture.clone();
+            copy.set_content_uuid();
+            assert_eq!(copy.uuid(), texture.uuid());
+        }
+
+        // Pinned so content uuids stay the same across platforms, toolchains and releases
+        let mut texture = Texture::solid([1, 2, 3, 4]);
+        texture.set_content_uuid();
+        assert_eq!(
+            texture.uuid(),
+            uuid::Uuid::parse_str("15727024-7af8-877c-86f5-2cf5d034fb15").unwrap()
+        );
+        assert_eq!(texture.uuid().get_version(), Some(uuid::Version::Custom));
+        assert_eq!(texture.uuid().get_variant(), uuid::Variant::RFC4122);
+        assert_eq!(texture.content_hash(), 0x7c37_f87a_2470_7215);
+    }
+
+    #[test]
+    fn pipeline_flags() {
+        let opaque = Material {
+            color_texture: Some(0),
+            ..Default::default()
+        };
+        let opaque_copy = Material {
+            color: [0.5; 3],
+            color_texture: Some(3),
+            ..opaque.clone()
+        };
+        let masked = Material {
+            is_opaque: false,
+            alpha_mode: AlphaMode::Mask,
+            ..opaque.clone()
+        };
+        let blended = Material {
+            alpha_mode: AlphaMode::Blend,
+            ..masked.clone()
+        };
+
+        assert_eq!(
+            opaque.pipeline_flags(),
+            Material::PIPELINE_OPAQUE | Material::PIPELINE_COLOR_TEXTURE
+        );
+        assert_eq!(opaque.pipeline_flags(), opaque_copy.pipeline_flags());
+        assert_eq!(masked.pipeline_flags(), Material::PIPELINE_COLOR_TEXTURE);
+
+        let keys = [
+            SortKey::new(&opaque, 0),
+            SortKey::new(&opaque_copy, 1),
+            SortKey::new(&masked, 2),
+            SortKey::new(&blended, 3),
+        ];
+        assert_eq!(keys[0].pipeline_flags, keys[1].pipeline_flags);
+        assert_eq!(keys[0].depth_sort_hint, DepthSortHint::FrontToBack);
+        assert_eq!(keys[2].depth_sort_hint, DepthSortHint::AlphaTested);
+        assert_eq!(keys[3].depth_sort_hint, DepthSortHint::BackToFront);
+        assert!(keys.windows(2).all(|pair| pair[0].key() < pair[1].key()));
+        assert_eq!(blended.packed().unpack().alpha_mode, AlphaMode::Blend);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn blended_material_sort_key() {
+        use ugm::test_util::{GlbBuilder, SyntheticMaterial, SyntheticMesh};
+
+        let mut builder = GlbBuilder::new();
+        let masked = builder.add_material(SyntheticMaterial {
+            alpha_cutoff: Some(0.5),
+            ..Default::default()
+        });
+        let blended = builder.add_material(SyntheticMaterial {
+            color: [1.0, 1.0, 1.0, 0.5],
+            blend: true,
+            ..Default::default()
+        });
+        for material in [masked, blended] {
+            let mesh = builder.add_mesh(SyntheticMesh {
+                material: Some(material),
+                ..SyntheticMesh::triangle()
+            });
+            builder.add_node(Some(mesh), Mat4::IDENTITY);
+        }
+
+        let model = Model::parse_glb(&builder.build().unwrap(), ParseOptions::default()).unwrap();
+        let hints = |alpha_mode| {
+            let material_idx = model
+                .materials
+                .iter()
+                .position(|material| material.alpha_mode == alpha_mode)
+                .unwrap() as u32;
+            model
+                .meshes
+                .iter()
+                .flat_map(|mesh| &mesh.sort_keys)
+                .filter(|sort_key| sort_key.material_idx == material_idx)
+                .map(|sort_key| sort_key.depth_sort_hint)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(hints(AlphaMode::Mask), vec![DepthSortHint::AlphaTested]);
+        assert_eq!(hints(AlphaMode::Blend), vec![DepthSortHint::BackToFront]);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn material_property_tracks() {
+        use ugm::{
+            animation::{Interpolation, PropertyPath, PropertyTarget, PropertyTrack},
+            parser::ParseWarning,
+            test_util::{GlbBuilder, SyntheticMaterial, SyntheticMesh},
+        };
+
+        let mut builder = GlbBuilder::new();
+        let mut meshes = vec![];
+        for _ in 0..2 {
+            let material = builder.add_material(SyntheticMaterial {
+                emission: [1.0, 0.5, 0.0],
+                ..Default::default()
+            });
+            meshes.push(builder.add_mesh(SyntheticMesh {
+                material: Some(material),
+                ..SyntheticMesh::triangle()
+            }));
+        }
+        for mesh in meshes {
+            builder.add_node(Some(mesh), Mat4::IDENTITY);
+        }
+
+        let emission = PropertyTrack {
+            path: PropertyPath::parse("/materials/1/emissiveFactor").unwrap(),
+            interpolation: Interpolation::Linear,
+            times: vec![0.0, 0.5, 1.0],
+            values: vec![1.0, 0.5, 0.0, 0.0, 0.0, 0.0, 1.0, 0.5, 0.0],
+            components: 3,
+        };
+        let scroll = PropertyTrack {
+            path: PropertyPath::parse(
+                "/materials/1/emissiveTexture/extensions/KHR_texture_transform/offset",
+            )
+            .unwrap(),
+            interpolation: Interpolation::Step,
+            times: vec![0.0, 1.0],
+            values: vec![0.0, 0.0, 0.5, 0.0],
+            components: 2,
+        };
+        let translation = PropertyTrack {
+            path: PropertyPath::parse("/nodes/0/translation").unwrap(),
+            interpolation: Interpolation::Linear,
+            times: vec![0.0, 1.0],
+            values: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+            components: 3,
+        };
+        assert_eq!(emission.path.target, PropertyTarget::Material);
+        assert_eq!(emission.path.index, 1);
+        assert_eq!(emission.path.property, "emissiveFactor");
+        assert_eq!(translation.path.pointer(), "/nodes/0/translation");
+        assert!(PropertyPath::parse("/animations/0/name").is_none());
+        for track in [&emission, &scroll, &translation] {
+            builder.add_property_track(track);
+        }
+
+        // Node tracks are skipped until node animation is imported, animated materials aren't merged
+        let (model, report) = Model::parse_glb_with_report(
+            &builder.build().unwrap(),
+            ParseOptions {
+                deduplicate_materials: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            model.property_tracks,
+            vec![emission.clone(), scroll.clone()]
+        );
+        assert_eq!(model.property_tracks[0].value(1), &[0.0, 0.0, 0.0]);
+        assert_eq!(model.materials.len(), 2);
+        assert_eq!(
+            report.warnings,
+            vec![ParseWarning::SkippedPropertyTrack {
+                pointer: "/nodes/0/translation".to_owned()
+            }]
+        );
+
+        let deserialized = Model::read_from_buffer(&model.write_to_vec().unwrap()).unwrap();
+        assert_eq!(deserialized.property_tracks, model.property_tracks);
+
+        let exported =
+            Model::parse_glb(&model.export_glb().unwrap(), ParseOptions::default()).unwrap();
+        assert_eq!(exported.property_tracks, model.property_tracks);
+    }
+
+    #[test]
+    fn texture_pool() {
+        let model_bytes = include_bytes!("ToyCar.glb");
+        let model = Model::parse_glb(model_bytes, ParseOptions::default()).unwrap();
+
+        let mut pool = TexturePool::new();
+        let first =
+            Model::parse_glb_pooled(model_bytes, ParseOptions::default(), &mut pool).unwrap();
+        let second =
+            Model::parse_glb_pooled(model_bytes, ParseOptions::default(), &mut pool).unwrap();
+        assert_eq!(pool.len(), model.textures.len());
+        assert!(first.model.textures.is_empty());
+        assert_eq!(first.texture_uuids, second.texture_uuids);
+
+        let (pooled_bytes, pool_bytes) = (
+            second.write_to_bytes().unwrap(),
+            pool.write_to_bytes().unwrap(),
+        );
+        assert!(PooledModel::read_from_bytes(&pool_bytes).is_err());
+        assert!(TexturePool::read_from_bytes(&pooled_bytes).is_err());
+        assert!(Model::read_ugm(&mut pooled_bytes.as_slice()).is_err());
+        assert!(TexturePool::read_from_bytes(&model.write_to_bytes().unwrap()).is_err());
+
+        let pooled = PooledModel::read_from_bytes(&pooled_bytes).unwrap();
+        let pool = TexturePool::read_from_bytes(&pool_bytes).unwrap();
+        let resolved = pool.resolve(&pooled).unwrap();
+        assert_eq!(resolved.textures.len(), model.textures.len());
+        for (resolved, texture) in resolved.textures.iter().zip(&model.textures) {
+            assert!(resolved.has_same_content(texture));
+        }
+
+        assert!(TexturePool::new().resolve(&pooled).is_err());
     }
 
     #[test]